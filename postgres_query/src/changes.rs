@@ -0,0 +1,99 @@
+//! Track the values of rows before and after an update.
+//!
+//! See [`execute_returning_changes`].
+//!
+//! [`execute_returning_changes`]: fn.execute_returning_changes.html
+
+use crate::client::GenericClient;
+use crate::error::Result;
+use crate::execute;
+use crate::extract::{FromSqlRow, Row};
+use crate::Query;
+
+/// Update rows in a table and return the values of each row before and after the update, which is
+/// useful for audit logs.
+///
+/// The rows matching `filter` are locked and read, then updated with the assignments in `set`.
+/// Both `set` and `filter` are regular queries, whose parameters are renumbered when combined.
+/// The table is expected to have a column, `key`, which uniquely identifies each row:
+///
+/// ```sql
+/// WITH __before AS (SELECT * FROM <table> WHERE (<filter>) FOR UPDATE),
+///      __after AS (
+///         UPDATE <table> SET <set> WHERE <key> IN (SELECT <key> FROM __before) RETURNING *
+///      )
+/// SELECT __before.*, __after.* FROM __before JOIN __after ON __before.<key> = __after.<key>
+/// ```
+///
+/// The table and key are inserted verbatim into the query.
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{changes::execute_returning_changes, query, FromSqlRow, Result};
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// #[derive(FromSqlRow)]
+/// struct Person {
+///     id: i32,
+///     age: i32,
+/// }
+///
+/// let changes: Vec<(Person, Person)> = execute_returning_changes(
+///     &client,
+///     "people",
+///     "id",
+///     query!("age = age + $years", years = 1),
+///     query!("name = $name", name = "John Wick"),
+/// )
+/// .await?;
+///
+/// for (before, after) in changes {
+///     println!("{}: {} -> {}", before.id, before.age, after.age);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn execute_returning_changes<T, C>(
+    client: &C,
+    table: &str,
+    key: &str,
+    set: Query<'_>,
+    filter: Query<'_>,
+) -> Result<Vec<(T, T)>>
+where
+    T: FromSqlRow,
+    C: GenericClient + Sync,
+{
+    let mut query = Query::new(
+        format!("WITH __before AS (SELECT * FROM {} WHERE (", table),
+        Vec::new(),
+    );
+    query.append(filter);
+    query.append(Query::new(
+        format!(") FOR UPDATE), __after AS (UPDATE {} SET ", table),
+        Vec::new(),
+    ));
+    query.append(set);
+    query.append(Query::new(
+        format!(
+            " WHERE {key} IN (SELECT {key} FROM __before) RETURNING *) \
+             SELECT __before.*, __after.* FROM __before \
+             JOIN __after ON __before.{key} = __after.{key}",
+            key = key
+        ),
+        Vec::new(),
+    ));
+
+    let rows = query.query(client).await?;
+
+    rows.iter()
+        .map(|row| {
+            let half = row.len() / 2;
+            let before = T::from_row(&row.slice(0..half)?)?;
+            let after = T::from_row(&row.slice(half..row.len())?)?;
+            Ok((before, after))
+        })
+        .collect::<Result<_, _>>()
+        .map_err(execute::Error::Extract)
+        .map_err(Into::into)
+}
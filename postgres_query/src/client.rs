@@ -1,17 +1,40 @@
 //! Abstractions over client-like types.
 
+mod balancer;
 mod cache;
+mod exclusive;
+mod health;
+mod integrity;
+mod recording;
+mod result_cache;
+mod router;
+mod savepoint;
+mod scoped;
 
-pub use cache::Caching;
+pub use balancer::{Backend, Balancer, Session};
+pub use cache::{Caching, Detached, PrepareEvent, PreparedQuery};
+pub use exclusive::Exclusive;
+pub use health::HealthCheck;
+pub use integrity::Integrity;
+pub use recording::{Mutation, Recording};
+pub use result_cache::ResultCache;
+pub use router::Router;
+pub use savepoint::Nested;
+pub use scoped::Scoped;
 
+use crate::execute::{self, ExecuteOptions};
+use crate::{parse, Parameter, Query};
 use async_trait::async_trait;
-use postgres_types::ToSql;
-use tokio_postgres::{error::Error as SqlError, Client, RowStream, Statement, Transaction};
+use bytes::Bytes;
+use postgres_types::{ToSql, Type};
+use std::sync::Arc;
+use tokio_postgres::{
+    error::Error as SqlError, Client, CopyInSink, RowStream, Statement, Transaction,
+};
 
 #[cfg(feature = "deadpool")]
 use deadpool_postgres::{Client as DpClient, ClientWrapper as DpClientWrapper};
 
-
 /// A generic client with basic functionality.
 #[async_trait]
 pub trait GenericClient {
@@ -32,6 +55,50 @@ pub trait GenericClient {
         self.prepare(sql).await
     }
 
+    /// Prepare a SQL query for execution, with explicit types for some or all of its parameters.
+    /// See [`Client::prepare_typed`] for more info.
+    ///
+    /// By default, the parameters are cast to their types within the SQL (`$1` becomes
+    /// `$1::"pg_catalog"."int8"`), which the server infers the same types from, and the result is
+    /// passed to [`prepare`]. Implementors wrapping a client should forward this method instead.
+    ///
+    /// [`Client::prepare_typed`]:
+    /// https://docs.rs/tokio-postgres/0.7.0/tokio_postgres/struct.Client.html#method.prepare_typed
+    /// [`prepare`]: #tymethod.prepare
+    async fn prepare_typed(&self, sql: &str, types: &[Type]) -> Result<Statement, SqlError> {
+        if types.is_empty() {
+            self.prepare(sql).await
+        } else {
+            self.prepare(&parse::cast_parameters(sql, types)).await
+        }
+    }
+
+    /// The same as [`prepare_static`], but with explicit parameter types. Implementors which cache
+    /// statements should take the types into account as well.
+    ///
+    /// [`prepare_static`]: #method.prepare_static
+    async fn prepare_typed_static(
+        &self,
+        sql: &'static str,
+        types: &[Type],
+    ) -> Result<Statement, SqlError> {
+        self.prepare_typed(sql, types).await
+    }
+
+    /// The same as [`prepare_static`], but with the SQL prefixed by a comment holding `tag`, as
+    /// set by [`ExecuteOptions::tag`]. Implementors which cache statements should take the tag
+    /// into account as well.
+    ///
+    /// [`prepare_static`]: #method.prepare_static
+    /// [`ExecuteOptions::tag`]: ../execute/struct.ExecuteOptions.html#method.tag
+    async fn prepare_tagged_static(
+        &self,
+        sql: &'static str,
+        tag: &str,
+    ) -> Result<Statement, SqlError> {
+        self.prepare(&execute::tagged(sql, tag)).await
+    }
+
     /// Execute the given statement with the parameters specified and return the number of affected
     /// rows. See [`Client::execute_raw`] for more info.
     ///
@@ -53,6 +120,68 @@ pub trait GenericClient {
         statement: &Statement,
         parameters: &[&'a (dyn ToSql + Sync)],
     ) -> Result<RowStream, SqlError>;
+
+    /// The options used by queries executed through this client, unless the query overrides them.
+    /// See [`ExecuteOptions`].
+    ///
+    /// [`ExecuteOptions`]: ../execute/struct.ExecuteOptions.html
+    fn execute_options(&self) -> ExecuteOptions {
+        ExecuteOptions::default()
+    }
+
+    /// Whether queries executed through this client run within a transaction, which is required
+    /// by options that change run-time parameters, such as [`ExecuteOptions::local`]. Wrappers
+    /// should forward it to the client they wrap.
+    ///
+    /// [`ExecuteOptions::local`]: ../execute/struct.ExecuteOptions.html#method.local
+    fn in_transaction(&self) -> bool {
+        false
+    }
+
+    /// Whether nothing but this client uses its connection, so that queries which set run-time
+    /// parameters may run within a transaction of their own when not already within one. See
+    /// [`Exclusive`]. Wrappers should forward it to the client they wrap.
+    ///
+    /// [`Exclusive`]: struct.Exclusive.html
+    fn exclusive(&self) -> bool {
+        false
+    }
+
+    /// Called by `Query` before it is prepared through this client, which may refuse to execute
+    /// it. Used by [`Scoped`]; wrappers should forward it to the client they wrap.
+    ///
+    /// [`Scoped`]: struct.Scoped.html
+    #[doc(hidden)]
+    fn __check_query(&self, _query: &Query<'_>) -> crate::Result<()> {
+        Ok(())
+    }
+
+    /// Called by `Query::execute` once the query has been executed through this client. Used by
+    /// [`Recording`]; wrappers should forward it to the client they wrap.
+    ///
+    /// [`Recording`]: struct.Recording.html
+    #[doc(hidden)]
+    fn __executed(
+        &self,
+        _sql: &str,
+        _parameters: &[Parameter<'_>],
+        _tag: Option<&str>,
+        _rows: u64,
+    ) {
+    }
+}
+
+/// A client which can also copy data into the database, as used by [`CopyIn`].
+///
+/// [`CopyIn`]: ../copy/struct.CopyIn.html
+#[async_trait]
+pub trait CopyClient: GenericClient {
+    /// Execute a `COPY ... FROM STDIN` statement and return a sink for the data to copy. See
+    /// [`Client::copy_in`] for more info.
+    ///
+    /// [`Client::copy_in`]:
+    /// https://docs.rs/tokio-postgres/0.7.0/tokio_postgres/struct.Client.html#method.copy_in
+    async fn copy_in_raw(&self, statement: &Statement) -> Result<CopyInSink<Bytes>, SqlError>;
 }
 
 fn slice_iter<'a>(
@@ -68,6 +197,11 @@ impl GenericClient for Client {
         Client::prepare(self, sql).await
     }
 
+    #[deny(unconditional_recursion)]
+    async fn prepare_typed(&self, sql: &str, types: &[Type]) -> Result<Statement, SqlError> {
+        Client::prepare_typed(self, sql, types).await
+    }
+
     #[deny(unconditional_recursion)]
     async fn execute_raw<'a>(
         &'a self,
@@ -87,6 +221,14 @@ impl GenericClient for Client {
     }
 }
 
+#[async_trait]
+impl CopyClient for Client {
+    #[deny(unconditional_recursion)]
+    async fn copy_in_raw(&self, statement: &Statement) -> Result<CopyInSink<Bytes>, SqlError> {
+        Client::copy_in(self, statement).await
+    }
+}
+
 #[cfg(feature = "deadpool")]
 #[async_trait]
 impl GenericClient for DpClient {
@@ -95,6 +237,11 @@ impl GenericClient for DpClient {
         DpClientWrapper::prepare(self, sql).await
     }
 
+    #[deny(unconditional_recursion)]
+    async fn prepare_typed(&self, sql: &str, types: &[Type]) -> Result<Statement, SqlError> {
+        DpClientWrapper::prepare_typed(self, sql, types).await
+    }
+
     #[deny(unconditional_recursion)]
     async fn execute_raw<'a>(
         &'a self,
@@ -114,12 +261,25 @@ impl GenericClient for DpClient {
     }
 }
 
+#[cfg(feature = "deadpool")]
+#[async_trait]
+impl CopyClient for DpClient {
+    #[deny(unconditional_recursion)]
+    async fn copy_in_raw(&self, statement: &Statement) -> Result<CopyInSink<Bytes>, SqlError> {
+        Client::copy_in(&*self, statement).await
+    }
+}
+
 #[async_trait]
 impl GenericClient for Transaction<'_> {
     async fn prepare(&self, sql: &str) -> Result<Statement, SqlError> {
         Transaction::prepare(self, sql).await
     }
 
+    async fn prepare_typed(&self, sql: &str, types: &[Type]) -> Result<Statement, SqlError> {
+        Transaction::prepare_typed(self, sql, types).await
+    }
+
     async fn execute_raw<'a>(
         &'a self,
         statement: &Statement,
@@ -135,16 +295,55 @@ impl GenericClient for Transaction<'_> {
     ) -> Result<RowStream, SqlError> {
         Transaction::query_raw(self, statement, slice_iter(parameters)).await
     }
+
+    fn in_transaction(&self) -> bool {
+        true
+    }
+}
+
+#[async_trait]
+impl CopyClient for Transaction<'_> {
+    async fn copy_in_raw(&self, statement: &Statement) -> Result<CopyInSink<Bytes>, SqlError> {
+        Transaction::copy_in(self, statement).await
+    }
 }
 
 macro_rules! client_deref_impl {
-    ($($target:tt)+) => {
+    ($target:ty $(, $bound:path)*) => {
         #[async_trait]
-        impl<T> GenericClient for $($target)+ where T: GenericClient + Sync {
+        impl<T> GenericClient for $target where T: GenericClient + Sync $(+ $bound)* {
             async fn prepare(&self, sql: &str) -> Result<Statement, SqlError> {
                 T::prepare(self, sql).await
             }
 
+            async fn prepare_static(&self, sql: &'static str) -> Result<Statement, SqlError> {
+                T::prepare_static(self, sql).await
+            }
+
+            async fn prepare_typed(
+                &self,
+                sql: &str,
+                types: &[Type],
+            ) -> Result<Statement, SqlError> {
+                T::prepare_typed(self, sql, types).await
+            }
+
+            async fn prepare_typed_static(
+                &self,
+                sql: &'static str,
+                types: &[Type],
+            ) -> Result<Statement, SqlError> {
+                T::prepare_typed_static(self, sql, types).await
+            }
+
+            async fn prepare_tagged_static(
+                &self,
+                sql: &'static str,
+                tag: &str,
+            ) -> Result<Statement, SqlError> {
+                T::prepare_tagged_static(self, sql, tag).await
+            }
+
             async fn execute_raw<'a>(
                 &'a self,
                 statement: &Statement,
@@ -160,8 +359,48 @@ macro_rules! client_deref_impl {
             ) -> Result<RowStream, SqlError> {
                 T::query_raw(self, statement, parameters).await
             }
+
+            fn execute_options(&self) -> ExecuteOptions {
+                T::execute_options(self)
+            }
+
+            fn in_transaction(&self) -> bool {
+                T::in_transaction(self)
+            }
+
+            fn exclusive(&self) -> bool {
+                T::exclusive(self)
+            }
+
+            fn __check_query(&self, query: &Query<'_>) -> crate::Result<()> {
+                T::__check_query(self, query)
+            }
+
+            fn __executed(
+                &self,
+                sql: &str,
+                parameters: &[Parameter<'_>],
+                tag: Option<&str>,
+                rows: u64,
+            ) {
+                T::__executed(self, sql, parameters, tag, rows)
+            }
+        }
+
+        #[async_trait]
+        impl<T> CopyClient for $target where T: CopyClient + Sync $(+ $bound)* {
+            async fn copy_in_raw(
+                &self,
+                statement: &Statement,
+            ) -> Result<CopyInSink<Bytes>, SqlError> {
+                T::copy_in_raw(self, statement).await
+            }
         }
     }
 }
 
 client_deref_impl!(&T);
+
+// Shared or boxed clients, such as an `Arc<Caching<Client>>` handed to several tasks.
+client_deref_impl!(Box<T>);
+client_deref_impl!(Arc<T>, Send);
@@ -0,0 +1,442 @@
+//! Balance reads across replicas according to how well they respond.
+
+use super::{CopyClient, GenericClient, HealthCheck};
+use crate::execute::ExecuteOptions;
+use crate::{Parameter, Query};
+use async_trait::async_trait;
+use bytes::Bytes;
+use postgres_types::{ToSql, Type};
+use std::error::Error as _;
+use std::future::Future;
+use std::io;
+use std::ops::Deref;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio_postgres::{error::Error as SqlError, CopyInSink, RowStream, Statement};
+
+/// The weight of the latest sample in the rolling latency and error rate.
+const DECAY: f64 = 0.2;
+
+/// Clients for a primary database and its read replicas, which sends reads to the replica that
+/// currently responds the fastest.
+///
+/// Every client is wrapped in a [`Backend`], which keeps a rolling average of the latency and the
+/// rate of failures of the statements executed through it. Only executing a statement is tracked,
+/// since preparing one may be answered from a cache without reaching the server. [`reader`] picks
+/// the replica with the lowest latency among those whose error rate is acceptable, and falls back
+/// to the primary if there are none. Replicas which have not executed anything yet are tried
+/// first.
+///
+/// Reads within a [`Session`] go to the primary for a while after the session wrote something,
+/// so that they see their own writes even if the replicas lag behind:
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{client::Balancer, query, Result};
+/// # use std::time::Duration;
+/// # async fn foo() -> Result<()> {
+/// # let (primary, east, west): (Client, Client, Client) = unimplemented!();
+/// let balancer = Balancer::new(primary)
+///     .replica(east)
+///     .replica(west)
+///     .pin_writes(Duration::from_secs(2));
+///
+/// // Runs on the replica which has been the fastest so far.
+/// let people: Vec<(String,)> = query!("SELECT name FROM people")
+///     .fetch(balancer.reader())
+///     .await?;
+///
+/// let session = balancer.session();
+/// query!("INSERT INTO people VALUES ($name)", name = "John Wick")
+///     .execute(session.writer())
+///     .await?;
+///
+/// // Runs on the primary, since the session just wrote to it.
+/// let people: Vec<(String,)> = query!("SELECT name FROM people")
+///     .fetch(session.reader())
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// As with [`Router`], the balancer hands out clients rather than being a client itself. Which
+/// queries only read is up to the caller.
+///
+/// [`Backend`]: struct.Backend.html
+/// [`reader`]: #method.reader
+/// [`Session`]: struct.Session.html
+/// [`Router`]: struct.Router.html
+#[derive(Debug)]
+pub struct Balancer<C> {
+    primary: Backend<C>,
+    replicas: Vec<Backend<C>>,
+    max_error_rate: f64,
+    pin: Duration,
+}
+
+/// A client whose latency and error rate are tracked by a [`Balancer`].
+///
+/// Dereferences to the underlying client, but only statements executed through the backend itself
+/// are tracked.
+///
+/// [`Balancer`]: struct.Balancer.html
+#[derive(Debug)]
+pub struct Backend<C> {
+    client: C,
+    stats: Mutex<Stats>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Stats {
+    /// The rolling average latency of successful statements, in seconds.
+    latency: Option<f64>,
+    error_rate: f64,
+}
+
+/// Reads and writes which should see each other. See [`Balancer::session`].
+///
+/// [`Balancer::session`]: struct.Balancer.html#method.session
+#[derive(Debug)]
+pub struct Session<'a, C> {
+    balancer: &'a Balancer<C>,
+    last_write: Mutex<Option<Instant>>,
+}
+
+impl<C> Balancer<C> {
+    /// Send everything to `primary`, until replicas are added.
+    pub fn new(primary: C) -> Balancer<C> {
+        Balancer {
+            primary: Backend::new(primary),
+            replicas: Vec::new(),
+            max_error_rate: 0.5,
+            pin: Duration::from_secs(1),
+        }
+    }
+
+    /// Add a replica to read from.
+    pub fn replica(mut self, client: C) -> Balancer<C> {
+        self.replicas.push(Backend::new(client));
+        self
+    }
+
+    /// Stop reading from replicas whose rolling error rate is above `rate` (between 0 and 1),
+    /// until they recover. Defaults to 0.5.
+    ///
+    /// A replica which is not read from cannot recover on its own, so use [`check`] to probe them
+    /// periodically.
+    ///
+    /// [`check`]: #method.check
+    pub fn max_error_rate(mut self, rate: f64) -> Balancer<C> {
+        self.max_error_rate = rate;
+        self
+    }
+
+    /// How long a [`Session`] reads from the primary after it last wrote. Defaults to one second.
+    ///
+    /// [`Session`]: struct.Session.html
+    pub fn pin_writes(mut self, window: Duration) -> Balancer<C> {
+        self.pin = window;
+        self
+    }
+
+    /// The client to write to.
+    pub fn primary(&self) -> &Backend<C> {
+        &self.primary
+    }
+
+    /// The replicas, in the order they were added.
+    pub fn replicas(&self) -> &[Backend<C>] {
+        &self.replicas
+    }
+
+    /// The healthiest replica, or the primary if every replica is failing.
+    pub fn reader(&self) -> &Backend<C> {
+        self.replicas
+            .iter()
+            .map(|replica| (replica, replica.stats()))
+            .filter(|(_, stats)| stats.error_rate <= self.max_error_rate)
+            .min_by(|(_, a), (_, b)| {
+                let a = a.latency.unwrap_or(0.0);
+                let b = b.latency.unwrap_or(0.0);
+                a.total_cmp(&b)
+            })
+            .map_or(&self.primary, |(replica, _)| replica)
+    }
+
+    /// Start a session, whose reads go to the primary for a while after each of its writes. See
+    /// [`pin_writes`].
+    ///
+    /// [`pin_writes`]: #method.pin_writes
+    pub fn session(&self) -> Session<'_, C> {
+        Session {
+            balancer: self,
+            last_write: Mutex::new(None),
+        }
+    }
+
+    /// Ping every backend, waiting at most `timeout` for each, and record the outcome as if a
+    /// statement had been executed. Lets replicas which are no longer read from recover.
+    pub async fn check(&self, timeout: Duration)
+    where
+        C: GenericClient + Sync,
+    {
+        let backends = std::iter::once(&self.primary).chain(&self.replicas);
+        for backend in backends {
+            let start = Instant::now();
+            let healthy = backend.client.is_healthy(timeout).await;
+            backend.record(start.elapsed(), healthy);
+        }
+    }
+}
+
+impl<C> Backend<C> {
+    fn new(client: C) -> Backend<C> {
+        Backend {
+            client,
+            stats: Mutex::new(Stats::default()),
+        }
+    }
+
+    fn stats(&self) -> Stats {
+        *self.stats.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// The rolling average latency of the statements which succeeded, if any have.
+    pub fn latency(&self) -> Option<Duration> {
+        self.stats().latency.map(Duration::from_secs_f64)
+    }
+
+    /// The rolling rate, between 0 and 1, at which statements failed because of the backend.
+    pub fn error_rate(&self) -> f64 {
+        self.stats().error_rate
+    }
+
+    /// Record the outcome of a statement which was executed without going through the backend.
+    pub fn record(&self, latency: Duration, success: bool) {
+        let mut stats = self.stats.lock().unwrap_or_else(|error| error.into_inner());
+        let failure = if success { 0.0 } else { 1.0 };
+        stats.error_rate += DECAY * (failure - stats.error_rate);
+
+        if success {
+            let latency = latency.as_secs_f64();
+            stats.latency = Some(match stats.latency {
+                Some(average) => average + DECAY * (latency - average),
+                None => latency,
+            });
+        }
+    }
+
+    async fn track<T, F>(&self, f: F) -> Result<T, SqlError>
+    where
+        F: Future<Output = Result<T, SqlError>>,
+    {
+        let start = Instant::now();
+        let result = f.await;
+        match &result {
+            Ok(_) => self.record(start.elapsed(), true),
+            Err(error) if is_backend_failure(error) => self.record(start.elapsed(), false),
+            // The backend responded, so it is healthy, but the latency says nothing about it.
+            Err(_) => {}
+        }
+        result
+    }
+}
+
+/// Whether the error says something about the health of the backend, rather than about the
+/// statement: the connection failed with an I/O error, or is closed.
+fn is_backend_failure(error: &SqlError) -> bool {
+    error.is_closed()
+        || error
+            .source()
+            .is_some_and(|source| source.is::<io::Error>())
+}
+
+impl<C> Deref for Backend<C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        &self.client
+    }
+}
+
+#[async_trait]
+impl<C> GenericClient for Backend<C>
+where
+    C: GenericClient + Sync + Send,
+{
+    async fn prepare(&self, sql: &str) -> Result<Statement, SqlError> {
+        self.client.prepare(sql).await
+    }
+
+    async fn prepare_static(&self, sql: &'static str) -> Result<Statement, SqlError> {
+        self.client.prepare_static(sql).await
+    }
+
+    async fn prepare_typed(&self, sql: &str, types: &[Type]) -> Result<Statement, SqlError> {
+        self.client.prepare_typed(sql, types).await
+    }
+
+    async fn prepare_typed_static(
+        &self,
+        sql: &'static str,
+        types: &[Type],
+    ) -> Result<Statement, SqlError> {
+        self.client.prepare_typed_static(sql, types).await
+    }
+
+    async fn prepare_tagged_static(
+        &self,
+        sql: &'static str,
+        tag: &str,
+    ) -> Result<Statement, SqlError> {
+        self.client.prepare_tagged_static(sql, tag).await
+    }
+
+    async fn execute_raw<'a>(
+        &'a self,
+        statement: &Statement,
+        parameters: &[&'a (dyn ToSql + Sync)],
+    ) -> Result<u64, SqlError> {
+        self.track(self.client.execute_raw(statement, parameters))
+            .await
+    }
+
+    /// Only the time until the first response is tracked, not the time spent streaming the rows.
+    async fn query_raw<'a>(
+        &'a self,
+        statement: &Statement,
+        parameters: &[&'a (dyn ToSql + Sync)],
+    ) -> Result<RowStream, SqlError> {
+        self.track(self.client.query_raw(statement, parameters))
+            .await
+    }
+
+    fn execute_options(&self) -> ExecuteOptions {
+        self.client.execute_options()
+    }
+
+    fn in_transaction(&self) -> bool {
+        self.client.in_transaction()
+    }
+
+    fn exclusive(&self) -> bool {
+        self.client.exclusive()
+    }
+
+    fn __check_query(&self, query: &Query<'_>) -> crate::Result<()> {
+        self.client.__check_query(query)
+    }
+
+    fn __executed(&self, sql: &str, parameters: &[Parameter<'_>], tag: Option<&str>, rows: u64) {
+        self.client.__executed(sql, parameters, tag, rows)
+    }
+}
+
+#[async_trait]
+impl<C> CopyClient for Backend<C>
+where
+    C: CopyClient + Sync + Send,
+{
+    async fn copy_in_raw(&self, statement: &Statement) -> Result<CopyInSink<Bytes>, SqlError> {
+        self.client.copy_in_raw(statement).await
+    }
+}
+
+impl<'a, C> Session<'a, C> {
+    /// The client to read from: the primary if the session wrote within the window given to
+    /// [`Balancer::pin_writes`], otherwise the healthiest replica.
+    ///
+    /// [`Balancer::pin_writes`]: struct.Balancer.html#method.pin_writes
+    pub fn reader(&self) -> &'a Backend<C> {
+        let last_write = *self
+            .last_write
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        match last_write {
+            Some(last_write) if last_write.elapsed() < self.balancer.pin => &self.balancer.primary,
+            _ => self.balancer.reader(),
+        }
+    }
+
+    /// The client to write to, which is always the primary. Reads are pinned to the primary from
+    /// now on, until the window has passed.
+    pub fn writer(&self) -> &'a Backend<C> {
+        let mut last_write = self
+            .last_write
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        *last_write = Some(Instant::now());
+        &self.balancer.primary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn read_from_fastest_replica() {
+        let balancer = Balancer::new("primary")
+            .replica("east")
+            .replica("west")
+            .replica("north");
+        assert_eq!(**balancer.reader(), "east");
+
+        balancer.replicas[0].record(30 * MS, true);
+        balancer.replicas[1].record(20 * MS, true);
+        assert_eq!(**balancer.reader(), "north");
+
+        balancer.replicas[2].record(50 * MS, true);
+        assert_eq!(**balancer.reader(), "west");
+
+        // The average only moves part of the way towards a slow sample.
+        balancer.replicas[1].record(60 * MS, true);
+        assert_eq!(balancer.replicas[1].latency(), Some(28 * MS));
+        assert_eq!(**balancer.reader(), "west");
+        balancer.replicas[1].record(60 * MS, true);
+        assert_eq!(**balancer.reader(), "east");
+    }
+
+    #[test]
+    fn avoid_failing_replicas() {
+        let balancer = Balancer::new("primary")
+            .replica("east")
+            .replica("west")
+            .max_error_rate(0.3);
+        balancer.replicas[0].record(10 * MS, true);
+        balancer.replicas[1].record(40 * MS, true);
+
+        balancer.replicas[0].record(10 * MS, false);
+        assert_eq!(**balancer.reader(), "east");
+        balancer.replicas[0].record(10 * MS, false);
+        assert_eq!(**balancer.reader(), "west");
+
+        for _ in 0..2 {
+            balancer.replicas[1].record(10 * MS, false);
+        }
+        assert_eq!(**balancer.reader(), "primary");
+
+        for _ in 0..3 {
+            balancer.replicas[0].record(10 * MS, true);
+        }
+        assert_eq!(**balancer.reader(), "east");
+    }
+
+    #[test]
+    fn pin_reads_after_writes() {
+        let balancer = Balancer::new("primary").replica("replica");
+        let session = balancer.session();
+        assert_eq!(**session.reader(), "replica");
+        assert_eq!(**session.writer(), "primary");
+        assert_eq!(**session.reader(), "primary");
+
+        let balancer = Balancer::new("primary")
+            .replica("replica")
+            .pin_writes(Duration::from_secs(0));
+        let session = balancer.session();
+        assert_eq!(**session.writer(), "primary");
+        assert_eq!(**session.reader(), "replica");
+    }
+}
@@ -1,21 +1,65 @@
 //! A client which caches repeated requests.
 
-use super::GenericClient;
-use crate::error::Error;
+use super::{CopyClient, GenericClient};
+use crate::error::{Error, Result as QueryResult};
+use crate::execute::{self, ExecuteOptions};
+use crate::extract::FromSqlRow;
+use crate::parse;
+use crate::{Parameter, Query, Sql};
 use async_trait::async_trait;
-use futures::lock::Mutex;
-use postgres_types::ToSql;
+use bytes::Bytes;
+use futures::channel::oneshot;
+use futures::TryStreamExt;
+use postgres_types::{ToSql, Type};
 use std::collections::HashMap;
-use std::hash::Hash;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
 use std::mem;
 use std::ops::{Deref, DerefMut};
-use std::sync::Arc;
-use tokio_postgres::{error::Error as SqlError, RowStream, Statement};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio_postgres::{error::Error as SqlError, CopyInSink, RowStream, Statement};
 
 /// A client wrapper which caches prepared queries.
 ///
-/// Only queries prepared through the `GenericClient::prepare_static` trait method are actually
-/// cached.
+/// Only queries prepared through the `GenericClient::prepare_static`,
+/// `GenericClient::prepare_typed_static` and `GenericClient::prepare_tagged_static` trait methods
+/// are actually cached. Typed and tagged statements are cached separately for each set of
+/// parameter types and each tag. Identical `query!` literals share the same
+/// statement, even when they are written out at different call sites.
+///
+/// If the same static query is prepared concurrently, for example by many queries running at once
+/// within [`execute_batch!`], it is only prepared once, and the statement is shared by all of them.
+///
+/// Statements which change the schema, such as `CREATE` or `ALTER`, are not cached, since cached
+/// statements would keep referring to the schema as it was when they were prepared. See
+/// [`skip_cache_for`] to change which statements are left out.
+///
+/// The wrapper may also hold the default [`ExecuteOptions`] of the queries executed through it.
+///
+/// Hooks set with [`on_prepare`] and [`on_evict`] observe the cache, for example to verify that
+/// it is effective or to log bursts of statements being prepared:
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::client::Caching;
+/// # fn foo(client: Client) {
+/// let client = Caching::new(client)
+///     .on_prepare(|event| {
+///         if !event.cached {
+///             println!("prepared `{}` in {:?}", event.sql, event.duration);
+///         }
+///     })
+///     .on_evict(|sql| println!("evicted `{}`", sql));
+/// # }
+/// ```
+///
+/// [`ExecuteOptions`]: ../execute/struct.ExecuteOptions.html
+/// [`execute_batch!`]: ../macro.execute_batch.html
+/// [`on_prepare`]: #method.on_prepare
+/// [`on_evict`]: #method.on_evict
+/// [`skip_cache_for`]: #method.skip_cache_for
 #[derive(Clone)]
 pub struct Caching<C>
 where
@@ -23,18 +67,95 @@ where
 {
     client: C,
     cache: Cache,
+    typed: TypedCache,
+    options: ExecuteOptions,
+    hooks: Hooks,
+    uncached: Keywords,
+    scope: Option<Arc<Scope>>,
 }
 
-type Cache = Arc<Mutex<DynamicCache<StrKey, Statement>>>;
+type Cache = Arc<Mutex<DynamicCache<StrKey, Entry>>>;
+type TypedCache = Arc<Mutex<DynamicCache<TypedKey, Statement>>>;
+
+/// The leading keywords of the statements which are never cached.
+type Keywords = Arc<[&'static str]>;
+
+/// Statements which change the schema, and `EXPLAIN`, whose plan should reflect the current state of
+/// the database.
+const UNCACHED_KEYWORDS: &[&str] = &[
+    "CREATE", "ALTER", "DROP", "TRUNCATE", "COMMENT", "GRANT", "REVOKE", "EXPLAIN",
+];
 
 // We uniquely identify a `&'static str` using a pointer and a length.
 // Since shared references with static lifetimes are guaranteed not to change we can assert that two
 // `&'static str`s that point to the same value in fact are the same value during the whole duration
-// of the program.
-#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+// of the program. The text itself is kept so that it can be reported to the hooks.
+#[derive(Debug, Copy, Clone)]
 struct StrKey {
-    ptr: usize,
-    len: usize,
+    text: &'static str,
+}
+
+type PrepareHook = Arc<dyn Fn(&PrepareEvent) + Send + Sync>;
+type EvictHook = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// The hooks of a [`Caching`] wrapper, shared by its clones.
+#[derive(Clone, Default)]
+struct Hooks {
+    prepare: Option<PrepareHook>,
+    evict: Option<EvictHook>,
+}
+
+/// Reported to the [`on_prepare`] hook whenever a statement is requested from a [`Caching`]
+/// wrapper.
+///
+/// [`on_prepare`]: struct.Caching.html#method.on_prepare
+/// [`Caching`]: struct.Caching.html
+#[derive(Debug, Clone)]
+pub struct PrepareEvent<'a> {
+    pub sql: &'a str,
+    /// The parameter types the statement was prepared with, which are empty unless they were
+    /// given explicitly.
+    pub types: &'a [Type],
+    /// Whether the statement was served from the cache, including when it was prepared by a
+    /// concurrent caller.
+    pub cached: bool,
+    /// The time it took to prepare the statement, or to find it in the cache.
+    pub duration: Duration,
+}
+
+/// The statements first prepared within a transaction, which are evicted from the cache unless the
+/// transaction is committed. Although prepared statements outlive the transaction, they may refer
+/// to types or tables created within it.
+struct Scope {
+    cache: Cache,
+    typed: TypedCache,
+    hooks: Hooks,
+    /// The scope of the enclosing transaction, which takes over the statements once this one is
+    /// committed.
+    parent: Option<Arc<Scope>>,
+    prepared: Mutex<Prepared>,
+    committed: AtomicBool,
+}
+
+#[derive(Default)]
+struct Prepared {
+    keys: Vec<StrKey>,
+    typed: Vec<TypedKey>,
+}
+
+/// A cached statement, or one which is still being prepared.
+enum Entry {
+    Prepared(Statement),
+    /// Other callers waiting for the statement to be prepared.
+    Preparing(Vec<oneshot::Sender<Statement>>),
+}
+
+/// Identifies a statement prepared with explicit parameter types, or with a tag.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+struct TypedKey {
+    sql: StrKey,
+    types: Vec<Type>,
+    tag: Option<String>,
 }
 
 /// A cache optimized for a small number of items.
@@ -62,6 +183,78 @@ where
         Caching {
             client,
             cache: Cache::default(),
+            typed: TypedCache::default(),
+            options: ExecuteOptions::default(),
+            hooks: Hooks::default(),
+            uncached: UNCACHED_KEYWORDS.into(),
+            scope: None,
+        }
+    }
+
+    /// Use these options for all queries executed through this client, unless a query overrides
+    /// them. Options which are not set fall back to those of the inner client.
+    pub fn with_options(mut self, options: ExecuteOptions) -> Caching<C> {
+        self.options = options;
+        self
+    }
+
+    /// Prepare statements which start with any of `keywords` (in any case) every time, instead of
+    /// caching them. Replaces the default keywords, which are those of statements that change the
+    /// schema (`CREATE`, `ALTER`, `DROP`, `TRUNCATE`, `COMMENT`, `GRANT` and `REVOKE`), as well as
+    /// `EXPLAIN`.
+    ///
+    /// A single query may bypass the cache with [`Query::no_cache`].
+    ///
+    /// [`Query::no_cache`]: ../struct.Query.html#method.no_cache
+    pub fn skip_cache_for(mut self, keywords: &[&'static str]) -> Caching<C> {
+        self.uncached = keywords.into();
+        self
+    }
+
+    /// Call `hook` whenever a statement is prepared through this client, or served from its cache.
+    /// Replaces any previous `on_prepare` hook, and is shared by clones of this client.
+    pub fn on_prepare<F>(mut self, hook: F) -> Caching<C>
+    where
+        F: Fn(&PrepareEvent) + Send + Sync + 'static,
+    {
+        self.hooks.prepare = Some(Arc::new(hook));
+        self
+    }
+
+    /// Call `hook` with the text of every statement removed from the cache, either by [`clear`],
+    /// when a transaction started with [`transaction`] is rolled back, or when the cache is moved
+    /// to a new connection with [`from_parts`]. Replaces any previous `on_evict` hook, and is
+    /// shared by clones of this client.
+    ///
+    /// [`clear`]: #method.clear
+    /// [`transaction`]: #method.transaction
+    /// [`from_parts`]: #method.from_parts
+    pub fn on_evict<F>(mut self, hook: F) -> Caching<C>
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.hooks.evict = Some(Arc::new(hook));
+        self
+    }
+
+    /// Remove all statements from the cache, so that they are prepared again the next time they
+    /// are used. Useful after the schema has changed, which may invalidate prepared statements.
+    ///
+    /// Statements which are still being prepared are kept.
+    pub async fn clear(&self) {
+        let evicted = {
+            let mut cache = lock(&self.cache);
+            cache.retain(|_, entry| matches!(entry, Entry::Preparing(_)))
+        };
+        let typed = mem::take(&mut *lock(&self.typed));
+
+        if let Some(hook) = &self.hooks.evict {
+            for key in evicted {
+                hook(key.text);
+            }
+            for key in typed.into_keys() {
+                hook(key.sql.text);
+            }
         }
     }
 
@@ -69,6 +262,214 @@ where
     pub fn into_inner(self) -> C {
         self.client
     }
+
+    /// Split the wrapper into the inner client and the rest of its state, such as its options.
+    ///
+    /// Together with [`from_parts`], this allows the wrapper to be moved to a new connection when
+    /// the current one is lost:
+    ///
+    /// ```
+    /// # use tokio_postgres::{Client, NoTls};
+    /// # use postgres_query::{client::Caching, Result};
+    /// # async fn foo() -> Result<()> {
+    /// # let client: Client = unimplemented!();
+    /// let client = Caching::new(client);
+    /// // ...
+    /// if client.is_closed() {
+    ///     let (_, detached) = client.into_parts();
+    ///     let (reconnected, connection) = tokio_postgres::connect("...", NoTls).await.unwrap();
+    /// #   tokio::spawn(connection);
+    ///     let client = Caching::from_parts(reconnected, detached);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`from_parts`]: #method.from_parts
+    pub fn into_parts(self) -> (C, Detached) {
+        let detached = Detached {
+            cache: self.cache,
+            typed: self.typed,
+            options: self.options,
+            hooks: self.hooks,
+            uncached: self.uncached,
+        };
+        (self.client, detached)
+    }
+
+    /// Wrap a client in the state detached from another wrapper with [`into_parts`].
+    ///
+    /// Prepared statements belong to the connection they were prepared on, so the cache starts
+    /// out empty. Clones of the previous wrapper keep their statements, and do not share the cache
+    /// with the new one. The statements of the previous connection are reported to the
+    /// [`on_evict`] hook, unless a clone still uses them.
+    ///
+    /// [`into_parts`]: #method.into_parts
+    /// [`on_evict`]: #method.on_evict
+    pub fn from_parts(client: C, detached: Detached) -> Caching<C> {
+        let Detached {
+            mut cache,
+            mut typed,
+            options,
+            hooks,
+            uncached,
+        } = detached;
+
+        // The allocations may be reused, unless a clone of the previous wrapper still uses them.
+        let evicted = match Arc::get_mut(&mut cache) {
+            Some(cache) => {
+                mem::take(cache.get_mut().unwrap_or_else(|e| e.into_inner())).into_keys()
+            }
+            None => {
+                cache = Cache::default();
+                Vec::new()
+            }
+        };
+        let evicted_typed = match Arc::get_mut(&mut typed) {
+            Some(typed) => {
+                mem::take(typed.get_mut().unwrap_or_else(|e| e.into_inner())).into_keys()
+            }
+            None => {
+                typed = TypedCache::default();
+                Vec::new()
+            }
+        };
+
+        if let Some(hook) = &hooks.evict {
+            for key in evicted {
+                hook(key.text);
+            }
+            for key in evicted_typed {
+                hook(key.sql.text);
+            }
+        }
+
+        Caching {
+            client,
+            cache,
+            typed,
+            options,
+            hooks,
+            uncached,
+            scope: None,
+        }
+    }
+
+    /// Whether the statement starts with one of the keywords which are never cached.
+    fn skips_cache(&self, sql: &str) -> bool {
+        match leading_keyword(sql) {
+            Some(keyword) => self
+                .uncached
+                .iter()
+                .any(|uncached| uncached.eq_ignore_ascii_case(&keyword)),
+            None => false,
+        }
+    }
+}
+
+/// The first keyword of the statement, in uppercase, skipping any whitespace and comments.
+fn leading_keyword(sql: &str) -> Option<String> {
+    let keywords = parse::top_level_keywords(sql);
+    keywords.into_iter().next().map(|(_, keyword)| keyword)
+}
+
+/// A statement prepared through a [`Caching`] wrapper. See [`Caching::prepared`].
+///
+/// [`Caching`]: struct.Caching.html
+/// [`Caching::prepared`]: struct.Caching.html#method.prepared
+pub struct PreparedQuery<'a, C>
+where
+    C: GenericClient,
+{
+    client: &'a Caching<C>,
+    sql: Sql,
+    options: ExecuteOptions,
+    statement: Statement,
+}
+
+impl<'a, C> PreparedQuery<'a, C>
+where
+    C: GenericClient + Sync + Send,
+{
+    /// Get the prepared statement.
+    pub fn statement(&self) -> &Statement {
+        &self.statement
+    }
+
+    /// Execute the statement with the given parameters and return the number of affected rows.
+    pub async fn execute(&self, parameters: &[Parameter<'_>]) -> QueryResult<u64> {
+        let client = self.client;
+        self.options
+            .run(client, async {
+                let rows = client
+                    .execute_raw(&self.statement, parameters)
+                    .await
+                    .map_err(execute::Error::from)?;
+
+                // Reported to clients such as `Recording`, as if the query had been executed.
+                client.__executed(&self.sql, parameters, self.options.tag.as_deref(), rows);
+                Ok(rows)
+            })
+            .await
+    }
+
+    /// Execute the statement with the given parameters and return the resulting values.
+    pub async fn fetch<T>(&self, parameters: &[Parameter<'_>]) -> QueryResult<Vec<T>>
+    where
+        T: FromSqlRow,
+    {
+        let client = self.client;
+        let rows = self
+            .options
+            .run(client, async {
+                let rows = client
+                    .query_raw(&self.statement, parameters)
+                    .await
+                    .map_err(execute::Error::from)?
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .map_err(execute::Error::from)?;
+                Ok(rows)
+            })
+            .await?;
+
+        let values = T::from_row_multi(&rows).map_err(execute::Error::from)?;
+        Ok(values)
+    }
+
+    /// Execute the statement with the given parameters and return the resulting value. Fails
+    /// unless exactly one row was returned.
+    pub async fn fetch_one<T>(&self, parameters: &[Parameter<'_>]) -> QueryResult<T>
+    where
+        T: FromSqlRow,
+    {
+        let client = self.client;
+        let row = self
+            .options
+            .run(client, async {
+                let rows = client
+                    .query_raw(&self.statement, parameters)
+                    .await
+                    .map_err(execute::Error::from)?;
+                execute::single_row(rows).await
+            })
+            .await?;
+
+        let value = T::from_row(&row).map_err(execute::Error::from)?;
+        Ok(value)
+    }
+}
+
+/// The state of a [`Caching`] wrapper without its client. See [`Caching::into_parts`].
+///
+/// [`Caching`]: struct.Caching.html
+/// [`Caching::into_parts`]: struct.Caching.html#method.into_parts
+pub struct Detached {
+    cache: Cache,
+    typed: TypedCache,
+    options: ExecuteOptions,
+    hooks: Hooks,
+    uncached: Keywords,
 }
 
 impl<C> From<C> for Caching<C>
@@ -106,17 +507,72 @@ where
     C: GenericClient + Sync + Send,
 {
     async fn prepare(&self, sql: &str) -> Result<Statement, SqlError> {
-        self.client.prepare(sql).await
+        let start = Instant::now();
+        let statement = self.client.prepare(sql).await?;
+        self.hooks.prepared(sql, &[], false, start);
+        Ok(statement)
     }
 
     async fn prepare_static(&self, sql: &'static str) -> Result<Statement, SqlError> {
-        if let Some(statement) = self.get_cached(sql).await {
-            Ok(statement)
-        } else {
-            let statement = self.client.prepare_static(sql).await?;
-            self.cache(sql, statement.clone()).await;
-            Ok(statement)
+        if self.skips_cache(sql) {
+            return self.prepare(sql).await;
+        }
+
+        let start = Instant::now();
+        let (statement, cached) = self.prepare_cached(sql).await?;
+        self.hooks.prepared(sql, &[], cached, start);
+        Ok(statement)
+    }
+
+    async fn prepare_typed(&self, sql: &str, types: &[Type]) -> Result<Statement, SqlError> {
+        let start = Instant::now();
+        let statement = self.client.prepare_typed(sql, types).await?;
+        self.hooks.prepared(sql, types, false, start);
+        Ok(statement)
+    }
+
+    async fn prepare_typed_static(
+        &self,
+        sql: &'static str,
+        types: &[Type],
+    ) -> Result<Statement, SqlError> {
+        if self.skips_cache(sql) {
+            return self.prepare_typed(sql, types).await;
         }
+
+        let start = Instant::now();
+        let key = TypedKey {
+            sql: StrKey::new(sql),
+            types: types.to_vec(),
+            tag: None,
+        };
+        let prepare = || self.client.prepare_typed_static(sql, types);
+        let (statement, cached) = self.prepare_keyed(key, prepare).await?;
+
+        self.hooks.prepared(sql, types, cached, start);
+        Ok(statement)
+    }
+
+    async fn prepare_tagged_static(
+        &self,
+        sql: &'static str,
+        tag: &str,
+    ) -> Result<Statement, SqlError> {
+        if self.skips_cache(sql) {
+            return self.prepare(&execute::tagged(sql, tag)).await;
+        }
+
+        let start = Instant::now();
+        let key = TypedKey {
+            sql: StrKey::new(sql),
+            types: Vec::new(),
+            tag: Some(tag.to_owned()),
+        };
+        let prepare = || self.client.prepare_tagged_static(sql, tag);
+        let (statement, cached) = self.prepare_keyed(key, prepare).await?;
+
+        self.hooks.prepared(sql, &[], cached, start);
+        Ok(statement)
     }
 
     async fn execute_raw<'a>(
@@ -134,29 +590,259 @@ where
     ) -> Result<RowStream, SqlError> {
         self.client.query_raw(statement, parameters).await
     }
+
+    fn execute_options(&self) -> ExecuteOptions {
+        self.options.or(self.client.execute_options())
+    }
+
+    fn in_transaction(&self) -> bool {
+        self.client.in_transaction()
+    }
+
+    fn exclusive(&self) -> bool {
+        self.client.exclusive()
+    }
+
+    fn __check_query(&self, query: &Query<'_>) -> crate::Result<()> {
+        self.client.__check_query(query)
+    }
+
+    fn __executed(&self, sql: &str, parameters: &[Parameter<'_>], tag: Option<&str>, rows: u64) {
+        self.client.__executed(sql, parameters, tag, rows)
+    }
+}
+
+#[async_trait]
+impl<C> CopyClient for Caching<C>
+where
+    C: CopyClient + Sync + Send,
+{
+    async fn copy_in_raw(&self, statement: &Statement) -> Result<CopyInSink<Bytes>, SqlError> {
+        self.client.copy_in_raw(statement).await
+    }
 }
 
 impl<C> Caching<C>
 where
-    C: GenericClient,
+    C: GenericClient + Sync + Send,
 {
-    async fn get_cached(&self, sql: &'static str) -> Option<Statement> {
-        let cache = self.cache.lock().await;
-        cache.get(&StrKey::new(sql)).map(Statement::clone)
+    /// Prepare a query once, and return a handle which executes the statement with different
+    /// parameters each time. Unlike executing the query again, which looks up the statement in the
+    /// cache, the handle uses the statement directly, which suits hot loops:
+    ///
+    /// ```
+    /// # use tokio_postgres::Client;
+    /// # use postgres_query::{client::Caching, Query, Result};
+    /// # async fn foo(client: Caching<Client>) -> Result<()> {
+    /// let insert = client
+    ///     .prepared(&Query::new_static("INSERT INTO people VALUES ($1, $2)", vec![]))
+    ///     .await?;
+    ///
+    /// for (name, age) in &[("John Wick", 42), ("Winston", 65)] {
+    ///     insert.execute(&[name, age]).await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// The parameters bound to `query` are ignored, but its options (such as a timeout) apply to
+    /// every execution through the handle.
+    pub async fn prepared<'a>(&'a self, query: &Query<'_>) -> QueryResult<PreparedQuery<'a, C>> {
+        let options = query.options_for(self);
+        let statement = query.prepare(self, &options).await?;
+        Ok(PreparedQuery {
+            client: self,
+            sql: query.sql.clone(),
+            options,
+            statement,
+        })
     }
 
-    async fn cache(&self, sql: &'static str, statement: Statement) {
-        let mut cache = self.cache.lock().await;
-        cache.insert(StrKey::new(sql), statement);
+    /// Prepare a typed or tagged statement with `prepare`, unless it is already cached. Also
+    /// returns whether it was.
+    async fn prepare_keyed<F, P>(
+        &self,
+        key: TypedKey,
+        prepare: P,
+    ) -> Result<(Statement, bool), SqlError>
+    where
+        P: FnOnce() -> F,
+        F: Future<Output = Result<Statement, SqlError>>,
+    {
+        let cached = lock(&self.typed).get(&key).cloned();
+        if let Some(statement) = cached {
+            return Ok((statement, true));
+        }
+
+        let statement = prepare().await?;
+        if let Some(scope) = &self.scope {
+            lock(&scope.prepared).typed.push(key.clone());
+        }
+        lock(&self.typed).insert(key, statement.clone());
+        Ok((statement, false))
+    }
+
+    /// Prepare a static statement, unless it is already cached. Also returns whether it was.
+    async fn prepare_cached(&self, sql: &'static str) -> Result<(Statement, bool), SqlError> {
+        let key = StrKey::new(sql);
+
+        let waiting = {
+            let mut cache = lock(&self.cache);
+            match cache.get_mut(&key) {
+                Some(Entry::Prepared(statement)) => return Ok((statement.clone(), true)),
+                Some(Entry::Preparing(waiting)) => {
+                    let (sender, receiver) = oneshot::channel();
+                    waiting.push(sender);
+                    Some(receiver)
+                }
+                None => {
+                    cache.insert(key, Entry::Preparing(Vec::new()));
+                    None
+                }
+            }
+        };
+
+        match waiting {
+            Some(receiver) => match receiver.await {
+                Ok(statement) => Ok((statement, true)),
+                // Preparing the statement failed, so it most likely fails for us as well, but we
+                // need an error of our own to report.
+                Err(oneshot::Canceled) => Ok((self.client.prepare_static(sql).await?, false)),
+            },
+            None => {
+                let preparing = Preparing {
+                    cache: &self.cache,
+                    key,
+                    finished: false,
+                };
+                let statement = self.client.prepare_static(sql).await?;
+                preparing.finish(&statement);
+                if let Some(scope) = &self.scope {
+                    lock(&scope.prepared).keys.push(key);
+                }
+                Ok((statement, false))
+            }
+        }
+    }
+}
+
+impl Hooks {
+    fn prepared(&self, sql: &str, types: &[Type], cached: bool, start: Instant) {
+        if let Some(hook) = &self.prepare {
+            hook(&PrepareEvent {
+                sql,
+                types,
+                cached,
+                duration: start.elapsed(),
+            });
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+impl Scope {
+    /// Keep the statements prepared within the transaction, handing them over to the enclosing
+    /// transaction, if any.
+    fn commit(&self) {
+        self.committed.store(true, Ordering::Release);
+        if let Some(parent) = &self.parent {
+            let prepared = mem::take(&mut *lock(&self.prepared));
+            let mut enclosing = lock(&parent.prepared);
+            enclosing.keys.extend(prepared.keys);
+            enclosing.typed.extend(prepared.typed);
+        }
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        if self.committed.load(Ordering::Acquire) {
+            return;
+        }
+
+        let prepared = mem::take(self.prepared.get_mut().unwrap_or_else(|e| e.into_inner()));
+        let mut evicted = Vec::new();
+        {
+            let mut cache = lock(&self.cache);
+            for key in prepared.keys {
+                if cache.remove(&key).is_some() {
+                    evicted.push(key.text);
+                }
+            }
+        }
+        {
+            let mut typed = lock(&self.typed);
+            for key in prepared.typed {
+                if typed.remove(&key).is_some() {
+                    evicted.push(key.sql.text);
+                }
+            }
+        }
+
+        if let Some(hook) = &self.hooks.evict {
+            for sql in evicted {
+                hook(sql);
+            }
+        }
+    }
+}
+
+/// A statement being prepared by the current caller. If preparing fails (or is cancelled), the
+/// entry is removed when this is dropped, so that those waiting for the statement give up.
+struct Preparing<'a> {
+    cache: &'a Cache,
+    key: StrKey,
+    finished: bool,
+}
+
+impl Preparing<'_> {
+    /// Cache the statement and hand it to those waiting for it.
+    fn finish(mut self, statement: &Statement) {
+        self.finished = true;
+        let mut cache = lock(self.cache);
+        if let Some(entry) = cache.get_mut(&self.key) {
+            let previous = mem::replace(entry, Entry::Prepared(statement.clone()));
+            if let Entry::Preparing(waiting) = previous {
+                for sender in waiting {
+                    let _ = sender.send(statement.clone());
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Preparing<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            lock(self.cache).remove(&self.key);
+        }
     }
 }
 
 impl StrKey {
     pub fn new(text: &'static str) -> StrKey {
-        StrKey {
-            ptr: text.as_ptr() as usize,
-            len: text.len(),
-        }
+        StrKey { text }
+    }
+
+    fn identity(&self) -> (usize, usize) {
+        (self.text.as_ptr() as usize, self.text.len())
+    }
+}
+
+impl PartialEq for StrKey {
+    fn eq(&self, other: &StrKey) -> bool {
+        self.identity() == other.identity()
+    }
+}
+
+impl Eq for StrKey {}
+
+impl Hash for StrKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.identity().hash(state)
     }
 }
 
@@ -165,6 +851,10 @@ impl DynamicKey for StrKey {
     const LINEAR_CUTOFF: usize = 64;
 }
 
+impl DynamicKey for TypedKey {
+    const LINEAR_CUTOFF: usize = StrKey::LINEAR_CUTOFF;
+}
+
 impl<K, V> DynamicCache<K, V>
 where
     K: DynamicKey,
@@ -173,12 +863,66 @@ where
         match self {
             DynamicCache::Linear(pairs) => pairs
                 .iter()
-                .find(|(key, _)| K::eq(key, &index))
+                .find(|(key, _)| K::eq(key, index))
                 .map(|(_, value)| value),
             DynamicCache::Hash(map) => map.get(index),
         }
     }
 
+    pub fn get_mut(&mut self, index: &K) -> Option<&mut V> {
+        match self {
+            DynamicCache::Linear(pairs) => pairs
+                .iter_mut()
+                .find(|(key, _)| K::eq(key, index))
+                .map(|(_, value)| value),
+            DynamicCache::Hash(map) => map.get_mut(index),
+        }
+    }
+
+    pub fn remove(&mut self, index: &K) -> Option<V> {
+        match self {
+            DynamicCache::Linear(pairs) => {
+                let position = pairs.iter().position(|(key, _)| K::eq(key, index))?;
+                Some(pairs.swap_remove(position).1)
+            }
+            DynamicCache::Hash(map) => map.remove(index),
+        }
+    }
+
+    /// Keep only the entries for which `keep` returns `true`, and return the keys of the others.
+    pub fn retain(&mut self, mut keep: impl FnMut(&K, &V) -> bool) -> Vec<K> {
+        let mut removed = Vec::new();
+        match self {
+            DynamicCache::Linear(pairs) => {
+                for (key, value) in mem::take(pairs) {
+                    if keep(&key, &value) {
+                        pairs.push((key, value));
+                    } else {
+                        removed.push(key);
+                    }
+                }
+            }
+            DynamicCache::Hash(map) => {
+                for (key, value) in mem::take(map) {
+                    if keep(&key, &value) {
+                        map.insert(key, value);
+                    } else {
+                        removed.push(key);
+                    }
+                }
+            }
+        }
+        removed
+    }
+
+    /// Consume the cache, returning its keys.
+    pub fn into_keys(self) -> Vec<K> {
+        match self {
+            DynamicCache::Linear(pairs) => pairs.into_iter().map(|(key, _)| key).collect(),
+            DynamicCache::Hash(map) => map.into_keys().collect(),
+        }
+    }
+
     /// Insert a new key-value pair into the cache, and grow the cache if necessary.
     pub fn insert(&mut self, key: K, value: V) {
         match self {
@@ -212,10 +956,39 @@ macro_rules! impl_cached_transaction {
     ($client:ty, $transaction:ty) => {
         impl Caching<$client> {
             /// Start a new transaction that shares the same cache as the current client.
+            ///
+            /// The transaction uses the same default options and hooks as the current client.
+            /// Statements first prepared within the transaction are evicted from the cache unless
+            /// it is committed with [`commit`], since they may refer to types or tables created
+            /// within it. This includes transactions which are dropped, or committed through the
+            /// inner transaction.
+            ///
+            /// [`commit`]: #method.commit
             pub async fn transaction(&mut self) -> Result<Caching<$transaction>, Error> {
+                let scope = Scope {
+                    cache: self.cache.clone(),
+                    typed: self.typed.clone(),
+                    hooks: self.hooks.clone(),
+                    parent: self.scope.clone(),
+                    prepared: Mutex::default(),
+                    committed: AtomicBool::new(false),
+                };
+                let cache = self.cache.clone();
+                let typed = self.typed.clone();
+                let options = self.options.clone();
+                let hooks = self.hooks.clone();
+                let uncached = self.uncached.clone();
                 <$client>::transaction(self)
                     .await
-                    .map(Caching::new)
+                    .map(|transaction| Caching {
+                        client: transaction,
+                        cache,
+                        typed,
+                        options,
+                        hooks,
+                        uncached,
+                        scope: Some(Arc::new(scope)),
+                    })
                     .map_err(Error::BeginTransaction)
             }
         }
@@ -227,3 +1000,44 @@ impl_cached_transaction!(
     tokio_postgres::Transaction<'_>,
     tokio_postgres::Transaction<'_>
 );
+
+impl Caching<tokio_postgres::Transaction<'_>> {
+    /// Commit the transaction, keeping the statements prepared within it in the cache.
+    pub async fn commit(self) -> Result<(), Error> {
+        self.client
+            .commit()
+            .await
+            .map_err(Error::CommitTransaction)?;
+        if let Some(scope) = &self.scope {
+            scope.commit();
+        }
+        Ok(())
+    }
+
+    /// Roll back the transaction, evicting the statements first prepared within it from the cache.
+    pub async fn rollback(self) -> Result<(), Error> {
+        self.client
+            .rollback()
+            .await
+            .map_err(Error::RollbackTransaction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leading_keywords() {
+        assert_eq!(
+            leading_keyword("  CREATE TABLE people (id INT)").as_deref(),
+            Some("CREATE")
+        );
+        assert_eq!(
+            leading_keyword("-- migrate\n/* v2 */ alter table people").as_deref(),
+            Some("ALTER")
+        );
+        assert_eq!(leading_keyword("(SELECT 1)"), None);
+        assert_eq!(leading_keyword("/* unterminated"), None);
+    }
+}
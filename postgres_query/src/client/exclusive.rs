@@ -0,0 +1,92 @@
+//! A client whose connection is not shared.
+
+use super::{CopyClient, GenericClient};
+use async_trait::async_trait;
+use bytes::Bytes;
+use postgres_types::{ToSql, Type};
+use std::ops::Deref;
+use tokio_postgres::{error::Error as SqlError, Client, CopyInSink, RowStream, Statement};
+
+/// A client borrowed mutably, so that nothing else can use its connection while queries execute
+/// through it.
+///
+/// Options which set run-time parameters, such as [`Query::in_schema`] and
+/// [`ExecuteOptions::local`], only apply within a transaction. A shared client may be used by
+/// other tasks at the same time, whose queries would end up in any transaction started for the
+/// query, so such queries fail with [`Error::NoTransaction`] outside of one. Through an
+/// `Exclusive` client, they instead run within an implicit transaction of their own: it is
+/// committed once the query has executed, and rolled back if the query fails.
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{client::Exclusive, query, Result};
+/// # async fn foo() -> Result<()> {
+/// # let mut client: Client = unimplemented!();
+/// let orders: Vec<(i32, String)> = query!("SELECT id, item FROM orders")
+///     .in_schema("tenant_42")
+///     .fetch(&Exclusive::new(&mut client))
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Query::in_schema`]: ../struct.Query.html#method.in_schema
+/// [`ExecuteOptions::local`]: ../execute/struct.ExecuteOptions.html#method.local
+/// [`Error::NoTransaction`]: ../execute/enum.Error.html#variant.NoTransaction
+#[derive(Debug)]
+pub struct Exclusive<'a> {
+    client: &'a mut Client,
+}
+
+impl<'a> Exclusive<'a> {
+    /// Borrow the client for as long as this is alive.
+    pub fn new(client: &'a mut Client) -> Exclusive<'a> {
+        Exclusive { client }
+    }
+}
+
+impl Deref for Exclusive<'_> {
+    type Target = Client;
+
+    fn deref(&self) -> &Self::Target {
+        self.client
+    }
+}
+
+#[async_trait]
+impl GenericClient for Exclusive<'_> {
+    async fn prepare(&self, sql: &str) -> Result<Statement, SqlError> {
+        Client::prepare(self.client, sql).await
+    }
+
+    async fn prepare_typed(&self, sql: &str, types: &[Type]) -> Result<Statement, SqlError> {
+        Client::prepare_typed(self.client, sql, types).await
+    }
+
+    async fn execute_raw<'a>(
+        &'a self,
+        statement: &Statement,
+        parameters: &[&'a (dyn ToSql + Sync)],
+    ) -> Result<u64, SqlError> {
+        GenericClient::execute_raw(&*self.client, statement, parameters).await
+    }
+
+    async fn query_raw<'a>(
+        &'a self,
+        statement: &Statement,
+        parameters: &[&'a (dyn ToSql + Sync)],
+    ) -> Result<RowStream, SqlError> {
+        GenericClient::query_raw(&*self.client, statement, parameters).await
+    }
+
+    fn exclusive(&self) -> bool {
+        true
+    }
+}
+
+#[async_trait]
+impl CopyClient for Exclusive<'_> {
+    async fn copy_in_raw(&self, statement: &Statement) -> Result<CopyInSink<Bytes>, SqlError> {
+        Client::copy_in(self.client, statement).await
+    }
+}
@@ -0,0 +1,80 @@
+//! Health checks for clients and pools.
+
+use super::GenericClient;
+use crate::error::Result;
+use crate::Query;
+use async_trait::async_trait;
+use futures::future::{self, Either};
+use futures::pin_mut;
+use futures_timer::Delay;
+use std::time::Duration;
+
+#[cfg(feature = "deadpool")]
+use crate::metrics;
+#[cfg(feature = "deadpool")]
+use deadpool_postgres::Pool;
+
+/// The query used to determine if the database is reachable.
+const PING: &str = "SELECT 1";
+
+/// Health and readiness checks, suitable for wiring up liveness/readiness probes.
+///
+/// Implemented for every [`GenericClient`] and, with the `deadpool` feature, for
+/// `deadpool_postgres::Pool`.
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{client::HealthCheck, Result};
+/// # use std::time::Duration;
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// // Fails if the database could not be reached.
+/// client.ping().await?;
+///
+/// // Gives up if the database did not respond within a second.
+/// let ready: bool = client.is_healthy(Duration::from_secs(1)).await;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`GenericClient`]: trait.GenericClient.html
+#[async_trait]
+pub trait HealthCheck: Sync {
+    /// Issue a trivial query (`SELECT 1`) against the database.
+    async fn ping(&self) -> Result<()>;
+
+    /// `true` if a [`ping`] succeeds within the given `timeout`, `false` otherwise.
+    ///
+    /// [`ping`]: #tymethod.ping
+    async fn is_healthy(&self, timeout: Duration) -> bool {
+        let ping = self.ping();
+        let delay = Delay::new(timeout);
+        pin_mut!(ping);
+
+        match future::select(ping, delay).await {
+            Either::Left((result, _)) => result.is_ok(),
+            Either::Right(_) => false,
+        }
+    }
+}
+
+#[async_trait]
+impl<C> HealthCheck for C
+where
+    C: GenericClient + Sync,
+{
+    async fn ping(&self) -> Result<()> {
+        Query::new_static(PING, Vec::new()).execute(self).await?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "deadpool")]
+#[async_trait]
+impl HealthCheck for Pool {
+    /// Acquire a client from the pool and ping the database through it.
+    async fn ping(&self) -> Result<()> {
+        let client = metrics::acquire(self).await?;
+        client.ping().await
+    }
+}
@@ -0,0 +1,127 @@
+//! Relax the integrity checks of a transaction, for bulk loads and migrations.
+
+use crate::error::Result;
+use crate::execute::{quote_identifier, Error};
+use async_trait::async_trait;
+use tokio_postgres::Transaction;
+
+/// Defer constraints and disable triggers within a transaction.
+///
+/// Loading data whose rows refer to each other (or which is inserted in an inconvenient order) is
+/// simpler if foreign keys are only checked on commit, and faster if triggers do not fire for
+/// every row:
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{client::Integrity, query, Result};
+/// # async fn foo() -> Result<()> {
+/// # let mut client: Client = unimplemented!();
+/// let tx = client.transaction().await.unwrap();
+///
+/// tx.defer_constraints(&["orders_user_id_fkey"]).await?;
+/// tx.disable_triggers("orders").await?;
+///
+/// // The users referred to by the orders are inserted afterwards.
+/// query!("INSERT INTO orders VALUES (1, $user)", user = 7)
+///     .execute(&tx)
+///     .await?;
+/// query!("INSERT INTO users VALUES ($user)", user = 7)
+///     .execute(&tx)
+///     .await?;
+///
+/// tx.check_constraints(&["orders_user_id_fkey"]).await?;
+/// tx.enable_triggers("orders").await?;
+/// tx.commit().await.unwrap();
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Names of constraints and tables may be qualified with a schema (`public.orders`). Each part of
+/// a name is quoted, so it has to match the name in the database exactly, including its case.
+#[async_trait]
+pub trait Integrity {
+    /// Check the given constraints when the transaction is committed, rather than after each
+    /// statement (`SET CONSTRAINTS ... DEFERRED`).
+    ///
+    /// Only constraints declared as `DEFERRABLE` may be deferred.
+    async fn defer_constraints(&self, constraints: &[&str]) -> Result<()>;
+
+    /// Check all deferrable constraints when the transaction is committed
+    /// (`SET CONSTRAINTS ALL DEFERRED`).
+    async fn defer_all_constraints(&self) -> Result<()>;
+
+    /// Check the given constraints after each statement again, which also checks any changes made
+    /// while they were deferred (`SET CONSTRAINTS ... IMMEDIATE`).
+    async fn check_constraints(&self, constraints: &[&str]) -> Result<()>;
+
+    /// Disable the user-defined triggers of a table (`ALTER TABLE ... DISABLE TRIGGER USER`).
+    ///
+    /// Unlike deferred constraints, this is not limited to the transaction: the triggers remain
+    /// disabled once it is committed, unless they are enabled again with [`enable_triggers`]. The
+    /// triggers which enforce foreign keys are not affected.
+    ///
+    /// [`enable_triggers`]: #tymethod.enable_triggers
+    async fn disable_triggers(&self, table: &str) -> Result<()>;
+
+    /// Enable the user-defined triggers of a table (`ALTER TABLE ... ENABLE TRIGGER USER`).
+    ///
+    /// The table may not be altered while checks of its deferred constraints are pending, so
+    /// these have to be made with [`check_constraints`] first.
+    ///
+    /// [`check_constraints`]: #tymethod.check_constraints
+    async fn enable_triggers(&self, table: &str) -> Result<()>;
+}
+
+#[async_trait]
+impl Integrity for Transaction<'_> {
+    async fn defer_constraints(&self, constraints: &[&str]) -> Result<()> {
+        if constraints.is_empty() {
+            return Ok(());
+        }
+        let sql = format!("SET CONSTRAINTS {} DEFERRED", qualified_list(constraints));
+        batch_execute(self, &sql).await
+    }
+
+    async fn defer_all_constraints(&self) -> Result<()> {
+        batch_execute(self, "SET CONSTRAINTS ALL DEFERRED").await
+    }
+
+    async fn check_constraints(&self, constraints: &[&str]) -> Result<()> {
+        if constraints.is_empty() {
+            return Ok(());
+        }
+        let sql = format!("SET CONSTRAINTS {} IMMEDIATE", qualified_list(constraints));
+        batch_execute(self, &sql).await
+    }
+
+    async fn disable_triggers(&self, table: &str) -> Result<()> {
+        let sql = format!("ALTER TABLE {} DISABLE TRIGGER USER", qualified(table));
+        batch_execute(self, &sql).await
+    }
+
+    async fn enable_triggers(&self, table: &str) -> Result<()> {
+        let sql = format!("ALTER TABLE {} ENABLE TRIGGER USER", qualified(table));
+        batch_execute(self, &sql).await
+    }
+}
+
+async fn batch_execute(transaction: &Transaction<'_>, sql: &str) -> Result<()> {
+    transaction.batch_execute(sql).await.map_err(Error::from)?;
+    Ok(())
+}
+
+/// Quote each part of a name which may be qualified with a schema.
+fn qualified(name: &str) -> String {
+    name.split('.')
+        .map(quote_identifier)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn qualified_list(names: &[&str]) -> String {
+    names
+        .iter()
+        .map(|name| qualified(name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
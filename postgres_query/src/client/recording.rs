@@ -0,0 +1,249 @@
+//! A client which records the mutations executed through it.
+
+use super::{CopyClient, GenericClient};
+use crate::execute::ExecuteOptions;
+use crate::{Parameter, Query};
+use async_trait::async_trait;
+use bytes::Bytes;
+use postgres_types::{ToSql, Type};
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio_postgres::{error::Error as SqlError, CopyInSink, RowStream, Statement};
+
+/// Replaces the value of a parameter in the log.
+const REDACTED: &str = "<redacted>";
+
+type Redact = Arc<dyn Fn(usize, &str) -> String + Send + Sync>;
+
+/// A client wrapper which records every query executed through it with [`Query::execute`], for
+/// writing audit entries or debugging complex transactions.
+///
+/// The values of the parameters are redacted, unless configured otherwise with
+/// [`Recording::redact`]. Queries which return rows (such as `INSERT ... RETURNING`) and statements
+/// executed directly on the client are not recorded.
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{client::Recording, query, Result};
+/// # async fn foo() -> Result<(), Box<dyn std::error::Error>> {
+/// # let mut client: Client = unimplemented!();
+/// let tx = Recording::new(client.transaction().await?);
+///
+/// query!("UPDATE orders SET paid = true WHERE id = $id", id = 7)
+///     .execute(&tx)
+///     .await?;
+///
+/// let (tx, mutations) = tx.into_parts();
+/// tx.commit().await?;
+///
+/// for mutation in mutations {
+///     // UPDATE orders SET paid = true WHERE id = $1 (["<redacted>"]): 1 rows
+///     println!("{} ({:?}): {} rows", mutation.sql, mutation.parameters, mutation.rows);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Query::execute`]: ../struct.Query.html#method.execute
+/// [`Recording::redact`]: #method.redact
+#[derive(Clone)]
+pub struct Recording<C>
+where
+    C: GenericClient,
+{
+    client: C,
+    mutations: Arc<Mutex<Vec<Mutation>>>,
+    redact: Redact,
+}
+
+/// A query executed through a [`Recording`] client.
+///
+/// [`Recording`]: struct.Recording.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mutation {
+    /// The SQL of the query, with positional parameters (`$1`, `$2`, ...).
+    pub sql: String,
+    /// The tag of the query, if any. See [`ExecuteOptions::tag`].
+    ///
+    /// [`ExecuteOptions::tag`]: ../execute/struct.ExecuteOptions.html#method.tag
+    pub tag: Option<String>,
+    /// The values of the parameters, as formatted by `Debug` and then redacted.
+    pub parameters: Vec<String>,
+    /// The number of affected rows.
+    pub rows: u64,
+}
+
+impl<C> Recording<C>
+where
+    C: GenericClient,
+{
+    /// Wrap a client, redacting the values of all parameters.
+    pub fn new(client: C) -> Recording<C> {
+        Recording {
+            client,
+            mutations: Arc::default(),
+            redact: Arc::new(|_, _| REDACTED.to_owned()),
+        }
+    }
+
+    /// Record the values of parameters as returned by `redact`, which is given the position of each
+    /// parameter (starting at 1, like `$1`) and its value as formatted by `Debug`.
+    ///
+    /// ```
+    /// # use tokio_postgres::Client;
+    /// # use postgres_query::client::Recording;
+    /// # fn foo(client: Client) {
+    /// // Record all values as they are.
+    /// let client = Recording::new(client).redact(|_, value| value.to_owned());
+    /// # }
+    /// ```
+    pub fn redact<F>(mut self, redact: F) -> Recording<C>
+    where
+        F: Fn(usize, &str) -> String + Send + Sync + 'static,
+    {
+        self.redact = Arc::new(redact);
+        self
+    }
+
+    /// The mutations recorded so far, in the order they completed.
+    pub fn mutations(&self) -> Vec<Mutation> {
+        self.lock().clone()
+    }
+
+    /// Remove the mutations recorded so far.
+    pub fn take_mutations(&self) -> Vec<Mutation> {
+        mem::take(&mut *self.lock())
+    }
+
+    /// Return the inner client and the mutations recorded through it, so that a transaction may
+    /// be committed.
+    pub fn into_parts(self) -> (C, Vec<Mutation>) {
+        let mutations = self.take_mutations();
+        (self.client, mutations)
+    }
+
+    /// Return the inner client.
+    pub fn into_inner(self) -> C {
+        self.client
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Mutation>> {
+        self.mutations
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+impl<C> Deref for Recording<C>
+where
+    C: GenericClient,
+{
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+impl<C> DerefMut for Recording<C>
+where
+    C: GenericClient,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.client
+    }
+}
+
+#[async_trait]
+impl<C> GenericClient for Recording<C>
+where
+    C: GenericClient + Sync + Send,
+{
+    async fn prepare(&self, sql: &str) -> Result<Statement, SqlError> {
+        self.client.prepare(sql).await
+    }
+
+    async fn prepare_static(&self, sql: &'static str) -> Result<Statement, SqlError> {
+        self.client.prepare_static(sql).await
+    }
+
+    async fn prepare_typed(&self, sql: &str, types: &[Type]) -> Result<Statement, SqlError> {
+        self.client.prepare_typed(sql, types).await
+    }
+
+    async fn prepare_typed_static(
+        &self,
+        sql: &'static str,
+        types: &[Type],
+    ) -> Result<Statement, SqlError> {
+        self.client.prepare_typed_static(sql, types).await
+    }
+
+    async fn prepare_tagged_static(
+        &self,
+        sql: &'static str,
+        tag: &str,
+    ) -> Result<Statement, SqlError> {
+        self.client.prepare_tagged_static(sql, tag).await
+    }
+
+    async fn execute_raw<'a>(
+        &'a self,
+        statement: &Statement,
+        parameters: &[&'a (dyn ToSql + Sync)],
+    ) -> Result<u64, SqlError> {
+        self.client.execute_raw(statement, parameters).await
+    }
+
+    async fn query_raw<'a>(
+        &'a self,
+        statement: &Statement,
+        parameters: &[&'a (dyn ToSql + Sync)],
+    ) -> Result<RowStream, SqlError> {
+        self.client.query_raw(statement, parameters).await
+    }
+
+    fn execute_options(&self) -> ExecuteOptions {
+        self.client.execute_options()
+    }
+
+    fn in_transaction(&self) -> bool {
+        self.client.in_transaction()
+    }
+
+    fn exclusive(&self) -> bool {
+        self.client.exclusive()
+    }
+
+    fn __check_query(&self, query: &Query<'_>) -> crate::Result<()> {
+        self.client.__check_query(query)
+    }
+
+    fn __executed(&self, sql: &str, parameters: &[Parameter<'_>], tag: Option<&str>, rows: u64) {
+        let redacted = parameters
+            .iter()
+            .enumerate()
+            .map(|(i, value)| (self.redact)(i + 1, &format!("{:?}", value)))
+            .collect();
+
+        self.lock().push(Mutation {
+            sql: sql.to_owned(),
+            tag: tag.map(String::from),
+            parameters: redacted,
+            rows,
+        });
+
+        self.client.__executed(sql, parameters, tag, rows);
+    }
+}
+
+#[async_trait]
+impl<C> CopyClient for Recording<C>
+where
+    C: CopyClient + Sync + Send,
+{
+    async fn copy_in_raw(&self, statement: &Statement) -> Result<CopyInSink<Bytes>, SqlError> {
+        self.client.copy_in_raw(statement).await
+    }
+}
@@ -0,0 +1,315 @@
+//! A client which caches the results of queries.
+
+use super::{CopyClient, GenericClient};
+use crate::error::Result;
+use crate::execute::ExecuteOptions;
+use crate::extract::FromSqlRow;
+use crate::{Parameter, Query};
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use futures::lock::Mutex;
+use postgres_types::{IsNull, ToSql, Type};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_postgres::{error::Error as SqlError, CopyInSink, RowStream, Statement};
+
+/// A client wrapper which caches the extracted values of queries for a limited time.
+///
+/// Only queries executed through [`ResultCache::fetch`] are cached. Results are keyed on the SQL,
+/// the types and encoded values of the parameters, the run-time parameters set by the options of
+/// the query (such as the schema of [`Query::in_schema`] or the role), and the type being
+/// extracted. Since the database is not consulted again until an entry expires, this should only
+/// be used for idempotent queries (ie. `SELECT`s) where slightly stale data is acceptable.
+///
+/// The types of the parameters are found by preparing the query, so the wrapped client should
+/// cache its statements (see [`Caching`]) for cached results to be found without a round trip.
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{client::ResultCache, query, Result};
+/// # use std::time::Duration;
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// let client = ResultCache::new(client, Duration::from_secs(30), 256);
+///
+/// let query = query!("SELECT name FROM people WHERE age > $age", age = 18);
+///
+/// // Only the first call reaches the database.
+/// let first: Vec<(String,)> = client.fetch(&query).await?;
+/// let second: Vec<(String,)> = client.fetch(&query).await?;
+///
+/// // After writing to the `people` table, drop any stale results.
+/// client.invalidate_where(|sql| sql.contains("people")).await;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`ResultCache::fetch`]: #method.fetch
+/// [`Query::in_schema`]: ../struct.Query.html#method.in_schema
+/// [`Caching`]: struct.Caching.html
+#[derive(Clone)]
+pub struct ResultCache<C>
+where
+    C: GenericClient,
+{
+    client: C,
+    ttl: Duration,
+    capacity: usize,
+    entries: Arc<Mutex<HashMap<Key, Entry>>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    sql: String,
+    /// The type of each parameter, along with its value in the binary format (`None` if `NULL`).
+    parameters: Vec<(Type, Option<Vec<u8>>)>,
+    /// The run-time parameters, which may change what the query refers to or may see.
+    settings: Vec<(String, String)>,
+    target: TypeId,
+}
+
+struct Entry {
+    inserted: Instant,
+    values: Arc<dyn Any + Send + Sync>,
+}
+
+impl<C> ResultCache<C>
+where
+    C: GenericClient,
+{
+    /// Wrap a client in a new cache, which keeps at most `capacity` results for up to `ttl`.
+    pub fn new(client: C, ttl: Duration, capacity: usize) -> ResultCache<C> {
+        ResultCache {
+            client,
+            ttl,
+            capacity,
+            entries: Arc::default(),
+        }
+    }
+
+    /// Return the inner client.
+    pub fn into_inner(self) -> C {
+        self.client
+    }
+
+    /// Remove all cached results.
+    pub async fn clear(&self) {
+        self.entries.lock().await.clear();
+    }
+
+    /// Remove all cached results of the given query, regardless of its parameters.
+    pub async fn invalidate(&self, query: &Query<'_>) {
+        let sql = query.sql();
+        self.invalidate_where(|cached| cached == sql).await;
+    }
+
+    /// Remove all cached results of queries whose SQL matches the predicate.
+    pub async fn invalidate_where<F>(&self, mut predicate: F)
+    where
+        F: FnMut(&str) -> bool,
+    {
+        let mut entries = self.entries.lock().await;
+        entries.retain(|key, _| !predicate(&key.sql));
+    }
+}
+
+impl<C> ResultCache<C>
+where
+    C: GenericClient + Sync,
+{
+    /// Execute the query and return the resulting values, or return the values of a previous
+    /// execution if one was made within the cache's time to live.
+    pub async fn fetch<T>(&self, query: &Query<'_>) -> Result<Vec<T>>
+    where
+        T: FromSqlRow + Clone + Send + Sync + 'static,
+    {
+        let options = query.options_for(&self.client);
+        // The statement depends on run-time parameters such as the `search_path`.
+        let statement = options
+            .run(&self.client, query.prepare(&self.client, &options))
+            .await?;
+        let key = match Key::new::<T>(query, &options, statement.params()) {
+            Some(key) => key,
+            // The parameters do not match their types, which executing the query reports.
+            None => return query.fetch(&self.client).await,
+        };
+
+        if let Some(values) = self.get_cached::<T>(&key).await {
+            return Ok(values);
+        }
+
+        let values = query.fetch::<T, _>(&self.client).await?;
+        self.cache(key, Arc::new(values.clone())).await;
+        Ok(values)
+    }
+
+    async fn get_cached<T>(&self, key: &Key) -> Option<Vec<T>>
+    where
+        T: Clone + 'static,
+    {
+        let mut entries = self.entries.lock().await;
+        match entries.get(key) {
+            Some(entry) if entry.inserted.elapsed() < self.ttl => {
+                entry.values.downcast_ref::<Vec<T>>().cloned()
+            }
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    async fn cache(&self, key: Key, values: Arc<dyn Any + Send + Sync>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().await;
+
+        if entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            entries.retain(|_, entry| entry.inserted.elapsed() < ttl);
+        }
+
+        if entries.len() >= self.capacity {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.inserted)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+
+        let entry = Entry {
+            inserted: Instant::now(),
+            values,
+        };
+        entries.insert(key, entry);
+    }
+}
+
+impl Key {
+    fn new<T: 'static>(query: &Query, options: &ExecuteOptions, types: &[Type]) -> Option<Key> {
+        let mut parameters = Vec::with_capacity(types.len());
+        for (parameter, ty) in query.parameters().iter().zip(types) {
+            let mut value = BytesMut::new();
+            let value = match parameter.to_sql_checked(ty, &mut value).ok()? {
+                IsNull::Yes => None,
+                IsNull::No => Some(value.to_vec()),
+            };
+            parameters.push((ty.clone(), value));
+        }
+
+        Some(Key {
+            sql: query.sql().to_owned(),
+            parameters,
+            settings: options.settings(),
+            target: TypeId::of::<T>(),
+        })
+    }
+}
+
+impl<C> Deref for ResultCache<C>
+where
+    C: GenericClient,
+{
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+impl<C> DerefMut for ResultCache<C>
+where
+    C: GenericClient,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.client
+    }
+}
+
+#[async_trait]
+impl<C> GenericClient for ResultCache<C>
+where
+    C: GenericClient + Sync + Send,
+{
+    async fn prepare(&self, sql: &str) -> Result<Statement, SqlError> {
+        self.client.prepare(sql).await
+    }
+
+    async fn prepare_static(&self, sql: &'static str) -> Result<Statement, SqlError> {
+        self.client.prepare_static(sql).await
+    }
+
+    async fn prepare_typed(&self, sql: &str, types: &[Type]) -> Result<Statement, SqlError> {
+        self.client.prepare_typed(sql, types).await
+    }
+
+    async fn prepare_typed_static(
+        &self,
+        sql: &'static str,
+        types: &[Type],
+    ) -> Result<Statement, SqlError> {
+        self.client.prepare_typed_static(sql, types).await
+    }
+
+    async fn prepare_tagged_static(
+        &self,
+        sql: &'static str,
+        tag: &str,
+    ) -> Result<Statement, SqlError> {
+        self.client.prepare_tagged_static(sql, tag).await
+    }
+
+    async fn execute_raw<'a>(
+        &'a self,
+        statement: &Statement,
+        parameters: &[&'a (dyn ToSql + Sync)],
+    ) -> Result<u64, SqlError> {
+        self.client.execute_raw(statement, parameters).await
+    }
+
+    async fn query_raw<'a>(
+        &'a self,
+        statement: &Statement,
+        parameters: &[&'a (dyn ToSql + Sync)],
+    ) -> Result<RowStream, SqlError> {
+        self.client.query_raw(statement, parameters).await
+    }
+
+    fn execute_options(&self) -> ExecuteOptions {
+        self.client.execute_options()
+    }
+
+    fn in_transaction(&self) -> bool {
+        self.client.in_transaction()
+    }
+
+    fn exclusive(&self) -> bool {
+        self.client.exclusive()
+    }
+
+    fn __check_query(&self, query: &Query<'_>) -> crate::Result<()> {
+        self.client.__check_query(query)
+    }
+
+    fn __executed(&self, sql: &str, parameters: &[Parameter<'_>], tag: Option<&str>, rows: u64) {
+        self.client.__executed(sql, parameters, tag, rows)
+    }
+}
+
+#[async_trait]
+impl<C> CopyClient for ResultCache<C>
+where
+    C: CopyClient + Sync + Send,
+{
+    async fn copy_in_raw(&self, statement: &Statement) -> Result<CopyInSink<Bytes>, SqlError> {
+        self.client.copy_in_raw(statement).await
+    }
+}
@@ -0,0 +1,125 @@
+//! Route queries to different databases by their tag.
+
+use crate::Query;
+
+/// Clients for several databases, which queries are routed to by their tag (see
+/// [`ExecuteOptions::tag`]).
+///
+/// Each route has a pattern, which is either a tag (`reports.daily`) or a prefix followed by `.*`
+/// (`analytics.*`, matching `analytics.visits` and `analytics.sales.monthly`). Queries whose tag
+/// does not match any pattern, or which are not tagged, use the default client:
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{client::Router, execute::ExecuteOptions, query, Result};
+/// # async fn foo() -> Result<()> {
+/// # let (primary, replica): (Client, Client) = unimplemented!();
+/// let router = Router::new(primary).route("analytics.*", replica);
+///
+/// // Runs on the replica.
+/// let visits = query!("SELECT page, COUNT(*) FROM visits GROUP BY page")
+///     .with_options(ExecuteOptions::new().tag("analytics.visits"));
+/// let visits: Vec<(String, i64)> = visits.fetch(router.client_for(&visits)).await?;
+///
+/// // Runs on the primary.
+/// let signup = query!("INSERT INTO users VALUES ($name)", name = "John Wick");
+/// signup.execute(router.client_for(&signup)).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The router hands out clients, rather than being a client itself, since a statement has to be
+/// executed through the same connection that prepared it. Only the tag set on the query itself is
+/// taken into account, not the defaults of the client.
+///
+/// [`ExecuteOptions::tag`]: ../execute/struct.ExecuteOptions.html#method.tag
+#[derive(Debug, Clone)]
+pub struct Router<C> {
+    default: C,
+    routes: Vec<(String, C)>,
+}
+
+impl<C> Router<C> {
+    /// Route all queries to `default`, until other routes are added.
+    pub fn new(default: C) -> Router<C> {
+        Router {
+            default,
+            routes: Vec::new(),
+        }
+    }
+
+    /// Route the queries whose tag matches `pattern` to `client`.
+    ///
+    /// If several patterns match a tag, the most specific one (an exact tag, or else the longest
+    /// prefix) is used.
+    pub fn route(mut self, pattern: impl Into<String>, client: C) -> Router<C> {
+        self.routes.push((pattern.into(), client));
+        self
+    }
+
+    /// The client for queries with the given tag.
+    pub fn client(&self, tag: Option<&str>) -> &C {
+        let tag = match tag {
+            Some(tag) => tag,
+            None => return &self.default,
+        };
+
+        self.routes
+            .iter()
+            .filter_map(|(pattern, client)| Some((specificity(pattern, tag)?, client)))
+            .fold(
+                None,
+                |best: Option<(usize, &C)>, (rank, client)| match best {
+                    Some((best_rank, _)) if best_rank >= rank => best,
+                    _ => Some((rank, client)),
+                },
+            )
+            .map_or(&self.default, |(_, client)| client)
+    }
+
+    /// The client for the query, according to its tag.
+    pub fn client_for(&self, query: &Query) -> &C {
+        self.client(query.tag())
+    }
+
+    /// The client for queries which are not routed elsewhere.
+    pub fn default_client(&self) -> &C {
+        &self.default
+    }
+}
+
+/// How well `pattern` matches `tag`, if at all. Exact matches rank above every prefix, and longer
+/// prefixes above shorter ones.
+fn specificity(pattern: &str, tag: &str) -> Option<usize> {
+    if pattern == tag {
+        return Some(usize::MAX);
+    }
+
+    let prefix = pattern.strip_suffix('*')?;
+    if prefix.ends_with('.') && tag.starts_with(prefix) {
+        Some(prefix.len())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn most_specific_route() {
+        let router = Router::new("primary")
+            .route("analytics.*", "replica")
+            .route("analytics.sales.*", "warehouse")
+            .route("analytics.sales.live", "primary")
+            .route("analytics.*", "unused");
+
+        assert_eq!(*router.client(None), "primary");
+        assert_eq!(*router.client(Some("billing")), "primary");
+        assert_eq!(*router.client(Some("analytics")), "primary");
+        assert_eq!(*router.client(Some("analytics.visits")), "replica");
+        assert_eq!(*router.client(Some("analytics.sales.monthly")), "warehouse");
+        assert_eq!(*router.client(Some("analytics.sales.live")), "primary");
+    }
+}
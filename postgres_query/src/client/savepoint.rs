@@ -0,0 +1,90 @@
+//! Run operations within a savepoint.
+
+use crate::error::Error;
+use futures::future::{BoxFuture, FutureExt};
+use tokio_postgres::{Client, Transaction};
+
+/// Scope a group of operations to a nested transaction (a savepoint if already within a
+/// transaction).
+///
+/// The nested transaction is committed (the savepoint released) if the closure returns `Ok`, and
+/// rolled back otherwise. This makes it safe to attempt an operation that might fail, and carry on
+/// with the outer transaction if it does:
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{client::Nested, query};
+/// # use futures::FutureExt;
+/// # async fn foo() -> Result<(), Box<dyn std::error::Error>> {
+/// # let mut client: Client = unimplemented!();
+/// let mut tx = client.transaction().await?;
+///
+/// let inserted = tx
+///     .nested(|sp| {
+///         async move {
+///             query!("INSERT INTO people VALUES ($name)", name = "John Wick")
+///                 .execute(sp)
+///                 .await
+///         }
+///         .boxed()
+///     })
+///     .await;
+///
+/// if inserted.is_err() {
+///     // The insert was rolled back, but `tx` is still usable.
+/// }
+///
+/// tx.commit().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait Nested {
+    /// Run `f` within a nested transaction, returning its value.
+    fn nested<'a, F, T, E>(&'a mut self, f: F) -> BoxFuture<'a, Result<T, E>>
+    where
+        F: for<'s, 't> FnOnce(&'s mut Transaction<'t>) -> BoxFuture<'s, Result<T, E>> + Send + 'a,
+        T: Send + 'a,
+        E: From<Error> + Send + 'a;
+}
+
+macro_rules! impl_nested {
+    ($client:ty) => {
+        impl Nested for $client {
+            fn nested<'a, F, T, E>(&'a mut self, f: F) -> BoxFuture<'a, Result<T, E>>
+            where
+                F: for<'s, 't> FnOnce(&'s mut Transaction<'t>) -> BoxFuture<'s, Result<T, E>>
+                    + Send
+                    + 'a,
+                T: Send + 'a,
+                E: From<Error> + Send + 'a,
+            {
+                async move {
+                    let mut savepoint = <$client>::transaction(self)
+                        .await
+                        .map_err(Error::BeginTransaction)?;
+
+                    match f(&mut savepoint).await {
+                        Ok(value) => {
+                            savepoint
+                                .commit()
+                                .await
+                                .map_err(Error::CommitTransaction)?;
+                            Ok(value)
+                        }
+                        Err(error) => {
+                            savepoint
+                                .rollback()
+                                .await
+                                .map_err(Error::RollbackTransaction)?;
+                            Err(error)
+                        }
+                    }
+                }
+                .boxed()
+            }
+        }
+    };
+}
+
+impl_nested!(Client);
+impl_nested!(Transaction<'_>);
@@ -0,0 +1,249 @@
+//! A client which injects scoped bindings, such as a tenant id, into queries.
+
+use super::{CopyClient, GenericClient};
+use crate::error::{Error, Result};
+use crate::execute::ExecuteOptions;
+use crate::{parse, Parameter, Query};
+use async_trait::async_trait;
+use bytes::Bytes;
+use postgres_types::{ToSql, Type};
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+use std::sync::Arc;
+use tokio_postgres::{error::Error as SqlError, CopyInSink, RowStream, Statement};
+
+/// A client wrapper which supplies a fixed set of bindings to the queries parsed through it.
+///
+/// This is useful for cross-cutting filters, such as the tenant in a multi-tenant application:
+/// the binding is configured once, when the client is handed out, and every query parsed with
+/// [`Scoped::parse`] may refer to it. In strict mode, parsing fails if a query does not refer to
+/// every scoped binding, which guards against queries that forget to filter by tenant. Executing
+/// a query through a strict client also fails, with [`Error::Unscoped`], unless the query was
+/// parsed by the client and refers to every scoped binding, so queries built with `query!` or
+/// `query_dyn!` are rejected.
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{client::Scoped, Result};
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// let client = Scoped::new(client).bind("tenant_id", 42).strict();
+///
+/// let orders: Vec<(String,)> = client
+///     .parse(
+///         "SELECT item FROM orders WHERE tenant_id = $tenant_id AND price > $price",
+///         &[("price", &100)],
+///     )?
+///     .fetch(&client)
+///     .await?;
+///
+/// // Fails, since the query does not filter by tenant.
+/// assert!(client.parse("SELECT item FROM orders", &[]).is_err());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Scoped bindings take precedence over the bindings given to [`Scoped::parse`], so they cannot be
+/// overridden by accident.
+///
+/// Only queries are checked: statements prepared directly through the [`GenericClient`] methods
+/// and copies made with [`CopyIn`] go through unchecked.
+///
+/// [`Scoped::parse`]: #method.parse
+/// [`Error::Unscoped`]: ../enum.Error.html#variant.Unscoped
+/// [`GenericClient`]: trait.GenericClient.html
+/// [`CopyIn`]: ../copy/struct.CopyIn.html
+#[derive(Clone)]
+pub struct Scoped<C>
+where
+    C: GenericClient,
+{
+    client: C,
+    bindings: Vec<(String, Arc<dyn ToSql + Send + Sync>)>,
+    strict: bool,
+}
+
+impl<C> Scoped<C>
+where
+    C: GenericClient,
+{
+    /// Wrap a client without any scoped bindings.
+    pub fn new(client: C) -> Scoped<C> {
+        Scoped {
+            client,
+            bindings: Vec::new(),
+            strict: false,
+        }
+    }
+
+    /// Supply `value` to every query which refers to `$name`.
+    pub fn bind<T>(mut self, name: impl Into<String>, value: T) -> Scoped<C>
+    where
+        T: ToSql + Send + Sync + 'static,
+    {
+        self.bindings.push((name.into(), Arc::new(value)));
+        self
+    }
+
+    /// Require every query to refer to all of the scoped bindings.
+    pub fn strict(mut self) -> Scoped<C> {
+        self.strict = true;
+        self
+    }
+
+    /// Return the inner client.
+    pub fn into_inner(self) -> C {
+        self.client
+    }
+
+    /// Parse a query in the same way as [`Query::parse`], with the scoped bindings in addition to
+    /// the given ones.
+    ///
+    /// In strict mode, fails with [`Error::Unscoped`] if the query does not refer to every
+    /// scoped binding.
+    ///
+    /// [`Query::parse`]: ../struct.Query.html#method.parse
+    /// [`Error::Unscoped`]: ../enum.Error.html#variant.Unscoped
+    pub fn parse<'a>(
+        &'a self,
+        text: &str,
+        bindings: &[(&str, Parameter<'a>)],
+    ) -> Result<Query<'a>> {
+        if self.strict {
+            let missing = self
+                .bindings
+                .iter()
+                .find(|(name, _)| !parse::refers_to(text, name));
+
+            if let Some((name, _)) = missing {
+                let binding = name.clone();
+                return Err(Error::Unscoped { binding });
+            }
+        }
+
+        let mut combined = Vec::with_capacity(self.bindings.len() + bindings.len());
+        combined.extend(
+            self.bindings
+                .iter()
+                .map(|(name, value)| (name.as_str(), &**value as Parameter<'a>)),
+        );
+        combined.extend_from_slice(bindings);
+
+        Query::parse(text, &combined)
+    }
+}
+
+impl<C> Deref for Scoped<C>
+where
+    C: GenericClient,
+{
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+impl<C> DerefMut for Scoped<C>
+where
+    C: GenericClient,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.client
+    }
+}
+
+#[async_trait]
+impl<C> GenericClient for Scoped<C>
+where
+    C: GenericClient + Sync + Send,
+{
+    async fn prepare(&self, sql: &str) -> Result<Statement, SqlError> {
+        self.client.prepare(sql).await
+    }
+
+    async fn prepare_static(&self, sql: &'static str) -> Result<Statement, SqlError> {
+        self.client.prepare_static(sql).await
+    }
+
+    async fn prepare_typed(&self, sql: &str, types: &[Type]) -> Result<Statement, SqlError> {
+        self.client.prepare_typed(sql, types).await
+    }
+
+    async fn prepare_typed_static(
+        &self,
+        sql: &'static str,
+        types: &[Type],
+    ) -> Result<Statement, SqlError> {
+        self.client.prepare_typed_static(sql, types).await
+    }
+
+    async fn prepare_tagged_static(
+        &self,
+        sql: &'static str,
+        tag: &str,
+    ) -> Result<Statement, SqlError> {
+        self.client.prepare_tagged_static(sql, tag).await
+    }
+
+    async fn execute_raw<'a>(
+        &'a self,
+        statement: &Statement,
+        parameters: &[&'a (dyn ToSql + Sync)],
+    ) -> Result<u64, SqlError> {
+        self.client.execute_raw(statement, parameters).await
+    }
+
+    async fn query_raw<'a>(
+        &'a self,
+        statement: &Statement,
+        parameters: &[&'a (dyn ToSql + Sync)],
+    ) -> Result<RowStream, SqlError> {
+        self.client.query_raw(statement, parameters).await
+    }
+
+    fn execute_options(&self) -> ExecuteOptions {
+        self.client.execute_options()
+    }
+
+    fn in_transaction(&self) -> bool {
+        self.client.in_transaction()
+    }
+
+    fn exclusive(&self) -> bool {
+        self.client.exclusive()
+    }
+
+    fn __check_query(&self, query: &Query<'_>) -> Result<()> {
+        if self.strict {
+            // Parsing binds the scoped values themselves, and only those the query refers to.
+            let missing = self.bindings.iter().find(|(_, value)| {
+                let value = Arc::as_ptr(value) as *const u8;
+                let bound =
+                    |parameter: &Parameter<'_>| ptr::eq(*parameter as *const _ as *const u8, value);
+                !query.parameters().iter().any(bound)
+            });
+
+            if let Some((name, _)) = missing {
+                let binding = name.clone();
+                return Err(Error::Unscoped { binding });
+            }
+        }
+
+        self.client.__check_query(query)
+    }
+
+    fn __executed(&self, sql: &str, parameters: &[Parameter<'_>], tag: Option<&str>, rows: u64) {
+        self.client.__executed(sql, parameters, tag, rows)
+    }
+}
+
+#[async_trait]
+impl<C> CopyClient for Scoped<C>
+where
+    C: CopyClient + Sync + Send,
+{
+    async fn copy_in_raw(&self, statement: &Statement) -> Result<CopyInSink<Bytes>, SqlError> {
+        self.client.copy_in_raw(statement).await
+    }
+}
@@ -0,0 +1,274 @@
+//! Generate the definition of a `FromSqlRow` struct from the columns of a query.
+//!
+//! Requires the `codegen` feature.
+//!
+//! Mapping a table with dozens of columns by hand is tedious. [`row_struct`] prepares a query
+//! (without executing it), and writes a struct with a field for each of its columns, which is a
+//! starting point to copy into the source or to write from a build script:
+//!
+//! ```no_run
+//! # use postgres_query::{codegen, Result};
+//! # async fn foo(client: tokio_postgres::Client) -> Result<()> {
+//! let source = codegen::row_struct(&client, "Person", "SELECT * FROM people").await?;
+//! std::fs::write("src/person.rs", source).unwrap();
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! For a table such as `people (id SERIAL PRIMARY KEY, "fullName" TEXT NOT NULL, born DATE)`,
+//! this generates:
+//!
+//! ```text
+//! #[derive(Debug, FromSqlRow)]
+//! pub struct Person {
+//!     pub id: i32,
+//!     #[row(rename = "fullName")]
+//!     pub full_name: String,
+//!     pub born: Option<chrono::NaiveDate>,
+//! }
+//! ```
+//!
+//! Columns which come straight from a table are `Option`al unless they are `NOT NULL`. Other
+//! columns (such as the results of expressions, or any column of a query with an outer join) are
+//! always `Option`al, since the database does not tell whether they may be `NULL`. Fields whose
+//! names are keywords are raw identifiers (`r#type`), and if several columns have the same name
+//! (such as `?column?`), the struct is extracted by position instead. Types without a Rust
+//! equivalent are left as `String`, with a `TODO` comment.
+//!
+//! [`row_struct`]: fn.row_struct.html
+
+use crate::client::GenericClient;
+use crate::error::Result;
+use crate::execute;
+use crate::parse::{self, Token};
+use crate::Query;
+use postgres_types::{Kind, Type};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// Generate a struct named `name` with a field for each column returned by `sql`.
+///
+/// The query is only prepared, so it may have parameters, and a query such as
+/// `SELECT * FROM people` does not read any rows.
+pub async fn row_struct<C>(client: &C, name: &str, sql: &str) -> Result<String>
+where
+    C: GenericClient + Sync,
+{
+    let statement = client.prepare(sql).await.map_err(execute::Error::from)?;
+    let columns = statement.columns();
+
+    // Columns from the nullable side of an outer join still refer to their table, whose
+    // constraints say nothing about the result.
+    let outer_join = has_outer_join(sql);
+
+    // Columns are extracted by name, which is ambiguous if several have the same name (such as
+    // expressions without an alias, which are all named `?column?`).
+    let mut names = HashSet::new();
+    let positional = !columns.iter().all(|column| names.insert(column.name()));
+
+    let mut source = String::new();
+    let _ = writeln!(source, "#[derive(Debug, FromSqlRow)]");
+    if positional {
+        let _ = writeln!(source, "#[row(positional)]");
+    }
+    let _ = writeln!(source, "pub struct {} {{", name);
+
+    let mut fields = HashSet::new();
+    for (i, column) in columns.iter().enumerate() {
+        let nullable = match (column.table_oid(), column.column_id()) {
+            (Some(table), Some(attribute)) if !outer_join => {
+                !not_null(client, table, attribute).await?
+            }
+            _ => true,
+        };
+
+        let mut field = field_name(column.name());
+        if field.is_empty() || fields.contains(&field) {
+            field = format!("column_{}", i);
+            while fields.contains(&field) {
+                field.push('_');
+            }
+        }
+        fields.insert(field.clone());
+
+        let ty = match rust_type(column.type_()) {
+            Some(ty) => ty,
+            None => {
+                let _ = writeln!(
+                    source,
+                    "    // TODO: no Rust type for `{}`",
+                    column.type_().name()
+                );
+                "String".to_owned()
+            }
+        };
+        let ty = if nullable {
+            format!("Option<{}>", ty)
+        } else {
+            ty
+        };
+
+        if !positional && field.trim_start_matches("r#") != column.name() {
+            let _ = writeln!(source, "    #[row(rename = {:?})]", column.name());
+        }
+        let _ = writeln!(source, "    pub {}: {},", field, ty);
+    }
+
+    let _ = writeln!(source, "}}");
+    Ok(source)
+}
+
+/// Whether a column of a table is `NOT NULL`. Columns of other relations, such as views, may
+/// always be `NULL`.
+async fn not_null<C>(client: &C, table: u32, attribute: i16) -> Result<bool>
+where
+    C: GenericClient + Sync,
+{
+    let rows: Vec<(bool,)> = Query::new_static(
+        "SELECT attribute.attnotnull
+         FROM pg_attribute AS attribute
+         JOIN pg_class AS class ON class.oid = attribute.attrelid
+         WHERE attribute.attrelid = $1 AND attribute.attnum = $2 AND class.relkind IN ('r', 'p')",
+        vec![&table, &attribute],
+    )
+    .fetch(client)
+    .await?;
+    Ok(rows.first().is_some_and(|&(not_null,)| not_null))
+}
+
+/// Whether `sql` contains a `LEFT`, `RIGHT` or `FULL` join anywhere, including in subqueries.
+fn has_outer_join(sql: &str) -> bool {
+    let words = parse::tokens(sql).filter_map(|(_, token)| match token {
+        Token::Word(word) => Some(word.to_uppercase()),
+        _ => None,
+    });
+
+    let mut previous = None;
+    for word in words {
+        if word == "JOIN" || word == "OUTER" {
+            if let Some("LEFT" | "RIGHT" | "FULL") = previous.as_deref() {
+                return true;
+            }
+        }
+        previous = Some(word);
+    }
+    false
+}
+
+/// The Rust type extracted from values of the SQL type, if there is one.
+fn rust_type(ty: &Type) -> Option<String> {
+    let name = match *ty {
+        Type::BOOL => "bool",
+        Type::CHAR => "i8",
+        Type::INT2 => "i16",
+        Type::INT4 => "i32",
+        Type::INT8 => "i64",
+        Type::OID => "u32",
+        Type::FLOAT4 => "f32",
+        Type::FLOAT8 => "f64",
+        Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME => "String",
+        Type::BYTEA => "Vec<u8>",
+        Type::MONEY => "postgres_query::types::Money",
+        Type::NUMERIC => "rust_decimal::Decimal",
+        Type::JSON | Type::JSONB => "serde_json::Value",
+        Type::UUID => "uuid::Uuid",
+        Type::TIMESTAMP => "chrono::NaiveDateTime",
+        Type::TIMESTAMPTZ => "chrono::DateTime<chrono::Utc>",
+        Type::DATE => "chrono::NaiveDate",
+        Type::TIME => "chrono::NaiveTime",
+        Type::INET => "std::net::IpAddr",
+        _ => {
+            return match ty.kind() {
+                Kind::Array(member) => rust_type(member).map(|member| format!("Vec<{}>", member)),
+                Kind::Domain(inner) => rust_type(inner),
+                _ => None,
+            }
+        }
+    };
+    Some(name.to_owned())
+}
+
+/// Convert the name of a column to a field name in `snake_case`, or an empty string if that is not
+/// possible.
+fn field_name(column: &str) -> String {
+    let mut field = String::with_capacity(column.len());
+    let mut previous_lowercase = false;
+    for ch in column.chars() {
+        if ch.is_ascii_uppercase() {
+            if previous_lowercase {
+                field.push('_');
+            }
+            field.push(ch.to_ascii_lowercase());
+            previous_lowercase = false;
+        } else if ch.is_ascii_alphanumeric() || ch == '_' {
+            field.push(ch);
+            previous_lowercase = ch.is_ascii_lowercase() || ch.is_ascii_digit();
+        } else if !field.is_empty() && !field.ends_with('_') {
+            field.push('_');
+            previous_lowercase = false;
+        }
+    }
+
+    let field = field.trim_end_matches('_');
+    if field.is_empty() || field.starts_with(|ch: char| ch.is_ascii_digit()) {
+        return String::new();
+    }
+    if field == "_" || field == "self" || field == "super" || field == "crate" {
+        // These may not be raw identifiers.
+        return format!("{}_", field);
+    }
+    if is_keyword(field) {
+        return format!("r#{}", field);
+    }
+    field.to_owned()
+}
+
+/// Whether `name` is a keyword in any edition, including those reserved for future use.
+fn is_keyword(name: &str) -> bool {
+    const KEYWORDS: &[&str] = &[
+        "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do",
+        "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in",
+        "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+        "return", "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe",
+        "unsized", "use", "virtual", "where", "while", "yield",
+    ];
+    KEYWORDS.contains(&name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_names() {
+        assert_eq!(field_name("id"), "id");
+        assert_eq!(field_name("fullName"), "full_name");
+        assert_eq!(field_name("HTTPStatus"), "httpstatus");
+        assert_eq!(field_name("address2"), "address2");
+        assert_eq!(field_name("?column?"), "column");
+        assert_eq!(field_name("first name"), "first_name");
+        assert_eq!(field_name("type"), "r#type");
+        assert_eq!(field_name("do"), "r#do");
+        assert_eq!(field_name("macro"), "r#macro");
+        assert_eq!(field_name("self"), "self_");
+        assert_eq!(field_name("2fa"), "");
+        assert_eq!(field_name("?"), "");
+    }
+
+    #[test]
+    fn outer_joins() {
+        assert!(has_outer_join("SELECT * FROM a LEFT JOIN b USING (id)"));
+        assert!(has_outer_join("SELECT * FROM a full outer join b ON true"));
+        assert!(!has_outer_join("SELECT * FROM a JOIN b USING (id)"));
+        assert!(!has_outer_join(
+            "SELECT 'LEFT JOIN' AS \"left\", 1 AS join_count"
+        ));
+    }
+
+    #[test]
+    fn rust_types() {
+        assert_eq!(rust_type(&Type::INT4).as_deref(), Some("i32"));
+        assert_eq!(rust_type(&Type::TEXT_ARRAY).as_deref(), Some("Vec<String>"));
+        assert_eq!(rust_type(&Type::TS_VECTOR), None);
+    }
+}
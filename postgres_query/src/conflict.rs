@@ -0,0 +1,227 @@
+//! Run mutations which may conflict with existing rows, without treating conflicts as errors.
+//!
+//! This is useful for idempotent inserts against schemas where `ON CONFLICT DO NOTHING` cannot be
+//! used, for example because the conflicting constraint is an exclusion constraint, or because
+//! the database predates `ON CONFLICT`. Unique and exclusion violations are reported as an
+//! [`Outcome::Conflict`], while all other errors are returned as usual.
+//!
+//! ```
+//! # use tokio_postgres::Client;
+//! # use postgres_query::{conflict::{self, Outcome}, query, Result};
+//! # async fn foo() -> Result<()> {
+//! # let client: Client = unimplemented!();
+//! let insert = query!("INSERT INTO people VALUES ($name)", name = "John Wick");
+//!
+//! match conflict::try_execute_or_ignore_conflict(&client, &insert).await? {
+//!     Outcome::Done(_) => println!("inserted"),
+//!     Outcome::Conflict(conflict) => println!("already exists: {:?}", conflict.constraint),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Note that the conflict is still an error as far as the database is concerned: within a
+//! transaction, the failed statement aborts the transaction. These helpers are therefore meant to
+//! be used outside of transactions, where each statement is committed on its own. Within a
+//! transaction, run the mutation in a [nested transaction] instead.
+//!
+//! Where `ON CONFLICT DO NOTHING` can be used, [`Query::execute_ignoring_conflicts`] reports
+//! whether the row was inserted or skipped instead.
+//!
+//! [`Outcome::Conflict`]: enum.Outcome.html#variant.Conflict
+//! [nested transaction]: ../client/trait.Nested.html
+//! [`Query::execute_ignoring_conflicts`]: ../struct.Query.html#method.execute_ignoring_conflicts
+
+use crate::client::GenericClient;
+use crate::error::{Error, Result};
+use crate::execute;
+use crate::extract::FromSqlRow;
+use crate::parse;
+use crate::Query;
+use tokio_postgres::error::SqlState;
+
+/// The result of a mutation which may conflict with existing rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome<T> {
+    /// The mutation succeeded.
+    Done(T),
+    /// The mutation was rejected, since it conflicts with an existing row.
+    Conflict(Conflict),
+}
+
+impl<T> Outcome<T> {
+    /// The result of the mutation, if it succeeded.
+    pub fn done(self) -> Option<T> {
+        match self {
+            Outcome::Done(value) => Some(value),
+            Outcome::Conflict(_) => None,
+        }
+    }
+
+    /// Returns `true` if the mutation conflicts with an existing row.
+    pub fn is_conflict(&self) -> bool {
+        matches!(self, Outcome::Conflict(_))
+    }
+}
+
+/// Describes why a mutation was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    /// The name of the violated constraint, as reported by the database.
+    pub constraint: Option<String>,
+}
+
+/// The kind of constraint that was violated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConflictKind {
+    /// A `UNIQUE` constraint or `PRIMARY KEY` (SQLSTATE `23505`).
+    Unique,
+    /// An `EXCLUDE` constraint (SQLSTATE `23P01`).
+    Exclusion,
+}
+
+/// Whether an `INSERT ... ON CONFLICT DO NOTHING` inserted its row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InsertOutcome {
+    /// The row was inserted.
+    Inserted,
+    /// The row conflicts with an existing one, and was not inserted.
+    Skipped,
+}
+
+impl InsertOutcome {
+    /// Returns `true` if the row was inserted.
+    pub fn is_inserted(self) -> bool {
+        self == InsertOutcome::Inserted
+    }
+}
+
+impl<'a> Query<'a> {
+    /// Execute an `INSERT` of a single row with an `ON CONFLICT DO NOTHING` clause, and report
+    /// whether the row was inserted, or skipped because of a conflict:
+    ///
+    /// ```
+    /// # use tokio_postgres::Client;
+    /// # use postgres_query::{conflict::InsertOutcome, query, Result};
+    /// # async fn foo() -> Result<()> {
+    /// # let client: Client = unimplemented!();
+    /// let outcome = query!(
+    ///     "INSERT INTO users (email) VALUES ($email) ON CONFLICT (email) DO NOTHING",
+    ///     email = "john@wick.com",
+    /// )
+    /// .execute_ignoring_conflicts(&client)
+    /// .await?;
+    ///
+    /// if outcome == InsertOutcome::Inserted {
+    ///     println!("welcome!");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// The outcome is decided by the number of inserted rows, so an `INSERT ... SELECT` which
+    /// selects nothing is reported as skipped as well. Unlike [`try_execute_or_ignore_conflict`],
+    /// the conflict is not an error, so this may be used within transactions.
+    ///
+    /// Inserting more than one row fails with [`Error::UnexpectedRowCount`]. An `INSERT` which
+    /// lists several rows in its `VALUES` is rejected before it is executed, with `actual` set to
+    /// the number of rows listed. Other statements, such as an `INSERT ... SELECT`, can only be
+    /// checked afterwards: the query has been executed by the time the error is returned, so run
+    /// it within a transaction for the changes to be rolled back.
+    ///
+    /// [`Error::UnexpectedRowCount`]: execute/enum.Error.html#variant.UnexpectedRowCount
+    /// [`try_execute_or_ignore_conflict`]: conflict/fn.try_execute_or_ignore_conflict.html
+    pub async fn execute_ignoring_conflicts<C>(&self, client: &C) -> Result<InsertOutcome>
+    where
+        C: GenericClient + Sync,
+    {
+        match parse::values_rows(&self.sql) {
+            Some(rows) if rows > 1 => {
+                return Err(execute::Error::UnexpectedRowCount {
+                    expected: 1,
+                    actual: rows as u64,
+                }
+                .into())
+            }
+            _ => {}
+        }
+
+        match self.execute(client).await? {
+            0 => Ok(InsertOutcome::Skipped),
+            1 => Ok(InsertOutcome::Inserted),
+            actual => Err(execute::Error::UnexpectedRowCount {
+                expected: 1,
+                actual,
+            }
+            .into()),
+        }
+    }
+}
+
+/// Execute the query, and return the number of affected rows unless it conflicts with an
+/// existing row.
+pub async fn try_execute_or_ignore_conflict<C>(
+    client: &C,
+    query: &Query<'_>,
+) -> Result<Outcome<u64>>
+where
+    C: GenericClient + Sync,
+{
+    ignore_conflict(query.execute(client).await)
+}
+
+/// Execute the query, and return the resulting value unless it conflicts with an existing row.
+/// Useful for `INSERT ... RETURNING`.
+pub async fn try_fetch_one_or_ignore_conflict<T, C>(
+    client: &C,
+    query: &Query<'_>,
+) -> Result<Outcome<T>>
+where
+    T: FromSqlRow,
+    C: GenericClient + Sync,
+{
+    ignore_conflict(query.fetch_one(client).await)
+}
+
+/// Execute the query, and return the resulting values unless it conflicts with an existing row.
+pub async fn try_fetch_or_ignore_conflict<T, C>(
+    client: &C,
+    query: &Query<'_>,
+) -> Result<Outcome<Vec<T>>>
+where
+    T: FromSqlRow,
+    C: GenericClient + Sync,
+{
+    ignore_conflict(query.fetch(client).await)
+}
+
+fn ignore_conflict<T>(result: Result<T>) -> Result<Outcome<T>> {
+    match result {
+        Ok(value) => Ok(Outcome::Done(value)),
+        Err(error) => match conflict(&error) {
+            Some(conflict) => Ok(Outcome::Conflict(conflict)),
+            None => Err(error),
+        },
+    }
+}
+
+fn conflict(error: &Error) -> Option<Conflict> {
+    let sql = match error {
+        Error::Execute(execute::Error::Sql(sql)) => sql,
+        _ => return None,
+    };
+
+    let kind = match sql.code() {
+        Some(code) if *code == SqlState::UNIQUE_VIOLATION => ConflictKind::Unique,
+        Some(code) if *code == SqlState::EXCLUSION_VIOLATION => ConflictKind::Exclusion,
+        _ => return None,
+    };
+
+    let constraint = sql
+        .as_db_error()
+        .and_then(|db| db.constraint())
+        .map(String::from);
+
+    Some(Conflict { kind, constraint })
+}
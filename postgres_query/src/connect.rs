@@ -0,0 +1,196 @@
+//! Connect to a database which may not be up yet.
+//!
+//! Requires the `connect` feature.
+//!
+//! When an application starts alongside its database (such as in `docker compose` or a
+//! Kubernetes pod), the first attempts to connect commonly fail while the database is still
+//! starting. [`connect_with_retry`] keeps trying, with exponential backoff, until it has a
+//! connection on which `SELECT 1` succeeds:
+//!
+//! ```no_run
+//! # use postgres_query::{connect_with_retry, query, RetryPolicy};
+//! # use std::time::Duration;
+//! # async fn foo() -> Result<(), Box<dyn std::error::Error>> {
+//! let config = "host=localhost user=postgres".parse()?;
+//! let policy = RetryPolicy::default()
+//!     .attempts(20)
+//!     .delay(Duration::from_millis(250), Duration::from_secs(5));
+//!
+//! let (client, connection) = connect_with_retry(&config, policy).await?;
+//!
+//! let (answer,): (i32,) = query!("SELECT 42").fetch_one(&client).await?;
+//!
+//! // Dropping the client closes the connection, which ends the task.
+//! drop(client);
+//! connection.await??;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The connection is spawned onto the current Tokio runtime. On other runtimes, use
+//! [`connect_with_retry_on`] with a [`Spawner`] instead.
+//!
+//! [`connect_with_retry`]: fn.connect_with_retry.html
+//! [`connect_with_retry_on`]: fn.connect_with_retry_on.html
+//! [`Spawner`]: ../spawn/trait.Spawner.html
+
+use crate::client::{Caching, HealthCheck};
+use crate::error::{Error, Result};
+use crate::spawn::{self, ConnectionHandle, Spawner};
+use futures::future::BoxFuture;
+use futures_timer::Delay;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_postgres::tls::{MakeTlsConnect, TlsConnect};
+use tokio_postgres::{error::Error as SqlError, Client, Config, NoTls, Socket};
+
+/// Drives a connection to completion, as returned by `Config::connect`.
+type Connection = BoxFuture<'static, Result<(), SqlError>>;
+
+/// How many times to try connecting, and how long to wait in between.
+///
+/// By default, 10 attempts are made, waiting 100 milliseconds after the first failure and doubling
+/// the delay after every attempt, up to 5 seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    attempts: u32,
+    min_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 10,
+            min_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Give up after this many attempts to connect. At least one attempt is always made.
+    pub fn attempts(mut self, attempts: u32) -> RetryPolicy {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    /// Wait at least `min` after the first failed attempt, doubling the delay after every failed
+    /// attempt up to `max`.
+    pub fn delay(mut self, min: Duration, max: Duration) -> RetryPolicy {
+        self.min_delay = min;
+        self.max_delay = max;
+        self
+    }
+}
+
+/// The task driving a connection, which completes once the connection is closed.
+pub type ConnectionTask = JoinHandle<Result<(), SqlError>>;
+
+/// Connect to the database without TLS, retrying according to `policy`. See the [module
+/// documentation].
+///
+/// [module documentation]: index.html
+pub async fn connect_with_retry(
+    config: &Config,
+    policy: RetryPolicy,
+) -> Result<(Caching<Client>, ConnectionTask)> {
+    connect_with_retry_tls(config, NoTls, policy).await
+}
+
+/// Connect to the database with `tls`, retrying according to `policy`.
+///
+/// Every error is retried, including those which are unlikely to go away by themselves (such as
+/// failed authentication), since a database which is starting up may report errors of any kind.
+/// Once all attempts fail, returns [`Error::Retries`] with the error of the last attempt.
+///
+/// The connection is spawned onto the current Tokio runtime.
+///
+/// [`Error::Retries`]: ../enum.Error.html#variant.Retries
+pub async fn connect_with_retry_tls<T>(
+    config: &Config,
+    tls: T,
+    policy: RetryPolicy,
+) -> Result<(Caching<Client>, ConnectionTask)>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + 'static,
+    T::Stream: Send + 'static,
+    T::TlsConnect: Send,
+    <T::TlsConnect as TlsConnect<Socket>>::Future: Send,
+{
+    retry(config, tls, policy, tokio::spawn).await
+}
+
+/// Connect to the database with `tls`, retrying according to `policy`, and drive the connection on
+/// a task spawned with `spawner`.
+///
+/// Behaves like [`connect_with_retry_tls`], but works on any runtime.
+///
+/// [`connect_with_retry_tls`]: fn.connect_with_retry_tls.html
+pub async fn connect_with_retry_on<T, S>(
+    config: &Config,
+    tls: T,
+    policy: RetryPolicy,
+    spawner: S,
+) -> Result<(Caching<Client>, ConnectionHandle)>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + 'static,
+    T::Stream: Send + 'static,
+    T::TlsConnect: Send,
+    <T::TlsConnect as TlsConnect<Socket>>::Future: Send,
+    S: Spawner,
+{
+    retry(config, tls, policy, |connection| {
+        spawn::spawn_connection(&spawner, connection)
+    })
+    .await
+}
+
+async fn retry<T, F, H>(
+    config: &Config,
+    tls: T,
+    policy: RetryPolicy,
+    mut spawn: F,
+) -> Result<(Caching<Client>, H)>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + 'static,
+    T::Stream: Send + 'static,
+    F: FnMut(Connection) -> H,
+{
+    let mut delay = policy.min_delay;
+    let mut attempt = 1;
+
+    loop {
+        let error = match connect(config, tls.clone(), &mut spawn).await {
+            Ok(connected) => return Ok(connected),
+            Err(error) => error,
+        };
+
+        if attempt >= policy.attempts {
+            return Err(Error::Retries {
+                attempts: attempt,
+                source: Box::new(error),
+            });
+        }
+
+        Delay::new(delay).await;
+        delay = Ord::min(delay * 2, policy.max_delay);
+        attempt += 1;
+    }
+}
+
+/// Connect once, and check that the connection is usable.
+async fn connect<T, F, H>(config: &Config, tls: T, spawn: F) -> Result<(Caching<Client>, H)>
+where
+    T: MakeTlsConnect<Socket> + 'static,
+    T::Stream: Send + 'static,
+    F: FnOnce(Connection) -> H,
+{
+    let (client, connection) = config.connect(tls).await.map_err(Error::Connect)?;
+    let connection = spawn(Box::pin(connection));
+
+    // Dropping the client closes the connection if it is not usable.
+    client.ping().await?;
+
+    Ok((Caching::new(client), connection))
+}
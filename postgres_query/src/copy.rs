@@ -0,0 +1,345 @@
+//! Bulk inserts through `COPY ... FROM STDIN`.
+//!
+//! See [`copy_in_rows`] and [`CopyIn`].
+//!
+//! [`copy_in_rows`]: fn.copy_in_rows.html
+//! [`CopyIn`]: struct.CopyIn.html
+
+use crate::client::CopyClient;
+use crate::error::Result;
+use crate::Parameter;
+use bytes::{BufMut, BytesMut};
+use futures::{pin_mut, SinkExt};
+use postgres_types::{IsNull, ToSql, Type};
+use std::convert::TryFrom;
+use std::error::Error as StdError;
+use thiserror::Error;
+use tokio_postgres::error::Error as SqlError;
+
+/// Header of the binary `COPY` format: the signature, followed by the flags and the length of the
+/// header extension (both zero).
+const HEADER: &[u8] = b"PGCOPY\n\xff\r\n\0\0\0\0\0\0\0\0\0";
+
+/// By default, data is sent to the database every 64 KiB.
+const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// An error that may arise when copying rows.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to copy rows")]
+    Sql(#[from] SqlError),
+
+    #[error("expected {expected} values in row, found {found}")]
+    ColumnCount { expected: usize, found: usize },
+
+    #[error("failed to encode value for column `{column}`")]
+    Encode {
+        column: String,
+        #[source]
+        source: Box<dyn StdError + Sync + Send>,
+    },
+}
+
+/// A type which can be written as a row of values.
+///
+/// Implemented for tuples of up to 8 values, which are written in order. Structs with named fields
+/// may derive the trait, which writes the fields in the order they are declared.
+pub trait ToSqlRow {
+    /// Number of values in each row.
+    const COLUMN_COUNT: usize;
+
+    /// Get the values of this row, in the order of the columns they belong to.
+    fn to_sql_row(&self) -> Vec<Parameter<'_>>;
+}
+
+/// A row whose columns have names and SQL types, which allows many rows to be bound as one array
+/// per column with [`Unnest`].
+///
+/// Derived along with [`ToSqlRow`] when every field has a type:
+///
+/// ```
+/// # use postgres_query::{copy::ColumnTypes, ToSqlRow};
+/// #[derive(ToSqlRow)]
+/// struct Person {
+///     #[sql_row(sql_type = "int4")]
+///     id: i32,
+///     #[sql_row(rename = "full_name", sql_type = "text")]
+///     name: String,
+///     #[sql_row(skip)]
+///     age: u8,
+/// }
+///
+/// assert_eq!(Person::COLUMNS, [("id", "int4"), ("full_name", "text")]);
+/// ```
+///
+/// [`Unnest`]: ../cte/struct.Unnest.html
+/// [`ToSqlRow`]: trait.ToSqlRow.html
+pub trait ColumnTypes: ToSqlRow {
+    /// The name and SQL type of each column, in the order of [`to_sql_row`].
+    ///
+    /// [`to_sql_row`]: trait.ToSqlRow.html#tymethod.to_sql_row
+    const COLUMNS: &'static [(&'static str, &'static str)];
+}
+
+/// Insert rows into the given columns of a table, with the default [`CopyIn`] settings.
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{copy::copy_in_rows, Result};
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// let people = vec![("John Wick", 42), ("Winston", 65)];
+///
+/// let count = copy_in_rows(&client, "people", &["name", "age"], &people).await?;
+/// assert_eq!(count, 2);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`CopyIn`]: struct.CopyIn.html
+pub async fn copy_in_rows<C, I>(client: &C, table: &str, columns: &[&str], rows: I) -> Result<u64>
+where
+    C: CopyClient + Sync,
+    I: IntoIterator,
+    I::Item: ToSqlRow,
+{
+    CopyIn::new(table, columns).rows(client, rows).await
+}
+
+/// Insert rows into a table using the binary `COPY` format, the fastest way to insert many rows.
+///
+/// The types of the columns are looked up in the database before copying. Rows are encoded into a
+/// buffer which is sent to the database whenever it grows beyond the [chunk size], at which point
+/// the progress callback is called with the total number of rows sent so far.
+///
+/// The table and column names are inserted verbatim into the `COPY` statement, so they must not
+/// come from untrusted input.
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{copy::CopyIn, Result};
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// let people = (0..1_000_000).map(|i| (format!("Person #{}", i), i % 100));
+///
+/// let count = CopyIn::new("people", &["name", "age"])
+///     .chunk_size(1024 * 1024)
+///     .on_progress(|rows| println!("sent {} rows", rows))
+///     .rows(&client, people)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [chunk size]: #method.chunk_size
+pub struct CopyIn<'a> {
+    table: &'a str,
+    columns: &'a [&'a str],
+    chunk_size: usize,
+    progress: Option<Box<dyn FnMut(u64) + Send + 'a>>,
+}
+
+impl<'a> CopyIn<'a> {
+    /// Copy into the given columns of a table.
+    pub fn new(table: &'a str, columns: &'a [&'a str]) -> CopyIn<'a> {
+        CopyIn {
+            table,
+            columns,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            progress: None,
+        }
+    }
+
+    /// Send the encoded rows to the database whenever they exceed this many bytes.
+    pub fn chunk_size(mut self, bytes: usize) -> CopyIn<'a> {
+        self.chunk_size = bytes;
+        self
+    }
+
+    /// Call `progress` with the total number of rows sent so far every time a chunk is sent.
+    pub fn on_progress<F>(mut self, progress: F) -> CopyIn<'a>
+    where
+        F: FnMut(u64) + Send + 'a,
+    {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Copy the rows into the table, returning the number of rows inserted.
+    pub async fn rows<C, I>(mut self, client: &C, rows: I) -> Result<u64>
+    where
+        C: CopyClient + Sync,
+        I: IntoIterator,
+        I::Item: ToSqlRow,
+    {
+        let columns = self.columns.join(", ");
+
+        let select = format!("SELECT {} FROM {} LIMIT 0", columns, self.table);
+        let statement = client.prepare(&select).await.map_err(Error::from)?;
+        let types = statement
+            .columns()
+            .iter()
+            .map(|column| column.type_().clone())
+            .collect::<Vec<_>>();
+
+        let copy = format!(
+            "COPY {} ({}) FROM STDIN (FORMAT binary)",
+            self.table, columns
+        );
+        let statement = client.prepare(&copy).await.map_err(Error::from)?;
+        let sink = client.copy_in_raw(&statement).await.map_err(Error::from)?;
+        pin_mut!(sink);
+
+        let mut buffer = BytesMut::new();
+        buffer.put_slice(HEADER);
+
+        let mut sent = 0;
+        let mut written = 0;
+        for row in rows {
+            encode_row(&mut buffer, &row.to_sql_row(), &types, self.columns)?;
+            written += 1;
+
+            if buffer.len() >= self.chunk_size {
+                sink.send(buffer.split().freeze())
+                    .await
+                    .map_err(Error::from)?;
+                sent = written;
+                self.report(sent);
+            }
+        }
+
+        buffer.put_i16(-1);
+        sink.send(buffer.split().freeze())
+            .await
+            .map_err(Error::from)?;
+        if sent != written {
+            self.report(written);
+        }
+
+        let count = sink.finish().await.map_err(Error::from)?;
+        Ok(count)
+    }
+
+    fn report(&mut self, rows: u64) {
+        if let Some(progress) = &mut self.progress {
+            progress(rows);
+        }
+    }
+}
+
+/// Append a single row to the buffer in the binary `COPY` format.
+fn encode_row(
+    buffer: &mut BytesMut,
+    values: &[Parameter],
+    types: &[Type],
+    columns: &[&str],
+) -> Result<(), Error> {
+    if values.len() != types.len() {
+        return Err(Error::ColumnCount {
+            expected: types.len(),
+            found: values.len(),
+        });
+    }
+
+    buffer.put_i16(types.len() as i16);
+
+    for ((value, ty), column) in values.iter().zip(types).zip(columns) {
+        let encode_error = |source| Error::Encode {
+            column: column.to_string(),
+            source,
+        };
+
+        let start = buffer.len();
+        buffer.put_i32(0);
+
+        let len = match value.to_sql_checked(ty, buffer).map_err(encode_error)? {
+            IsNull::Yes => -1,
+            IsNull::No => {
+                let len = buffer.len() - start - 4;
+                i32::try_from(len).map_err(|error| encode_error(error.into()))?
+            }
+        };
+
+        buffer[start..start + 4].copy_from_slice(&len.to_be_bytes());
+    }
+
+    Ok(())
+}
+
+impl<T> ToSqlRow for &T
+where
+    T: ToSqlRow,
+{
+    const COLUMN_COUNT: usize = T::COLUMN_COUNT;
+
+    fn to_sql_row(&self) -> Vec<Parameter<'_>> {
+        T::to_sql_row(self)
+    }
+}
+
+macro_rules! impl_to_row_for_tuple {
+    ($($elem:ident => $index:tt),+) => {
+        impl<$($elem),+> ToSqlRow for ($($elem,)+)
+        where
+            $($elem: ToSql + Sync),+
+        {
+            const COLUMN_COUNT: usize = [$($index),+].len();
+
+            fn to_sql_row(&self) -> Vec<Parameter<'_>> {
+                vec![$(&self.$index),+]
+            }
+        }
+    };
+}
+
+impl_to_row_for_tuple!(A => 0);
+impl_to_row_for_tuple!(A => 0, B => 1);
+impl_to_row_for_tuple!(A => 0, B => 1, C => 2);
+impl_to_row_for_tuple!(A => 0, B => 1, C => 2, D => 3);
+impl_to_row_for_tuple!(A => 0, B => 1, C => 2, D => 3, E => 4);
+impl_to_row_for_tuple!(A => 0, B => 1, C => 2, D => 3, E => 4, F => 5);
+impl_to_row_for_tuple!(A => 0, B => 1, C => 2, D => 3, E => 4, F => 5, G => 6);
+impl_to_row_for_tuple!(A => 0, B => 1, C => 2, D => 3, E => 4, F => 5, G => 6, H => 7);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_values() {
+        let mut buffer = BytesMut::new();
+        let row = (1i32, None::<String>, "ab");
+        let types = [Type::INT4, Type::TEXT, Type::TEXT];
+        encode_row(&mut buffer, &row.to_sql_row(), &types, &["a", "b", "c"]).unwrap();
+
+        assert_eq!(
+            &buffer[..],
+            &[
+                0, 3, // number of values
+                0, 0, 0, 4, 0, 0, 0, 1, // 1i32
+                255, 255, 255, 255, // NULL
+                0, 0, 0, 2, b'a', b'b', // "ab"
+            ][..]
+        );
+    }
+
+    #[test]
+    fn encode_wrong_type() {
+        let mut buffer = BytesMut::new();
+        let row = ("abc",);
+        match encode_row(&mut buffer, &row.to_sql_row(), &[Type::INT4], &["age"]) {
+            Err(Error::Encode { column, .. }) => assert_eq!(column, "age"),
+            result => panic!("expected encoding error, found {:?}", result),
+        }
+    }
+
+    #[test]
+    fn encode_column_count() {
+        let mut buffer = BytesMut::new();
+        let row = (1i32, 2i32);
+        match encode_row(&mut buffer, &row.to_sql_row(), &[Type::INT4], &["a"]) {
+            Err(Error::ColumnCount { expected, found }) => assert_eq!((expected, found), (1, 2)),
+            result => panic!("expected column count error, found {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! Assemble queries with common table expressions (`WITH ... AS (...)`).
+//!
+//! See [`Cte`], and [`Unnest`] for expressions over rows held in memory.
+//!
+//! [`Cte`]: struct.Cte.html
+//! [`Unnest`]: struct.Unnest.html
+
+use crate::copy::ColumnTypes;
+use crate::{Parameter, Query};
+use bytes::{BufMut, BytesMut};
+use postgres_types::{to_sql_checked, IsNull, Kind, ToSql, Type};
+use std::convert::TryFrom;
+use std::error::Error as StdError;
+use std::fmt::Write;
+
+/// A builder for queries with common table expressions.
+///
+/// Each sub-query is a complete [`Query`] with its own parameters, which are renumbered when the
+/// final query is assembled, so there is no need to keep track of placeholders across sub-queries.
+///
+/// ```
+/// # use postgres_query::{cte::Cte, query};
+/// let query = Cte::new()
+///     .with("adults", query!("SELECT * FROM people WHERE age >= $age", age = 18))
+///     .with("named", query!("SELECT * FROM adults WHERE name = $name", name = "John"))
+///     .select(query!("SELECT * FROM named LIMIT $limit", limit = 10i64));
+///
+/// assert_eq!(
+///     query.sql(),
+///     "WITH adults AS (SELECT * FROM people WHERE age >= $1), \
+///      named AS (SELECT * FROM adults WHERE name = $2) \
+///      SELECT * FROM named LIMIT $3"
+/// );
+/// assert_eq!(query.parameters().len(), 3);
+/// ```
+///
+/// The names of the expressions are inserted verbatim into the query.
+///
+/// [`Query`]: ../struct.Query.html
+#[derive(Debug, Clone, Default)]
+pub struct Cte<'a> {
+    recursive: bool,
+    expressions: Vec<(String, Query<'a>)>,
+}
+
+impl<'a> Cte<'a> {
+    /// Start a query without any common table expressions.
+    pub fn new() -> Cte<'a> {
+        Cte::default()
+    }
+
+    /// Use `WITH RECURSIVE`, which allows expressions to refer to themselves.
+    pub fn recursive(mut self) -> Cte<'a> {
+        self.recursive = true;
+        self
+    }
+
+    /// Add an expression, which may be referred to by `name` in later expressions and in the final
+    /// query.
+    pub fn with(mut self, name: impl Into<String>, query: Query<'a>) -> Cte<'a> {
+        self.expressions.push((name.into(), query));
+        self
+    }
+
+    /// Assemble the expressions and the final query into a single query.
+    pub fn select(self, query: Query<'a>) -> Query<'a> {
+        if self.expressions.is_empty() {
+            return query;
+        }
+
+        let keyword = if self.recursive {
+            "WITH RECURSIVE "
+        } else {
+            "WITH "
+        };
+
+        let mut combined = Query::new_static(keyword, Vec::new());
+
+        for (i, (name, expression)) in self.expressions.into_iter().enumerate() {
+            let separator = if i == 0 { "" } else { ", " };
+            combined.append(Query::new(
+                format!("{}{} AS (", separator, name),
+                Vec::new(),
+            ));
+            combined.append(expression);
+            combined.append(Query::new_static(")", Vec::new()));
+        }
+
+        combined.append(Query::new_static(" ", Vec::new()));
+        combined.append(query);
+        combined
+    }
+}
+
+/// Rows held in memory, bound as a single array per column and expanded into a table with
+/// `unnest`.
+///
+/// Since the number of parameters does not depend on the number of rows, the statement is the same
+/// for any number of rows, and it may be used to upsert or join against thousands of rows at once:
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{cte::{Cte, Unnest}, query, Result, ToSqlRow};
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// #[derive(ToSqlRow)]
+/// struct Person {
+///     #[sql_row(sql_type = "int4")]
+///     id: i32,
+///     #[sql_row(sql_type = "text")]
+///     name: String,
+/// }
+///
+/// let people = vec![
+///     Person { id: 1, name: "John Wick".to_owned() },
+///     Person { id: 2, name: "Winston".to_owned() },
+/// ];
+///
+/// let values = Unnest::new(&people);
+/// let query = Cte::new().with("new_people", values.query()).select(query!(
+///     "INSERT INTO people (id, name) SELECT id, name FROM new_people
+///      ON CONFLICT (id) DO UPDATE SET name = excluded.name"
+/// ));
+///
+/// assert_eq!(
+///     values.query().sql(),
+///     "SELECT * FROM unnest($1::int4[], $2::text[]) AS unnest (id, name)"
+/// );
+///
+/// query.execute(&client).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The names and types of the columns come from [`ColumnTypes`], which may be derived, and are
+/// inserted verbatim into the query.
+///
+/// [`ColumnTypes`]: ../copy/trait.ColumnTypes.html
+#[derive(Debug)]
+pub struct Unnest<'a> {
+    columns: &'static [(&'static str, &'static str)],
+    arrays: Vec<ColumnArray<'a>>,
+}
+
+/// The values of a single column, bound as an array.
+#[derive(Debug)]
+struct ColumnArray<'a> {
+    values: Vec<Parameter<'a>>,
+}
+
+impl<'a> Unnest<'a> {
+    /// Split the rows into one array per column.
+    pub fn new<T>(rows: &'a [T]) -> Unnest<'a>
+    where
+        T: ColumnTypes,
+    {
+        let mut arrays = (0..T::COLUMNS.len())
+            .map(|_| ColumnArray {
+                values: Vec::with_capacity(rows.len()),
+            })
+            .collect::<Vec<_>>();
+
+        for row in rows {
+            for (array, value) in arrays.iter_mut().zip(row.to_sql_row()) {
+                array.values.push(value);
+            }
+        }
+
+        Unnest {
+            columns: T::COLUMNS,
+            arrays,
+        }
+    }
+
+    /// Select the rows as a table, with a column for each field of the rows.
+    pub fn query(&self) -> Query<'_> {
+        let mut arrays = String::new();
+        let mut names = String::new();
+        for (i, (name, ty)) in self.columns.iter().enumerate() {
+            let separator = if i == 0 { "" } else { ", " };
+            let _ = write!(arrays, "{}${}::{}[]", separator, i + 1, ty);
+            let _ = write!(names, "{}{}", separator, name);
+        }
+
+        Query::new(
+            format!("SELECT * FROM unnest({}) AS unnest ({})", arrays, names),
+            self.arrays.iter().map(|array| array as Parameter).collect(),
+        )
+    }
+}
+
+impl ToSql for ColumnArray<'_> {
+    /// Write the values in the binary format of a one-dimensional array.
+    fn to_sql(
+        &self,
+        ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn StdError + Sync + Send>> {
+        let member = match ty.kind() {
+            Kind::Array(member) => member,
+            _ => return Err(format!("cannot bind an array as `{}`", ty).into()),
+        };
+
+        let dimensions = if self.values.is_empty() { 0 } else { 1 };
+        out.put_i32(dimensions);
+        let has_nulls = out.len();
+        out.put_i32(0);
+        out.put_u32(member.oid());
+        if dimensions == 1 {
+            out.put_i32(i32::try_from(self.values.len())?);
+            out.put_i32(1);
+        }
+
+        for value in &self.values {
+            let start = out.len();
+            out.put_i32(0);
+            let len = match value.to_sql_checked(member, out)? {
+                IsNull::Yes => {
+                    out[has_nulls..has_nulls + 4].copy_from_slice(&1i32.to_be_bytes());
+                    -1
+                }
+                IsNull::No => i32::try_from(out.len() - start - 4)?,
+            };
+            out[start..start + 4].copy_from_slice(&len.to_be_bytes());
+        }
+
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(ty.kind(), Kind::Array(_))
+    }
+
+    to_sql_checked!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn without_expressions() {
+        let query = Cte::new().select(Query::new_static("SELECT $1", vec![&1]));
+        assert_eq!(query.sql(), "SELECT $1");
+    }
+
+    #[test]
+    fn encode_column_array() {
+        let array = ColumnArray {
+            values: vec![&1i32, &None::<i32>],
+        };
+        let mut out = BytesMut::new();
+        array.to_sql(&Type::INT4_ARRAY, &mut out).unwrap();
+
+        assert_eq!(
+            &out[..],
+            &[
+                0, 0, 0, 1, // dimensions
+                0, 0, 0, 1, // has nulls
+                0, 0, 0, 23, // INT4
+                0, 0, 0, 2, 0, 0, 0, 1, // length and lower bound
+                0, 0, 0, 4, 0, 0, 0, 1, // 1i32
+                255, 255, 255, 255, // NULL
+            ][..]
+        );
+    }
+
+    #[test]
+    fn recursive_expression() {
+        let query = Cte::new()
+            .recursive()
+            .with(
+                "numbers(n)",
+                Query::new_static(
+                    "SELECT $1 UNION ALL SELECT n + 1 FROM numbers WHERE n < $2",
+                    vec![&1, &10],
+                ),
+            )
+            .select(Query::new_static(
+                "SELECT n FROM numbers WHERE n > $1",
+                vec![&5],
+            ));
+
+        assert_eq!(
+            query.sql(),
+            "WITH RECURSIVE numbers(n) AS \
+             (SELECT $1 UNION ALL SELECT n + 1 FROM numbers WHERE n < $2) \
+             SELECT n FROM numbers WHERE n > $3"
+        );
+        assert_eq!(query.parameters().len(), 3);
+    }
+}
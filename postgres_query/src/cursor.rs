@@ -0,0 +1,183 @@
+//! Opaque, tamper-evident cursors for keyset pagination.
+//!
+//! Requires the `cursor` feature.
+//!
+//! With keyset pagination, the next page starts after the key of the last item of the current one
+//! (see [`prefetch_pages`]). An API handing out that key as a continuation token should not let
+//! clients forge keys of their own, nor depend on its format. A [`Cursors`] encodes any key which
+//! implements `Serialize` as a URL-safe token, signed with a secret, and decodes tokens back into
+//! keys, rejecting any which have been altered:
+//!
+//! ```
+//! # use postgres_query::{cursor::Cursors, query, Result};
+//! # async fn foo(client: tokio_postgres::Client, after: Option<&str>) -> Result<()> {
+//! let cursors = Cursors::new(b"a secret only the server knows");
+//!
+//! // The cursor of the first page is made up by the server, the others are given by the client.
+//! let (created_at, id): (i64, i32) = match after {
+//!     Some(cursor) => cursors.decode(cursor)?,
+//!     None => (0, 0),
+//! };
+//!
+//! let posts: Vec<(i64, i32, String)> = query!(
+//!     "SELECT created_at, id, title FROM posts
+//!      WHERE (created_at, id) > ($created_at, $id)
+//!      ORDER BY created_at, id
+//!      LIMIT 50",
+//!     created_at,
+//!     id,
+//! )
+//! .fetch(&client)
+//! .await?;
+//!
+//! let next = posts
+//!     .last()
+//!     .map(|(created_at, id, _)| cursors.encode(&(created_at, id)));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The key is signed, not encrypted: clients cannot change it, but may still read it.
+//!
+//! [`prefetch_pages`]: ../stream/fn.prefetch_pages.html
+//! [`Cursors`]: struct.Cursors.html
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::Sha256;
+use std::fmt;
+use thiserror::Error;
+
+type Signature = Hmac<Sha256>;
+
+/// The length of the signature at the end of a token, in bytes.
+const SIGNATURE_LEN: usize = 32;
+
+/// An error while decoding a cursor.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("the cursor is not valid base64")]
+    Encoding(#[source] base64::DecodeError),
+
+    #[error("the cursor has been altered, or was signed with a different secret")]
+    Signature,
+
+    #[error("the cursor does not contain a key of the expected type")]
+    Key(#[source] serde_json::Error),
+}
+
+/// Encodes and decodes cursors, which are signed with a secret.
+#[derive(Clone)]
+pub struct Cursors {
+    secret: Vec<u8>,
+}
+
+impl Cursors {
+    /// Sign cursors with `secret`. Cursors may only be decoded with the same secret they were
+    /// encoded with, so changing it invalidates all cursors handed out so far.
+    pub fn new(secret: impl AsRef<[u8]>) -> Cursors {
+        Cursors {
+            secret: secret.as_ref().to_vec(),
+        }
+    }
+
+    /// Encode a key as a cursor.
+    ///
+    /// # Panics
+    ///
+    /// If the key cannot be serialized, such as a map with keys that are not strings.
+    pub fn encode<K>(&self, key: &K) -> String
+    where
+        K: Serialize + ?Sized,
+    {
+        let mut token = serde_json::to_vec(key).expect("failed to serialize cursor key");
+        let signature = self.signature(&token).finalize().into_bytes();
+        token.extend_from_slice(&signature);
+        URL_SAFE_NO_PAD.encode(token)
+    }
+
+    /// Decode a cursor returned by `encode`, after verifying that it has not been altered.
+    pub fn decode<K>(&self, cursor: &str) -> Result<K, Error>
+    where
+        K: DeserializeOwned,
+    {
+        let token = URL_SAFE_NO_PAD.decode(cursor).map_err(Error::Encoding)?;
+        if token.len() < SIGNATURE_LEN {
+            return Err(Error::Signature);
+        }
+
+        let (key, signature) = token.split_at(token.len() - SIGNATURE_LEN);
+        self.signature(key)
+            .verify_slice(signature)
+            .map_err(|_| Error::Signature)?;
+
+        serde_json::from_slice(key).map_err(Error::Key)
+    }
+
+    fn signature(&self, key: &[u8]) -> Signature {
+        let mut signature =
+            Signature::new_from_slice(&self.secret).expect("HMAC accepts secrets of any length");
+        signature.update(key);
+        signature
+    }
+}
+
+impl fmt::Debug for Cursors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cursors").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let cursors = Cursors::new("secret");
+        let cursor = cursors.encode(&("2021-01-01", 42));
+        assert!(cursor
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_'));
+
+        let key: (String, i32) = cursors.decode(&cursor).unwrap();
+        assert_eq!(key, ("2021-01-01".to_owned(), 42));
+    }
+
+    #[test]
+    fn reject_altered_cursors() {
+        let cursors = Cursors::new("secret");
+        let cursor = cursors.encode(&17);
+
+        // A key signed with another secret.
+        let forged = Cursors::new("guess").encode(&18);
+        assert!(matches!(
+            cursors.decode::<i32>(&forged),
+            Err(Error::Signature)
+        ));
+
+        // The key itself changed.
+        let mut token = URL_SAFE_NO_PAD.decode(&cursor).unwrap();
+        token[1] = b'8';
+        let altered = URL_SAFE_NO_PAD.encode(token);
+        assert!(matches!(
+            cursors.decode::<i32>(&altered),
+            Err(Error::Signature)
+        ));
+
+        assert!(matches!(
+            cursors.decode::<i32>("abc"),
+            Err(Error::Signature)
+        ));
+        assert!(matches!(
+            cursors.decode::<i32>("not base64!"),
+            Err(Error::Encoding(_))
+        ));
+        assert!(matches!(
+            cursors.decode::<String>(&cursor),
+            Err(Error::Key(_))
+        ));
+    }
+}
@@ -1,17 +1,73 @@
-use crate::execute;
+use crate::{copy, execute, order};
 use thiserror::Error;
 
 /// Any error that this crate may produce.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
     #[error("failed to execute the query")]
     Execute(#[from] execute::Error),
 
+    #[error("failed to copy rows")]
+    Copy(#[from] copy::Error),
+
+    #[error("invalid sort order")]
+    Order(#[from] order::Error),
+
     #[error("failed to start new transaction")]
     BeginTransaction(#[source] tokio_postgres::Error),
 
+    #[error("failed to commit transaction")]
+    CommitTransaction(#[source] tokio_postgres::Error),
+
+    #[error("failed to roll back transaction")]
+    RollbackTransaction(#[source] tokio_postgres::Error),
+
     #[error("failed to parse query: {0}")]
     Parse(#[from] ParseError),
+
+    #[error("query does not refer to the scoped binding `${binding}`")]
+    Unscoped { binding: String },
+
+    #[cfg(feature = "deadpool")]
+    #[error("failed to get a client from the pool")]
+    Pool(#[source] deadpool_postgres::PoolError),
+
+    #[cfg(feature = "deadpool")]
+    #[error("timed out waiting for a client from the pool after {waited:?}")]
+    PoolTimeout { waited: std::time::Duration },
+
+    #[cfg(feature = "lint")]
+    #[error("query violates the lint rule `{rule}`")]
+    Lint { rule: String },
+
+    #[cfg(feature = "sync")]
+    #[error("failed to start the runtime of the blocking client")]
+    Runtime(#[source] std::io::Error),
+
+    #[cfg(any(feature = "sync", feature = "connect"))]
+    #[error("failed to connect to the database")]
+    Connect(#[source] tokio_postgres::Error),
+
+    #[cfg(feature = "connect")]
+    #[error("failed to connect to the database after {attempts} attempts")]
+    Retries {
+        attempts: u32,
+        #[source]
+        source: Box<Error>,
+    },
+
+    #[cfg(feature = "cursor")]
+    #[error("invalid pagination cursor")]
+    Cursor(#[from] crate::cursor::Error),
+
+    #[cfg(feature = "test-db")]
+    #[error("failed to provision a test database")]
+    TestDb(#[from] crate::test_db::Error),
+
+    #[cfg(any(feature = "with-sea-query-0_32", feature = "with-sql_builder-3"))]
+    #[error("failed to convert the output of a query builder")]
+    Interop(#[from] crate::interop::Error),
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -26,4 +82,10 @@ pub enum ParseError {
         next = found.map(|ch| ch.to_string()).unwrap_or_else(|| "EOF".to_owned())
     )]
     EmptyIdentifier { found: Option<char> },
+
+    #[error(
+        "positional placeholders (`${index}`) are not supported, since the bindings are numbered \
+         automatically: bind the value to a name and use `$name` instead"
+    )]
+    PositionalParameter { index: String },
 }
@@ -7,13 +7,26 @@
 use super::{Query, Sql};
 use crate::client::GenericClient;
 use crate::error::Result;
-use crate::extract::{self, FromSqlRow};
+use crate::extract::{self, Columns, FromSqlRow};
+use crate::parse;
+use crate::stream::{Adaptive, StatementRows};
+use bytes::BytesMut;
+use futures::future::{self, Either, Future};
+use futures::io::{AsyncWrite, AsyncWriteExt};
 use futures::{pin_mut, Stream, StreamExt, TryStreamExt};
+use futures_timer::Delay;
+use postgres_types::{FromSql, IsNull, ToSql, Type};
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use tokio_postgres::{error::Error as SqlError, Row, Statement};
+use tokio_postgres::error::{Error as SqlError, SqlState};
+use tokio_postgres::{Row, Statement};
 
 /// An error that may arise when executing a query.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
     #[error("failed to execute query")]
     Sql(#[from] SqlError),
@@ -22,23 +35,726 @@ pub enum Error {
     NoRows,
     #[error("expected 1 row, found more than 1")]
     TooManyRows,
+    #[error("expected the query to affect {expected} rows, but it affected {actual}")]
+    UnexpectedRowCount { expected: u64, actual: u64 },
 
     #[error("failed to extract value from row")]
     Extract(#[from] extract::Error),
+
+    #[error("query timed out after {0:?}")]
+    Timeout(Duration),
+
+    #[error("the deadline of the query passed before it completed")]
+    DeadlineExceeded,
+
+    /// Options which set run-time parameters, such as [`ExecuteOptions::local`], were used outside
+    /// of a transaction, through a client which may be shared (see [`Exclusive`]).
+    ///
+    /// [`ExecuteOptions::local`]: struct.ExecuteOptions.html#method.local
+    /// [`Exclusive`]: ../client/struct.Exclusive.html
+    #[error("run-time parameters may only be set for queries within a transaction")]
+    NoTransaction,
+
+    /// The query consists of more than one statement, where only one is supported, such as in
+    /// [`Query::fetch_json_stream`].
+    ///
+    /// [`Query::fetch_json_stream`]: ../struct.Query.html#method.fetch_json_stream
+    #[error("expected a single statement, found several separated by `;`")]
+    MultipleStatements,
+
+    #[error("failed to write the results of the query")]
+    Write(#[source] std::io::Error),
+
+    /// The database could not infer the type of a parameter, which commonly happens when it is
+    /// bound to `None`, since `NULL` carries no type information.
+    #[error(
+        "could not determine the type of parameter `${index}`{}: add a cast in the query \
+         (for example `${index}::INT`) or prepare the statement with `prepare_typed`",
+        if *.null { ", which is bound to `None`" } else { "" }
+    )]
+    IndeterminateType {
+        /// The position of the parameter, starting at 1 (like `$1`).
+        index: usize,
+        /// `true` if the parameter is bound to `None`.
+        null: bool,
+        #[source]
+        source: SqlError,
+    },
+}
+
+/// Options controlling how queries are executed.
+///
+/// Options may be set on a single query, with [`Query::with_options`], or as the defaults of a
+/// client, with [`Caching::with_options`]. Options which are not set on a query fall back to
+/// those of the client, so policies like "all queries time out after 5 seconds" can be declared
+/// once:
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{client::Caching, execute::ExecuteOptions, query, Result};
+/// # use std::time::Duration;
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// let client = Caching::new(client).with_options(
+///     ExecuteOptions::new()
+///         .timeout(Duration::from_secs(5))
+///         .tag("billing"),
+/// );
+///
+/// // Times out after 5 seconds, and is tagged with `/* billing */`.
+/// let people: Vec<(String,)> = query!("SELECT name FROM people").fetch(&client).await?;
+///
+/// // Times out after 30 seconds, and is still tagged.
+/// let report: Vec<(String, i64)> = query!("SELECT name, COUNT(*) FROM orders GROUP BY name")
+///     .with_options(ExecuteOptions::new().timeout(Duration::from_secs(30)))
+///     .fetch(&client)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Query::with_options`]: ../struct.Query.html#method.with_options
+/// [`Caching::with_options`]: ../client/struct.Caching.html#method.with_options
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecuteOptions {
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    prepare: Option<PrepareMode>,
+    pub(crate) tag: Option<String>,
+    role: Option<String>,
+    locals: Vec<(String, String)>,
+}
+
+/// How queries are prepared before they are executed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PrepareMode {
+    /// Queries with static SQL (ie. those built by `query!`) are prepared through
+    /// `GenericClient::prepare_static`, which clients such as [`Caching`] may cache. This is the
+    /// default.
+    ///
+    /// [`Caching`]: ../client/struct.Caching.html
+    Cached,
+    /// All queries are prepared again, bypassing any caches. This may be useful after the schema
+    /// of the database has changed.
+    Uncached,
+}
+
+// Deriving `Default` for enums requires Rust 1.62.
+#[allow(clippy::derivable_impls)]
+impl Default for PrepareMode {
+    fn default() -> PrepareMode {
+        PrepareMode::Cached
+    }
+}
+
+impl ExecuteOptions {
+    /// Options where nothing is set.
+    pub fn new() -> ExecuteOptions {
+        ExecuteOptions::default()
+    }
+
+    /// Fail with [`Error::Timeout`] if the query has not completed within `timeout`.
+    ///
+    /// The timeout is only enforced by the client: the query is not cancelled on the server, and
+    /// keeps running there after the timeout. For streaming queries it only applies until the
+    /// first row is available.
+    ///
+    /// [`Error::Timeout`]: enum.Error.html#variant.Timeout
+    pub fn timeout(mut self, timeout: Duration) -> ExecuteOptions {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Fail with [`Error::DeadlineExceeded`] if the query has not completed by `deadline`, such as
+    /// the deadline of the request being served.
+    ///
+    /// The time remaining until the deadline is enforced by the client. Within a transaction, it
+    /// is also enforced by the server through the `statement_timeout` parameter (as if set with
+    /// [`local`], so it is restored after the query), which cancels the query once it runs out.
+    /// Outside of a transaction the query keeps running on the server after the deadline has
+    /// passed. A query whose deadline has already passed is not sent at all. If both the query and
+    /// the client have a deadline, the earlier one applies.
+    ///
+    /// ```
+    /// # use tokio_postgres::Client;
+    /// # use postgres_query::{client::Caching, execute::ExecuteOptions, query, Result};
+    /// # use std::time::Instant;
+    /// # async fn foo(deadline: Instant) -> Result<()> {
+    /// # let client: Client = unimplemented!();
+    /// // Every query made while handling the request shares its deadline.
+    /// let client = Caching::new(client).with_options(ExecuteOptions::new().deadline(deadline));
+    ///
+    /// let people: Vec<(String,)> = query!("SELECT name FROM people").fetch(&client).await?;
+    /// let orders: Vec<(i32,)> = query!("SELECT id FROM orders").fetch(&client).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Error::DeadlineExceeded`]: enum.Error.html#variant.DeadlineExceeded
+    /// [`local`]: #method.local
+    pub fn deadline(mut self, deadline: Instant) -> ExecuteOptions {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Decide how queries are prepared. See [`PrepareMode`].
+    ///
+    /// [`PrepareMode`]: enum.PrepareMode.html
+    pub fn prepare(mut self, mode: PrepareMode) -> ExecuteOptions {
+        self.prepare = Some(mode);
+        self
+    }
+
+    /// Prefix the SQL with a comment (`/* tag */`), which makes the query easy to identify in the
+    /// logs and statistics of the server.
+    ///
+    /// Clients such as [`Caching`] cache tagged queries separately for each tag.
+    ///
+    /// [`Caching`]: ../client/struct.Caching.html
+    pub fn tag(mut self, tag: impl Into<String>) -> ExecuteOptions {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    /// Execute the query as the given role, as if by `SET LOCAL ROLE`. This is the same as using
+    /// [`local`] with the `role` parameter: it requires a transaction, and the previous role is
+    /// restored after the query.
+    ///
+    /// [`local`]: #method.local
+    pub fn role(mut self, role: impl Into<String>) -> ExecuteOptions {
+        self.role = Some(role.into());
+        self
+    }
+
+    /// Set the run-time parameter `name` to `value` while executing the query, as if by
+    /// `SET LOCAL`. This is useful for planner hints, such as `enable_seqscan = off`, which should
+    /// only apply to a single query. May be given multiple times.
+    ///
+    /// Since the client may be shared with other tasks, the parameters are only set within a
+    /// transaction, and the query fails with [`Error::NoTransaction`] otherwise. The previous
+    /// values are restored after the query, unless the server reported an error, in which case
+    /// the transaction is aborted and rolling it back restores them. This requires a few
+    /// additional round trips to the server. Through an [`Exclusive`] client, which has the
+    /// connection to itself, queries outside of a transaction run within one of their own instead.
+    ///
+    /// ```
+    /// # use tokio_postgres::Transaction;
+    /// # use postgres_query::{execute::ExecuteOptions, query, Result};
+    /// # async fn foo() -> Result<()> {
+    /// # let tx: Transaction = unimplemented!();
+    /// let people: Vec<(String,)> = query!("SELECT name FROM people WHERE age > 30")
+    ///     .with_options(ExecuteOptions::new().local("enable_seqscan", "off"))
+    ///     .fetch(&tx)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Error::NoTransaction`]: enum.Error.html#variant.NoTransaction
+    /// [`Exclusive`]: ../client/struct.Exclusive.html
+    pub fn local(mut self, name: impl Into<String>, value: impl Into<String>) -> ExecuteOptions {
+        self.locals.push((name.into(), value.into()));
+        self
+    }
+
+    /// Use the options of `self`, and those of `defaults` for the options which are not set.
+    /// Parameters set with `local` are combined, with those of `self` taking precedence.
+    pub(crate) fn or(&self, defaults: ExecuteOptions) -> ExecuteOptions {
+        let mut locals = defaults.locals;
+        locals.extend(self.locals.iter().cloned());
+
+        ExecuteOptions {
+            timeout: self.timeout.or(defaults.timeout),
+            deadline: match (self.deadline, defaults.deadline) {
+                (Some(deadline), Some(default)) => Some(Ord::min(deadline, default)),
+                (deadline, default) => deadline.or(default),
+            },
+            prepare: self.prepare.or(defaults.prepare),
+            tag: self.tag.clone().or(defaults.tag),
+            role: self.role.clone().or(defaults.role),
+            locals,
+        }
+    }
+
+    /// The run-time parameters set while the query executes, including the role.
+    pub(crate) fn settings(&self) -> Vec<(String, String)> {
+        let mut settings = self.locals.clone();
+        if let Some(role) = &self.role {
+            settings.push(("role".to_owned(), role.clone()));
+        }
+        settings
+    }
+
+    /// Run `future` with these options, which performs a query against `client`.
+    pub(crate) async fn run<C, F, T>(&self, client: &C, future: F) -> Result<T>
+    where
+        C: GenericClient + Sync,
+        F: Future<Output = Result<T>>,
+    {
+        let mut locals = self.settings();
+        if let Some(deadline) = self.deadline {
+            let remaining = remaining(deadline)?;
+            if client.in_transaction() {
+                // A `statement_timeout` of zero would disable it instead. `u128::div_ceil`
+                // requires Rust 1.73.
+                #[allow(clippy::manual_div_ceil)]
+                let millis = ((remaining.as_micros() + 999) / 1000).max(1);
+                locals.push(("statement_timeout".to_owned(), format!("{}ms", millis)));
+            }
+        }
+
+        let locals = if locals.is_empty() {
+            None
+        } else {
+            Some(Locals::set(client, &locals).await?)
+        };
+
+        let result = self.limit(future).await;
+
+        match locals {
+            None => result,
+            Some(locals) => {
+                let reset = locals.reset(client, &result).await;
+                let value = result?;
+                reset?;
+                Ok(value)
+            }
+        }
+    }
+
+    /// Wait for `future`, until either the timeout or the deadline runs out.
+    async fn limit<F, T>(&self, future: F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let deadline = self
+            .deadline
+            .map(|deadline| remaining(deadline).map(|remaining| (deadline, remaining)))
+            .transpose()?;
+
+        let (limit, error) = match (self.timeout, deadline) {
+            (None, None) => return future.await,
+            (Some(timeout), Some((_, remaining))) if timeout < remaining => {
+                (timeout, Error::Timeout(timeout))
+            }
+            (_, Some((_, remaining))) => (remaining, Error::DeadlineExceeded),
+            (Some(timeout), None) => (timeout, Error::Timeout(timeout)),
+        };
+
+        pin_mut!(future);
+        match future::select(future, Delay::new(limit)).await {
+            Either::Left((Err(crate::Error::Execute(Error::Sql(error))), _))
+                if error.code() == Some(&SqlState::QUERY_CANCELED)
+                    && matches!(deadline, Some((deadline, _)) if Instant::now() >= deadline) =>
+            {
+                // Cancelled by the `statement_timeout` of the deadline.
+                Err(Error::DeadlineExceeded.into())
+            }
+            Either::Left((result, _)) => result,
+            Either::Right(_) => Err(error.into()),
+        }
+    }
+}
+
+/// The time remaining until `deadline`, which must not have passed.
+fn remaining(deadline: Instant) -> Result<Duration> {
+    match deadline.checked_duration_since(Instant::now()) {
+        Some(remaining) if remaining > Duration::from_secs(0) => Ok(remaining),
+        _ => Err(Error::DeadlineExceeded.into()),
+    }
+}
+
+/// The previous values of the run-time parameters set by `ExecuteOptions::local`, which are
+/// restored after the query.
+struct Locals {
+    previous: Vec<(String, String)>,
+    /// Whether the parameters were set within an implicit transaction, which ends with the query.
+    implicit: bool,
+}
+
+impl Locals {
+    async fn set<C>(client: &C, locals: &[(String, String)]) -> Result<Locals>
+    where
+        C: GenericClient + Sync,
+    {
+        // Outside of a transaction the parameters would only apply to the statement setting them,
+        // and starting a transaction of our own would interfere with other users of the client,
+        // unless it has the connection to itself.
+        if !client.in_transaction() {
+            if !client.exclusive() {
+                return Err(Error::NoTransaction.into());
+            }
+
+            execute_sql(client, "BEGIN").await?;
+            if let Err(error) = set_locals(client, locals).await {
+                // The error which aborted the transaction is the one worth reporting.
+                let _ = execute_sql(client, "ROLLBACK").await;
+                return Err(error);
+            }
+
+            return Ok(Locals {
+                previous: Vec::new(),
+                implicit: true,
+            });
+        }
+
+        let names = locals.iter().map(|(name, _)| name.as_str());
+        let sql = format!(
+            "SELECT {}",
+            names
+                .clone()
+                .map(|name| format!("current_setting({})", quote_literal(name)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let statement = client.prepare(&sql).await.map_err(Error::from)?;
+        let rows = client
+            .query_raw(&statement, &[])
+            .await
+            .map_err(Error::from)?;
+        pin_mut!(rows);
+        let row = rows.try_next().await.map_err(Error::from)?;
+        let row = row.ok_or(Error::NoRows)?;
+
+        let previous = names
+            .enumerate()
+            .map(|(i, name)| {
+                let value = row.try_get::<_, String>(i).map_err(Error::from)?;
+                Ok((name.to_owned(), value))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        set_locals(client, locals).await?;
+        Ok(Locals {
+            previous,
+            implicit: false,
+        })
+    }
+
+    async fn reset<C, T>(self, client: &C, result: &Result<T>) -> Result<()>
+    where
+        C: GenericClient + Sync,
+    {
+        // Ending the implicit transaction resets the parameters either way.
+        if self.implicit {
+            let end = if result.is_ok() { "COMMIT" } else { "ROLLBACK" };
+            return execute_sql(client, end).await;
+        }
+
+        // An error reported by the server aborts the transaction, and rolling it back resets the
+        // parameters.
+        if let Err(crate::Error::Execute(Error::Sql(error))) = result {
+            if error.as_db_error().is_some() {
+                return Ok(());
+            }
+        }
+
+        match set_locals(client, &self.previous).await {
+            // The query may have failed on the server after we stopped waiting for it.
+            Err(crate::Error::Execute(Error::Sql(error)))
+                if error.code() == Some(&SqlState::IN_FAILED_SQL_TRANSACTION) =>
+            {
+                Ok(())
+            }
+            reset => reset,
+        }
+    }
+}
+
+async fn set_locals<C>(client: &C, locals: &[(String, String)]) -> Result<()>
+where
+    C: GenericClient + Sync,
+{
+    let assignments = locals
+        .iter()
+        .map(|(name, value)| {
+            format!(
+                "set_config({}, {}, true)",
+                quote_literal(name),
+                quote_literal(value)
+            )
+        })
+        .collect::<Vec<_>>();
+    execute_sql(client, &format!("SELECT {}", assignments.join(", "))).await
+}
+
+async fn execute_sql<C>(client: &C, sql: &str) -> Result<()>
+where
+    C: GenericClient + Sync,
+{
+    let statement = client.prepare(sql).await.map_err(Error::from)?;
+    client
+        .execute_raw(&statement, &[])
+        .await
+        .map_err(Error::from)?;
+    Ok(())
+}
+
+/// Prefix `sql` with a comment holding `tag`.
+pub(crate) fn tagged(sql: &str, tag: &str) -> String {
+    format!("/* {} */ {}", tag.replace("*/", "* /"), sql)
+}
+
+/// Read the only row of `rows`, failing with `NoRows` or `TooManyRows` if there is not exactly
+/// one.
+pub(crate) async fn single_row(rows: tokio_postgres::RowStream) -> Result<Row> {
+    pin_mut!(rows);
+
+    let row = match rows.try_next().await.map_err(Error::from)? {
+        Some(row) => row,
+        None => return Err(Error::NoRows.into()),
+    };
+
+    if rows.try_next().await.map_err(Error::from)?.is_some() {
+        return Err(Error::TooManyRows.into());
+    }
+
+    Ok(row)
+}
+
+/// Check whether a parameter is bound to `NULL`.
+///
+/// `to_sql` may panic when given a type the value does not accept, and the type of the parameter
+/// is not known, so the value is encoded as the first built-in type which accepts it. Values of
+/// other types are never considered `NULL`.
+fn is_null(parameter: &(dyn ToSql + Sync)) -> bool {
+    // Every built-in type has an OID below `FirstNormalObjectId`.
+    const FIRST_NORMAL_OID: u32 = 16384;
+
+    let mut buffer = BytesMut::new();
+    let encoded = (0..FIRST_NORMAL_OID)
+        .filter_map(Type::from_oid)
+        .find_map(|ty| {
+            buffer.clear();
+            parameter.to_sql_checked(&ty, &mut buffer).ok()
+        });
+    matches!(encoded, Some(IsNull::Yes))
+}
+
+pub(crate) fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+fn quote_literal(literal: &str) -> String {
+    format!("'{}'", literal.replace('\'', "''"))
+}
+
+/// Wrap a conversion given to `try_fetch_with`, so that it fails with `extract::Error::Convert`.
+fn converted<T, U, E, F>(mut map: F) -> impl FnMut(T) -> Result<U>
+where
+    E: Into<Box<dyn StdError + Send + Sync>>,
+    F: FnMut(T) -> std::result::Result<U, E>,
+{
+    move |value| {
+        map(value).map_err(|error| Error::Extract(extract::Error::Convert(error.into())).into())
+    }
+}
+
+/// The number of rows affected by a statement, along with the kind of statement. Returned by
+/// [`Query::execute_verbose`].
+///
+/// [`Query::execute_verbose`]: ../struct.Query.html#method.execute_verbose
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecStatus {
+    pub rows_affected: u64,
+    pub command: CommandTag,
+}
+
+/// The kind of a statement, derived from its text. Named after the command tags of the server
+/// (`INSERT 0 1`, `UPDATE 3`, etc.), though the two need not agree (see [`CommandTag::of`]).
+///
+/// [`CommandTag::of`]: #method.of
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CommandTag {
+    /// A `SELECT`, `VALUES` or `TABLE` statement.
+    Select,
+    Insert,
+    Update,
+    Delete,
+    Merge,
+    /// Any other statement, by its first keyword (such as `CREATE` or `LOCK`).
+    Other(String),
+}
+
+impl CommandTag {
+    /// The command of a statement, which is the first keyword of the statement, or of the main
+    /// statement following a `WITH` clause.
+    ///
+    /// This only looks at the text of the statement, so statements which the server reports under
+    /// the tag of another command are not recognized as such: `CREATE TABLE ... AS SELECT` is
+    /// `Other("CREATE")`, even though the server reports it as a `SELECT`.
+    pub fn of(sql: &str) -> CommandTag {
+        const COMMANDS: &[&str] = &[
+            "SELECT", "VALUES", "TABLE", "INSERT", "UPDATE", "DELETE", "MERGE",
+        ];
+
+        let keywords = parse::top_level_keywords(sql);
+        let mut words = keywords.iter().map(|(_, word)| word.as_str());
+
+        let command = match words.next() {
+            // The expressions of the `WITH` clause are parenthesized, so the next command at the
+            // top level is the main statement.
+            Some("WITH") => words.find(|word| COMMANDS.contains(word)).unwrap_or("WITH"),
+            Some(word) => word,
+            None => "",
+        };
+
+        match command {
+            "SELECT" | "VALUES" | "TABLE" => CommandTag::Select,
+            "INSERT" => CommandTag::Insert,
+            "UPDATE" => CommandTag::Update,
+            "DELETE" => CommandTag::Delete,
+            "MERGE" => CommandTag::Merge,
+            other => CommandTag::Other(other.to_owned()),
+        }
+    }
 }
 
 impl<'a> Query<'a> {
+    /// Execute this query with the given options, which take precedence over the defaults of the
+    /// client. See [`ExecuteOptions`].
+    ///
+    /// [`ExecuteOptions`]: execute/struct.ExecuteOptions.html
+    pub fn with_options(mut self, options: ExecuteOptions) -> Query<'a> {
+        self.options = Some(Box::new(options));
+        self
+    }
+
+    /// Resolve unqualified names in this query within `schema`, by setting the `search_path` to it
+    /// while the query executes. This is the same as using [`ExecuteOptions::local`], which
+    /// describes how the setting is scoped to the query. Outside of a transaction, the query has
+    /// to be executed through an [`Exclusive`] client, which runs it within an implicit
+    /// transaction.
+    ///
+    /// The name of the schema is quoted as an identifier, so it cannot inject SQL, but it is case
+    /// sensitive. Only `schema` is searched (along with the system catalogs), and objects in other
+    /// schemas must be qualified.
+    ///
+    /// ```
+    /// # use tokio_postgres::Client;
+    /// # use postgres_query::{client::Exclusive, query, Result};
+    /// # async fn foo() -> Result<()> {
+    /// # let mut client: Client = unimplemented!();
+    /// let tenant = 42;
+    /// let orders: Vec<(i32, String)> = query!("SELECT id, item FROM orders")
+    ///     .in_schema(&format!("tenant_{}", tenant))
+    ///     .fetch(&Exclusive::new(&mut client))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Statements cached by [`Caching`] are shared between schemas, and Postgres plans them again
+    /// for each `search_path`. The schemas should therefore have the same structure: if a table has
+    /// different columns in different schemas, the statement fails when its result changes.
+    ///
+    /// [`ExecuteOptions::local`]: execute/struct.ExecuteOptions.html#method.local
+    /// [`Exclusive`]: client/struct.Exclusive.html
+    /// [`Caching`]: client/struct.Caching.html
+    pub fn in_schema(mut self, schema: &str) -> Query<'a> {
+        let options = self.options.take().map(|options| *options);
+        let options = options
+            .unwrap_or_default()
+            .local("search_path", quote_identifier(schema));
+        self.with_options(options)
+    }
+
+    /// Prepare this query every time it is executed, bypassing the statement cache of clients such
+    /// as [`Caching`]. This is the same as [`PrepareMode::Uncached`], and is useful for statements
+    /// which should not outlive the schema they were prepared for, such as those of a migration.
+    ///
+    /// [`Caching`]: client/struct.Caching.html
+    /// [`PrepareMode::Uncached`]: execute/enum.PrepareMode.html#variant.Uncached
+    pub fn no_cache(mut self) -> Query<'a> {
+        let options = self.options.take().map(|options| *options);
+        let options = options.unwrap_or_default().prepare(PrepareMode::Uncached);
+        self.with_options(options)
+    }
+
     /// Execute this query and return the number of affected rows.
     pub async fn execute<C>(&self, client: &C) -> Result<u64>
     where
         C: GenericClient + Sync,
     {
-        let statement = self.prepare(&client).await?;
-        let rows = client
-            .execute_raw(&statement, &self.parameters)
+        let options = self.options_for(client);
+        options
+            .run(client, async {
+                let statement = self.prepare(client, &options).await?;
+                let rows = client
+                    .execute_raw(&statement, &self.parameters)
+                    .await
+                    .map_err(Error::from)?;
+                client.__executed(&self.sql, &self.parameters, options.tag.as_deref(), rows);
+                Ok(rows)
+            })
             .await
-            .map_err(Error::from)?;
-        Ok(rows)
+    }
+
+    /// Execute this query, failing with [`Error::UnexpectedRowCount`] unless it affected exactly
+    /// `expected` rows.
+    ///
+    /// ```
+    /// # use tokio_postgres::Client;
+    /// # use postgres_query::{query, Result};
+    /// # async fn foo() -> Result<()> {
+    /// # let client: Client = unimplemented!();
+    /// query!("UPDATE accounts SET balance = balance - 10 WHERE id = $id", id = 7)
+    ///     .execute_expecting(&client, 1)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Note that the query has been executed by the time the error is returned: run it within a
+    /// transaction for the changes to be rolled back.
+    ///
+    /// [`Error::UnexpectedRowCount`]: execute/enum.Error.html#variant.UnexpectedRowCount
+    pub async fn execute_expecting<C>(&self, client: &C, expected: u64) -> Result<()>
+    where
+        C: GenericClient + Sync,
+    {
+        let actual = self.execute(client).await?;
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(Error::UnexpectedRowCount { expected, actual }.into())
+        }
+    }
+
+    /// Execute this query and return the number of affected rows, along with the kind of
+    /// statement that was executed.
+    ///
+    /// Useful for generic endpoints, which may be handed any kind of statement:
+    ///
+    /// ```
+    /// # use tokio_postgres::Client;
+    /// # use postgres_query::{execute::CommandTag, query, Result};
+    /// # async fn foo() -> Result<()> {
+    /// # let client: Client = unimplemented!();
+    /// let status = query!("UPDATE people SET age = age + 1")
+    ///     .execute_verbose(&client)
+    ///     .await?;
+    ///
+    /// assert_eq!(status.command, CommandTag::Update);
+    /// println!("{} people got older", status.rows_affected);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// The driver does not report the command tag itself, so the command is derived from the text
+    /// of the query with [`CommandTag::of`]. This may differ from the tag sent by the server: for
+    /// example, the server reports `CREATE TABLE ... AS SELECT` as a `SELECT`.
+    ///
+    /// [`CommandTag::of`]: execute/enum.CommandTag.html#method.of
+    pub async fn execute_verbose<C>(&self, client: &C) -> Result<ExecStatus>
+    where
+        C: GenericClient + Sync,
+    {
+        let rows_affected = self.execute(client).await?;
+        Ok(ExecStatus {
+            rows_affected,
+            command: CommandTag::of(self.sql()),
+        })
     }
 
     /// Execute this query and return the resulting values.
@@ -64,6 +780,150 @@ impl<'a> Query<'a> {
         Ok(value)
     }
 
+    /// Execute this query, which returns a single array (such as `SELECT array_agg(x) FROM ...`),
+    /// and return its elements. A `NULL` array, which `array_agg` returns when there is nothing to
+    /// aggregate, is returned as an empty `Vec`.
+    ///
+    /// ```
+    /// # use tokio_postgres::Client;
+    /// # use postgres_query::{query, Result};
+    /// # async fn foo() -> Result<()> {
+    /// # let client: Client = unimplemented!();
+    /// let names: Vec<String> = query!("SELECT array_agg(name ORDER BY name) FROM people")
+    ///     .fetch_array(&client)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn fetch_array<T, C>(&self, client: &C) -> Result<Vec<T>>
+    where
+        T: for<'r> FromSql<'r>,
+        C: GenericClient + Sync,
+    {
+        let array = self.fetch_one::<Option<Vec<T>>, C>(client).await?;
+        Ok(array.unwrap_or_default())
+    }
+
+    /// Execute this query and return the resulting values, indexed by the key `key` computes for
+    /// each of them. If several values have the same key, the last of them is kept.
+    ///
+    /// ```
+    /// # use tokio_postgres::Client;
+    /// # use postgres_query::{query, FromSqlRow, Result};
+    /// # use std::collections::HashMap;
+    /// # async fn foo() -> Result<()> {
+    /// # let client: Client = unimplemented!();
+    /// #[derive(FromSqlRow)]
+    /// struct Person {
+    ///     id: i32,
+    ///     name: String,
+    /// }
+    ///
+    /// let people: HashMap<i32, Person> = query!("SELECT id, name FROM people")
+    ///     .fetch_map_by_key(&client, |person: &Person| person.id)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn fetch_map_by_key<K, T, C, F>(
+        &self,
+        client: &C,
+        mut key: F,
+    ) -> Result<HashMap<K, T>>
+    where
+        K: Eq + Hash,
+        T: FromSqlRow,
+        C: GenericClient + Sync,
+        F: FnMut(&T) -> K,
+    {
+        let values = self.fetch::<T, C>(client).await?;
+        let map = values
+            .into_iter()
+            .map(|value| (key(&value), value))
+            .collect();
+        Ok(map)
+    }
+
+    /// Execute this query and return the resulting values, grouped by the key `key` computes for
+    /// each of them. The values within each group are in the order they were returned.
+    ///
+    /// Unlike `#[row(group)]`, which merges consecutive rows into a single value, this keeps every
+    /// value, and rows with the same key need not be next to each other.
+    pub async fn fetch_grouped_by_key<K, T, C, F>(
+        &self,
+        client: &C,
+        mut key: F,
+    ) -> Result<HashMap<K, Vec<T>>>
+    where
+        K: Eq + Hash,
+        T: FromSqlRow,
+        C: GenericClient + Sync,
+        F: FnMut(&T) -> K,
+    {
+        let values = self.fetch::<T, C>(client).await?;
+        let mut groups = HashMap::<K, Vec<T>>::new();
+        for value in values {
+            groups.entry(key(&value)).or_default().push(value);
+        }
+        Ok(groups)
+    }
+
+    /// Execute this query and return the resulting values, each converted with `map`.
+    ///
+    /// This keeps the conversion from the rows to the types of the application next to the query,
+    /// instead of following every call:
+    ///
+    /// ```
+    /// # use tokio_postgres::Client;
+    /// # use postgres_query::{query, FromSqlRow, Result};
+    /// # async fn foo() -> Result<()> {
+    /// # let client: Client = unimplemented!();
+    /// #[derive(FromSqlRow)]
+    /// struct PersonRow {
+    ///     name: String,
+    ///     birth_year: i32,
+    /// }
+    ///
+    /// struct Person {
+    ///     name: String,
+    ///     age: i32,
+    /// }
+    ///
+    /// let people: Vec<Person> = query!("SELECT name, birth_year FROM people")
+    ///     .fetch_with(&client, |row: PersonRow| Person {
+    ///         name: row.name,
+    ///         age: 2020 - row.birth_year,
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn fetch_with<T, U, C, F>(&self, client: &C, map: F) -> Result<Vec<U>>
+    where
+        T: FromSqlRow,
+        C: GenericClient + Sync,
+        F: FnMut(T) -> U,
+    {
+        let values = self.fetch::<T, C>(client).await?;
+        Ok(values.into_iter().map(map).collect())
+    }
+
+    /// Execute this query and return the resulting values, each converted with `map`, which may
+    /// reject them. The first value which is rejected fails the whole query, with
+    /// [`extract::Error::Convert`].
+    ///
+    /// [`extract::Error::Convert`]: ../extract/enum.Error.html#variant.Convert
+    pub async fn try_fetch_with<T, U, E, C, F>(&self, client: &C, map: F) -> Result<Vec<U>>
+    where
+        T: FromSqlRow,
+        E: Into<Box<dyn StdError + Send + Sync>>,
+        C: GenericClient + Sync,
+        F: FnMut(T) -> std::result::Result<U, E>,
+    {
+        let values = self.fetch::<T, C>(client).await?;
+        values.into_iter().map(converted(map)).collect()
+    }
+
     /// Execute this query and return the resulting values as an asynchronous stream of values.
     pub async fn fetch_streaming<T, C>(&self, client: &C) -> Result<impl Stream<Item = Result<T>>>
     where
@@ -81,20 +941,119 @@ impl<'a> Query<'a> {
         Ok(values)
     }
 
+    /// Execute this query and return the resulting values as an asynchronous stream, each value
+    /// converted with `map`. See [`fetch_with`].
+    ///
+    /// [`fetch_with`]: #method.fetch_with
+    pub async fn fetch_streaming_with<T, U, C, F>(
+        &self,
+        client: &C,
+        mut map: F,
+    ) -> Result<impl Stream<Item = Result<U>>>
+    where
+        T: FromSqlRow,
+        C: GenericClient + Sync,
+        F: FnMut(T) -> U,
+    {
+        let values = self.fetch_streaming::<T, C>(client).await?;
+        Ok(values.map(move |value| value.map(&mut map)))
+    }
+
+    /// Execute this query and return the resulting values as an asynchronous stream, each value
+    /// converted with `map`, which may reject them. See [`try_fetch_with`].
+    ///
+    /// [`try_fetch_with`]: #method.try_fetch_with
+    pub async fn try_fetch_streaming_with<T, U, E, C, F>(
+        &self,
+        client: &C,
+        map: F,
+    ) -> Result<impl Stream<Item = Result<U>>>
+    where
+        T: FromSqlRow,
+        E: Into<Box<dyn StdError + Send + Sync>>,
+        C: GenericClient + Sync,
+        F: FnMut(T) -> std::result::Result<U, E>,
+    {
+        let values = self.fetch_streaming::<T, C>(client).await?;
+        let mut convert = converted(map);
+        Ok(values.map(move |value| value.and_then(&mut convert)))
+    }
+
+    /// Execute this query and return the resulting values, buffered if there are at most
+    /// `threshold` of them, and as a stream otherwise.
+    ///
+    /// This lets small results be handled as a whole while large ones are not held in memory at
+    /// once. Both may also be handled the same way, with [`Adaptive::into_stream`]:
+    ///
+    /// ```
+    /// # use tokio_postgres::Client;
+    /// # use postgres_query::{query, stream::Adaptive, Result};
+    /// # async fn foo() -> Result<()> {
+    /// # let client: Client = unimplemented!();
+    /// let names = query!("SELECT name FROM people")
+    ///     .fetch_adaptive::<(String,), _>(&client, 1000)
+    ///     .await?;
+    ///
+    /// match names {
+    ///     Adaptive::Buffered(names) => println!("{} people", names.len()),
+    ///     Adaptive::Streaming(_) => println!("more than 1000 people"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// As with [`fetch_streaming`], each row is extracted on its own, so rows are not merged by
+    /// `#[row(group)]` or `#[row(hash)]`.
+    ///
+    /// [`Adaptive::into_stream`]: stream/enum.Adaptive.html#method.into_stream
+    /// [`fetch_streaming`]: #method.fetch_streaming
+    pub async fn fetch_adaptive<T, C>(
+        &self,
+        client: &C,
+        threshold: usize,
+    ) -> Result<Adaptive<T, impl Stream<Item = Result<T>>>>
+    where
+        T: FromSqlRow,
+        C: GenericClient + Sync,
+    {
+        let extract = |row: Result<Row>| {
+            row.and_then(|row| T::from_row(&row).map_err(|error| Error::Extract(error).into()))
+        };
+
+        let mut rows = self.query_streaming(client).await?;
+        let mut head = Vec::new();
+
+        // Read one more row than the threshold, to know if it is exceeded.
+        while head.len() <= threshold {
+            match rows.next().await {
+                Some(row) => head.push(extract(row)?),
+                None => return Ok(Adaptive::Buffered(head)),
+            }
+        }
+
+        let values = futures::stream::iter(head.into_iter().map(Ok)).chain(rows.map(extract));
+        Ok(Adaptive::Streaming(values))
+    }
+
     /// Execute this query and return the resulting rows.
     pub async fn query<C>(&self, client: &C) -> Result<Vec<Row>>
     where
         C: GenericClient + Sync,
     {
-        let statement = self.prepare(&client).await?;
-        let rows = client
-            .query_raw(&statement, &self.parameters)
-            .await
-            .map_err(Error::from)?
-            .try_collect::<Vec<_>>()
+        let options = self.options_for(client);
+        options
+            .run(client, async {
+                let statement = self.prepare(client, &options).await?;
+                let rows = client
+                    .query_raw(&statement, &self.parameters)
+                    .await
+                    .map_err(Error::from)?
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .map_err(Error::from)?;
+                Ok(rows)
+            })
             .await
-            .map_err(Error::from)?;
-        Ok(rows)
     }
 
     /// Execute this query and return the resulting row. This method will return an error if, not
@@ -103,50 +1062,189 @@ impl<'a> Query<'a> {
     where
         C: GenericClient + Sync,
     {
-        let statement = self.prepare(&client).await?;
-        let rows = client
-            .query_raw(&statement, &self.parameters)
+        let options = self.options_for(client);
+        options
+            .run(client, async {
+                let statement = self.prepare(client, &options).await?;
+                let rows = client
+                    .query_raw(&statement, &self.parameters)
+                    .await
+                    .map_err(Error::from)?;
+
+                single_row(rows).await
+            })
             .await
-            .map_err(Error::from)?;
+    }
 
-        pin_mut!(rows);
+    /// Execute this query and return the resulting rows as an asynchronous stream of rows, which
+    /// also exposes the columns of the rows. See [`StatementRows`].
+    ///
+    /// [`StatementRows`]: stream/struct.StatementRows.html
+    pub async fn query_streaming<C>(&self, client: &C) -> Result<StatementRows>
+    where
+        C: GenericClient + Sync,
+    {
+        let options = self.options_for(client);
+        options
+            .run(client, async {
+                let statement = self.prepare(client, &options).await?;
+                let rows = client
+                    .query_raw(&statement, &self.parameters)
+                    .await
+                    .map_err(Error::from)?;
+                Ok(StatementRows::new(statement, rows))
+            })
+            .await
+    }
+}
 
-        let row = match rows.try_next().await.map_err(Error::from)? {
-            Some(row) => row,
-            None => return Err(Error::NoRows.into()),
+impl<'a> Query<'a> {
+    /// Prepare this query and return the name and type of each column in its result, without
+    /// executing it.
+    pub async fn columns<C>(&self, client: &C) -> Result<Columns>
+    where
+        C: GenericClient + Sync,
+    {
+        let options = self.options_for(client);
+        let statement = options.run(client, self.prepare(client, &options)).await?;
+        Ok(Columns::new(statement.columns()))
+    }
+
+    /// Execute this query and write the resulting rows to `writer` as a JSON array of objects, with
+    /// one key per column. Returns the number of rows written.
+    ///
+    /// The rows are encoded by the database (with `row_to_json`) and written as they arrive, in the
+    /// order they are returned by the query, so even large results are exported without holding
+    /// them in memory. Each row is written separately, so `writer` should usually be buffered.
+    ///
+    /// The query is wrapped in a `SELECT` of its own, so it has to be a single statement. A
+    /// terminating semicolon is allowed, but fails with [`Error::MultipleStatements`] if it is
+    /// followed by another statement.
+    ///
+    /// ```
+    /// # use tokio_postgres::Client;
+    /// # use postgres_query::{query, Result};
+    /// # async fn foo() -> Result<()> {
+    /// # let client: Client = unimplemented!();
+    /// let mut json = Vec::new();
+    /// query!("SELECT 1 AS id, 'John Wick' AS name")
+    ///     .fetch_json_stream(&client, &mut json)
+    ///     .await?;
+    ///
+    /// assert_eq!(json, br#"[{"id":1,"name":"John Wick"}]"#);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Error::MultipleStatements`]: execute/enum.Error.html#variant.MultipleStatements
+    pub async fn fetch_json_stream<C, W>(&self, client: &C, writer: W) -> Result<u64>
+    where
+        C: GenericClient + Sync,
+        W: AsyncWrite + Unpin,
+    {
+        let mut writer = writer;
+
+        let statement = parse::single_statement(&self.sql).ok_or(Error::MultipleStatements)?;
+        // Selecting from a subquery, without any joins or grouping, keeps the order of its rows.
+        // The newline ends any trailing `--` comment.
+        let sql = format!(
+            "SELECT row_to_json(__rows)::text FROM ({}\n) AS __rows",
+            statement
+        );
+        let json = Query {
+            sql: Sql::Dynamic(sql),
+            parameters: self.parameters.clone(),
+            bindings: self.bindings.clone(),
+            options: self.options.clone(),
         };
 
-        if rows.try_next().await.map_err(Error::from)?.is_some() {
-            return Err(Error::TooManyRows.into());
+        let rows = json.query_streaming(client).await?;
+        pin_mut!(rows);
+
+        writer.write_all(b"[").await.map_err(Error::Write)?;
+
+        let mut count = 0;
+        while let Some(row) = rows.try_next().await? {
+            let object: &str = row.try_get(0).map_err(Error::Sql)?;
+            if count != 0 {
+                writer.write_all(b",").await.map_err(Error::Write)?;
+            }
+            writer
+                .write_all(object.as_bytes())
+                .await
+                .map_err(Error::Write)?;
+            count += 1;
         }
 
-        Ok(row)
+        writer.write_all(b"]").await.map_err(Error::Write)?;
+        writer.flush().await.map_err(Error::Write)?;
+
+        Ok(count)
     }
 
-    /// Execute this query and return the resulting values as an asynchronous stream of values.
-    pub async fn query_streaming<C>(&self, client: &C) -> Result<impl Stream<Item = Result<Row>>>
+    /// The tag set by the options of this query, if any.
+    pub(crate) fn tag(&self) -> Option<&str> {
+        self.options.as_ref()?.tag.as_deref()
+    }
+
+    /// The options of this query, with the defaults of the client for those which are not set.
+    pub(crate) fn options_for<C>(&self, client: &C) -> ExecuteOptions
     where
-        C: GenericClient + Sync,
+        C: GenericClient,
     {
-        let statement = self.prepare(&client).await?;
-        let rows = client
-            .query_raw(&statement, &self.parameters)
-            .await
-            .map_err(Error::from)?;
-        Ok(rows.map_err(Error::from).map_err(Into::into))
+        match &self.options {
+            Some(options) => options.or(client.execute_options()),
+            None => client.execute_options(),
+        }
     }
-}
 
-impl<'a> Query<'a> {
-    async fn prepare<C>(&self, client: &C) -> Result<Statement>
+    pub(crate) async fn prepare<C>(&self, client: &C, options: &ExecuteOptions) -> Result<Statement>
     where
         C: GenericClient + Sync,
     {
-        let result = match &self.sql {
-            Sql::Static(text) => client.prepare_static(text).await,
-            Sql::Dynamic(text) => client.prepare(&text).await,
+        #[cfg(feature = "lint")]
+        crate::lint::check(&self.sql)?;
+
+        client.__check_query(self)?;
+
+        let mode = options.prepare.unwrap_or_default();
+
+        let result = match (&options.tag, &self.sql) {
+            (Some(tag), Sql::Static(text, _)) if mode == PrepareMode::Cached => {
+                client.prepare_tagged_static(text, tag).await
+            }
+            (Some(tag), sql) => client.prepare(&tagged(sql, tag)).await,
+            (None, Sql::Static(text, _)) if mode == PrepareMode::Cached => {
+                client.prepare_static(text).await
+            }
+            (None, sql) => client.prepare(sql).await,
         };
 
-        result.map_err(Error::Sql).map_err(Into::into)
+        result
+            .map_err(|error| self.indeterminate_type(error))
+            .map_err(Into::into)
+    }
+
+    /// Explain errors caused by parameters whose type could not be inferred.
+    fn indeterminate_type(&self, error: SqlError) -> Error {
+        if error.code() != Some(&SqlState::INDETERMINATE_DATATYPE) {
+            return Error::Sql(error);
+        }
+
+        // The message is on the form "could not determine data type of parameter $1".
+        let index = error.as_db_error().and_then(|db| {
+            let message = db.message();
+            let digits = &message[message.rfind('$')? + 1..];
+            digits.parse::<usize>().ok()
+        });
+
+        match index.filter(|&index| index >= 1 && index <= self.parameters.len()) {
+            Some(index) => Error::IndeterminateType {
+                index,
+                null: is_null(self.parameters[index - 1]),
+                source: error,
+            },
+            None => Error::Sql(error),
+        }
     }
 }
@@ -0,0 +1,214 @@
+//! Inspect the plans of queries, to guard against regressions in tests.
+//!
+//! Requires the `explain` feature.
+//!
+//! A change to a query (or to the schema) may silently make the database scan a whole table
+//! instead of using an index. The [`assert_plan!`] macro runs `EXPLAIN` on a query and checks the
+//! resulting plan, so that such changes are caught in CI:
+//!
+//! ```no_run
+//! # use postgres_query::{assert_plan, query};
+//! # async fn foo(client: tokio_postgres::Client) {
+//! let query = query!("SELECT name FROM people WHERE id = $id", id = 7);
+//!
+//! assert_plan!(client, query, contains: "Index Scan", excludes: "Seq Scan");
+//! assert_plan!(client, query, max_cost: 100.0);
+//! # }
+//! ```
+//!
+//! The plan may also be inspected directly with [`explain`].
+//!
+//! [`assert_plan!`]: ../macro.assert_plan.html
+//! [`explain`]: fn.explain.html
+
+use crate::client::GenericClient;
+use crate::error::Result;
+use crate::Query;
+use postgres_types::{FromSql, Type};
+use serde_json::Value;
+use std::error::Error as StdError;
+use std::fmt::{self, Display};
+
+/// The plan the database chose for a query, as reported by `EXPLAIN (FORMAT JSON)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+    /// The node producing the result of the query.
+    pub root: Node,
+}
+
+/// A step of a [`Plan`].
+///
+/// [`Plan`]: struct.Plan.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    /// The kind of node, such as `Seq Scan`, `Index Scan` or `Hash Join`.
+    pub node_type: String,
+    /// The table scanned by the node, if any.
+    pub relation: Option<String>,
+    /// The index used by the node, if any.
+    pub index: Option<String>,
+    /// The estimated cost before the first row is returned.
+    pub startup_cost: f64,
+    /// The estimated cost of returning all rows.
+    pub total_cost: f64,
+    /// The estimated number of rows returned.
+    pub rows: f64,
+    /// The nodes whose output this node consumes.
+    pub children: Vec<Node>,
+}
+
+/// Explain the plan of a query, without executing it.
+pub async fn explain<C>(client: &C, query: &Query<'_>) -> Result<Plan>
+where
+    C: GenericClient + Sync,
+{
+    let explain = Query::new(
+        format!("EXPLAIN (FORMAT JSON) {}", query.sql()),
+        query.parameters().to_vec(),
+    );
+    let (plan,) = explain.fetch_one::<(Plan,), _>(client).await?;
+    Ok(plan)
+}
+
+impl Plan {
+    /// Every node in the plan, parents before their children.
+    pub fn nodes(&self) -> Vec<&Node> {
+        let mut nodes = Vec::new();
+        let mut stack = vec![&self.root];
+        while let Some(node) = stack.pop() {
+            nodes.push(node);
+            stack.extend(node.children.iter().rev());
+        }
+        nodes
+    }
+
+    /// `true` if any node in the plan is of the given type (such as `Index Scan`).
+    pub fn contains(&self, node_type: &str) -> bool {
+        self.nodes().iter().any(|node| node.node_type == node_type)
+    }
+
+    /// The estimated cost of the whole query.
+    pub fn total_cost(&self) -> f64 {
+        self.root.total_cost
+    }
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write_node(f: &mut fmt::Formatter<'_>, node: &Node, depth: usize) -> fmt::Result {
+            write!(f, "{:indent$}{}", "", node.node_type, indent = 2 * depth)?;
+            if let Some(index) = &node.index {
+                write!(f, " using {}", index)?;
+            }
+            if let Some(relation) = &node.relation {
+                write!(f, " on {}", relation)?;
+            }
+            writeln!(
+                f,
+                " (cost={:.2}..{:.2} rows={})",
+                node.startup_cost, node.total_cost, node.rows
+            )?;
+
+            node.children
+                .iter()
+                .try_for_each(|child| write_node(f, child, depth + 1))
+        }
+
+        write_node(f, &self.root, 0)
+    }
+}
+
+impl<'a> FromSql<'a> for Plan {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn StdError + Sync + Send>> {
+        let json: Value = serde_json::from_slice(raw)?;
+        let root = json
+            .get(0)
+            .and_then(|explained| explained.get("Plan"))
+            .ok_or("expected the output of `EXPLAIN (FORMAT JSON)`")?;
+        Ok(Plan {
+            root: parse_node(root)?,
+        })
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::JSON
+    }
+}
+
+fn parse_node(json: &Value) -> Result<Node, Box<dyn StdError + Sync + Send>> {
+    let text = |key: &str| json.get(key).and_then(Value::as_str).map(String::from);
+    let number = |key: &str| json.get(key).and_then(Value::as_f64).unwrap_or_default();
+
+    let children = match json.get("Plans").and_then(Value::as_array) {
+        Some(plans) => plans.iter().map(parse_node).collect::<Result<_, _>>()?,
+        None => Vec::new(),
+    };
+
+    Ok(Node {
+        node_type: text("Node Type").ok_or("plan node without a type")?,
+        relation: text("Relation Name"),
+        index: text("Index Name"),
+        startup_cost: number("Startup Cost"),
+        total_cost: number("Total Cost"),
+        rows: number("Plan Rows"),
+        children,
+    })
+}
+
+/// The checks available in `assert_plan!`.
+#[doc(hidden)]
+pub mod __checks {
+    use super::Plan;
+
+    pub fn contains(plan: &Plan, node_type: &str) {
+        assert!(
+            plan.contains(node_type),
+            "expected the plan to contain `{}`:\n{}",
+            node_type,
+            plan
+        );
+    }
+
+    pub fn excludes(plan: &Plan, node_type: &str) {
+        assert!(
+            !plan.contains(node_type),
+            "expected the plan not to contain `{}`:\n{}",
+            node_type,
+            plan
+        );
+    }
+
+    pub fn max_cost(plan: &Plan, cost: f64) {
+        assert!(
+            plan.total_cost() <= cost,
+            "expected the plan to cost at most {}, but it costs {}:\n{}",
+            cost,
+            plan.total_cost(),
+            plan
+        );
+    }
+}
+
+/// Asserts that the plan of a query satisfies a number of checks. See the [`explain`] module.
+///
+/// Expands to an expression which awaits the plan, so it may only be used in asynchronous
+/// functions. The client and query are taken by reference. The available checks are:
+///
+/// - `contains: "..."`: some node of the plan has the given type.
+/// - `excludes: "..."`: no node of the plan has the given type.
+/// - `max_cost: N`: the estimated total cost of the plan is at most `N`.
+///
+/// # Panics
+///
+/// If the query could not be explained, or if any of the checks fail, printing the plan.
+///
+/// [`explain`]: explain/index.html
+#[macro_export]
+macro_rules! assert_plan {
+    ($client:expr, $query:expr, $($check:ident: $value:expr),+ $(,)?) => {{
+        let plan = $crate::explain::explain(&$client, &$query)
+            .await
+            .unwrap_or_else(|error| panic!("failed to explain the query: {:?}", error));
+        $( $crate::explain::__checks::$check(&plan, $value); )+
+    }};
+}
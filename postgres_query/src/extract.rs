@@ -1,30 +1,48 @@
 //! Extract typed values from rows.
 
 use postgres_types::FromSql;
-use postgres_types::WasNull;
-use std::collections::{BTreeSet, HashSet};
+use postgres_types::{Type, WasNull};
+use std::any::Any;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::error::Error as StdError;
-use std::fmt::{Display, Write};
+use std::fmt::{self, Display};
 use std::hash::Hash;
 use std::iter;
-use std::ops::Range;
+use std::mem;
+use std::ops::{Deref, DerefMut, Range};
+use std::str::FromStr;
 use thiserror::Error;
 use tokio_postgres::{error::Error as SqlError, row::RowIndex, Column};
 
 /// An error that can occur while extracting values from a row.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
     #[error("{msg}")]
     Custom { msg: String },
 
-    #[error("invalid number of columns, found {found} but expected {expected}")]
-    ColumnCount { found: usize, expected: usize },
+    #[error(
+        "invalid number of columns, found {found} but expected {expected} (columns were: {columns})"
+    )]
+    ColumnCount {
+        found: usize,
+        expected: usize,
+        columns: Columns,
+    },
 
     #[error("failed to get column: `{index}` (columns were: {columns})")]
-    SliceLookup { index: String, columns: String },
+    SliceLookup { index: String, columns: Columns },
 
     #[error("failed to split on: `{split}` (columns were: {columns})")]
-    InvalidSplit { split: String, columns: String },
+    InvalidSplit { split: String, columns: Columns },
+
+    #[error("failed to parse column `{index}` from `{text}`: {msg}")]
+    Parse {
+        index: String,
+        text: String,
+        msg: String,
+    },
 
     #[error(
         "failed to slice row on: `{start}..{end}` (len was: {len})", 
@@ -33,6 +51,13 @@ pub enum Error {
     )]
     SliceIndex { range: Range<usize>, len: usize },
 
+    #[error("mismatched column types: {}", display_mismatches(.mismatches))]
+    TypeMismatch { mismatches: Vec<TypeMismatch> },
+
+    /// A value was extracted, but rejected by the conversion given to `try_fetch_with`.
+    #[error("failed to convert the extracted value")]
+    Convert(#[source] Box<dyn StdError + Send + Sync>),
+
     /// An error occured within postgres itself.
     #[error("internal postgres error")]
     Sql(#[from] SqlError),
@@ -116,8 +141,17 @@ pub trait Row: private::row::Sealed {
         }
     }
 
+    /// The type of the column with the given name or index, if there is such a column.
+    fn column_type<I>(&self, index: I) -> Option<&Type>
+    where
+        I: RowIndex,
+    {
+        let columns = self.columns();
+        index.__idx(columns).map(|index| columns[index].type_())
+    }
+
     /// Return a subslice of this row's columns.
-    fn slice(&self, range: Range<usize>) -> Result<RowSlice<Self>, Error>
+    fn slice(&self, range: Range<usize>) -> Result<RowSlice<'_, Self>, Error>
     where
         Self: Sized,
     {
@@ -131,6 +165,100 @@ pub trait Row: private::row::Sealed {
             Ok(slice)
         }
     }
+
+    /// The name of the column that a value named `name` is looked up by, which differs from `name`
+    /// within a [`Prefixed`] row.
+    ///
+    /// Used by `#[derive(FromSqlRow)]` for every column it looks up by name.
+    ///
+    /// [`Prefixed`]: struct.Prefixed.html
+    #[doc(hidden)]
+    fn __column_name<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        Cow::Borrowed(name)
+    }
+}
+
+/// The name and type of each column in a row.
+///
+/// The list is only formatted when displayed, so it is cheap to carry around in errors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Columns {
+    columns: Vec<(String, Type)>,
+}
+
+impl Columns {
+    /// Copy the name and type of each column.
+    pub fn new(columns: &[Column]) -> Columns {
+        Columns {
+            columns: columns
+                .iter()
+                .map(|col| (col.name().to_owned(), col.type_().clone()))
+                .collect(),
+        }
+    }
+
+    /// Iterate over the name and type of each column.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Type)> {
+        self.columns.iter().map(|(name, ty)| (name.as_str(), ty))
+    }
+
+    /// Iterate over the name of each column.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.iter().map(|(name, _)| name)
+    }
+
+    /// The number of columns.
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// `true` if there are no columns, `false` otherwise.
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+}
+
+impl Display for Columns {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, ty)) in self.iter().enumerate() {
+            if i != 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "`{}` ({})", name, ty)?;
+        }
+        Ok(())
+    }
+}
+
+/// A column whose type can't be extracted as the field it is used for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeMismatch {
+    /// The name of the field.
+    pub field: String,
+    /// The name of the column.
+    pub column: String,
+    /// The name of the field's type.
+    pub expected: &'static str,
+    /// The type of the column.
+    pub found: Type,
+}
+
+impl Display for TypeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "field `{}` of type `{}` cannot be extracted from column `{}` ({})",
+            self.field, self.expected, self.column, self.found
+        )
+    }
+}
+
+fn display_mismatches(mismatches: &[TypeMismatch]) -> String {
+    mismatches
+        .iter()
+        .map(TypeMismatch::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
 /// A contiguous subset of columns in a row.
@@ -142,6 +270,30 @@ where
     range: Range<usize>,
 }
 
+/// A row in which values are looked up by name with a prefix, such that a type expecting the column
+/// `name` is extracted from the column `parent_name` instead.
+///
+/// Used by `#[derive(FromSqlRow)]` when a field is tagged with the attribute
+/// `#[row(flatten, rename_prefix = "...")]`. Only names looked up by types deriving `FromSqlRow` are
+/// prefixed; columns are still indexed by their position in the whole row.
+pub struct Prefixed<'a, R>
+where
+    R: Row,
+{
+    row: &'a R,
+    prefix: &'a str,
+}
+
+impl<'a, R> Prefixed<'a, R>
+where
+    R: Row,
+{
+    /// Look up the columns of `row` with `prefix`.
+    pub fn new(row: &'a R, prefix: &'a str) -> Prefixed<'a, R> {
+        Prefixed { row, prefix }
+    }
+}
+
 /// Extract values from a row.
 ///
 /// May be derived for `struct`s using `#[derive(FromSqlRow)]`.
@@ -158,6 +310,32 @@ where
 ///     birthday: Option<Date<String>>,
 /// }
 /// ```
+///
+/// Also implemented for tuples of up to 16 values, which are extracted from the columns in order,
+/// and for `Vec<T>`, which is extracted from a single array column.
+///
+/// # `NULL` values
+///
+/// Extracting a value from a column that is `NULL` fails, unless the type of the value accepts
+/// `NULL`. Use `Option<T>` for values which may be missing, or [`Nullable<T>`] to fall back to
+/// `T::default()` instead:
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{extract::Nullable, query, Result};
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// let (name, nickname, age): (String, Option<String>, Nullable<i32>) =
+///     query!("SELECT name, nickname, age FROM people LIMIT 1")
+///         .fetch_one(&client)
+///         .await?;
+///
+/// let age: i32 = age.into_inner();
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Nullable<T>`]: struct.Nullable.html
 pub trait FromSqlRow: Sized {
     /// Number of columns required to construct this type.
     ///
@@ -180,19 +358,150 @@ pub trait FromSqlRow: Sized {
     where
         R: Row,
     {
-        rows.iter().map(Self::from_row).collect()
+        __profile::scope::<Self, _>(rows.len(), || rows.iter().map(Self::from_row).collect())
+    }
+
+    /// The names of the columns this type expects, in order. Columns without a known name are
+    /// given as `"?"`.
+    ///
+    /// Used by the tests generated by `#[row(test_layout)]`.
+    #[doc(hidden)]
+    fn __column_names() -> Vec<String> {
+        vec![UNKNOWN_COLUMN.to_owned(); Self::COLUMN_COUNT]
+    }
+
+    /// Whether this value is missing from the row, according to the `NULL`s in its columns.
+    /// Implemented by `#[derive(FromSqlRow)]`, which knows the names of its columns up front.
+    ///
+    /// Used by [`from_row_or_none`] and flattened `Option`s.
+    ///
+    /// [`from_row_or_none`]: fn.from_row_or_none.html
+    #[doc(hidden)]
+    fn __is_none<R>(row: &R, when: NoneWhen) -> Result<bool, Error>
+    where
+        R: Row,
+    {
+        let checked = match when {
+            NoneWhen::AllNull => Self::COLUMN_COUNT,
+            NoneWhen::KeyNull => Self::COLUMN_COUNT.min(1),
+        };
+
+        for i in 0..checked {
+            let Null(is_null) = row.try_get(i)?;
+            if !is_null {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Extract a value from a row, as a field tagged with `#[row(flatten)]`.
+    ///
+    /// Overridden by `Option<T>`, which is `None` if all the columns of `T` are `NULL`.
+    #[doc(hidden)]
+    fn __from_flattened<R>(row: &R) -> Result<Self, Error>
+    where
+        R: Row,
+    {
+        Self::from_row(row)
+    }
+
+    /// Describes which columns each field is extracted from, if known. Implemented by
+    /// `#[derive(FromSqlRow)]`, and used by [`describe_layout`].
+    ///
+    /// [`describe_layout`]: fn.describe_layout.html
+    fn layout() -> Option<RowLayout> {
+        None
+    }
+
+    /// How rows are merged by `#[row(group)]` and `#[row(hash)]`, if at all.
+    ///
+    /// Used by [`GroupAccumulator`].
+    ///
+    /// [`GroupAccumulator`]: struct.GroupAccumulator.html
+    #[doc(hidden)]
+    const __MERGE_MODE: Option<MergeMode> = None;
+
+    /// Extract a value from each row, and merge them with the values extracted so far.
+    ///
+    /// Used by [`GroupAccumulator`].
+    ///
+    /// [`GroupAccumulator`]: struct.GroupAccumulator.html
+    #[doc(hidden)]
+    fn __merge_rows<R>(state: &mut MergeState<Self>, rows: &[R]) -> Result<(), Error>
+    where
+        R: Row,
+    {
+        for row in rows {
+            state.values.push(Self::from_row(row)?);
+        }
+        Ok(())
+    }
+
+    /// Check that the columns of a row have the types expected by the fields of this type,
+    /// collecting every mismatch.
+    ///
+    /// Used by [`check_types`].
+    ///
+    /// [`check_types`]: fn.check_types.html
+    #[doc(hidden)]
+    fn __check_types<R>(_row: &R, _mismatches: &mut Vec<TypeMismatch>) -> Result<(), Error>
+    where
+        R: Row,
+    {
+        Ok(())
     }
 }
 
+const UNKNOWN_COLUMN: &str = "?";
+
 /// For collections that can be built from single elements.
 ///
-/// Used by `#[derive(FromSqlRow)]` when a field is tagged with the attribute `#[row(merge)]`.
+/// Used by `#[derive(FromSqlRow)]` when a field is tagged with the attribute `#[row(merge)]`. The
+/// field's collection is created with `Default::default()` when a group is first seen, after which
+/// `insert` is called once for the item extracted from each row of the group, in the order of the
+/// rows. No items are skipped or deduplicated, except by the collection itself (as is the case
+/// for `HashSet` and `BTreeSet`).
+///
+/// Implemented for `Vec`, `HashSet` and `BTreeSet`, as well as `SmallVec` and `ArrayVec` with the
+/// `with-smallvec-1` and `with-arrayvec-0_7` features, which keep small groups off the heap.
+/// Newtypes around collections may derive the trait, which delegates to the collection:
+///
+/// ```
+/// # use postgres_query::{FromSqlRow, Merge};
+/// #[derive(Default, Merge)]
+/// struct Books(Vec<Book>);
+///
+/// #[derive(FromSqlRow)]
+/// #[row(group)]
+/// struct Author {
+///     #[row(key)]
+///     name: String,
+///     #[row(merge)]
+///     books: Books,
+/// }
+///
+/// #[derive(FromSqlRow)]
+/// struct Book {
+///     title: String,
+/// }
+/// ```
 pub trait Merge {
     /// The type of item being merged.
     type Item;
 
     /// Insert one item into this collection.
     fn insert(&mut self, item: Self::Item);
+
+    /// Insert one item into this collection, failing if it cannot hold any more items. This is
+    /// what `#[row(merge)]` uses, and defaults to [`insert`].
+    ///
+    /// [`insert`]: #tymethod.insert
+    fn try_insert(&mut self, item: Self::Item) -> Result<(), Error> {
+        self.insert(item);
+        Ok(())
+    }
 }
 
 impl<T> Merge for Vec<T> {
@@ -222,6 +531,438 @@ where
     }
 }
 
+#[cfg(feature = "with-smallvec-1")]
+impl<A> Merge for smallvec::SmallVec<A>
+where
+    A: smallvec::Array,
+{
+    type Item = A::Item;
+    fn insert(&mut self, item: A::Item) {
+        self.push(item)
+    }
+}
+
+/// Merging fails if a group has more than `CAP` items, while `insert` panics.
+#[cfg(feature = "with-arrayvec-0_7")]
+impl<T, const CAP: usize> Merge for arrayvec::ArrayVec<T, CAP> {
+    type Item = T;
+    fn insert(&mut self, item: T) {
+        self.push(item)
+    }
+
+    fn try_insert(&mut self, item: T) -> Result<(), Error> {
+        self.try_push(item).map_err(|_| {
+            Error::new(format!(
+                "cannot merge more than {} items into an `ArrayVec`",
+                CAP
+            ))
+        })
+    }
+}
+
+/// Merges rows into values incrementally, so that the rows of a query can be processed in chunks.
+///
+/// [`FromSqlRow::from_row_multi`] only merges the rows it is given, which means that a group may
+/// be split in two when a large result is extracted in chunks. The accumulator instead remembers
+/// the groups across chunks, and only returns those which are known to be complete:
+///
+/// - With `#[row(group)]`, every group except the last is complete, since the groups are
+///   consecutive. Memory use is bounded by the size of the chunks and groups.
+/// - With `#[row(hash)]`, any group may grow until the last row, so no group is complete until
+///   [`finish`] is called.
+/// - Types which are not merged are returned as soon as they are extracted.
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{extract::GroupAccumulator, query, FromSqlRow, Result};
+/// # use futures::TryStreamExt;
+/// # async fn foo() -> Result<(), Box<dyn std::error::Error>> {
+/// # let client: Client = unimplemented!();
+/// #[derive(FromSqlRow)]
+/// #[row(group)]
+/// struct Author {
+///     #[row(key)]
+///     name: String,
+///     #[row(merge)]
+///     books: Vec<Book>,
+/// }
+///
+/// #[derive(FromSqlRow)]
+/// struct Book {
+///     title: String,
+/// }
+///
+/// let rows = query!("SELECT name, title FROM books ORDER BY name")
+///     .query_streaming(&client)
+///     .await?;
+/// let mut chunks = rows.try_chunks(1000);
+///
+/// let mut authors = GroupAccumulator::<Author>::new();
+/// while let Some(chunk) = chunks.try_next().await? {
+///     for author in authors.feed(&chunk)? {
+///         println!("{} wrote {} books", author.name, author.books.len());
+///     }
+/// }
+/// for author in authors.finish() {
+///     println!("{} wrote {} books", author.name, author.books.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`FromSqlRow::from_row_multi`]: trait.FromSqlRow.html#method.from_row_multi
+/// [`finish`]: #method.finish
+pub struct GroupAccumulator<T> {
+    state: MergeState<T>,
+}
+
+impl<T> Default for GroupAccumulator<T> {
+    fn default() -> Self {
+        GroupAccumulator {
+            state: MergeState::default(),
+        }
+    }
+}
+
+impl<T> GroupAccumulator<T>
+where
+    T: FromSqlRow,
+{
+    pub fn new() -> GroupAccumulator<T> {
+        GroupAccumulator::default()
+    }
+
+    /// Merge a chunk of rows into the groups extracted so far, and return the groups which are
+    /// complete.
+    pub fn feed<R>(&mut self, rows: &[R]) -> Result<Vec<T>, Error>
+    where
+        R: Row,
+    {
+        T::__merge_rows(&mut self.state, rows)?;
+
+        let values = &mut self.state.values;
+        let complete = match T::__MERGE_MODE {
+            None => mem::take(values),
+            Some(MergeMode::Group) => {
+                let last = values.pop();
+                let complete = mem::take(values);
+                values.extend(last);
+                complete
+            }
+            Some(MergeMode::Hash) => Vec::new(),
+        };
+
+        Ok(complete)
+    }
+
+    /// Return the remaining groups, once all rows have been fed to the accumulator.
+    pub fn finish(self) -> Vec<T> {
+        self.state.values
+    }
+}
+
+/// How rows are merged.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeMode {
+    Group,
+    Hash,
+}
+
+/// The values extracted so far while merging rows.
+#[doc(hidden)]
+pub struct MergeState<T> {
+    pub values: Vec<T>,
+    /// The index of each key in `values`, used by `#[row(hash)]`. The type of the key is only
+    /// known to the derived code.
+    pub indices: Option<Box<dyn Any + Send>>,
+}
+
+impl<T> Default for MergeState<T> {
+    fn default() -> Self {
+        MergeState {
+            values: Vec::new(),
+            indices: None,
+        }
+    }
+}
+
+/// Used by `#[derive(FromSqlRow)]` to take the key indices of a `#[row(hash)]` container out of
+/// the merge state, so that a chunk of rows can be merged with a typed `HashMap`.
+#[doc(hidden)]
+pub fn __take_indices<K>(indices: &mut Option<Box<dyn Any + Send>>) -> HashMap<K, usize>
+where
+    K: 'static,
+{
+    match indices.take() {
+        None => HashMap::new(),
+        Some(indices) => *indices
+            .downcast()
+            .expect("the type of the keys changed while merging rows"),
+    }
+}
+
+/// Get a text cell in the row and parse it with `FromStr`.
+///
+/// Used by `#[derive(FromSqlRow)]` when a field is tagged with the attribute `#[row(parse)]`.
+pub fn try_parse<R, I, T>(row: &R, index: I) -> Result<T, Error>
+where
+    R: Row,
+    I: RowIndex + Display,
+    T: FromStr,
+    T::Err: Display,
+{
+    let text: &str = row.try_get(&index)?;
+    text.parse().map_err(|error: T::Err| Error::Parse {
+        index: index.to_string(),
+        text: text.to_owned(),
+        msg: error.to_string(),
+    })
+}
+
+/// Check that the columns of a row have the types expected by `T`, without extracting any values.
+///
+/// Fails with a single [`Error::TypeMismatch`] listing every field whose column has the wrong
+/// type. Columns which are missing are not reported here, but by `T::from_row`. Only types deriving
+/// `FromSqlRow` describe their expected types; for any other type this always succeeds.
+///
+/// Called at the start of `from_row` for containers tagged with `#[row(check_types)]`.
+///
+/// [`Error::TypeMismatch`]: enum.Error.html#variant.TypeMismatch
+pub fn check_types<T, R>(row: &R) -> Result<(), Error>
+where
+    T: FromSqlRow,
+    R: Row,
+{
+    let mut mismatches = Vec::new();
+    T::__check_types(row, &mut mismatches)?;
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::TypeMismatch { mismatches })
+    }
+}
+
+/// Used by `#[derive(FromSqlRow)]` to check the type of the column of a single field.
+#[doc(hidden)]
+pub fn __check_type<'a, T, R, I>(row: &R, index: I, field: &str, mismatches: &mut Vec<TypeMismatch>)
+where
+    T: FromSql<'a>,
+    R: Row,
+    I: RowIndex,
+{
+    let columns = row.columns();
+    let column = match index.__idx(columns) {
+        Some(index) => &columns[index],
+        None => return,
+    };
+
+    if !T::accepts(column.type_()) {
+        mismatches.push(TypeMismatch {
+            field: field.to_owned(),
+            column: column.name().to_owned(),
+            expected: std::any::type_name::<T>(),
+            found: column.type_().clone(),
+        });
+    }
+}
+
+/// Used by `#[derive(FromSqlRow)]` to report key fields of `#[row(hash)]` containers that can't be
+/// used as keys, at the location of the field.
+#[doc(hidden)]
+pub fn __assert_hash_key<T>(_: &T)
+where
+    T: Clone + Hash + Eq,
+{
+}
+
+/// Used by `#[derive(FromSqlRow)]` to time the extraction of rows and fields. Does nothing unless
+/// the `profile` feature is enabled.
+#[doc(hidden)]
+pub mod __profile {
+    #[cfg(feature = "profile")]
+    pub use crate::profile::{__field as field, __scope as scope};
+
+    #[cfg(not(feature = "profile"))]
+    #[inline(always)]
+    pub fn scope<T: ?Sized, R>(_rows: usize, extract: impl FnOnce() -> R) -> R {
+        extract()
+    }
+
+    #[cfg(not(feature = "profile"))]
+    #[inline(always)]
+    pub fn field<T: ?Sized, R>(_field: &'static str, extract: impl FnOnce() -> R) -> R {
+        extract()
+    }
+}
+
+/// Determines when an optional value is missing from a row.
+///
+/// Used by `#[derive(FromSqlRow)]` when a field is tagged with the attribute
+/// `#[row(flatten, none_when = "...")]`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NoneWhen {
+    /// Every column of the value is `NULL`.
+    AllNull,
+    /// The first column of the value (usually its primary key) is `NULL`.
+    KeyNull,
+}
+
+/// Extract an optional value from a row, which is `None` if the row contains `NULL`s according to
+/// `when`.
+///
+/// Unlike the implementation of `FromSqlRow` for `Option<T>`, this never swallows errors: if the
+/// value is present but could not be extracted, the error is returned.
+pub fn from_row_or_none<T, R>(row: &R, when: NoneWhen) -> Result<Option<T>, Error>
+where
+    T: FromSqlRow,
+    R: Row,
+{
+    if T::COLUMN_COUNT == 0 {
+        return T::from_row(row).map(Some);
+    }
+
+    if T::__is_none(row, when)? {
+        Ok(None)
+    } else {
+        T::from_row(row).map(Some)
+    }
+}
+
+/// Used by `#[derive(FromSqlRow)]` to check whether a column is `NULL`.
+#[doc(hidden)]
+pub fn __is_null<R, I>(row: &R, index: I) -> Result<bool, Error>
+where
+    R: Row,
+    I: RowIndex + Display,
+{
+    let Null(is_null) = row.try_get(index)?;
+    Ok(is_null)
+}
+
+/// Extract a value from a row, which is `T::default()` if any of its columns are missing from the
+/// row.
+///
+/// Used by `#[derive(FromSqlRow)]` when a field is tagged with the attribute
+/// `#[row(flatten, fallback)]`. Like [`from_row_or_none`], errors are returned if all columns are
+/// present but the value could not be extracted.
+///
+/// [`from_row_or_none`]: fn.from_row_or_none.html
+pub fn from_row_or_default<T, R>(row: &R) -> Result<T, Error>
+where
+    T: FromSqlRow + Default,
+    R: Row,
+{
+    let columns = row.columns();
+    let missing = T::__column_names()
+        .iter()
+        .enumerate()
+        .any(|(i, name)| {
+            if name == UNKNOWN_COLUMN {
+                i >= columns.len()
+            } else {
+                let name = row.__column_name(name);
+                columns.iter().all(|column| column.name() != name)
+            }
+        });
+
+    if missing {
+        Ok(T::default())
+    } else {
+        T::from_row(row)
+    }
+}
+
+/// Split columns like [`split_columns_many`], but the partitions following the last `optional`
+/// splits are left empty (at the end of the row) if any of those splits are missing.
+///
+/// Used by `#[derive(FromSqlRow)]` when the last field is tagged with the attribute
+/// `#[row(flatten, fallback)]`.
+///
+/// [`split_columns_many`]: fn.split_columns_many.html
+#[doc(hidden)]
+pub fn __split_columns_fallback<S>(
+    columns: &[Column],
+    splits: &[S],
+    optional: usize,
+) -> std::vec::IntoIter<Result<Range<usize>, Error>>
+where
+    S: AsRef<str>,
+{
+    let ranges = split_columns_many(columns, splits).collect::<Vec<_>>();
+    if ranges.iter().all(Result::is_ok) {
+        return ranges.into_iter();
+    }
+
+    let required = splits.len() - optional;
+    let end = columns.len();
+    split_columns_many(columns, &splits[..required])
+        .chain(iter::repeat_with(|| Ok(end..end)).take(optional))
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+/// A value which is `T::default()` if the column is `NULL`.
+///
+/// Dereferences to the inner value.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Nullable<T>(pub T);
+
+impl<T> Nullable<T> {
+    /// Return the inner value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Nullable<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Nullable<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<'a, T> FromSql<'a> for Nullable<T>
+where
+    T: FromSql<'a> + Default,
+{
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn StdError + Sync + Send>> {
+        T::from_sql(ty, raw).map(Nullable)
+    }
+
+    fn from_sql_null(_: &Type) -> Result<Self, Box<dyn StdError + Sync + Send>> {
+        Ok(Nullable(T::default()))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        T::accepts(ty)
+    }
+}
+
+/// Checks if a value of any type is `NULL`.
+struct Null(bool);
+
+impl<'a> FromSql<'a> for Null {
+    fn from_sql(_: &Type, _: &'a [u8]) -> Result<Self, Box<dyn StdError + Sync + Send>> {
+        Ok(Null(false))
+    }
+
+    fn from_sql_null(_: &Type) -> Result<Self, Box<dyn StdError + Sync + Send>> {
+        Ok(Null(true))
+    }
+
+    fn accepts(_: &Type) -> bool {
+        true
+    }
+}
+
 impl private::row::Sealed for tokio_postgres::Row {}
 
 impl Row for tokio_postgres::Row {
@@ -254,6 +995,30 @@ impl Row for tokio_postgres::Row {
     }
 }
 
+impl<R> private::row::Sealed for Prefixed<'_, R> where R: Row {}
+
+impl<R> Row for Prefixed<'_, R>
+where
+    R: Row,
+{
+    fn columns(&self) -> &[Column] {
+        self.row.columns()
+    }
+
+    fn try_get<'a, I, T>(&'a self, index: I) -> Result<T, Error>
+    where
+        I: RowIndex + Display,
+        T: FromSql<'a>,
+    {
+        self.row.try_get(index)
+    }
+
+    fn __column_name<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        let prefixed = format!("{}{}", self.prefix, name);
+        Cow::Owned(self.row.__column_name(&prefixed).into_owned())
+    }
+}
+
 impl<R> private::row::Sealed for RowSlice<'_, R> where R: Row {}
 
 impl<R> Row for RowSlice<'_, R>
@@ -274,10 +1039,14 @@ where
         } else {
             Err(Error::SliceLookup {
                 index: index.to_string(),
-                columns: format_columns(self.columns()),
+                columns: Columns::new(self.columns()),
             })
         }
     }
+
+    fn __column_name<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        self.row.__column_name(name)
+    }
 }
 
 impl<R> RowSlice<'_, R>
@@ -288,7 +1057,7 @@ where
     ///
     /// This is an optimized version of `Row::slice` which reduces the number of
     /// pointer-indirections.
-    pub fn slice(&self, range: Range<usize>) -> Result<RowSlice<R>, Error>
+    pub fn slice(&self, range: Range<usize>) -> Result<RowSlice<'_, R>, Error>
     where
         Self: Sized,
     {
@@ -350,11 +1119,45 @@ where
         SplitResult::Range(range) => Ok(range),
         SplitResult::NotFound { split, start } => Err(Error::InvalidSplit {
             split,
-            columns: format_columns(&columns[start..]),
+            columns: Columns::new(&columns[start..]),
         }),
     })
 }
 
+/// Split a list of column names, in the same way as [`split_columns_many`].
+///
+/// Used by the tests generated by `#[row(test_layout)]`.
+///
+/// [`split_columns_many`]: fn.split_columns_many.html
+#[doc(hidden)]
+pub fn __split_names<S>(names: &[String], splits: &[S]) -> Result<Vec<Range<usize>>, Error>
+where
+    S: AsRef<str>,
+{
+    partition_many(names.iter(), splits.iter())
+        .map(|split| match split {
+            SplitResult::Range(range) => Ok(range),
+            SplitResult::NotFound { split, start } => Err(Error::new(format!(
+                "failed to split on: `{}` (columns were: {:?})",
+                split,
+                &names[start..]
+            ))),
+        })
+        .collect()
+}
+
+/// Make sure that a partition in a synthetic row starts with the column it was split on.
+///
+/// Used by the tests generated by `#[row(test_layout)]`.
+#[doc(hidden)]
+pub fn __start_partition(names: &mut Vec<String>, split: &str) {
+    match names.first_mut() {
+        Some(first) if first == split => {}
+        Some(first) if first == UNKNOWN_COLUMN => *first = split.to_owned(),
+        _ => names.insert(0, split.to_owned()),
+    }
+}
+
 #[cfg_attr(test, derive(Debug, PartialEq))]
 enum SplitResult {
     NotFound { split: String, start: usize },
@@ -369,37 +1172,591 @@ fn partition_many<'a>(
     let mut splits = splits;
 
     let mut previous_end = 0;
+    // Number of columns consumed so far, in case the last partition only contains its split.
+    let mut consumed = 0;
+    let mut done = false;
 
     iter::from_fn(move || -> Option<_> {
-        if let Some(split) = splits.next() {
+        if done {
+            None
+        } else if let Some(split) = splits.next() {
             let split = split.as_ref();
             if let Some((end, _)) = columns.find(|(_, name)| name.as_ref() == split) {
                 let range = previous_end..end;
                 previous_end = end;
+                consumed = end + 1;
                 Some(SplitResult::Range(range))
             } else {
+                done = true;
                 Some(SplitResult::NotFound {
                     split: split.to_owned(),
                     start: previous_end,
                 })
             }
         } else {
-            let (last, _) = columns.by_ref().last()?;
-            let len = last + 1;
+            done = true;
+            let len = columns
+                .by_ref()
+                .last()
+                .map_or(consumed, |(last, _)| last + 1);
             Some(SplitResult::Range(previous_end..len))
         }
     })
 }
 
-fn format_columns(columns: &[Column]) -> String {
-    let mut total = String::with_capacity(16 * columns.len());
-    for col in columns {
-        if !total.is_empty() {
-            total.push_str(", ");
+/// How a type extracts its fields from the columns of a row, as returned by
+/// [`FromSqlRow::layout`].
+///
+/// [`FromSqlRow::layout`]: trait.FromSqlRow.html#method.layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RowLayout {
+    /// Every field is extracted from the whole row.
+    Plain(Vec<FieldLayout>),
+    /// The row is split into partitions of the given lengths (`#[row(exact)]`).
+    Exact(Vec<(usize, Vec<FieldLayout>)>),
+    /// The row is split before each of the named columns (`#[row(split)]`), giving one more
+    /// partition than there are splits.
+    Split {
+        splits: Vec<&'static str>,
+        partitions: Vec<Vec<FieldLayout>>,
+    },
+}
+
+/// A field of a [`RowLayout`].
+///
+/// [`RowLayout`]: enum.RowLayout.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldLayout {
+    /// The name of the field, or its index in a tuple struct.
+    pub field: &'static str,
+    pub source: FieldSource,
+}
+
+/// Where a field is extracted from, relative to its partition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldSource {
+    /// The column at the given index.
+    Position(usize),
+    /// The first column with the given name.
+    Name(&'static str),
+    /// Another type which implements `FromSqlRow` (`#[row(flatten)]` or `#[row(merge)]`).
+    Flatten {
+        columns: usize,
+        layout: Option<Box<RowLayout>>,
+        /// Prepended to the names of its columns (`#[row(flatten, rename_prefix = "...")]`).
+        prefix: Option<&'static str>,
+    },
+    /// A number of consecutive elements of another type (`#[row(repeat = N)]`).
+    Repeat {
+        count: usize,
+        columns: usize,
+        layout: Option<Box<RowLayout>>,
+    },
+}
+
+/// Describes how the columns of a row would be partitioned and which field maps to which column,
+/// as returned by [`describe_layout`]. Printed as a table when displayed.
+///
+/// [`describe_layout`]: fn.describe_layout.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutReport {
+    /// The name of the described type.
+    pub ty: &'static str,
+    /// The names of the columns.
+    pub columns: Vec<String>,
+    /// The ranges of columns in each (top-level) partition.
+    pub partitions: Vec<Range<usize>>,
+    pub fields: Vec<FieldReport>,
+    /// Problems that would cause the extraction to fail, such as missing columns.
+    pub errors: Vec<String>,
+}
+
+/// A field of a [`LayoutReport`].
+///
+/// [`LayoutReport`]: struct.LayoutReport.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldReport {
+    /// The path to the field, such as `parent.name` for flattened fields or `items[1]` for repeated
+    /// ones.
+    pub field: String,
+    /// The index of the (top-level) partition the field is extracted from.
+    pub partition: usize,
+    /// The columns the field is extracted from, or `None` if they could not be found.
+    pub columns: Option<Range<usize>>,
+}
+
+/// Describe how `T` would be extracted from a row with the given columns.
+///
+/// This is useful when debugging the extraction of types with nested or partitioned layouts, for
+/// example by printing the report in a test:
+///
+/// ```
+/// # use postgres_query::{extract, FromSqlRow};
+/// #[derive(FromSqlRow)]
+/// #[row(split)]
+/// struct Family {
+///     #[row(flatten, split = "id")]
+///     parent: Person,
+///     #[row(flatten, split = "id")]
+///     child: Person,
+/// }
+///
+/// #[derive(FromSqlRow)]
+/// struct Person {
+///     id: i32,
+///     name: String,
+/// }
+///
+/// let report = extract::describe_layout::<Family, _>(&["id", "name", "id", "name"]);
+/// assert!(report.errors.is_empty());
+/// assert_eq!(report.fields[3].field, "child.name");
+/// assert_eq!(report.fields[3].columns, Some(3..4));
+/// println!("{}", report);
+/// ```
+///
+/// Types which do not describe their layout (see [`FromSqlRow::layout`]) are reported as a single
+/// field spanning the columns they would be extracted from.
+///
+/// [`FromSqlRow::layout`]: trait.FromSqlRow.html#method.layout
+pub fn describe_layout<T, S>(columns: &[S]) -> LayoutReport
+where
+    T: FromSqlRow,
+    S: AsRef<str>,
+{
+    let mut report = LayoutReport {
+        ty: std::any::type_name::<T>(),
+        columns: columns.iter().map(|col| col.as_ref().to_owned()).collect(),
+        partitions: Vec::new(),
+        fields: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    let all = 0..columns.len();
+    match T::layout() {
+        Some(layout) => {
+            report.partitions = describe_partitions(&mut report, "", "", &layout, all, None);
+        }
+        None => {
+            report.partitions.push(all.clone());
+            let field = Some(describe_opaque(&mut report, "", T::COLUMN_COUNT, all));
+            report.fields.push(FieldReport {
+                field: String::from("*"),
+                partition: 0,
+                columns: field,
+            });
+        }
+    }
+
+    report
+}
+
+/// Describe the fields of a layout, within the columns in `range`. Returns the ranges of the
+/// partitions. `partition` is the top-level partition, if already known. `rename` is prepended to
+/// the names of columns (see `Prefixed`).
+fn describe_partitions(
+    report: &mut LayoutReport,
+    prefix: &str,
+    rename: &str,
+    layout: &RowLayout,
+    range: Range<usize>,
+    partition: Option<usize>,
+) -> Vec<Range<usize>> {
+    let mut partitions = Vec::new();
+
+    match layout {
+        RowLayout::Plain(_) => partitions.push(range.clone()),
+        RowLayout::Exact(groups) => {
+            let mut start = range.start;
+            for (len, _) in groups {
+                let end = start + len;
+                if end > range.end {
+                    report.errors.push(format!(
+                        "{}: expected {} more columns, found {}",
+                        describe_prefix(prefix),
+                        end - range.end,
+                        range.end.saturating_sub(start),
+                    ));
+                }
+                partitions.push(start..end.min(range.end));
+                start = end.min(range.end);
+            }
+        }
+        RowLayout::Split { splits, .. } => {
+            let names = report.columns[range.clone()].to_vec();
+            for split in partition_many(names.iter(), splits.iter()) {
+                match split {
+                    SplitResult::Range(split) => {
+                        partitions.push(range.start + split.start..range.start + split.end)
+                    }
+                    SplitResult::NotFound { split, start } => {
+                        report.errors.push(format!(
+                            "{}: failed to split on `{}`, since no later column has that name",
+                            describe_prefix(prefix),
+                            split,
+                        ));
+                        partitions.push(range.start + start..range.end);
+                    }
+                }
+            }
+            // The fields of the partitions after a failed split are missing.
+            while partitions.len() <= splits.len() {
+                partitions.push(range.end..range.end);
+            }
+        }
+    }
+
+    let groups: Vec<&[FieldLayout]> = match layout {
+        RowLayout::Plain(fields) => vec![fields],
+        RowLayout::Exact(groups) => groups.iter().map(|(_, fields)| &fields[..]).collect(),
+        RowLayout::Split { partitions, .. } => {
+            partitions.iter().map(|fields| &fields[..]).collect()
+        }
+    };
+
+    for (i, (fields, range)) in groups.into_iter().zip(partitions.clone()).enumerate() {
+        let partition = partition.unwrap_or(i);
+        for field in fields {
+            describe_field(report, prefix, rename, field, range.clone(), partition);
+        }
+    }
+
+    partitions
+}
+
+fn describe_field(
+    report: &mut LayoutReport,
+    prefix: &str,
+    rename: &str,
+    field: &FieldLayout,
+    range: Range<usize>,
+    partition: usize,
+) {
+    let path = format!("{}{}", prefix, field.field);
+
+    let columns = match &field.source {
+        FieldSource::Position(index) => {
+            let column = range.start + index;
+            if column < range.end {
+                Some(column..column + 1)
+            } else {
+                report.errors.push(format!(
+                    "{}: expected a column at index {}, but the partition only has {}",
+                    path,
+                    index,
+                    range.len(),
+                ));
+                None
+            }
+        }
+        FieldSource::Name(name) => {
+            let name = format!("{}{}", rename, name);
+            match report.columns[range.clone()]
+                .iter()
+                .position(|col| *col == name)
+            {
+                Some(index) => Some(range.start + index..range.start + index + 1),
+                None => {
+                    report
+                        .errors
+                        .push(format!("{}: no column named `{}`", path, name));
+                    None
+                }
+            }
+        }
+        FieldSource::Flatten {
+            columns,
+            layout,
+            prefix: nested,
+        } => {
+            let prefix = format!("{}.", path);
+            let rename = format!("{}{}", rename, nested.unwrap_or_default());
+            match layout {
+                Some(layout) => {
+                    describe_partitions(report, &prefix, &rename, layout, range, Some(partition));
+                    return;
+                }
+                None => Some(describe_opaque(report, &path, *columns, range)),
+            }
+        }
+        FieldSource::Repeat {
+            count,
+            columns,
+            layout,
+        } => {
+            for i in 0..*count {
+                let start = (range.start + i * columns).min(range.end);
+                let end = (start + columns).min(range.end);
+                let element = FieldLayout {
+                    field: "",
+                    source: FieldSource::Flatten {
+                        columns: *columns,
+                        layout: layout.clone(),
+                        prefix: None,
+                    },
+                };
+                let prefix = format!("{}[{}]", path, i);
+                describe_field(report, &prefix, rename, &element, start..end, partition);
+            }
+            return;
+        }
+    };
+
+    report.fields.push(FieldReport {
+        field: path,
+        partition,
+        columns,
+    });
+}
+
+/// The columns of a type without a known layout, which is extracted from the start of `range`.
+fn describe_opaque(
+    report: &mut LayoutReport,
+    path: &str,
+    columns: usize,
+    range: Range<usize>,
+) -> Range<usize> {
+    if columns > range.len() {
+        report.errors.push(format!(
+            "{}: expected {} columns, found {}",
+            describe_prefix(path),
+            columns,
+            range.len(),
+        ));
+    }
+    range.start..range.end.min(range.start + columns)
+}
+
+fn describe_prefix(prefix: &str) -> &str {
+    match prefix.trim_end_matches('.') {
+        "" => "row",
+        prefix => prefix,
+    }
+}
+
+impl Display for LayoutReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "layout of `{}`:", self.ty)?;
+
+        for (i, partition) in self.partitions.iter().enumerate() {
+            let names = self.columns[partition.clone()].join(", ");
+            writeln!(f, "  partition {} ({:?}): {}", i, partition, names)?;
+
+            for field in self.fields.iter().filter(|field| field.partition == i) {
+                match &field.columns {
+                    Some(columns) => writeln!(
+                        f,
+                        "    {} <- {:?} ({})",
+                        field.field,
+                        columns,
+                        self.columns[columns.clone()].join(", "),
+                    )?,
+                    None => writeln!(f, "    {} <- (missing)", field.field)?,
+                }
+            }
+        }
+
+        for error in &self.errors {
+            writeln!(f, "  error: {}", error)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The names of the columns `T` is extracted from, in order, with renames applied.
+///
+/// Columns without a known name, such as those of tuples, are given as `"?"`.
+///
+/// ```
+/// # use postgres_query::{extract, FromSqlRow};
+/// #[derive(FromSqlRow)]
+/// struct Person {
+///     id: i32,
+///     #[row(rename = "firstName")]
+///     name: String,
+/// }
+///
+/// assert_eq!(extract::columns::<Person>(), ["id", "firstName"]);
+/// ```
+pub fn columns<T>() -> Vec<String>
+where
+    T: FromSqlRow,
+{
+    let mut columns = Vec::new();
+    select_columns(T::layout().as_ref(), T::COLUMN_COUNT, "", &mut columns);
+    columns.into_iter().map(|(_, name)| name).collect()
+}
+
+/// A `SELECT` list for the columns `T` is extracted from, in order, taking each column from the
+/// field of the same name in `table` and aliasing it to the name `T` expects. This keeps queries
+/// in sync with renamed fields.
+///
+/// `table` is inserted into the SQL as is.
+///
+/// ```
+/// # use postgres_query::{extract, FromSqlRow};
+/// #[derive(FromSqlRow)]
+/// struct Person {
+///     id: i32,
+///     #[row(rename = "firstName")]
+///     name: String,
+/// }
+///
+/// let select = extract::select_list::<Person>("t");
+/// assert_eq!(select, r#"t.id, t.name AS "firstName""#);
+/// ```
+///
+/// # Panics
+///
+/// If the names of some columns are not known (see [`columns`]).
+///
+/// [`columns`]: fn.columns.html
+pub fn select_list<T>(table: &str) -> String
+where
+    T: FromSqlRow,
+{
+    let mut columns = Vec::new();
+    select_columns(T::layout().as_ref(), T::COLUMN_COUNT, "", &mut columns);
+
+    let items = columns.into_iter().map(|(field, name)| {
+        let field = field.unwrap_or_else(|| {
+            panic!(
+                "cannot select the columns of `{}`, since some of them are unnamed",
+                std::any::type_name::<T>()
+            )
+        });
+
+        if field == name {
+            format!("{}.{}", table, column_label(field))
+        } else {
+            format!("{}.{} AS {}", table, column_label(field), column_label(&name))
+        }
+    });
+
+    items.collect::<Vec<_>>().join(", ")
+}
+
+/// The names of the columns a type is extracted from, known at compile-time.
+///
+/// Implemented by `#[derive(FromSqlRow)]` with [`#[row(columns)]`], which also adds the constant
+/// to the type itself, so that it may be used as `Person::COLUMNS`.
+///
+/// [`#[row(columns)]`]: ../derive.FromSqlRow.html#rowcolumns
+pub trait StaticColumns: FromSqlRow {
+    /// The names of the columns, in order, with renames applied and flattened fields expanded.
+    const COLUMNS: &'static [&'static str];
+}
+
+impl<T> StaticColumns for Option<T>
+where
+    T: StaticColumns,
+{
+    const COLUMNS: &'static [&'static str] = T::COLUMNS;
+}
+
+/// Used by `#[row(columns)]` to count the columns of the fields.
+#[doc(hidden)]
+pub const fn __count_columns(parts: &[&[&str]]) -> usize {
+    let mut count = 0;
+    let mut i = 0;
+    while i < parts.len() {
+        count += parts[i].len();
+        i += 1;
+    }
+    count
+}
+
+/// Used by `#[row(columns)]` to concatenate the columns of the fields.
+#[doc(hidden)]
+pub const fn __concat_columns<const N: usize>(parts: &[&[&'static str]]) -> [&'static str; N] {
+    let mut columns = [""; N];
+    let mut count = 0;
+    let mut i = 0;
+    while i < parts.len() {
+        let mut j = 0;
+        while j < parts[i].len() {
+            columns[count] = parts[i][j];
+            count += 1;
+            j += 1;
+        }
+        i += 1;
+    }
+    columns
+}
+
+/// Collect the columns of a layout, as the name of the field each is extracted into (if known)
+/// and the name of the column, prepending `prefix` to the latter.
+fn select_columns(
+    layout: Option<&RowLayout>,
+    count: usize,
+    prefix: &str,
+    columns: &mut Vec<(Option<&'static str>, String)>,
+) {
+    let layout = match layout {
+        Some(layout) => layout,
+        None => {
+            // `iter::repeat_n` requires Rust 1.82.
+            #[allow(clippy::manual_repeat_n)]
+            columns.extend(iter::repeat((None, UNKNOWN_COLUMN.to_owned())).take(count));
+            return;
+        }
+    };
+
+    let groups: Vec<&[FieldLayout]> = match layout {
+        RowLayout::Plain(fields) => vec![fields],
+        RowLayout::Exact(groups) => groups.iter().map(|(_, fields)| &fields[..]).collect(),
+        RowLayout::Split { partitions, .. } => {
+            partitions.iter().map(|fields| &fields[..]).collect()
+        }
+    };
+
+    for field in groups.into_iter().flatten() {
+        match &field.source {
+            // The fields of tuple structs are named by their index.
+            FieldSource::Position(_) if field.field.starts_with(|ch: char| ch.is_ascii_digit()) => {
+                columns.push((None, UNKNOWN_COLUMN.to_owned()))
+            }
+            FieldSource::Position(_) => {
+                columns.push((Some(field.field), format!("{}{}", prefix, field.field)))
+            }
+            FieldSource::Name(name) => {
+                columns.push((Some(field.field), format!("{}{}", prefix, name)))
+            }
+            FieldSource::Flatten {
+                columns: count,
+                layout,
+                prefix: nested,
+            } => {
+                let prefix = format!("{}{}", prefix, nested.unwrap_or_default());
+                select_columns(layout.as_deref(), *count, &prefix, columns)
+            }
+            FieldSource::Repeat {
+                count,
+                columns: elem,
+                layout,
+            } => {
+                for _ in 0..*count {
+                    select_columns(layout.as_deref(), *elem, prefix, columns);
+                }
+            }
         }
-        write!(total, "`{}`", col.name()).unwrap();
     }
-    total
+}
+
+/// Quote a column name, unless it is a plain lowercase identifier. Since the names are qualified
+/// by a table (or follow `AS`), keywords need not be quoted.
+fn column_label(name: &str) -> Cow<'_, str> {
+    let mut chars = name.chars();
+    let plain = chars
+        .next()
+        .is_some_and(|ch| ch.is_ascii_lowercase() || ch == '_')
+        && chars.all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '_');
+
+    if plain {
+        name.into()
+    } else {
+        crate::execute::quote_identifier(name).into()
+    }
 }
 
 mod from_row_sql_impls {
@@ -411,7 +1768,7 @@ mod from_row_sql_impls {
     macro_rules! impl_from_row_for_tuple {
         (($($elem:ident),+)) => {
             impl<$($elem),+> FromSqlRow for ($($elem,)+)
-                where $($elem: for<'a> FromSql<'a> + std::fmt::Display),+
+                where $($elem: for<'a> FromSql<'a>),+
                 {
                     const COLUMN_COUNT: usize = impl_from_row_for_tuple!(@count ($($elem),*));
 
@@ -421,6 +1778,7 @@ mod from_row_sql_impls {
                                 Err(Error::ColumnCount {
                                     expected: Self::COLUMN_COUNT,
                                     found: row.len(),
+                                    columns: Columns::new(row.columns()),
                                 })
                             } else {
                                 let result = (
@@ -445,6 +1803,14 @@ mod from_row_sql_impls {
         (@index F) => { 5 };
         (@index G) => { 6 };
         (@index H) => { 7 };
+        (@index I) => { 8 };
+        (@index J) => { 9 };
+        (@index K) => { 10 };
+        (@index L) => { 11 };
+        (@index M) => { 12 };
+        (@index N) => { 13 };
+        (@index O) => { 14 };
+        (@index P) => { 15 };
 
         (@count ()) => { 0 };
         (@count ($head:ident $(, $tail:ident)*)) => {{
@@ -460,6 +1826,36 @@ mod from_row_sql_impls {
     impl_from_row_for_tuple!((A, B, C, D, E, F));
     impl_from_row_for_tuple!((A, B, C, D, E, F, G));
     impl_from_row_for_tuple!((A, B, C, D, E, F, G, H));
+    impl_from_row_for_tuple!((A, B, C, D, E, F, G, H, I));
+    impl_from_row_for_tuple!((A, B, C, D, E, F, G, H, I, J));
+    impl_from_row_for_tuple!((A, B, C, D, E, F, G, H, I, J, K));
+    impl_from_row_for_tuple!((A, B, C, D, E, F, G, H, I, J, K, L));
+    impl_from_row_for_tuple!((A, B, C, D, E, F, G, H, I, J, K, L, M));
+    impl_from_row_for_tuple!((A, B, C, D, E, F, G, H, I, J, K, L, M, N));
+    impl_from_row_for_tuple!((A, B, C, D, E, F, G, H, I, J, K, L, M, N, O));
+    impl_from_row_for_tuple!((A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P));
+
+    impl<T> FromSqlRow for Vec<T>
+    where
+        T: for<'a> FromSql<'a>,
+    {
+        const COLUMN_COUNT: usize = 1;
+
+        fn from_row<R>(row: &R) -> Result<Self, Error>
+        where
+            R: Row,
+        {
+            if row.len() != Self::COLUMN_COUNT {
+                Err(Error::ColumnCount {
+                    expected: Self::COLUMN_COUNT,
+                    found: row.len(),
+                    columns: Columns::new(row.columns()),
+                })
+            } else {
+                row.try_get(0)
+            }
+        }
+    }
 
     impl<T> FromSqlRow for Option<T>
     where
@@ -477,6 +1873,40 @@ mod from_row_sql_impls {
                 Err(error) => Err(error),
             }
         }
+
+        fn __is_none<R>(row: &R, when: NoneWhen) -> Result<bool, Error>
+        where
+            R: Row,
+        {
+            T::__is_none(row, when)
+        }
+
+        fn __from_flattened<R>(row: &R) -> Result<Self, Error>
+        where
+            R: Row,
+        {
+            // A flattened value whose columns are all `NULL` is missing, even if it could be
+            // extracted (because all of its fields are optional, for example).
+            if T::COLUMN_COUNT > 0 && T::__is_none(row, NoneWhen::AllNull)? {
+                return Ok(None);
+            }
+            Self::from_row(row)
+        }
+
+        fn __column_names() -> Vec<String> {
+            T::__column_names()
+        }
+
+        fn layout() -> Option<RowLayout> {
+            T::layout()
+        }
+
+        fn __check_types<R>(row: &R, mismatches: &mut Vec<TypeMismatch>) -> Result<(), Error>
+        where
+            R: Row,
+        {
+            T::__check_types(row, mismatches)
+        }
     }
 
     impl<T, E> FromSqlRow for Result<T, E>
@@ -495,6 +1925,28 @@ mod from_row_sql_impls {
                 Err(error) => Ok(Err(E::from(error))),
             }
         }
+
+        fn __is_none<R>(row: &R, when: NoneWhen) -> Result<bool, Error>
+        where
+            R: Row,
+        {
+            T::__is_none(row, when)
+        }
+
+        fn __column_names() -> Vec<String> {
+            T::__column_names()
+        }
+
+        fn layout() -> Option<RowLayout> {
+            T::layout()
+        }
+
+        fn __check_types<R>(row: &R, mismatches: &mut Vec<TypeMismatch>) -> Result<(), Error>
+        where
+            R: Row,
+        {
+            T::__check_types(row, mismatches)
+        }
     }
 
     macro_rules! impl_from_row_for_wrapper {
@@ -512,6 +1964,31 @@ mod from_row_sql_impls {
                     let value = T::from_row(row)?;
                     Ok($constructor(value))
                 }
+
+                fn __is_none<R>(row: &R, when: NoneWhen) -> Result<bool, Error>
+                where
+                    R: Row,
+                {
+                    T::__is_none(row, when)
+                }
+
+                fn __column_names() -> Vec<String> {
+                    T::__column_names()
+                }
+
+                fn layout() -> Option<RowLayout> {
+                    T::layout()
+                }
+
+                fn __check_types<R>(
+                    row: &R,
+                    mismatches: &mut Vec<TypeMismatch>,
+                ) -> Result<(), Error>
+                where
+                    R: Row,
+                {
+                    T::__check_types(row, mismatches)
+                }
             }
         };
     }
@@ -550,6 +2027,26 @@ mod tests {
         })
     }
 
+    #[test]
+    fn nullable_defaults() {
+        let value = Nullable::<i32>::from_sql_null(&Type::INT4).unwrap();
+        assert_eq!(value.into_inner(), 0);
+
+        let value = Nullable::<i32>::from_sql(&Type::INT4, &[0, 0, 0, 7]).unwrap();
+        assert_eq!(*value, 7);
+    }
+
+    #[test]
+    fn display_columns() {
+        let columns = Columns {
+            columns: vec![
+                ("id".to_owned(), Type::INT4),
+                ("name".to_owned(), Type::TEXT),
+            ],
+        };
+        assert_eq!(columns.to_string(), "`id` (int4), `name` (text)");
+    }
+
     #[test]
     fn split_columns_many_no_excess() {
         let partitions = split_chars("abcabdab", "aaa").collect::<Vec<_>>();
@@ -562,6 +2059,12 @@ mod tests {
         assert_eq!(partitions, vec![0..2, 2..5, 5..8, 8..10,])
     }
 
+    #[test]
+    fn split_columns_many_trailing_split() {
+        let partitions = split_chars("abcab", "aab").collect::<Vec<_>>();
+        assert_eq!(partitions, vec![0..0, 0..3, 3..4, 4..5])
+    }
+
     #[test]
     fn split_columns_many_too_many_splits() {
         let partitions = split_chars_fallible("abcabc", "aaa").collect::<Vec<_>>();
@@ -577,4 +2080,18 @@ mod tests {
             ]
         )
     }
+
+    #[test]
+    fn start_partition() {
+        let start = |names: &[&str], split| {
+            let mut names = names.iter().map(|name| name.to_string()).collect();
+            __start_partition(&mut names, split);
+            names
+        };
+
+        assert_eq!(start(&["id", "name"], "id"), vec!["id", "name"]);
+        assert_eq!(start(&["?", "?"], "id"), vec!["id", "?"]);
+        assert_eq!(start(&["name"], "id"), vec!["id", "name"]);
+        assert_eq!(start(&[], "id"), vec!["id"]);
+    }
 }
@@ -0,0 +1,336 @@
+//! Reusable fragments for dynamic `WHERE` clauses.
+//!
+//! Each function returns a [`Filter`]: a piece of SQL along with the bindings it refers to.
+//! Filters are combined with [`Filter::and`], [`Filter::or`], [`all`] and [`any`], and the result
+//! is spliced into a query with `query_dyn!`, using the `..bindings` syntax:
+//!
+//! ```
+//! # use postgres_query::{filters, query_dyn, Result};
+//! # fn foo() -> Result<()> {
+//! // Filters we got from the user.
+//! let min_age: Option<i32> = Some(18);
+//! let name: Option<&str> = None;
+//!
+//! let mut conditions = Vec::new();
+//! if let Some(min_age) = &min_age {
+//!     conditions.push(filters::between("age", min_age, &65));
+//! }
+//! if let Some(name) = &name {
+//!     conditions.push(filters::ilike("name", name));
+//! }
+//!
+//! let filter = filters::all(conditions);
+//! let sql = format!("SELECT * FROM people WHERE {}", filter.sql());
+//! let query = query_dyn!(&sql, ..filter.bindings())?;
+//!
+//! assert_eq!(query.sql(), "SELECT * FROM people WHERE (age BETWEEN $1 AND $2)");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Column names are inserted verbatim into the SQL. When filters are combined, their bindings are
+//! renamed so that they never clash, even if the same filter is used twice.
+//!
+//! Search terms entered by users should be bound to `LIKE` and `ILIKE` as a [`SafePattern`], so
+//! that any `%` or `_` they contain is matched literally rather than as a wildcard.
+//!
+//! [`Filter`]: struct.Filter.html
+//! [`Filter::and`]: struct.Filter.html#method.and
+//! [`Filter::or`]: struct.Filter.html#method.or
+//! [`all`]: fn.all.html
+//! [`any`]: fn.any.html
+//! [`SafePattern`]: struct.SafePattern.html
+
+use crate::parse::{self, Token};
+use crate::Parameter;
+use bytes::BytesMut;
+use postgres_types::{to_sql_checked, IsNull, ToSql, Type};
+use std::error::Error as StdError;
+
+/// A condition, given as SQL with named bindings (`$name`) and the values bound to them.
+#[derive(Debug, Clone)]
+pub struct Filter<'a> {
+    sql: String,
+    bindings: Vec<(String, Parameter<'a>)>,
+}
+
+impl<'a> Filter<'a> {
+    /// A filter from arbitrary SQL, which may refer to the given bindings.
+    ///
+    /// ```
+    /// # use postgres_query::filters::Filter;
+    /// let adults = Filter::new("age >= $age", &[("age", &18)]);
+    /// ```
+    pub fn new(sql: impl Into<String>, bindings: &[(&str, Parameter<'a>)]) -> Filter<'a> {
+        Filter {
+            sql: sql.into(),
+            bindings: bindings
+                .iter()
+                .map(|&(name, value)| (name.to_owned(), value))
+                .collect(),
+        }
+    }
+
+    /// The SQL of the filter.
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// The bindings of the filter, in the form expected by `query_dyn!` (`..filter.bindings()`).
+    pub fn bindings(&self) -> impl Iterator<Item = (&str, Parameter<'a>)> + '_ {
+        self.bindings
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+    }
+
+    /// Matches the rows matched by both filters.
+    pub fn and(self, other: Filter<'a>) -> Filter<'a> {
+        combine(vec![self, other], " AND ", "TRUE")
+    }
+
+    /// Matches the rows matched by either filter.
+    pub fn or(self, other: Filter<'a>) -> Filter<'a> {
+        combine(vec![self, other], " OR ", "FALSE")
+    }
+}
+
+/// Matches the rows matched by every filter, or all rows if there are none.
+pub fn all<'a>(filters: impl IntoIterator<Item = Filter<'a>>) -> Filter<'a> {
+    combine(filters.into_iter().collect(), " AND ", "TRUE")
+}
+
+/// Matches the rows matched by any of the filters, or no rows if there are none.
+pub fn any<'a>(filters: impl IntoIterator<Item = Filter<'a>>) -> Filter<'a> {
+    combine(filters.into_iter().collect(), " OR ", "FALSE")
+}
+
+/// `column BETWEEN low AND high`, which includes both bounds.
+pub fn between<'a>(column: &str, low: Parameter<'a>, high: Parameter<'a>) -> Filter<'a> {
+    Filter::new(
+        format!("{} BETWEEN $low AND $high", column),
+        &[("low", low), ("high", high)],
+    )
+}
+
+/// `column ILIKE pattern`, a case-insensitive match against a pattern with the wildcards `%` and
+/// `_`. Use a [`SafePattern`] to match user input.
+///
+/// [`SafePattern`]: struct.SafePattern.html
+pub fn ilike<'a>(column: &str, pattern: Parameter<'a>) -> Filter<'a> {
+    Filter::new(
+        format!("{} ILIKE $pattern", column),
+        &[("pattern", pattern)],
+    )
+}
+
+/// `column = ANY(values)`, where `values` is an array (such as a `Vec` or a slice).
+///
+/// Unlike `IN (...)`, this takes a single parameter regardless of the number of values, and
+/// matches nothing if there are none.
+pub fn in_list<'a>(column: &str, values: Parameter<'a>) -> Filter<'a> {
+    Filter::new(format!("{} = ANY($values)", column), &[("values", values)])
+}
+
+/// `column >= from AND column < until`, where either bound may be left out.
+///
+/// The range is half-open, so consecutive ranges (such as one per day) do not overlap. Matches all
+/// rows if both bounds are left out.
+pub fn date_range<'a>(
+    column: &str,
+    from: Option<Parameter<'a>>,
+    until: Option<Parameter<'a>>,
+) -> Filter<'a> {
+    let from = from.map(|from| Filter::new(format!("{} >= $from", column), &[("from", from)]));
+    let until = until.map(|until| Filter::new(format!("{} < $until", column), &[("until", until)]));
+    all(from.into_iter().chain(until))
+}
+
+/// A pattern for `LIKE` and `ILIKE` built from arbitrary text, such as a search term entered by a
+/// user.
+///
+/// The wildcards `%` and `_` (and the escape character `\`) in the text are escaped, so that they
+/// only match themselves, and the wildcards of the pattern are added explicitly:
+///
+/// ```
+/// # use postgres_query::{filters::{self, SafePattern}, query};
+/// let search = "100%_sure";
+///
+/// let pattern = SafePattern::contains(search);
+/// assert_eq!(pattern.as_str(), r"%100\%\_sure%");
+///
+/// let query = query!("SELECT * FROM posts WHERE title ILIKE $pattern", pattern);
+/// let filter = filters::ilike("title", &pattern);
+/// ```
+///
+/// The pattern relies on `\` being the escape character, which is the default unless the query
+/// specifies another one with `ESCAPE`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SafePattern(String);
+
+impl SafePattern {
+    /// Matches exactly `text`, like `=` but subject to the case-insensitivity of `ILIKE`.
+    pub fn exact(text: &str) -> SafePattern {
+        SafePattern::build("", text, "")
+    }
+
+    /// Matches values which contain `text`.
+    pub fn contains(text: &str) -> SafePattern {
+        SafePattern::build("%", text, "%")
+    }
+
+    /// Matches values which start with `text`.
+    pub fn starts_with(text: &str) -> SafePattern {
+        SafePattern::build("", text, "%")
+    }
+
+    /// Matches values which end with `text`.
+    pub fn ends_with(text: &str) -> SafePattern {
+        SafePattern::build("%", text, "")
+    }
+
+    /// The escaped pattern, as it is bound to the query.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn build(before: &str, text: &str, after: &str) -> SafePattern {
+        let mut pattern = String::with_capacity(before.len() + text.len() + after.len());
+        pattern.push_str(before);
+        for ch in text.chars() {
+            if matches!(ch, '%' | '_' | '\\') {
+                pattern.push('\\');
+            }
+            pattern.push(ch);
+        }
+        pattern.push_str(after);
+        SafePattern(pattern)
+    }
+}
+
+impl ToSql for SafePattern {
+    fn to_sql(
+        &self,
+        ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn StdError + Sync + Send>> {
+        self.0.to_sql(ty, out)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        <String as ToSql>::accepts(ty)
+    }
+
+    to_sql_checked!();
+}
+
+/// Join the filters with `operator`, renaming their bindings so that they do not clash.
+fn combine<'a>(filters: Vec<Filter<'a>>, operator: &str, empty: &str) -> Filter<'a> {
+    if filters.is_empty() {
+        return Filter::new(empty, &[]);
+    }
+
+    let mut sql = String::new();
+    let mut bindings = Vec::new();
+
+    for (i, filter) in filters.into_iter().enumerate() {
+        let prefix = format!("f{}_", i);
+        if i > 0 {
+            sql.push_str(operator);
+        }
+        sql.push('(');
+        sql.push_str(&prefix_bindings(&filter.sql, &prefix));
+        sql.push(')');
+
+        bindings.extend(
+            filter
+                .bindings
+                .into_iter()
+                .map(|(name, value)| (format!("{}{}", prefix, name), value)),
+        );
+    }
+
+    Filter { sql, bindings }
+}
+
+/// Prefix the name of every binding (`$name`) in `sql`, leaving literals and comments untouched.
+fn prefix_bindings(sql: &str, prefix: &str) -> String {
+    let mut prefixed = String::with_capacity(sql.len());
+
+    for (_, token) in parse::named_tokens(sql) {
+        match token {
+            Token::Binding(binding) => {
+                prefixed.push('$');
+                prefixed.push_str(prefix);
+                prefixed.push_str(&binding[1..]);
+            }
+            token => prefixed.push_str(token.text()),
+        }
+    }
+
+    prefixed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names<'a>(filter: &'a Filter) -> Vec<&'a str> {
+        filter.bindings().map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    fn combined_bindings_do_not_clash() {
+        let filter = between("age", &18, &30).or(between("age", &60, &70));
+
+        assert_eq!(
+            filter.sql(),
+            "(age BETWEEN $f0_low AND $f0_high) OR (age BETWEEN $f1_low AND $f1_high)"
+        );
+        assert_eq!(names(&filter), ["f0_low", "f0_high", "f1_low", "f1_high"]);
+    }
+
+    #[test]
+    fn nested_filters() {
+        let ids = vec![1, 2];
+        let filter = all(vec![
+            ilike("name", &"john%"),
+            any(vec![in_list("id", &ids), Filter::new("$$ = '$'", &[])]),
+        ]);
+
+        assert_eq!(
+            filter.sql(),
+            "(name ILIKE $f0_pattern) AND ((id = ANY($f1_f0_values)) OR ($$ = '$'))"
+        );
+        assert_eq!(names(&filter), ["f0_pattern", "f1_f0_values"]);
+    }
+
+    #[test]
+    fn literals_and_comments_are_not_prefixed() {
+        let note = Filter::new("note <> '$draft' /* $skip */ AND $1 = $$", &[]);
+        let filter = between("age", &18, &30).and(note);
+
+        assert_eq!(
+            filter.sql(),
+            "(age BETWEEN $f0_low AND $f0_high) AND (note <> '$draft' /* $skip */ AND $1 = $$)"
+        );
+    }
+
+    #[test]
+    fn escaped_patterns() {
+        assert_eq!(SafePattern::exact("a_b").as_str(), r"a\_b");
+        assert_eq!(SafePattern::starts_with(r"C:\").as_str(), r"C:\\%");
+        assert_eq!(SafePattern::ends_with("50%").as_str(), r"%50\%");
+        assert_eq!(SafePattern::contains("").as_str(), "%%");
+    }
+
+    #[test]
+    fn open_date_ranges() {
+        assert_eq!(date_range("day", None, None).sql(), "TRUE");
+        assert_eq!(date_range("day", Some(&1), None).sql(), "(day >= $f0_from)");
+        assert_eq!(
+            date_range("day", Some(&1), Some(&2)).sql(),
+            "(day >= $f0_from) AND (day < $f1_until)"
+        );
+        assert_eq!(any(Vec::new()).sql(), "FALSE");
+    }
+}
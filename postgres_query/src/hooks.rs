@@ -0,0 +1,93 @@
+//! Global lists of hooks, such as those of the `metrics` module.
+
+use std::fmt::{self, Debug};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+/// Identifies registered hooks across all lists.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A list of hooks, each of which may be removed on its own.
+pub(crate) struct Hooks<H: ?Sized> {
+    hooks: RwLock<Vec<(usize, Box<H>)>>,
+}
+
+impl<H: ?Sized> Hooks<H> {
+    pub(crate) const fn new() -> Hooks<H> {
+        Hooks {
+            hooks: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Call `f` with every registered hook.
+    pub(crate) fn for_each(&self, mut f: impl FnMut(&H)) {
+        let hooks = self.hooks.read().unwrap_or_else(|error| error.into_inner());
+        for (_, hook) in hooks.iter() {
+            f(hook);
+        }
+    }
+}
+
+impl<H> Hooks<H>
+where
+    H: ?Sized + Send + Sync + 'static,
+{
+    pub(crate) fn register(&'static self, hook: Box<H>) -> Registration {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let mut hooks = self
+            .hooks
+            .write()
+            .unwrap_or_else(|error| error.into_inner());
+        hooks.push((id, hook));
+        Registration { id, hooks: self }
+    }
+}
+
+trait Remove: Sync {
+    fn remove(&self, id: usize);
+}
+
+impl<H> Remove for Hooks<H>
+where
+    H: ?Sized + Send + Sync,
+{
+    fn remove(&self, id: usize) {
+        let mut hooks = self
+            .hooks
+            .write()
+            .unwrap_or_else(|error| error.into_inner());
+        hooks.retain(|(hook, _)| *hook != id);
+    }
+}
+
+/// A registered hook, which is removed once this is dropped.
+///
+/// Use [`keep`] to leave the hook registered for the rest of the program.
+///
+/// [`keep`]: #method.keep
+#[must_use = "the hook is removed as soon as the registration is dropped"]
+pub struct Registration {
+    id: usize,
+    hooks: &'static dyn Remove,
+}
+
+impl Registration {
+    /// Leave the hook registered for the rest of the program.
+    pub fn keep(self) {
+        std::mem::forget(self)
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.hooks.remove(self.id);
+    }
+}
+
+impl Debug for Registration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Registration")
+            .field("id", &self.id)
+            .finish()
+    }
+}
@@ -0,0 +1,205 @@
+//! Execute SQL generated by query builders.
+//!
+//! Query builders usually produce SQL with positional parameters (`$1`, `$2`, ...) along with the
+//! values of those parameters. Since a [`Query`] only borrows its parameters, the values are first
+//! collected into a [`Built`], which owns them, and from which queries may then be borrowed. This
+//! allows builder-generated SQL to be used with the extraction and caching of this crate.
+//!
+//! ```
+//! # use tokio_postgres::Client;
+//! # use postgres_query::{interop::Built, Result};
+//! # async fn foo() -> Result<()> {
+//! # let client: Client = unimplemented!();
+//! // The output of some query builder.
+//! let (sql, values) = (
+//!     "SELECT name FROM people WHERE age > $1 AND city = $2".to_owned(),
+//!     vec![Box::new(30) as Box<_>, Box::new("Stockholm") as Box<_>],
+//! );
+//!
+//! let built = Built::from_boxed(sql, values);
+//! let names: Vec<(String,)> = built.query().fetch(&client).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! With the `with-sea-query-0_32` feature, the output of `sea-query` may be converted directly,
+//! mapping its values to parameters the same way as its own Postgres driver:
+//!
+//! ```
+//! # #[cfg(feature = "with-sea-query-0_32")]
+//! # fn foo() -> postgres_query::Result<()> {
+//! # use postgres_query::interop::Built;
+//! # use std::convert::TryFrom;
+//! use sea_query::{Alias, Expr, PostgresQueryBuilder, Query};
+//!
+//! let select = Query::select()
+//!     .column(Alias::new("name"))
+//!     .from(Alias::new("people"))
+//!     .and_where(Expr::col(Alias::new("age")).gt(30))
+//!     .to_owned();
+//!
+//! let built = Built::try_from(select.build(PostgresQueryBuilder))?;
+//! assert_eq!(built.values().len(), 1);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! With the `with-sql_builder-3` feature, a `SqlBuilder` may be converted as well. Since
+//! `sql_builder` writes values into the SQL itself, any parameters left in it are bound with
+//! [`Built::bind`].
+//!
+//! [`Query`]: ../struct.Query.html
+//! [`Built`]: struct.Built.html
+//! [`Built::bind`]: struct.Built.html#method.bind
+
+use crate::{Parameter, Query};
+use postgres_types::ToSql;
+
+#[cfg(any(feature = "with-sea-query-0_32", feature = "with-sql_builder-3"))]
+use {std::convert::TryFrom, thiserror::Error};
+
+/// An error that may arise when converting the output of a query builder.
+#[cfg(any(feature = "with-sea-query-0_32", feature = "with-sql_builder-3"))]
+#[derive(Debug, Error)]
+pub enum Error {
+    #[cfg(feature = "with-sea-query-0_32")]
+    #[error("cannot use the `sea-query` value `{value}` as a parameter")]
+    UnsupportedValue { value: String },
+
+    #[cfg(feature = "with-sql_builder-3")]
+    #[error("failed to build the query")]
+    Build(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// A value which may be used as the parameter of a query, owned by a [`Built`].
+///
+/// [`Built`]: struct.Built.html
+pub type Value = Box<dyn ToSql + Send + Sync>;
+
+/// The SQL and parameter values produced by a query builder.
+#[derive(Debug, Default)]
+pub struct Built {
+    sql: String,
+    values: Vec<Value>,
+}
+
+impl Built {
+    /// SQL with parameters of a single type.
+    pub fn new<T>(sql: impl Into<String>, values: impl IntoIterator<Item = T>) -> Built
+    where
+        T: ToSql + Send + Sync + 'static,
+    {
+        Built {
+            sql: sql.into(),
+            values: values
+                .into_iter()
+                .map(|value| Box::new(value) as Value)
+                .collect(),
+        }
+    }
+
+    /// SQL with parameters of any type.
+    pub fn from_boxed(sql: impl Into<String>, values: impl IntoIterator<Item = Value>) -> Built {
+        Built {
+            sql: sql.into(),
+            values: values.into_iter().collect(),
+        }
+    }
+
+    /// Add the value of the next parameter.
+    pub fn bind<T>(mut self, value: T) -> Built
+    where
+        T: ToSql + Send + Sync + 'static,
+    {
+        self.values.push(Box::new(value));
+        self
+    }
+
+    /// The SQL of the query.
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// The values of the parameters, in order.
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    /// A query which borrows the parameters of this one.
+    pub fn query(&self) -> Query<'_> {
+        let parameters = self
+            .values
+            .iter()
+            .map(|value| &**value as Parameter)
+            .collect();
+        Query::new(self.sql.clone(), parameters)
+    }
+}
+
+impl<T> From<(String, Vec<T>)> for Built
+where
+    T: ToSql + Send + Sync + 'static,
+{
+    fn from((sql, values): (String, Vec<T>)) -> Built {
+        Built::new(sql, values)
+    }
+}
+
+/// Values of the types behind features of `sea-query` (such as `with-json`) are not supported, nor
+/// are `BigUnsigned` values which do not fit in a `BIGINT`.
+#[cfg(feature = "with-sea-query-0_32")]
+impl TryFrom<(String, sea_query::Values)> for Built {
+    type Error = Error;
+
+    fn try_from((sql, values): (String, sea_query::Values)) -> Result<Built, Error> {
+        let values = values
+            .into_iter()
+            .map(sea_query_value)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Built::from_boxed(sql, values))
+    }
+}
+
+/// Convert a value the same way as `sea-query`'s Postgres driver: unsigned integers are widened to
+/// the next larger signed integer, and characters are sent as text.
+#[cfg(feature = "with-sea-query-0_32")]
+fn sea_query_value(value: sea_query::Value) -> Result<Value, Error> {
+    use sea_query::Value as V;
+
+    let value: Value = match value {
+        V::Bool(value) => Box::new(value),
+        V::TinyInt(value) => Box::new(value.map(i16::from)),
+        V::SmallInt(value) => Box::new(value),
+        V::Int(value) => Box::new(value),
+        V::BigInt(value) => Box::new(value),
+        V::TinyUnsigned(value) => Box::new(value.map(i16::from)),
+        V::SmallUnsigned(value) => Box::new(value.map(i32::from)),
+        V::Unsigned(value) => Box::new(value.map(i64::from)),
+        // `None` compares less than any value, so `NULL` is accepted as well.
+        V::BigUnsigned(value) if value <= Some(i64::MAX as u64) => {
+            Box::new(value.map(|value| value as i64))
+        }
+        V::Float(value) => Box::new(value),
+        V::Double(value) => Box::new(value),
+        V::String(value) => Box::new(value.map(|value| *value)),
+        V::Char(value) => Box::new(value.map(String::from)),
+        V::Bytes(value) => Box::new(value.map(|value| *value)),
+        value => {
+            return Err(Error::UnsupportedValue {
+                value: format!("{:?}", value),
+            })
+        }
+    };
+
+    Ok(value)
+}
+
+#[cfg(feature = "with-sql_builder-3")]
+impl TryFrom<&sql_builder::SqlBuilder> for Built {
+    type Error = Error;
+
+    fn try_from(builder: &sql_builder::SqlBuilder) -> Result<Built, Error> {
+        let sql = builder.sql().map_err(|error| Error::Build(error.into()))?;
+        Ok(Built::from_boxed(sql, Vec::new()))
+    }
+}
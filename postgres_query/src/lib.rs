@@ -66,6 +66,13 @@
 //! );
 //! ```
 //!
+//! Since the numbers are assigned automatically, the query itself may not contain positional
+//! parameters (`$1`, `$2`, etc.), and using one is an error. Use `Query::new` for SQL that already
+//! uses positional parameters.
+//!
+//! When the parameters are the fields of a struct (such as a form), [`query_with!`] binds each
+//! placeholder to the field with the same name instead.
+//!
 //!
 //! ## Dynamic Queries
 //!
@@ -118,9 +125,9 @@
 //! trait for new `struct`s is to use the included [`derive(FromSqlRow)`] macro.
 //!
 //! - If used on a tuple struct, values will be extracted from the corresponding columns based on
-//! their position in the tuple.
+//!   their position in the tuple.
 //! - If used on a stuct with named fields, values will be extracted from the column with the same
-//! name as the field.
+//!   name as the field.
 //!
 //! ```
 //! # use postgres_query::*;
@@ -302,31 +309,75 @@
 //! [`Query`]: struct.Query.html
 //! [`query!`]: macro.query.html
 //! [`query_dyn!`]: macro.query_dyn.html
+//! [`query_with!`]: macro.query_with.html
 //! [`FromSqlRow`]: extract/trait.FromSqlRow.html
 //! [`derive(FromSqlRow)`]: derive.FromSqlRow.html
 //! [`Caching`]: client/struct.Caching.html
 
+pub mod changes;
 pub mod client;
+#[cfg(feature = "codegen")]
+pub mod codegen;
+pub mod conflict;
+#[cfg(feature = "connect")]
+pub mod connect;
+pub mod copy;
+pub mod cte;
+#[cfg(feature = "cursor")]
+pub mod cursor;
 pub mod execute;
+#[cfg(feature = "explain")]
+pub mod explain;
 pub mod extract;
+pub mod filters;
+pub mod interop;
+#[cfg(feature = "lint")]
+pub mod lint;
+pub mod metrics;
+pub mod notify;
+pub mod order;
+pub mod outbox;
+pub mod patch;
+#[cfg(feature = "profile")]
+pub mod profile;
+pub mod queryable;
+pub mod spawn;
+#[cfg(feature = "with-http-1")]
+pub mod status;
+pub mod stream;
+#[cfg(feature = "sync")]
+pub mod sync;
+#[cfg(feature = "test-db")]
+pub mod test_db;
+pub mod testing;
+pub mod types;
 
 mod error;
+mod hooks;
 mod parse;
 
+use crate::execute::ExecuteOptions;
 use postgres_types::ToSql;
 use proc_macro_hack::proc_macro_hack;
+use std::borrow::Cow;
 use std::ops::Deref;
+use std::sync::OnceLock;
 
 pub use crate::client::Caching;
+#[cfg(feature = "connect")]
+pub use crate::connect::{connect_with_retry, RetryPolicy};
+pub use crate::copy::ToSqlRow;
 pub use crate::error::{Error, Result};
 pub use crate::extract::FromSqlRow;
+pub use crate::patch::Patch;
+pub use crate::queryable::Queryable;
 
 /// Extract values from a row.
 ///
 /// - If used on a tuple struct, values will be extracted from the corresponding columns based on
-/// their position in the tuple.
+///   their position in the tuple.
 /// - If used on a stuct with named fields, values will be extracted from the column with the same
-/// name as the field.
+///   name as the field.
 ///
 /// # Example
 ///
@@ -352,15 +403,24 @@ pub use crate::extract::FromSqlRow;
 /// - [`#[row(split)]`](#rowsplit)
 /// - [`#[row(group)]`](#rowgroup)
 /// - [`#[row(hash)]`](#rowhash)
+/// - [`#[row(positional)]`](#rowpositional)
+/// - [`#[row(test_layout)]`](#rowtest_layout)
+/// - [`#[row(check_types)]`](#rowcheck_types)
+/// - [`#[row(columns)]`](#rowcolumns)
 ///
 /// and those which are placed on the container's fields:
 ///
 /// - [`#[row(rename = "...")]`](#rowrename--)
 /// - [`#[row(flatten)]`](#rowflatten)
+/// - [`#[row(parse)]`](#rowparse)
 /// - [`#[row(stride = N)]`](#rowstride--n)
+/// - [`#[row(repeat = N)]`](#rowrepeat--n)
 /// - [`#[row(split = "...")]`](#rowsplit--)
 /// - [`#[row(key)]`](#rowkey)
 /// - [`#[row(merge)]`](#rowmerge)
+/// - [`#[row(none_when = "...")]`](#rownone_when--)
+/// - [`#[row(fallback)]`](#rowfallback)
+/// - [`#[row(rename_prefix = "...")]`](#rowrename_prefix--)
 ///
 ///
 /// ## Container attributes
@@ -551,6 +611,137 @@ pub use crate::extract::FromSqlRow;
 /// # }
 /// ```
 ///
+///
+/// ### `#[row(positional)]`
+///
+/// Extract the fields of a struct with named fields by their position, like a tuple struct,
+/// instead of by the names of the columns. This is useful when the names of the columns don't match
+/// the fields, for example when calling a function which returns a `record`, and renaming every
+/// field would be noisy:
+///
+/// ```
+/// # use postgres_query::{FromSqlRow, query, Result};
+/// # use tokio_postgres::Client;
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// #[derive(FromSqlRow)]
+/// #[row(positional)]
+/// struct Stats {
+///     count: i64,
+///     average: f64,
+/// }
+///
+/// let stats = query!("SELECT * FROM compute_stats() AS (n bigint, avg double precision)")
+///     .fetch_one::<Stats, _>(&client)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Fields with the `flatten` attribute are still extracted by name. The `rename` attribute is not
+/// available, since names are not used.
+///
+/// ### `#[row(test_layout)]`
+///
+/// Generate a `#[cfg(test)]` unit test, named `__row_test_layout_<Container>`, which lays out a
+/// synthetic row from the names of the container's fields and checks it without a database:
+///
+/// - without partitioning, the number of columns must match `COLUMN_COUNT`,
+/// - with `#[row(exact)]`, every partition must fit within its `stride` (or match the
+///   `COLUMN_COUNT` of the flattened field),
+/// - with `#[row(split)]`, the split points must partition the row exactly as the fields expect.
+///
+/// Fields extracted by position are represented by a `?` column. Since the test is a regular
+/// `#[test]` function, the container has to be declared at module level for the test to be found.
+///
+/// ```
+/// # use postgres_query::FromSqlRow;
+/// #[derive(FromSqlRow)]
+/// #[row(split, test_layout)]
+/// struct Family {
+///     // The synthetic row is `id, name, id, name`, which is split before each `id`. Without
+///     // the split on `parent`, it would get an empty partition, and `cargo test` would fail.
+///     #[row(flatten, split = "id")]
+///     parent: Person,
+///     #[row(flatten, split = "id")]
+///     child: Person,
+/// }
+///
+/// #[derive(FromSqlRow)]
+/// struct Person {
+///     id: i32,
+///     name: String,
+/// }
+/// ```
+///
+/// ### `#[row(check_types)]`
+///
+/// Check the types of all columns before extracting any fields. If any column has a type which
+/// cannot be extracted as its field, a single [`extract::Error::TypeMismatch`] listing every such
+/// field is returned, instead of an error for the first field that fails. The types of flattened
+/// fields are checked if they also derive `FromSqlRow`. The same check is available for any row
+/// through [`extract::check_types`].
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{query, FromSqlRow, Result};
+/// #[derive(FromSqlRow)]
+/// #[row(check_types)]
+/// struct Person {
+///     id: i32,
+///     name: String,
+/// }
+///
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// // error: mismatched column types: field `id` of type `i32` cannot be extracted from column
+/// // `id` (text); field `name` of type `alloc::string::String` cannot be extracted from column
+/// // `name` (int4)
+/// let person = query!("SELECT 'one' as id, 1 as name")
+///     .fetch_one::<Person, _>(&client)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`extract::Error::TypeMismatch`]: extract/enum.Error.html#variant.TypeMismatch
+/// [`extract::check_types`]: extract/fn.check_types.html
+///
+/// ### `#[row(columns)]`
+///
+/// Add a constant `COLUMNS` to the struct, with the names of the columns it is extracted from, in
+/// order. Renames are applied and flattened fields are expanded, which requires those to have
+/// `#[row(columns)]` as well. The constant is also available through [`extract::StaticColumns`].
+///
+/// This makes it possible to check that a query selects the columns a struct expects, or to build
+/// the list of columns to select:
+///
+/// ```
+/// # use postgres_query::FromSqlRow;
+/// #[derive(FromSqlRow)]
+/// #[row(columns)]
+/// struct Address {
+///     street: String,
+///     city: String,
+/// }
+///
+/// #[derive(FromSqlRow)]
+/// #[row(columns)]
+/// struct Person {
+///     #[row(rename = "firstName")]
+///     name: String,
+///     #[row(flatten)]
+///     address: Address,
+/// }
+///
+/// assert_eq!(Person::COLUMNS, ["firstName", "street", "city"]);
+/// ```
+///
+/// Only structs with named fields, which are not partitioned with `exact` or `split`, have a list
+/// of columns known at compile-time.
+///
+/// [`extract::StaticColumns`]: extract/trait.StaticColumns.html
+///
 /// ## Field attributes
 ///
 /// These attributes are put on the fields of a container.
@@ -605,6 +796,28 @@ pub use crate::extract::FromSqlRow;
 /// # }
 /// ```
 ///
+/// ### `#[row(parse)]`
+///
+/// Extract the column as text and parse it into the field's type using [`FromStr`]. This is useful
+/// when values are stored as text in the database, such as numbers in a legacy schema:
+///
+/// ```
+/// # use postgres_query::FromSqlRow;
+/// # use std::net::IpAddr;
+/// #[derive(FromSqlRow)]
+/// struct Device {
+///     name: String,
+///     #[row(parse)]
+///     address: IpAddr,
+///     #[row(parse)]
+///     weight: f64,
+/// }
+/// ```
+///
+/// The column may not be `NULL`.
+///
+/// [`FromStr`]: https://doc.rust-lang.org/std/str/trait.FromStr.html
+///
 /// ### `#[row(stride = N)]`
 ///
 /// Puts this field into a partition with exactly `N` columns. Only available when using the
@@ -649,6 +862,50 @@ pub use crate::extract::FromSqlRow;
 /// # }
 /// ```
 ///
+/// ### `#[row(repeat = N)]`
+///
+/// Extracts a `Vec<T>` or `[T; N]` from `N` consecutive groups of columns, each matching a `T`,
+/// such as the columns produced by joining the same table multiple times. Only available when
+/// using the `#[row(exact)]` attribute on the container.
+///
+/// Each group spans `T::COLUMN_COUNT` columns, and columns are matched by name within each group:
+///
+/// ```
+/// # use postgres_query::{FromSqlRow, query, Result};
+/// # use tokio_postgres::Client;
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// #[derive(Debug, FromSqlRow)]
+/// struct Person {
+///     id: i32,
+///     name: String,
+/// }
+///
+/// #[derive(Debug, FromSqlRow)]
+/// #[row(exact)]
+/// struct Race {
+///     id: i32,
+///     // Matches the next 6 columns
+///     #[row(repeat = 3)]
+///     podium: [Person; 3],
+/// }
+///
+/// let race = query!(
+///     "SELECT
+///         7 as id,
+///         1 as id, 'Bob' as name,
+///         2 as id, 'Ike' as name,
+///         3 as id, 'Amy' as name"
+///     )
+///     .fetch_one::<Race, _>(&client)
+///     .await?;
+///
+/// assert_eq!(race.podium[0].name, "Bob");
+/// assert_eq!(race.podium[2].name, "Amy");
+/// # Ok(())
+/// # }
+/// ```
+///
 /// ### `#[row(split = "...")]`
 ///
 /// Introduce an additional [split](extract/fn.split_columns_many.html#split-points) right
@@ -730,6 +987,33 @@ pub use crate::extract::FromSqlRow;
 /// have the `#[row(merge)]` attribute. Multiple `key` fields will be treated as a tuple in
 /// comparisons.
 ///
+/// With `#[row(hash)]`, key fields have to implement `Clone`, `Hash`, `Eq` and `Send`. If a field
+/// does not, the key can instead be derived from an expression with `#[row(key, by = "...")]`,
+/// which may refer to any of the container's fields:
+///
+/// ```
+/// # use postgres_query::FromSqlRow;
+/// # #[derive(FromSqlRow)] struct Book { title: String }
+/// # use postgres_types::{accepts, FromSql, Type};
+/// # struct AuthorId(i32);
+/// # impl<'a> FromSql<'a> for AuthorId {
+/// #     fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+/// #         i32::from_sql(ty, raw).map(AuthorId)
+/// #     }
+/// #     accepts!(INT4);
+/// # }
+/// #[derive(FromSqlRow)]
+/// #[row(hash)]
+/// struct Author {
+///     // `AuthorId` does not implement `Hash`, so use the inner `i32` instead
+///     #[row(key, by = "id.0")]
+///     id: AuthorId,
+///
+///     #[row(merge)]
+///     books: Vec<Book>,
+/// }
+/// ```
+///
 ///
 /// ### `#[row(merge)]`
 ///
@@ -738,9 +1022,174 @@ pub use crate::extract::FromSqlRow;
 /// corresponding `merge` fields in those rows will be merged. You may specify multiple `merge`
 /// fields within one container, but none of them may have the `#[row(key)]` attribute.
 ///
+///
+/// ### `#[row(none_when = "...")]`
+///
+/// Decides when a flattened `Option<T>` is `None`. By default, the field is `None` if all of the
+/// columns of `T` are `NULL`, or if `T` could not be extracted because of a `NULL` in one of its
+/// columns (a field of `T` which is not an `Option`, for example). Partial data is kept otherwise,
+/// so the optional fields of `T` may still be `None`.
+///
+/// This attribute is stricter: it only checks for `NULL`s up front, after which any error while
+/// extracting `T` is returned as an error, so that incomplete data is never silently dropped:
+///
+/// - `none_when = "all_null"`: `None` if all of the columns of `T` are `NULL`.
+/// - `none_when = "key_null"`: `None` if the first column of `T` is `NULL`. This is usually the
+///   primary key, which makes it a good fit for the missing side of a `LEFT JOIN`.
+///
+/// Requires the `#[row(flatten)]` attribute.
+///
+/// ```
+/// # use postgres_query::FromSqlRow;
+/// #[derive(FromSqlRow)]
+/// #[row(split)]
+/// struct Family {
+///     #[row(flatten, split = "id")]
+///     child: Person,
+///     // With a `NULL` id there is no father, but a `NULL` name is an error.
+///     #[row(flatten, split = "id", none_when = "key_null")]
+///     father: Option<Person>,
+/// }
+///
+/// #[derive(FromSqlRow)]
+/// struct Person {
+///     id: i32,
+///     name: String,
+/// }
+/// ```
+///
+///
+/// ### `#[row(fallback)]`
+///
+/// Use `Default::default()` for a flattened field if its columns are missing from the row, instead
+/// of failing. This lets the same type be extracted from queries which only sometimes join an
+/// optional table. If the columns are present, any error while extracting the field is returned.
+///
+/// Requires the `#[row(flatten)]` attribute, and is only available on the last field. With split
+/// partitioning, the splits leading up to the field may also be missing.
+///
+/// ```
+/// # use postgres_query::FromSqlRow;
+/// #[derive(FromSqlRow)]
+/// #[row(split)]
+/// struct Order {
+///     #[row(flatten, split = "id")]
+///     order: OrderInfo,
+///     // Only present in queries that join the customer.
+///     #[row(flatten, split = "id", fallback)]
+///     customer: Option<Customer>,
+/// }
+///
+/// #[derive(FromSqlRow)]
+/// struct OrderInfo {
+///     id: i32,
+///     total: i64,
+/// }
+///
+/// #[derive(FromSqlRow)]
+/// struct Customer {
+///     id: i32,
+///     name: String,
+/// }
+/// ```
+///
+///
+/// ### `#[row(rename_prefix = "...")]`
+///
+/// Look up the columns of a flattened field with a prefix, such that a field `name` of the nested
+/// type is extracted from the column `parent_name`. This makes it possible to flatten the same type
+/// more than once by aliasing the columns in the query, rather than partitioning the row with
+/// `exact` or `split`:
+///
+/// ```
+/// # use postgres_query::{FromSqlRow, query, Result};
+/// # use tokio_postgres::Client;
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// #[derive(FromSqlRow)]
+/// struct Family {
+///     #[row(flatten, rename_prefix = "parent_")]
+///     parent: Person,
+///     #[row(flatten, rename_prefix = "child_")]
+///     child: Person,
+/// }
+///
+/// #[derive(FromSqlRow)]
+/// struct Person {
+///     id: i32,
+///     name: String,
+/// }
+///
+/// let family: Family = query!(
+///     "SELECT parent.id AS parent_id, parent.name AS parent_name,
+///             child.id AS child_id, child.name AS child_name
+///      FROM people parent JOIN people child ON child.parent = parent.id"
+/// )
+/// .fetch_one(&client)
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Requires the `#[row(flatten)]` attribute. Prefixes of nested fields are combined, outermost
+/// first. The prefix only applies to columns looked up by name by types deriving `FromSqlRow`: the
+/// columns of tuples and `#[row(positional)]` containers are still taken by position, and the split
+/// points of `#[row(split)]` containers are not prefixed.
+///
 /// [`Merge`]: extract/trait.Merge.html
 pub use postgres_query_macro::FromSqlRow;
 
+/// Derive [`Merge`] for a struct with a single field, which is a collection, see the trait for
+/// details.
+///
+/// [`Merge`]: extract/trait.Merge.html
+pub use postgres_query_macro::Merge;
+
+/// Derive [`Patch`] for a struct, see the trait for details.
+///
+/// [`Patch`]: patch/trait.Patch.html
+pub use postgres_query_macro::Patch;
+
+/// Derive [`ToSqlRow`] for a struct with named fields, and [`ColumnTypes`] if the SQL type of every
+/// field is given.
+///
+/// The following attributes may be placed on fields:
+///
+/// - `#[sql_row(rename = "...")]`: the name of the column, which defaults to the name of the field.
+/// - `#[sql_row(sql_type = "...")]`: the SQL type of the column, such as `int4` or `text`.
+/// - `#[sql_row(skip)]`: leave the field out of the row.
+///
+/// [`ToSqlRow`]: copy/trait.ToSqlRow.html
+/// [`ColumnTypes`]: copy/trait.ColumnTypes.html
+pub use postgres_query_macro::ToSqlRow;
+
+/// Derive `ToSql` and `FromSql` for a struct with a single field, by delegating to the type of the
+/// field.
+///
+/// This allows newtypes to be used as query parameters and extracted from rows, without
+/// implementing the traits from `postgres-types` by hand. As required by `ToSql`, the struct must
+/// also implement `Debug`.
+///
+/// ```
+/// # use postgres_query::{query, ToSqlDelegate};
+/// # async fn foo(client: &tokio_postgres::Client) -> postgres_query::Result<()> {
+/// #[derive(Debug, ToSqlDelegate)]
+/// struct UserId(i64);
+///
+/// #[derive(Debug, ToSqlDelegate)]
+/// struct Email {
+///     address: String,
+/// }
+///
+/// let id = UserId(7);
+/// let (email,): (Email,) = query!("SELECT email FROM users WHERE id = $id", id)
+///     .fetch_one(client)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub use postgres_query_macro::ToSqlDelegate;
+
 /// Constructs a new query at compile-time. See also `query_dyn!`.
 ///
 /// # Usage
@@ -773,6 +1222,49 @@ pub use postgres_query_macro::FromSqlRow;
 ///     vec![&age, &"John Wick"],
 /// );
 /// ```
+///
+/// ## Field access
+///
+/// The fields of structs and tuples may be bound to separate parameters by using dotted access
+/// (`$point.0`, `$person.name`, etc.). Each distinct field is bound exactly once:
+///
+/// ```
+/// # use postgres_query::query;
+/// let point = (3, 7);
+/// let insert_point = query!("INSERT INTO points VALUES ($point.0, $point.1)", point);
+///
+/// assert_eq!(insert_point.sql(), "INSERT INTO points VALUES ($1, $2)");
+/// ```
+///
+/// This is only supported by `query!`: in `query_dyn!` bindings are resolved at runtime, where
+/// fields are not available.
+///
+/// ## Splitting long queries
+///
+/// Adjacent string literals, and `concat!` of string literals, are joined into a single query at
+/// compile-time. This makes it possible to spread long queries over multiple lines, and to share
+/// fragments between queries using `macro_rules!`:
+///
+/// ```
+/// # use postgres_query::query;
+/// macro_rules! select_people {
+///     ($($rest:tt)*) => {
+///         query!("SELECT name FROM people " $($rest)*)
+///     };
+/// }
+///
+/// let min_age = 18;
+/// let adults = select_people!(
+///     "WHERE age >= $min_age "
+///     "ORDER BY name",
+///     min_age,
+/// );
+///
+/// assert_eq!(adults.sql(), "SELECT name FROM people WHERE age >= $1 ORDER BY name");
+/// ```
+///
+/// Note that the literals are joined as-is: remember the whitespace between them. Only literals
+/// and `concat!` are expanded, other macros (such as `include_str!`) are not supported.
 #[macro_export]
 macro_rules! query {
     ($($tt:tt)*) => {
@@ -780,27 +1272,74 @@ macro_rules! query {
     };
 }
 
-/// Constructs a new query dynamically at runtime. See also `query!`.
+/// Constructs a new query at compile-time, binding every parameter to the field of a struct with
+/// the same name. See also `query!`.
 ///
 /// # Usage
 ///
-/// This macro expands to an expression with the type `Result<Query>`.
+/// This macro expands to an expression with the type `Query`.
 ///
-/// The first parameter is the SQL query and is always given as a `&str`. This string may contain
-/// parameter bindings on the form `$ident` where `ident` is any valid Rust identifier (`$abc`,
-/// `$value_123`, etc.). The order of the parameters does not matter.
+/// The first parameter is the SQL query, given in the same way as to `query!`, and the second is a
+/// reference to the struct. Each placeholder `$ident` is bound to the field `ident`, so forms with
+/// many fields do not have to be spelled out as `name = form.name, age = form.age, ...`:
 ///
 /// ```
-/// # use postgres_query::{query_dyn, Result};
-/// # fn foo() -> Result<()> {
-/// // We can construct the actual query at runtime
-/// let mut sql = "INSERT INTO people VALUES".to_owned();
-/// sql.push_str("($age, $name)");
+/// # use postgres_query::query_with;
+/// struct NewPerson {
+///     name: String,
+///     age: i32,
+/// }
 ///
-/// let age = 42;
+/// let person = NewPerson {
+///     name: "John Wick".to_owned(),
+///     age: 42,
+/// };
 ///
-/// let insert_person = query_dyn!(
-///     &sql,
+/// let insert_person = query_with!("INSERT INTO people VALUES ($name, $age)", &person);
+/// assert_eq!(insert_person.sql(), "INSERT INTO people VALUES ($1, $2)");
+/// ```
+///
+/// A placeholder without a matching field fails to compile, as does any field which cannot be
+/// bound as a parameter. Fields which are not used by the query are ignored. Dotted access
+/// (`$address.city`) is supported, starting from a field of the struct.
+///
+/// ```compile_fail
+/// # use postgres_query::query_with;
+/// struct NewPerson {
+///     name: String,
+/// }
+///
+/// let person = NewPerson { name: "John Wick".to_owned() };
+/// let insert_person = query_with!("INSERT INTO people VALUES ($name, $age)", &person);
+/// ```
+#[macro_export]
+macro_rules! query_with {
+    ($($tt:tt)*) => {
+        $crate::__query_with!($($tt)*)
+    };
+}
+
+/// Constructs a new query dynamically at runtime. See also `query!`.
+///
+/// # Usage
+///
+/// This macro expands to an expression with the type `Result<Query>`.
+///
+/// The first parameter is the SQL query and is always given as a `&str`. This string may contain
+/// parameter bindings on the form `$ident` where `ident` is any valid Rust identifier (`$abc`,
+/// `$value_123`, etc.). The order of the parameters does not matter.
+///
+/// ```
+/// # use postgres_query::{query_dyn, Result};
+/// # fn foo() -> Result<()> {
+/// // We can construct the actual query at runtime
+/// let mut sql = "INSERT INTO people VALUES".to_owned();
+/// sql.push_str("($age, $name)");
+///
+/// let age = 42;
+///
+/// let insert_person = query_dyn!(
+///     &sql,
 ///     name = "John Wick", // Binds "$name" to "John Wick"
 ///     age,                // Binds "$age" to the value of `age`
 /// )?;
@@ -825,6 +1364,25 @@ macro_rules! query {
 /// );
 /// ```
 ///
+/// The text may be anything that implements `AsRef<str>` (`String`, `Cow<str>`, `Arc<str>`, ...)
+/// or `Display` (such as `format_args!`), which is formatted into a reused buffer rather than a new
+/// `String`:
+///
+/// ```
+/// # use postgres_query::{query_dyn, Result};
+/// # use std::sync::Arc;
+/// # fn foo() -> Result<()> {
+/// let table = "people";
+/// let select = query_dyn!(format_args!("SELECT * FROM {} WHERE age > $age", table), age = 42)?;
+///
+/// let sql: Arc<str> = Arc::from("SELECT * FROM people WHERE age > $age");
+/// let cached = query_dyn!(sql, age = 42)?;
+///
+/// assert_eq!(select.sql(), cached.sql());
+/// # Ok(())
+/// # }
+/// ```
+///
 ///
 /// ## Dynamic Binding
 ///
@@ -895,6 +1453,10 @@ macro_rules! query {
 /// # Ok(())
 /// # }
 /// ```
+///
+/// Common conditions like these are available as reusable fragments in the [`filters`] module.
+///
+/// [`filters`]: filters/index.html
 #[macro_export]
 macro_rules! query_dyn {
     ($($tt:tt)*) => {
@@ -902,9 +1464,181 @@ macro_rules! query_dyn {
     };
 }
 
+/// Constructs an `UPDATE` which only assigns the columns present in a [`Patch`].
+///
+/// # Usage
+///
+/// This macro expands to an expression with the type `Option<Query>`, which is `None` if the patch
+/// does not assign any columns.
+///
+/// The first parameter is the name of the table, and the second is a reference to the patch. These
+/// are followed by `where` and a filter, which is given in the same way as to [`query!`]. The
+/// parameters of the filter are renumbered to follow those of the patch.
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{update, Patch, Result};
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// #[derive(Patch)]
+/// struct PersonPatch {
+///     name: Option<String>,
+///     age: Option<i32>,
+/// }
+///
+/// let patch = PersonPatch {
+///     name: None,
+///     age: Some(42),
+/// };
+///
+/// let query = update!("people", &patch, where "id = $id", id = 7).unwrap();
+/// assert_eq!(query.sql(), "UPDATE people SET age = $1 WHERE id = $2");
+///
+/// query.execute(&client).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Patch`]: patch/trait.Patch.html
+/// [`query!`]: macro.query.html
+#[macro_export]
+macro_rules! update {
+    ($table:expr, $patch:expr, where $($filter:tt)+) => {
+        $crate::patch::update($table, $patch, $crate::query!($($filter)+))
+    };
+}
+
+/// Executes multiple queries within a single transaction.
+///
+/// # Usage
+///
+/// This macro expands to a future which resolves to a `Result` of a tuple with the number of rows
+/// affected by each query.
+///
+/// The first parameter is the client on which to begin the transaction (either a
+/// `tokio_postgres::Client` or a `tokio_postgres::Transaction`, in which case a savepoint is
+/// used), followed by a semicolon and the queries to execute. All queries are sent to the database
+/// at once (they are pipelined), and the transaction is only committed if all of them succeed.
+///
+/// Static queries which appear multiple times in the batch (such as the same `SELECT` for many
+/// ids) are only prepared once, and the statement is reused by all of them.
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{execute_batch, query, Result};
+/// # async fn foo() -> Result<()> {
+/// # let mut client: Client = unimplemented!();
+/// let insert_user = query!("INSERT INTO users VALUES ($id)", id = 7);
+/// let insert_profile = query!("INSERT INTO profiles VALUES ($id)", id = 7);
+///
+/// let (users, profiles, settings) = execute_batch!(
+///     client;
+///     insert_user,
+///     insert_profile,
+///     query!("INSERT INTO settings VALUES ($id)", id = 7),
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[macro_export]
+macro_rules! execute_batch {
+    ($client:expr; $($query:expr),+ $(,)?) => {
+        async {
+            let transaction = $client
+                .transaction()
+                .await
+                .map_err($crate::Error::BeginTransaction)?;
+            let transaction = $crate::client::Caching::new(transaction);
+
+            let rows = $crate::__try_join!(
+                $(async { $query.execute(&transaction).await }),+
+            )?;
+
+            transaction
+                .into_inner()
+                .commit()
+                .await
+                .map_err($crate::Error::CommitTransaction)?;
+
+            ::std::result::Result::Ok::<_, $crate::Error>(rows)
+        }
+    };
+}
+
+/// Fetches a single row into local variables, without declaring a struct for it.
+///
+/// # Usage
+///
+/// This macro expands to a future which resolves to a `Result` of a tuple with the value of each
+/// column.
+///
+/// The first parameter is the client, followed by the SQL query, the columns to extract in braces,
+/// and any parameters of the query (given in the same way as to [`query!`]). Each column is
+/// extracted by name, as if by a struct deriving [`FromSqlRow`] with the same fields, and the query
+/// must return exactly one row:
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{fetch_row, Result};
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// let (n, oldest) = fetch_row!(
+///     &client,
+///     "SELECT count(*) AS n, max(age) AS oldest FROM people WHERE age > $min",
+///     { n: i64, oldest: Option<i32> },
+///     min = 18,
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`query!`]: macro.query.html
+/// [`FromSqlRow`]: extract/trait.FromSqlRow.html
+#[macro_export]
+macro_rules! fetch_row {
+    (
+        $client:expr,
+        $sql:expr,
+        { $($column:ident : $ty:ty),+ $(,)? }
+        $(, $($parameters:tt)*)?
+    ) => {
+        async {
+            #[derive($crate::FromSqlRow)]
+            struct __Row {
+                $($column: $ty),+
+            }
+
+            let __Row { $($column),+ } = $crate::query!($sql $(, $($parameters)*)?)
+                .fetch_one::<__Row, _>($client)
+                .await?;
+
+            ::std::result::Result::Ok::<_, $crate::Error>(($($column,)+))
+        }
+    };
+}
+
 #[proc_macro_hack]
 #[doc(hidden)]
-pub use postgres_query_macro::{query_dynamic as __query_dynamic, query_static as __query_static};
+pub use postgres_query_macro::{
+    query_dynamic as __query_dynamic, query_static as __query_static, query_with as __query_with,
+};
+
+#[doc(hidden)]
+pub use parse::text as __text;
+
+#[doc(hidden)]
+pub use parse::intern::Interned as __Interned;
+
+#[doc(hidden)]
+pub use futures::try_join as __try_join;
+
+#[doc(hidden)]
+pub use bytes as __bytes;
+
+#[doc(hidden)]
+pub use postgres_types as __postgres_types;
 
 /// A shorthand for types that can be treated as SQL parameters.
 ///
@@ -938,6 +1672,47 @@ pub use postgres_query_macro::{query_dynamic as __query_dynamic, query_static as
 /// ```
 pub type Parameter<'a> = &'a (dyn ToSql + Sync);
 
+/// Borrows a value as a [`Parameter`].
+///
+/// The bindings of `query!` and `query_dyn!` are converted with this trait, using method call
+/// syntax. Values are therefore dereferenced as far as needed, so that bindings behind smart
+/// pointers (such as `Arc<String>`, `Box<i32>` or the guard of a lock) may be bound directly:
+///
+/// ```
+/// # use postgres_query::query;
+/// # use std::sync::{Arc, RwLock};
+/// struct Config {
+///     name: Arc<String>,
+///     limit: Box<i64>,
+/// }
+///
+/// let config = Arc::new(RwLock::new(Config {
+///     name: Arc::new("John Wick".to_owned()),
+///     limit: Box::new(10),
+/// }));
+///
+/// let config = config.read().unwrap();
+/// let query = query!(
+///     "SELECT * FROM people WHERE name = $name LIMIT $limit",
+///     name = config.name,
+///     limit = config.limit,
+/// );
+/// ```
+///
+/// [`Parameter`]: type.Parameter.html
+pub trait AsParameter {
+    fn as_parameter(&self) -> Parameter<'_>;
+}
+
+impl<T> AsParameter for T
+where
+    T: ToSql + Sync,
+{
+    fn as_parameter(&self) -> Parameter<'_> {
+        self
+    }
+}
+
 /// A static query with dynamic parameters.
 ///
 /// # Usage
@@ -989,14 +1764,47 @@ pub type Parameter<'a> = &'a (dyn ToSql + Sync);
 pub struct Query<'a> {
     sql: Sql,
     parameters: Vec<Parameter<'a>>,
+    bindings: Bindings,
+    options: Option<Box<ExecuteOptions>>,
 }
 
 #[derive(Debug, Clone)]
 enum Sql {
-    Static(&'static str),
+    /// Static SQL, along with its hash once it has been fingerprinted.
+    Static(&'static str, OnceLock<u64>),
     Dynamic(String),
 }
 
+/// The names of the bindings of a query, along with the index of the placeholder (`$1`, `$2`, ...)
+/// each of them was numbered as.
+#[derive(Debug, Clone)]
+enum Bindings {
+    Static(&'static [(&'static str, usize)]),
+    Dynamic(Vec<(String, usize)>),
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings::Static(&[])
+    }
+}
+
+impl Bindings {
+    fn iter(&self) -> impl Iterator<Item = (&str, usize)> {
+        let (head, tail) = match self {
+            Bindings::Static(bindings) => (*bindings, &[][..]),
+            Bindings::Dynamic(bindings) => (&[][..], bindings.as_slice()),
+        };
+        let head = head.iter().map(|&(name, index)| (name, index));
+        let tail = tail.iter().map(|(name, index)| (name.as_str(), *index));
+        head.chain(tail)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+}
+
 impl<'a> Query<'a> {
     /// Create a new query an already prepared string.
     ///
@@ -1007,6 +1815,8 @@ impl<'a> Query<'a> {
         Query {
             sql: Sql::Dynamic(sql),
             parameters,
+            bindings: Bindings::default(),
+            options: None,
         }
     }
 
@@ -1017,23 +1827,79 @@ impl<'a> Query<'a> {
     /// are given in the same format required by `tokio_postgres` (`$1`, `$2`, ...).
     pub fn new_static(sql: &'static str, parameters: Vec<Parameter<'a>>) -> Query<'a> {
         Query {
-            sql: Sql::Static(sql),
+            sql: Sql::Static(sql, OnceLock::new()),
             parameters,
+            bindings: Bindings::default(),
+            options: None,
         }
     }
 
+    /// Record the names of the bindings the placeholders of a static query were numbered after.
+    /// Called by `query!` and `query_with!`.
+    #[doc(hidden)]
+    pub fn __with_bindings(mut self, bindings: &'static [(&'static str, usize)]) -> Query<'a> {
+        self.bindings = Bindings::Static(bindings);
+        self
+    }
+
     /// Parses a string that may contain parameter bindings on the form `$abc_123`. This is the same
     /// function that is called when passing dynamically generated strings to the `query_dyn!`
     /// macro.
     ///
+    /// Bindings are not replaced inside string literals, quoted identifiers and comments. A dollar
+    /// sign is written as `$$`, even there.
+    ///
     /// Because this is a function there will some runtime overhead unlike the `query!` macro which
     /// has zero overhead when working with string literals.
     pub fn parse(text: &str, bindings: &[(&str, Parameter<'a>)]) -> Result<Query<'a>> {
-        let (sql, parameters) = parse::parse(text, bindings)?;
+        let (sql, parameters, bindings) = parse::parse(text, bindings)?;
+
+        #[cfg(feature = "lint")]
+        lint::check(&sql)?;
 
         Ok(Query {
             sql: Sql::Dynamic(sql),
             parameters,
+            bindings,
+            options: None,
+        })
+    }
+
+    /// Like [`Query::parse`], but for text which is only `Display`, such as SQL which is formatted
+    /// lazily. This is called by `query_dyn!` for text which is not `AsRef<str>`.
+    ///
+    /// The text is formatted into a buffer that is reused between calls on the same thread, so no
+    /// intermediate `String` is allocated.
+    ///
+    /// [`Query::parse`]: #method.parse
+    pub fn parse_display(
+        text: impl std::fmt::Display,
+        bindings: &[(&str, Parameter<'a>)],
+    ) -> Result<Query<'a>> {
+        use std::cell::RefCell;
+        use std::fmt::Write;
+
+        /// Buffers larger than this are not kept around after use.
+        const MAX_RETAINED: usize = 64 * 1024;
+
+        thread_local! {
+            static BUFFER: RefCell<String> = const { RefCell::new(String::new()) };
+        }
+
+        let format = |buffer: &mut String| {
+            write!(buffer, "{}", text).expect("a Display implementation returned an error");
+            let query = Query::parse(buffer, bindings);
+            buffer.clear();
+            if buffer.capacity() > MAX_RETAINED {
+                *buffer = String::new();
+            }
+            query
+        };
+
+        BUFFER.with(|buffer| match buffer.try_borrow_mut() {
+            Ok(mut buffer) => format(&mut buffer),
+            // The text is formatting another query from within its `Display` implementation.
+            Err(_) => format(&mut String::new()),
         })
     }
 
@@ -1044,9 +1910,300 @@ impl<'a> Query<'a> {
 
     /// Get the parameters of this query in the order expected by the query returned by
     /// `Query::sql`.
-    pub fn parameters(&'a self) -> &[Parameter<'a>] {
+    pub fn parameters(&'a self) -> &'a [Parameter<'a>] {
         &self.parameters
     }
+
+    /// Get the names of the bindings of this query, along with the index of the placeholder each of
+    /// them was numbered as, in order of the placeholders.
+    ///
+    /// A binding which is used several times is only numbered once, and bindings of queries which
+    /// are combined (such as with [`Query::append`]) keep their names, so this shows exactly how
+    /// the parameters of a dynamically assembled query line up:
+    ///
+    /// ```
+    /// # use postgres_query::query;
+    /// let mut query = query!(
+    ///     "SELECT * FROM people WHERE age > $min AND age < $max OR age = $min",
+    ///     min = 18,
+    ///     max = 65,
+    /// );
+    /// query.append(query!(" AND name = $name", name = "John Wick"));
+    ///
+    /// assert_eq!(query.binding_order(), [("min", 1), ("max", 2), ("name", 3)]);
+    /// ```
+    ///
+    /// Parameters given by position (such as to [`Query::new`]) have no name, and are left out.
+    ///
+    /// [`Query::append`]: #method.append
+    /// [`Query::new`]: #method.new
+    pub fn binding_order(&self) -> Vec<(&str, usize)> {
+        let mut order = self.bindings.iter().collect::<Vec<_>>();
+        order.sort_by_key(|&(_, index)| index);
+        order
+    }
+
+    /// Get a stable identity of this query, derived from its SQL and the number of parameters.
+    ///
+    /// The fingerprint is the same across runs of the program, and is cheap to hash and compare,
+    /// which makes it suitable as the key in application-level caches. Note that the values of the
+    /// parameters are not part of the fingerprint. Static SQL is only hashed once per query.
+    ///
+    /// ```
+    /// # use postgres_query::query;
+    /// let a = query!("SELECT * FROM people WHERE age = $age", age = 42);
+    /// let b = query!("SELECT * FROM people WHERE age = $age", age = 37);
+    /// assert_eq!(a.fingerprint(), b.fingerprint());
+    /// ```
+    pub fn fingerprint(&self) -> Fingerprint {
+        let hash = match &self.sql {
+            Sql::Static(text, hash) => *hash.get_or_init(|| Fingerprint::hash(text.bytes())),
+            Sql::Dynamic(text) => Fingerprint::hash(text.bytes()),
+        };
+        Fingerprint::new(hash, self.parameters.len())
+    }
+
+    /// Append another query to the end of this one.
+    ///
+    /// The positional parameters (`$1`, `$2`, ...) of `other` are renumbered to follow the
+    /// parameters of this query, and its parameters are added after this query's. The SQL is
+    /// concatenated as is, so any whitespace between the two has to be part of either query.
+    ///
+    /// ```
+    /// # use postgres_query::query;
+    /// let mut query = query!("SELECT name FROM people WHERE age = $age", age = 42);
+    /// query.append(query!(" AND name = $name", name = "John Wick"));
+    ///
+    /// assert_eq!(query.sql(), "SELECT name FROM people WHERE age = $1 AND name = $2");
+    /// assert_eq!(query.parameters().len(), 2);
+    /// ```
+    pub fn append(&mut self, other: Query<'a>) {
+        let mut sql = String::with_capacity(self.sql.len() + other.sql.len());
+        sql.push_str(&self.sql);
+        parse::renumber(&mut sql, &other.sql, self.parameters.len());
+
+        if !other.bindings.is_empty() {
+            let offset = self.parameters.len();
+            let bindings = self
+                .bindings
+                .iter()
+                .map(|(name, index)| (name.to_owned(), index))
+                .chain(
+                    other
+                        .bindings
+                        .iter()
+                        .map(|(name, index)| (name.to_owned(), index + offset)),
+                )
+                .collect();
+            self.bindings = Bindings::Dynamic(bindings);
+        }
+
+        self.sql = Sql::Dynamic(sql);
+        self.parameters.extend(other.parameters);
+    }
+
+    /// Like [`Query::append`], but static SQL stays static, so that the query is still prepared
+    /// through the statement cache. Every distinct SQL text is interned (and leaked), so `other`
+    /// may only take a bounded number of forms.
+    pub(crate) fn append_interned(&mut self, other: Query<'a>) {
+        let was_static = matches!(self.sql, Sql::Static(..));
+        self.append(other);
+
+        if let (true, Sql::Dynamic(sql)) = (was_static, &self.sql) {
+            let sql = parse::intern::intern(sql);
+            self.sql = Sql::Static(sql, OnceLock::new());
+        }
+    }
+
+    /// Combine multiple queries into one, placing `separator` between each of them.
+    ///
+    /// Parameters are renumbered in the same way as by [`Query::append`], which makes it possible
+    /// to assemble whole queries, for example with `UNION ALL`:
+    ///
+    /// ```
+    /// # use postgres_query::{query, Query};
+    /// let adults = query!("SELECT name FROM people WHERE age >= $age", age = 18);
+    /// let admins = query!("SELECT name FROM admins WHERE level > $level", level = 2);
+    ///
+    /// let query = Query::join(" UNION ALL ", vec![adults, admins]);
+    /// assert_eq!(
+    ///     query.sql(),
+    ///     "SELECT name FROM people WHERE age >= $1 UNION ALL SELECT name FROM admins WHERE level > $2"
+    /// );
+    /// ```
+    ///
+    /// [`Query::append`]: #method.append
+    pub fn join<I>(separator: &str, queries: I) -> Query<'a>
+    where
+        I: IntoIterator<Item = Query<'a>>,
+    {
+        let mut queries = queries.into_iter();
+        let mut joined = queries
+            .next()
+            .unwrap_or_else(|| Query::new_static("", Vec::new()));
+
+        for query in queries {
+            joined.append(Query::new(separator.to_owned(), Vec::new()));
+            joined.append(query);
+        }
+
+        joined
+    }
+
+    /// Only include rows which have not been soft-deleted, ie. where `column` is `NULL`.
+    ///
+    /// The condition is added to the `WHERE` clause of the query, or a `WHERE` clause is added
+    /// before any `GROUP BY`, `ORDER BY`, `LIMIT`, etc., so this works with `SELECT`, `UPDATE` and
+    /// `DELETE` statements. The column is inserted verbatim, and may be qualified with a table
+    /// name when the query joins multiple tables:
+    ///
+    /// ```
+    /// # use postgres_query::query;
+    /// let query = query!("SELECT name FROM people WHERE age > $age OR admin ORDER BY name", age = 18)
+    ///     .excluding_deleted("deleted_at");
+    ///
+    /// assert_eq!(
+    ///     query.sql(),
+    ///     "SELECT name FROM people WHERE (age > $1 OR admin) AND deleted_at IS NULL ORDER BY name"
+    /// );
+    /// ```
+    ///
+    /// Only the outermost statement is changed: sub-queries and common table expressions are left
+    /// as they are, and in compound queries (`UNION`, `INTERSECT` and `EXCEPT`) only the first
+    /// part is filtered.
+    pub fn excluding_deleted(mut self, column: &str) -> Query<'a> {
+        const CLAUSES: &[&str] = &[
+            "GROUP",
+            "HAVING",
+            "WINDOW",
+            "ORDER",
+            "LIMIT",
+            "OFFSET",
+            "FETCH",
+            "FOR",
+            "RETURNING",
+            "UNION",
+            "INTERSECT",
+            "EXCEPT",
+            ";",
+        ];
+
+        const COMPOUND: &[&str] = &["UNION", "INTERSECT", "EXCEPT", ";"];
+
+        let keywords = parse::top_level_keywords(&self.sql);
+
+        // Only the first part of a compound query is filtered, even if a later part has a `WHERE`.
+        let first = keywords
+            .iter()
+            .position(|(_, word)| COMPOUND.contains(&word.as_str()))
+            .unwrap_or(keywords.len());
+        let filter = keywords[..first]
+            .iter()
+            .position(|(_, word)| word == "WHERE");
+
+        let end = keywords[filter.map_or(0, |index| index + 1)..]
+            .iter()
+            .find(|(_, word)| CLAUSES.contains(&word.as_str()))
+            .map_or(self.sql.len(), |&(offset, _)| offset);
+
+        let (head, tail) = self.sql.split_at(end);
+        let separator = if tail.is_empty() { "" } else { " " };
+
+        let sql = match filter {
+            Some(index) => {
+                let (offset, _) = keywords[index];
+                let body = offset + "WHERE".len();
+                format!(
+                    "{} ({}) AND {} IS NULL{}{}",
+                    &head[..body],
+                    head[body..].trim(),
+                    column,
+                    separator,
+                    tail
+                )
+            }
+            None => format!(
+                "{} WHERE {} IS NULL{}{}",
+                head.trim_end(),
+                column,
+                separator,
+                tail
+            ),
+        };
+
+        self.sql = Sql::Dynamic(sql);
+        self
+    }
+
+    /// Split this query into its SQL and parameters, in the same format as [`Query::sql`] and
+    /// [`Query::parameters`].
+    ///
+    /// This is useful when handing the query to another client layer, since the SQL has already
+    /// been prepared and does not have to be parsed again. Static SQL is returned without
+    /// allocating.
+    ///
+    /// ```
+    /// # use postgres_query::query;
+    /// # use std::borrow::Cow;
+    /// let query = query!("SELECT name FROM people WHERE age = $age", age = 42);
+    ///
+    /// let (sql, parameters) = query.into_parts();
+    /// assert_eq!(sql, Cow::Borrowed("SELECT name FROM people WHERE age = $1"));
+    /// assert_eq!(parameters.len(), 1);
+    /// ```
+    ///
+    /// [`Query::sql`]: #method.sql
+    /// [`Query::parameters`]: #method.parameters
+    pub fn into_parts(self) -> (Cow<'static, str>, Vec<Parameter<'a>>) {
+        let sql = match self.sql {
+            Sql::Static(text, _) => Cow::Borrowed(text),
+            Sql::Dynamic(text) => Cow::Owned(text),
+        };
+        (sql, self.parameters)
+    }
+}
+
+/// Split a query into its SQL and parameters, see [`Query::into_parts`].
+///
+/// [`Query::into_parts`]: struct.Query.html#method.into_parts
+impl<'a> From<Query<'a>> for (String, Vec<Parameter<'a>>) {
+    fn from(query: Query<'a>) -> Self {
+        let (sql, parameters) = query.into_parts();
+        (sql.into_owned(), parameters)
+    }
+}
+
+/// A stable identity of a query. See [`Query::fingerprint`].
+///
+/// [`Query::fingerprint`]: struct.Query.html#method.fingerprint
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Fingerprint(u64);
+
+impl Fingerprint {
+    // We use FNV-1a, since the hashers in the standard library are not guaranteed to be stable.
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    /// Combine the hash of the SQL with the number of parameters.
+    fn new(sql: u64, parameters: usize) -> Fingerprint {
+        let count = (parameters as u64).to_le_bytes();
+        Fingerprint(Self::hash_from(sql, count.iter().copied()))
+    }
+
+    fn hash(bytes: impl Iterator<Item = u8>) -> u64 {
+        Self::hash_from(Self::OFFSET_BASIS, bytes)
+    }
+
+    fn hash_from(hash: u64, bytes: impl Iterator<Item = u8>) -> u64 {
+        bytes.fold(hash, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(Self::PRIME)
+        })
+    }
+
+    /// Get the fingerprint as an integer.
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
 }
 
 impl Deref for Sql {
@@ -1054,8 +2211,8 @@ impl Deref for Sql {
 
     fn deref(&self) -> &Self::Target {
         match self {
-            Sql::Static(text) => text,
-            Sql::Dynamic(text) => &text,
+            Sql::Static(text, _) => text,
+            Sql::Dynamic(text) => text,
         }
     }
 }
@@ -1086,6 +2243,184 @@ mod tests {
         assert_eq!(query.sql(), "SELECT $1");
     }
 
+    #[test]
+    fn fingerprint_ignores_parameter_values() {
+        let a = Query::new_static("SELECT $1", vec![&1]);
+        let b = Query::parse("SELECT $number", &[("number", &2)]).unwrap();
+        assert_eq!(a.fingerprint(), b.fingerprint());
+    }
+
+    #[test]
+    fn fingerprint_parameter_count() {
+        let a = Query::new_static("SELECT 1", vec![]);
+        let b = Query::new_static("SELECT 1", vec![&1]);
+        assert_ne!(a.fingerprint(), b.fingerprint());
+    }
+
+    #[test]
+    fn append_renumbers_parameters() {
+        let mut query = Query::new_static("SELECT $1, $2", vec![&1, &2]);
+        query.append(Query::new_static(", $2, $1, '$'", vec![&3, &4]));
+        assert_eq!(query.sql(), "SELECT $1, $2, $4, $3, '$'");
+        assert_eq!(query.parameters().len(), 4);
+    }
+
+    #[test]
+    fn single_statement_strips_terminator() {
+        use crate::parse::single_statement;
+
+        assert_eq!(single_statement("SELECT 1"), Some("SELECT 1"));
+        assert_eq!(single_statement("SELECT 1 ;\n-- done\n"), Some("SELECT 1 "));
+        assert_eq!(single_statement("SELECT ';' -- ;"), Some("SELECT ';' -- ;"));
+        assert_eq!(single_statement("SELECT 1; SELECT 2"), None);
+    }
+
+    #[test]
+    fn values_rows_counts_top_level_rows() {
+        use crate::parse::values_rows;
+
+        assert_eq!(values_rows("INSERT INTO t (a, b) VALUES ($1, ','), ($2, 'b')"), Some(2));
+        assert_eq!(values_rows("INSERT INTO t VALUES (1) ON CONFLICT (a, b) DO NOTHING"), Some(1));
+        assert_eq!(values_rows("INSERT INTO t DEFAULT VALUES RETURNING a, b"), Some(1));
+        assert_eq!(values_rows("INSERT INTO t SELECT * FROM (VALUES (1), (2)) v"), None);
+    }
+
+    #[test]
+    fn append_skips_quoted_parameters() {
+        let mut query = Query::new_static("SELECT $1", vec![&1]);
+        query.append(Query::new_static(
+            ", '$1', \"$1\", $$ $1 $$, $tag$ $1 $tag$, $1 -- $1\n /* $1 */",
+            vec![&2],
+        ));
+        assert_eq!(
+            query.sql(),
+            "SELECT $1, '$1', \"$1\", $$ $1 $$, $tag$ $1 $tag$, $2 -- $1\n /* $1 */"
+        );
+    }
+
+    #[test]
+    fn append_keeps_binding_names() {
+        let mut query = Query::parse("SELECT $a, $b, $a", &[("b", &2), ("a", &1)]).unwrap();
+        assert_eq!(query.binding_order(), [("a", 1), ("b", 2)]);
+
+        query.append(Query::new_static(", $1", vec![&3]));
+        query.append(Query::parse(", $c", &[("c", &4)]).unwrap());
+        assert_eq!(query.sql(), "SELECT $1, $2, $1, $3, $4");
+        assert_eq!(query.binding_order(), [("a", 1), ("b", 2), ("c", 4)]);
+    }
+
+    #[test]
+    fn join_queries() {
+        let queries = vec![
+            Query::new_static("SELECT $1", vec![&1]),
+            Query::new_static("SELECT 2", vec![]),
+            Query::new_static("SELECT $1 + $1", vec![&3]),
+        ];
+        let query = Query::join(" UNION ALL ", queries);
+        assert_eq!(
+            query.sql(),
+            "SELECT $1 UNION ALL SELECT 2 UNION ALL SELECT $2 + $2"
+        );
+        assert_eq!(query.parameters().len(), 2);
+
+        let empty = Query::join(", ", Vec::new());
+        assert_eq!(empty.sql(), "");
+    }
+
+    #[test]
+    fn into_parts() {
+        let query = Query::new_static("SELECT $1", vec![&1]);
+        let (sql, parameters) = query.into_parts();
+        assert!(is_match!(sql, Cow::Borrowed("SELECT $1")));
+        assert_eq!(parameters.len(), 1);
+
+        let query = Query::parse("SELECT $number", &[("number", &2)]).unwrap();
+        let (sql, parameters): (String, Vec<Parameter>) = query.into();
+        assert_eq!(sql, "SELECT $1");
+        assert_eq!(parameters.len(), 1);
+    }
+
+    #[test]
+    fn excluding_deleted() {
+        let query = Query::new_static("SELECT name FROM people", vec![]);
+        assert_eq!(
+            query.excluding_deleted("deleted_at").sql(),
+            "SELECT name FROM people WHERE deleted_at IS NULL"
+        );
+
+        let query = Query::new_static("DELETE FROM people WHERE id = $1 RETURNING name;", vec![&1]);
+        assert_eq!(
+            query.excluding_deleted("deleted_at").sql(),
+            "DELETE FROM people WHERE (id = $1) AND deleted_at IS NULL RETURNING name;"
+        );
+
+        let query = Query::new_static(
+            "SELECT p.name FROM people p JOIN (SELECT id FROM t WHERE x LIMIT 1) AS s ON s.id = p.id \
+             ORDER BY 'WHERE' LIMIT 10",
+            vec![],
+        );
+        assert_eq!(
+            query.excluding_deleted("p.deleted_at").sql(),
+            "SELECT p.name FROM people p JOIN (SELECT id FROM t WHERE x LIMIT 1) AS s ON s.id = p.id \
+             WHERE p.deleted_at IS NULL ORDER BY 'WHERE' LIMIT 10"
+        );
+    }
+
+    #[test]
+    fn excluding_deleted_filters_first_compound_part() {
+        let query = Query::new_static(
+            "SELECT name FROM people WHERE age > 18 UNION SELECT name FROM admins",
+            vec![],
+        );
+        assert_eq!(
+            query.excluding_deleted("deleted_at").sql(),
+            "SELECT name FROM people WHERE (age > 18) AND deleted_at IS NULL \
+             UNION SELECT name FROM admins"
+        );
+
+        let query = Query::new_static(
+            "SELECT name FROM people UNION SELECT name FROM admins WHERE active",
+            vec![],
+        );
+        assert_eq!(
+            query.excluding_deleted("deleted_at").sql(),
+            "SELECT name FROM people WHERE deleted_at IS NULL \
+             UNION SELECT name FROM admins WHERE active"
+        );
+
+        let query = Query::new_static(
+            "SELECT name FROM people EXCEPT SELECT name FROM admins",
+            vec![],
+        );
+        assert_eq!(
+            query.excluding_deleted("deleted_at").sql(),
+            "SELECT name FROM people WHERE deleted_at IS NULL EXCEPT SELECT name FROM admins"
+        );
+    }
+
+    #[test]
+    fn top_level_keywords_skip_quotes() {
+        let keywords = parse::top_level_keywords(
+            "SELECT $1, $tag$ WHERE $tag$, \"where\" -- WHERE\n FROM /* WHERE */ t WHERE (a)",
+        );
+        let words = keywords
+            .iter()
+            .map(|(_, word)| word.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(words, ["SELECT", "FROM", "T", "WHERE"]);
+    }
+
+    #[test]
+    fn parse_query_skips_literals_and_comments() {
+        let query = Query::parse(
+            "SELECT $a, '$a $$b', \"$a\" -- $b\n /* $b */ $$",
+            &[("a", &1), ("b", &2)],
+        )
+        .unwrap();
+        assert_eq!(query.sql(), "SELECT $1, '$a $b', \"$a\" -- $b\n /* $b */ $");
+        assert_eq!(query.parameters().len(), 1);
+    }
+
     #[test]
     fn parse_query_missing_identifier_eof() {
         let query = Query::parse("SELECT $", &[]);
@@ -1103,4 +2438,13 @@ mod tests {
             Error::Parse(ParseError::EmptyIdentifier { found: Some(' ') })
         ));
     }
+
+    #[test]
+    fn parse_query_positional_parameter() {
+        let query = Query::parse("SELECT $name, $1", &[("name", &1), ("1", &2)]);
+        assert!(is_match!(
+            query.unwrap_err(),
+            Error::Parse(ParseError::PositionalParameter { .. })
+        ));
+    }
 }
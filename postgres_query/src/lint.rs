@@ -0,0 +1,158 @@
+//! Check queries against user-defined rules before they are executed.
+//!
+//! Requires the `lint` feature.
+//!
+//! Rules are registered once, globally, with [`register`]. Every query is then checked against
+//! them when it is parsed with [`Query::parse`] (or `query_dyn!`) and whenever it is executed. A
+//! query which violates a rule fails with [`Error::Lint`], naming the rule.
+//!
+//! Rules are only checked in debug builds (with `debug_assertions` enabled), so they catch mistakes
+//! during development and in tests without any cost in release builds.
+//!
+//! ```
+//! # use postgres_query::{lint, query, Error};
+//! lint::register("no_select_star", lint::no_select_star);
+//! lint::register("delete_has_where", lint::delete_has_where);
+//! lint::register("no_cross_join", |sql| !sql.contains("CROSS JOIN"));
+//!
+//! match lint::check(query!("SELECT * FROM people").sql()) {
+//!     Err(Error::Lint { rule }) => assert_eq!(rule, "no_select_star"),
+//!     # Ok(()) => {}
+//!     Err(error) => panic!("unexpected error: {}", error),
+//! }
+//! ```
+//!
+//! The built-in rules ([`no_select_star`], [`select_has_limit`] and [`delete_has_where`]) only
+//! look at the keywords of the query: they are meant to catch common mistakes, not to understand
+//! SQL.
+//!
+//! [`register`]: fn.register.html
+//! [`Query::parse`]: ../struct.Query.html#method.parse
+//! [`Error::Lint`]: ../enum.Error.html#variant.Lint
+//! [`no_select_star`]: fn.no_select_star.html
+//! [`select_has_limit`]: fn.select_has_limit.html
+//! [`delete_has_where`]: fn.delete_has_where.html
+
+use crate::error::{Error, Result};
+use crate::parse::{self, Token};
+use std::sync::RwLock;
+
+type Rule = Box<dyn Fn(&str) -> bool + Send + Sync>;
+
+static RULES: RwLock<Vec<(String, Rule)>> = RwLock::new(Vec::new());
+
+/// Register a rule, which returns `false` for queries that violate it.
+pub fn register<F>(name: impl Into<String>, rule: F)
+where
+    F: Fn(&str) -> bool + Send + Sync + 'static,
+{
+    let mut rules = RULES.write().unwrap_or_else(|error| error.into_inner());
+    rules.push((name.into(), Box::new(rule)));
+}
+
+/// Remove all registered rules.
+pub fn clear() {
+    let mut rules = RULES.write().unwrap_or_else(|error| error.into_inner());
+    rules.clear();
+}
+
+/// Check a query against the registered rules, returning an error for the first rule it violates.
+///
+/// Always succeeds in release builds.
+pub fn check(sql: &str) -> Result<()> {
+    if !cfg!(debug_assertions) {
+        return Ok(());
+    }
+
+    let rules = RULES.read().unwrap_or_else(|error| error.into_inner());
+    match rules.iter().find(|(_, rule)| !rule(sql)) {
+        Some((name, _)) => Err(Error::Lint { rule: name.clone() }),
+        None => Ok(()),
+    }
+}
+
+/// Rejects `SELECT *`, including `SELECT DISTINCT *` and `SELECT table.*`. `COUNT(*)` is allowed.
+pub fn no_select_star(sql: &str) -> bool {
+    let tokens = tokenize(sql);
+    !tokens.windows(2).any(|pair| {
+        pair[1] == "*" && ["SELECT", "DISTINCT", "ALL", ",", "."].contains(&pair[0].as_str())
+    })
+}
+
+/// Requires a `LIMIT` (or `FETCH`) in queries starting with `SELECT`.
+pub fn select_has_limit(sql: &str) -> bool {
+    let tokens = tokenize(sql);
+    match tokens.first() {
+        Some(first) if first == "SELECT" => tokens.iter().any(|t| t == "LIMIT" || t == "FETCH"),
+        _ => true,
+    }
+}
+
+/// Requires a `WHERE` after every `DELETE`.
+pub fn delete_has_where(sql: &str) -> bool {
+    let tokens = tokenize(sql);
+    tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, token)| *token == "DELETE")
+        .all(|(i, _)| tokens[i + 1..].iter().any(|token| token == "WHERE"))
+}
+
+/// Split a query into uppercase words and single punctuation characters, skipping whitespace,
+/// comments, string literals and quoted identifiers.
+fn tokenize(sql: &str) -> Vec<String> {
+    parse::tokens(sql)
+        .filter_map(|(_, token)| match token {
+            Token::Word(word) => Some(word.to_uppercase()),
+            Token::Quoted(_) | Token::Comment(_) => None,
+            Token::Other(text) if text.trim().is_empty() => None,
+            token => Some(token.text().to_owned()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_skips_strings_and_comments() {
+        let tokens = tokenize("select a.* -- all\n FROM \"x\"/* y */ WHERE b = 'it''s'");
+        assert_eq!(tokens, ["SELECT", "A", ".", "*", "FROM", "WHERE", "B", "="]);
+
+        let tokens = tokenize("SELECT $body$ it's * $body$, $1 FROM x");
+        assert_eq!(tokens, ["SELECT", ",", "$1", "FROM", "X"]);
+    }
+
+    #[test]
+    fn select_star() {
+        assert!(!no_select_star("SELECT * FROM people"));
+        assert!(!no_select_star("select distinct * from people"));
+        assert!(!no_select_star("SELECT p.* FROM people p"));
+        assert!(!no_select_star("SELECT id, * FROM people"));
+        assert!(no_select_star("SELECT COUNT(*) FROM people"));
+        assert!(no_select_star("SELECT age * 2 FROM people"));
+        assert!(no_select_star("SELECT '*' FROM people"));
+    }
+
+    #[test]
+    fn select_limit() {
+        assert!(!select_has_limit("SELECT name FROM people"));
+        assert!(select_has_limit("SELECT name FROM people LIMIT 10"));
+        assert!(select_has_limit(
+            "SELECT name FROM people FETCH FIRST 10 ROWS ONLY"
+        ));
+        assert!(select_has_limit(
+            "INSERT INTO people SELECT name FROM others"
+        ));
+        assert!(!select_has_limit("SELECT 'LIMIT' FROM people"));
+    }
+
+    #[test]
+    fn delete_where() {
+        assert!(!delete_has_where("DELETE FROM people"));
+        assert!(!delete_has_where("DELETE FROM people -- WHERE age > 18"));
+        assert!(delete_has_where("DELETE FROM people WHERE age > 18"));
+        assert!(delete_has_where("SELECT name FROM people"));
+    }
+}
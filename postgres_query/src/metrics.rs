@@ -0,0 +1,324 @@
+//! Collect metrics about the transactions run by an application.
+//!
+//! Hooks are registered globally with [`register`], and stay registered until the returned
+//! [`Registration`] is dropped. Transactions started with [`transaction_named`] are then timed as a
+//! whole, and every hook is called with a [`TransactionEvent`] once the transaction has been
+//! committed or rolled back.
+//!
+//! ```
+//! # use postgres_query::metrics;
+//! metrics::register(|event| {
+//!     println!("{} {:?} in {:?}", event.name, event.outcome, event.duration);
+//! })
+//! .keep();
+//! ```
+//!
+//! With the `deadpool` feature, clients acquired from a pool with [`acquire`] are timed as well:
+//! hooks registered with [`register_acquire`] are called with an [`AcquireEvent`] for every
+//! attempt, which makes a saturated pool visible long before requests start timing out.
+//!
+//! [`register`]: fn.register.html
+//! [`Registration`]: struct.Registration.html
+//! [`transaction_named`]: fn.transaction_named.html
+//! [`TransactionEvent`]: struct.TransactionEvent.html
+//! [`acquire`]: fn.acquire.html
+//! [`register_acquire`]: fn.register_acquire.html
+//! [`AcquireEvent`]: struct.AcquireEvent.html
+
+use crate::client::{CopyClient, GenericClient, Nested};
+use crate::error::Error;
+use crate::execute::ExecuteOptions;
+use crate::hooks::Hooks;
+use crate::Parameter;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::future::{BoxFuture, FutureExt};
+use postgres_types::{ToSql, Type};
+use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
+use tokio_postgres::{error::Error as SqlError, CopyInSink, RowStream, Statement, Transaction};
+
+#[cfg(feature = "deadpool")]
+use deadpool_postgres::{Client as DpClient, Pool, PoolError};
+
+pub use crate::hooks::Registration;
+
+static HOOKS: Hooks<dyn Fn(&TransactionEvent) + Send + Sync> = Hooks::new();
+
+#[cfg(feature = "deadpool")]
+static ACQUIRE_HOOKS: Hooks<dyn Fn(&AcquireEvent) + Send + Sync> = Hooks::new();
+
+/// How a transaction ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Committed,
+    /// The transaction was rolled back, either because it failed or because it could not be
+    /// committed.
+    RolledBack,
+}
+
+/// Reported to the hooks when a named transaction ends.
+#[derive(Debug, Clone)]
+pub struct TransactionEvent<'a> {
+    /// The name given to [`transaction_named`].
+    ///
+    /// [`transaction_named`]: fn.transaction_named.html
+    pub name: &'a str,
+    /// The time from beginning the transaction until it was committed or rolled back.
+    pub duration: Duration,
+    pub outcome: Outcome,
+}
+
+/// Register a hook, which is called whenever a named transaction ends, until the returned
+/// [`Registration`] is dropped.
+///
+/// [`Registration`]: struct.Registration.html
+pub fn register<F>(hook: F) -> Registration
+where
+    F: Fn(&TransactionEvent) + Send + Sync + 'static,
+{
+    HOOKS.register(Box::new(hook))
+}
+
+fn report(event: &TransactionEvent) {
+    HOOKS.for_each(|hook| hook(event));
+}
+
+/// Run `f` within a (nested) transaction named `name`, in the same way as [`Nested::nested`].
+///
+/// Every query executed through the [`NamedTransaction`] is tagged with the name (see
+/// [`ExecuteOptions::tag`]), unless the query sets a tag of its own. Once the transaction has been
+/// committed or rolled back, its duration and outcome are reported to the registered hooks.
+///
+/// As with [`Nested::nested`], the closure must own any state it needs.
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{metrics, query, Error, Result};
+/// # use futures::FutureExt;
+/// # async fn foo() -> Result<()> {
+/// # let mut client: Client = unimplemented!();
+/// let order = 7;
+/// metrics::transaction_named(&mut client, "checkout", move |tx| {
+///     async move {
+///         // Executed as `/* checkout */ UPDATE orders ...`
+///         query!("UPDATE orders SET paid = true WHERE id = $order", order)
+///             .execute(tx)
+///             .await?;
+///         Ok::<_, Error>(())
+///     }
+///     .boxed()
+/// })
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Nested::nested`]: ../client/trait.Nested.html#tymethod.nested
+/// [`NamedTransaction`]: struct.NamedTransaction.html
+/// [`ExecuteOptions::tag`]: ../execute/struct.ExecuteOptions.html#method.tag
+pub async fn transaction_named<C, F, T, E>(client: &mut C, name: &str, f: F) -> Result<T, E>
+where
+    C: Nested,
+    F: for<'s, 'a, 't> FnOnce(&'s mut NamedTransaction<'a, 't>) -> BoxFuture<'s, Result<T, E>>
+        + Send
+        + 'static,
+    T: Send,
+    E: From<Error> + Send,
+{
+    let options = ExecuteOptions::new().tag(name);
+    let start = Instant::now();
+
+    let result = client
+        .nested(move |transaction| {
+            async move {
+                let mut named = NamedTransaction {
+                    transaction,
+                    options,
+                };
+                f(&mut named).await
+            }
+            .boxed()
+        })
+        .await;
+
+    let outcome = match result {
+        Ok(_) => Outcome::Committed,
+        Err(_) => Outcome::RolledBack,
+    };
+
+    report(&TransactionEvent {
+        name,
+        duration: start.elapsed(),
+        outcome,
+    });
+
+    result
+}
+
+/// A transaction started by [`transaction_named`], which tags the queries executed through it.
+///
+/// Dereferences to the underlying transaction.
+///
+/// [`transaction_named`]: fn.transaction_named.html
+pub struct NamedTransaction<'a, 't> {
+    transaction: &'a mut Transaction<'t>,
+    options: ExecuteOptions,
+}
+
+impl<'t> Deref for NamedTransaction<'_, 't> {
+    type Target = Transaction<'t>;
+
+    fn deref(&self) -> &Self::Target {
+        self.transaction
+    }
+}
+
+impl DerefMut for NamedTransaction<'_, '_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.transaction
+    }
+}
+
+#[async_trait]
+impl GenericClient for NamedTransaction<'_, '_> {
+    async fn prepare(&self, sql: &str) -> Result<Statement, SqlError> {
+        GenericClient::prepare(&*self.transaction, sql).await
+    }
+
+    async fn prepare_static(&self, sql: &'static str) -> Result<Statement, SqlError> {
+        GenericClient::prepare_static(&*self.transaction, sql).await
+    }
+
+    async fn prepare_typed(&self, sql: &str, types: &[Type]) -> Result<Statement, SqlError> {
+        GenericClient::prepare_typed(&*self.transaction, sql, types).await
+    }
+
+    async fn prepare_typed_static(
+        &self,
+        sql: &'static str,
+        types: &[Type],
+    ) -> Result<Statement, SqlError> {
+        GenericClient::prepare_typed_static(&*self.transaction, sql, types).await
+    }
+
+    async fn prepare_tagged_static(
+        &self,
+        sql: &'static str,
+        tag: &str,
+    ) -> Result<Statement, SqlError> {
+        GenericClient::prepare_tagged_static(&*self.transaction, sql, tag).await
+    }
+
+    async fn execute_raw<'a>(
+        &'a self,
+        statement: &Statement,
+        parameters: &[&'a (dyn ToSql + Sync)],
+    ) -> Result<u64, SqlError> {
+        GenericClient::execute_raw(&*self.transaction, statement, parameters).await
+    }
+
+    async fn query_raw<'a>(
+        &'a self,
+        statement: &Statement,
+        parameters: &[&'a (dyn ToSql + Sync)],
+    ) -> Result<RowStream, SqlError> {
+        GenericClient::query_raw(&*self.transaction, statement, parameters).await
+    }
+
+    fn execute_options(&self) -> ExecuteOptions {
+        self.options.clone()
+    }
+
+    fn in_transaction(&self) -> bool {
+        true
+    }
+
+    fn __executed(&self, sql: &str, parameters: &[Parameter<'_>], tag: Option<&str>, rows: u64) {
+        GenericClient::__executed(&*self.transaction, sql, parameters, tag, rows)
+    }
+}
+
+#[async_trait]
+impl CopyClient for NamedTransaction<'_, '_> {
+    async fn copy_in_raw(&self, statement: &Statement) -> Result<CopyInSink<Bytes>, SqlError> {
+        CopyClient::copy_in_raw(&*self.transaction, statement).await
+    }
+}
+
+/// Reported to the hooks whenever [`acquire`] is done waiting for a client.
+///
+/// Requires the `deadpool` feature.
+///
+/// [`acquire`]: fn.acquire.html
+#[cfg(feature = "deadpool")]
+#[derive(Debug, Clone)]
+pub struct AcquireEvent {
+    /// The time spent waiting for the pool, whether or not a client was acquired.
+    pub waited: Duration,
+    /// `true` if a client was acquired, `false` if the pool timed out or failed to connect.
+    pub acquired: bool,
+}
+
+/// Register a hook, which is called whenever [`acquire`] is done waiting for a client, until the
+/// returned [`Registration`] is dropped.
+///
+/// Requires the `deadpool` feature.
+///
+/// [`acquire`]: fn.acquire.html
+/// [`Registration`]: struct.Registration.html
+#[cfg(feature = "deadpool")]
+pub fn register_acquire<F>(hook: F) -> Registration
+where
+    F: Fn(&AcquireEvent) + Send + Sync + 'static,
+{
+    ACQUIRE_HOOKS.register(Box::new(hook))
+}
+
+#[cfg(feature = "deadpool")]
+fn report_acquire(event: &AcquireEvent) {
+    ACQUIRE_HOOKS.for_each(|hook| hook(event));
+}
+
+/// Acquire a client from the pool, reporting the time spent waiting to the hooks registered with
+/// [`register_acquire`].
+///
+/// Requires the `deadpool` feature.
+///
+/// If the pool times out, this fails with [`Error::PoolTimeout`] (which records how long it waited)
+/// rather than the more general [`Error::Pool`], so that a saturated pool can be told apart from
+/// an unreachable database.
+///
+/// ```no_run
+/// # use postgres_query::{metrics, query, Result};
+/// # async fn foo(pool: deadpool_postgres::Pool) -> Result<()> {
+/// metrics::register_acquire(|event| {
+///     println!("waited {:?} for a client", event.waited);
+/// })
+/// .keep();
+///
+/// let client = metrics::acquire(&pool).await?;
+/// query!("SELECT 1").execute(&client).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`register_acquire`]: fn.register_acquire.html
+/// [`Error::PoolTimeout`]: ../enum.Error.html#variant.PoolTimeout
+/// [`Error::Pool`]: ../enum.Error.html#variant.Pool
+#[cfg(feature = "deadpool")]
+pub async fn acquire(pool: &Pool) -> Result<DpClient, Error> {
+    let start = Instant::now();
+    let result = pool.get().await;
+    let waited = start.elapsed();
+
+    report_acquire(&AcquireEvent {
+        waited,
+        acquired: result.is_ok(),
+    });
+
+    result.map_err(|error| match error {
+        PoolError::Timeout(_) => Error::PoolTimeout { waited },
+        error => Error::Pool(error),
+    })
+}
@@ -0,0 +1,422 @@
+//! Consume notifications sent with `NOTIFY`, surviving lost connections.
+//!
+//! A [`Subscription`] owns a dedicated connection to the database, on which it `LISTEN`s to the
+//! given channels. Whenever the connection is lost, it reconnects (with exponential backoff) and
+//! `LISTEN`s again. Since notifications sent while reconnecting are missed, a [`Gap`] is reported
+//! in their place, so that consumers may catch up by other means.
+//!
+//! By default it retries forever. With [`Subscription::max_attempts`] it gives up instead,
+//! reporting the error of the last attempt as [`Event::Failed`] and ending the stream.
+//!
+//! ```
+//! # use postgres_query::{notify::{self, Event}, Result};
+//! # use futures::StreamExt;
+//! # async fn foo() -> Result<(), Box<dyn std::error::Error>> {
+//! let config = "host=localhost user=postgres".parse()?;
+//! let mut events = notify::subscribe(config, &["orders"])
+//!     .dedup_by(1024, |notification| notification.payload().to_owned());
+//!
+//! while let Some(event) = events.next().await {
+//!     match event {
+//!         Event::Notification(notification) => println!("order {}", notification.payload()),
+//!         Event::Gap(_) => println!("reconnected: reload all orders"),
+//!         Event::Failed(error) => return Err(error.into()),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Subscription`]: struct.Subscription.html
+//! [`Gap`]: struct.Gap.html
+//! [`Subscription::max_attempts`]: struct.Subscription.html#method.max_attempts
+//! [`Event::Failed`]: enum.Event.html#variant.Failed
+
+use crate::execute::quote_identifier;
+use futures::future::{self, BoxFuture, Either, FutureExt};
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use futures::{pin_mut, ready};
+use futures_timer::Delay;
+use std::collections::{HashSet, VecDeque};
+use std::hash::Hash;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio_postgres::tls::{MakeTlsConnect, TlsConnect};
+use tokio_postgres::{error::Error as SqlError, AsyncMessage, Client, Config, NoTls, Socket};
+
+pub use tokio_postgres::Notification;
+
+/// Received from a [`Subscription`].
+///
+/// [`Subscription`]: struct.Subscription.html
+#[derive(Debug)]
+pub enum Event {
+    /// A notification was sent on one of the channels.
+    Notification(Notification),
+    /// The connection was lost, and notifications sent until it was re-established were missed.
+    Gap(Gap),
+    /// Every attempt to connect failed, the last one with this error. This is the last event of the
+    /// stream. Only reported if the number of attempts is limited with [`max_attempts`].
+    ///
+    /// [`max_attempts`]: struct.Subscription.html#method.max_attempts
+    Failed(SqlError),
+}
+
+/// Describes a period during which the connection was lost.
+///
+/// Reported once the subscription has been re-established, so notifications sent after the gap
+/// are received.
+#[derive(Debug)]
+pub struct Gap {
+    /// The error that caused the connection to be lost, if any.
+    pub error: Option<SqlError>,
+    /// The time from losing the connection until the channels were listened to again.
+    pub duration: Duration,
+    /// The number of attempts it took to reconnect.
+    pub attempts: u32,
+}
+
+/// Subscribe to the given channels, connecting to the database without TLS.
+///
+/// No connection is made until the subscription is first polled.
+pub fn subscribe<I>(config: Config, channels: I) -> Subscription
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    Subscription::with_tls(config, NoTls, channels)
+}
+
+type Connect = Box<dyn Fn() -> BoxFuture<'static, Result<Live, SqlError>> + Send + Sync>;
+
+type Filter = Box<dyn FnMut(&Notification) -> bool + Send>;
+
+/// A stream of notifications from one or more channels. See the [module documentation].
+///
+/// The stream only ends after reporting [`Event::Failed`], which requires a limit on the number
+/// of attempts to connect.
+///
+/// [`Event::Failed`]: enum.Event.html#variant.Failed
+/// [module documentation]: index.html
+pub struct Subscription {
+    state: Option<State>,
+    next: Option<BoxFuture<'static, (Option<Event>, State)>>,
+}
+
+struct State {
+    connect: Connect,
+    listen: String,
+    filter: Option<Filter>,
+    min_delay: Duration,
+    max_delay: Duration,
+    max_attempts: Option<u32>,
+    failed: bool,
+    live: Option<Live>,
+    lost: Option<(Instant, Option<SqlError>)>,
+    pending: VecDeque<Event>,
+}
+
+/// An open connection, and the messages received on it.
+struct Live {
+    // Dropping the client closes the connection.
+    client: Client,
+    messages: BoxStream<'static, Result<AsyncMessage, SqlError>>,
+}
+
+impl Subscription {
+    /// Subscribe to the given channels, connecting to the database with `tls`.
+    pub fn with_tls<T, I>(config: Config, tls: T, channels: I) -> Subscription
+    where
+        T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+        T::Stream: Send + 'static,
+        T::TlsConnect: Send,
+        <T::TlsConnect as TlsConnect<Socket>>::Future: Send,
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let listen = channels
+            .into_iter()
+            .map(|channel| format!("LISTEN {};", quote_identifier(channel.as_ref())))
+            .collect();
+
+        let connect: Connect = Box::new(move || {
+            let config = config.clone();
+            let tls = tls.clone();
+            async move {
+                let (client, mut connection) = config.connect(tls).await?;
+                let messages = stream::poll_fn(move |cx| connection.poll_message(cx)).boxed();
+                Ok(Live { client, messages })
+            }
+            .boxed()
+        });
+
+        Subscription {
+            state: Some(State {
+                connect,
+                listen,
+                filter: None,
+                min_delay: Duration::from_millis(100),
+                max_delay: Duration::from_secs(10),
+                max_attempts: None,
+                failed: false,
+                live: None,
+                lost: None,
+                pending: VecDeque::new(),
+            }),
+            next: None,
+        }
+    }
+
+    /// Skip notifications with the same key as one of the last `capacity` notifications received,
+    /// including those received before reconnecting.
+    ///
+    /// Useful when senders retry notifications which may already have been delivered.
+    ///
+    /// # Panics
+    ///
+    /// If the subscription has already been polled.
+    pub fn dedup_by<K, F>(mut self, capacity: usize, key: F) -> Subscription
+    where
+        F: Fn(&Notification) -> K + Send + 'static,
+        K: Clone + Hash + Eq + Send + 'static,
+    {
+        let mut recent = Recent::new(capacity);
+        self.state_mut().filter = Some(Box::new(move |notification| {
+            recent.insert(key(notification))
+        }));
+        self
+    }
+
+    /// Wait at least `min` before reconnecting, doubling the delay after every failed attempt up
+    /// to `max`. Defaults to 100 milliseconds and 10 seconds.
+    ///
+    /// # Panics
+    ///
+    /// If the subscription has already been polled.
+    pub fn retry_delay(mut self, min: Duration, max: Duration) -> Subscription {
+        let state = self.state_mut();
+        state.min_delay = min;
+        state.max_delay = max;
+        self
+    }
+
+    /// Give up after failing to connect `attempts` times in a row, reporting the last error as
+    /// [`Event::Failed`]. Unlimited by default.
+    ///
+    /// # Panics
+    ///
+    /// If the subscription has already been polled.
+    ///
+    /// [`Event::Failed`]: enum.Event.html#variant.Failed
+    pub fn max_attempts(mut self, attempts: u32) -> Subscription {
+        self.state_mut().max_attempts = Some(attempts);
+        self
+    }
+
+    fn state_mut(&mut self) -> &mut State {
+        self.state
+            .as_mut()
+            .expect("the subscription cannot be configured after it has been polled")
+    }
+}
+
+impl Stream for Subscription {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Event>> {
+        let this = &mut *self;
+
+        let next = match &mut this.next {
+            Some(next) => next,
+            None => {
+                let mut state = this.state.take().expect("polled after panicking");
+                this.next.get_or_insert(
+                    async move {
+                        let event = state.next_event().await;
+                        (event, state)
+                    }
+                    .boxed(),
+                )
+            }
+        };
+
+        let (event, state) = ready!(next.poll_unpin(cx));
+        this.next = None;
+        this.state = Some(state);
+        Poll::Ready(event)
+    }
+}
+
+impl State {
+    async fn next_event(&mut self) -> Option<Event> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            if self.failed {
+                return None;
+            }
+
+            let live = match &mut self.live {
+                Some(live) => live,
+                None => {
+                    if let Err(error) = self.reconnect().await {
+                        self.failed = true;
+                        return Some(Event::Failed(error));
+                    }
+                    continue;
+                }
+            };
+
+            match live.messages.next().await {
+                Some(Ok(AsyncMessage::Notification(notification))) => self.receive(notification),
+                Some(Ok(_)) => {}
+                Some(Err(error)) => self.lose(Some(error)),
+                None => self.lose(None),
+            }
+        }
+    }
+
+    fn receive(&mut self, notification: Notification) {
+        let fresh = match &mut self.filter {
+            Some(filter) => filter(&notification),
+            None => true,
+        };
+        if fresh {
+            self.pending.push_back(Event::Notification(notification));
+        }
+    }
+
+    fn lose(&mut self, error: Option<SqlError>) {
+        self.live = None;
+        self.lost = Some((Instant::now(), error));
+    }
+
+    /// Connect and listen to the channels, retrying until it succeeds or runs out of attempts.
+    async fn reconnect(&mut self) -> Result<(), SqlError> {
+        let mut attempts = 0;
+        let mut delay = self.min_delay;
+
+        let received = loop {
+            attempts += 1;
+            match self.listen().await {
+                Ok((live, received)) => {
+                    self.live = Some(live);
+                    break received;
+                }
+                Err(error) if self.max_attempts.is_some_and(|max| attempts >= max) => {
+                    return Err(error)
+                }
+                Err(_) => {
+                    Delay::new(delay).await;
+                    delay = Ord::min(delay * 2, self.max_delay);
+                }
+            }
+        };
+
+        if let Some((since, error)) = self.lost.take() {
+            self.pending.push_back(Event::Gap(Gap {
+                error,
+                duration: since.elapsed(),
+                attempts,
+            }));
+        }
+
+        for notification in received {
+            self.receive(notification);
+        }
+
+        Ok(())
+    }
+
+    /// Open a new connection and listen to the channels. Returns any notifications received
+    /// meanwhile.
+    async fn listen(&mut self) -> Result<(Live, Vec<Notification>), SqlError> {
+        let mut live = (self.connect)().await?;
+        let mut received = Vec::new();
+
+        {
+            let Live { client, messages } = &mut live;
+            let listen = client.batch_execute(&self.listen);
+            pin_mut!(listen);
+
+            // The connection has to be driven for the statements to complete.
+            loop {
+                match future::select(listen.as_mut(), messages.next()).await {
+                    Either::Left((result, _)) => break result?,
+                    Either::Right((Some(Ok(AsyncMessage::Notification(notification))), _)) => {
+                        received.push(notification)
+                    }
+                    Either::Right((Some(Ok(_)), _)) => {}
+                    Either::Right((Some(Err(error)), _)) => return Err(error),
+                    // The statements fail now that the connection is closed.
+                    Either::Right((None, _)) => break listen.await?,
+                }
+            }
+        }
+
+        Ok((live, received))
+    }
+}
+
+/// The most recently inserted keys.
+struct Recent<K> {
+    capacity: usize,
+    order: VecDeque<K>,
+    keys: HashSet<K>,
+}
+
+impl<K> Recent<K>
+where
+    K: Clone + Hash + Eq,
+{
+    fn new(capacity: usize) -> Recent<K> {
+        Recent {
+            capacity,
+            order: VecDeque::new(),
+            keys: HashSet::new(),
+        }
+    }
+
+    /// Insert a key, forgetting the oldest one if full. Returns `false` if the key was present.
+    fn insert(&mut self, key: K) -> bool {
+        if self.keys.contains(&key) {
+            return false;
+        }
+
+        if self.order.len() == self.capacity {
+            match self.order.pop_front() {
+                Some(oldest) => self.keys.remove(&oldest),
+                None => return true,
+            };
+        }
+
+        self.order.push_back(key.clone());
+        self.keys.insert(key);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_keys() {
+        let mut recent = Recent::new(2);
+        assert!(recent.insert(1));
+        assert!(recent.insert(2));
+        assert!(!recent.insert(1));
+        assert!(recent.insert(3));
+        assert!(recent.insert(1));
+        assert!(!recent.insert(3));
+    }
+
+    #[test]
+    fn without_capacity() {
+        let mut recent = Recent::new(0);
+        assert!(recent.insert(1));
+        assert!(recent.insert(1));
+    }
+}
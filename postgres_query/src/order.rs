@@ -0,0 +1,236 @@
+//! Sort and limit queries according to user input.
+//!
+//! Column names cannot be bound as parameters, so sorting by a column chosen by the user means
+//! inserting it into the SQL. [`OrderBy::try_new`] parses the sort order given by the user and
+//! checks every column against a list of allowed columns, after which [`Query::order_by`] appends
+//! it to a query:
+//!
+//! ```
+//! # use postgres_query::{order::OrderBy, query, Result};
+//! # fn foo() -> Result<()> {
+//! // From the query string of a request, such as `?sort=-age,name`.
+//! let sort = "-age,name";
+//!
+//! let order = OrderBy::try_new(sort, &["name", "age", "created_at"])?;
+//! let query = query!("SELECT name, age FROM people WHERE age > $age", age = 18)
+//!     .order_by(&order)
+//!     .limit(&20);
+//!
+//! assert_eq!(
+//!     query.sql(),
+//!     "SELECT name, age FROM people WHERE age > $1 ORDER BY age DESC, name ASC LIMIT $2"
+//! );
+//!
+//! assert!(OrderBy::try_new("age; DROP TABLE people", &["name", "age"]).is_err());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Queries written with `query!` are still prepared through the statement cache afterwards: there
+//! is only one statement for each of the (finitely many) orders allowed, and the limit is bound as
+//! a parameter.
+//!
+//! [`OrderBy::try_new`]: struct.OrderBy.html#method.try_new
+//! [`Query::order_by`]: ../struct.Query.html#method.order_by
+
+use crate::Query;
+use thiserror::Error;
+
+/// An error in a sort order given by the user.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Error {
+    #[error("the sort order does not contain any columns")]
+    Empty,
+
+    #[error("cannot sort by `{column}`")]
+    UnknownColumn { column: String },
+
+    #[error("`{direction}` is not a sort direction, expected `asc` or `desc`")]
+    UnknownDirection { direction: String },
+}
+
+/// The direction in which to sort a column.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+/// A validated sort order, which only refers to allowed columns.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OrderBy {
+    columns: Vec<(&'static str, Direction)>,
+}
+
+impl OrderBy {
+    /// Parse a sort order given by the user, where every column must be one of `allowed`.
+    ///
+    /// The order is a list of columns separated by commas, each of which may be followed by `asc`
+    /// or `desc` (in any case), or be prefixed by `-` to sort in descending order:
+    /// `name, age desc` and `name,-age` are the same order. Columns are ascending by default.
+    ///
+    /// Only the names in `allowed` end up in the SQL, and they are inserted verbatim, so they may be
+    /// qualified (such as `p.name`). The input has to match one of them exactly.
+    pub fn try_new(input: &str, allowed: &[&'static str]) -> Result<OrderBy, Error> {
+        let mut columns = Vec::new();
+
+        for term in input.split(',') {
+            let term = term.trim();
+            if term.is_empty() {
+                continue;
+            }
+
+            let (name, direction) = match term.strip_prefix('-') {
+                Some(name) => (name.trim_start(), Some("desc")),
+                None => match term.split_once(char::is_whitespace) {
+                    Some((name, direction)) => (name, Some(direction.trim())),
+                    None => (term, None),
+                },
+            };
+
+            let column = allowed
+                .iter()
+                .find(|&&column| column == name)
+                .ok_or_else(|| Error::UnknownColumn {
+                    column: name.to_owned(),
+                })?;
+            let direction = match direction {
+                Some(direction) => parse_direction(direction)?,
+                None => Direction::Asc,
+            };
+
+            columns.push((*column, direction));
+        }
+
+        if columns.is_empty() {
+            return Err(Error::Empty);
+        }
+
+        Ok(OrderBy { columns })
+    }
+
+    /// Sort by a single column, which is inserted verbatim.
+    pub fn column(column: &'static str, direction: Direction) -> OrderBy {
+        OrderBy {
+            columns: vec![(column, direction)],
+        }
+    }
+
+    /// The columns to sort by, in order of priority.
+    pub fn columns(&self) -> &[(&'static str, Direction)] {
+        &self.columns
+    }
+
+    /// The order as SQL, without the `ORDER BY` keywords.
+    pub fn sql(&self) -> String {
+        self.columns
+            .iter()
+            .map(|(column, direction)| match direction {
+                Direction::Asc => format!("{} ASC", column),
+                Direction::Desc => format!("{} DESC", column),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn parse_direction(direction: &str) -> Result<Direction, Error> {
+    if direction.eq_ignore_ascii_case("asc") {
+        Ok(Direction::Asc)
+    } else if direction.eq_ignore_ascii_case("desc") {
+        Ok(Direction::Desc)
+    } else {
+        Err(Error::UnknownDirection {
+            direction: direction.to_owned(),
+        })
+    }
+}
+
+impl<'a> Query<'a> {
+    /// Append an `ORDER BY` clause to the end of this query, which must not have one already.
+    /// See the [module documentation].
+    ///
+    /// [module documentation]: order/index.html
+    pub fn order_by(mut self, order: &OrderBy) -> Query<'a> {
+        self.append_interned(Query::new(format!(" ORDER BY {}", order.sql()), Vec::new()));
+        self
+    }
+
+    /// Append a `LIMIT` clause to the end of this query, which must not have one already. The
+    /// limit is bound as a parameter.
+    pub fn limit(mut self, limit: &'a i64) -> Query<'a> {
+        self.append_interned(Query::new_static(" LIMIT $1", vec![limit]));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sql;
+
+    const ALLOWED: &[&str] = &["name", "age", "p.created_at"];
+
+    #[test]
+    fn parse_orders() {
+        let order = OrderBy::try_new("name, age DESC,p.created_at asc", ALLOWED).unwrap();
+        assert_eq!(order.sql(), "name ASC, age DESC, p.created_at ASC");
+
+        let order = OrderBy::try_new(" -age ,name", ALLOWED).unwrap();
+        assert_eq!(
+            order.columns(),
+            [("age", Direction::Desc), ("name", Direction::Asc)]
+        );
+    }
+
+    #[test]
+    fn static_queries_stay_static() {
+        let order = OrderBy::try_new("-age", ALLOWED).unwrap();
+        let first = Query::new_static("SELECT name FROM people", Vec::new())
+            .order_by(&order)
+            .limit(&10);
+        let second = Query::new_static("SELECT name FROM people", Vec::new())
+            .order_by(&order)
+            .limit(&20);
+
+        assert_eq!(
+            first.sql(),
+            "SELECT name FROM people ORDER BY age DESC LIMIT $1"
+        );
+        match (&first.sql, &second.sql) {
+            (Sql::Static(first, _), Sql::Static(second, _)) => {
+                assert!(std::ptr::eq(*first, *second))
+            }
+            _ => panic!("expected static SQL"),
+        }
+    }
+
+    #[test]
+    fn reject_orders() {
+        assert_eq!(OrderBy::try_new(" , ", ALLOWED), Err(Error::Empty));
+        assert_eq!(
+            OrderBy::try_new("Name", ALLOWED),
+            Err(Error::UnknownColumn {
+                column: "Name".to_owned()
+            })
+        );
+        assert_eq!(
+            OrderBy::try_new("name; DROP TABLE people", ALLOWED),
+            Err(Error::UnknownColumn {
+                column: "name;".to_owned()
+            })
+        );
+        assert_eq!(
+            OrderBy::try_new("(SELECT 1)", ALLOWED),
+            Err(Error::UnknownColumn {
+                column: "(SELECT".to_owned()
+            })
+        );
+        assert_eq!(
+            OrderBy::try_new("name sideways", ALLOWED),
+            Err(Error::UnknownDirection {
+                direction: "sideways".to_owned()
+            })
+        );
+    }
+}
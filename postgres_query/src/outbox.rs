@@ -0,0 +1,152 @@
+//! A transactional outbox: publish events reliably alongside writes to the database.
+//!
+//! Events are [enqueued] in the same transaction as the changes they describe, so either both are
+//! committed or neither is. A separate process then [drains] the outbox, handing each event to
+//! whatever system it should be delivered to. Events are only removed once they have been handled,
+//! which means that each event is delivered at least once.
+//!
+//! The events are stored in a table called `outbox`, which can be created with [`create_table`]:
+//!
+//! ```sql
+//! CREATE TABLE IF NOT EXISTS outbox (
+//!     id BIGSERIAL PRIMARY KEY,
+//!     topic TEXT NOT NULL,
+//!     payload TEXT NOT NULL,
+//!     created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+//! )
+//! ```
+//!
+//! [enqueued]: fn.enqueue.html
+//! [drains]: fn.drain.html
+//! [`create_table`]: fn.create_table.html
+
+use crate::client::{GenericClient, Nested};
+use crate::error::{Error, Result};
+use crate::Query;
+use futures::future::{Future, FutureExt};
+use std::convert::TryFrom;
+
+const CREATE_TABLE: &str = "CREATE TABLE IF NOT EXISTS outbox (
+    id BIGSERIAL PRIMARY KEY,
+    topic TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+)";
+
+const INSERT: &str = "INSERT INTO outbox (topic, payload) VALUES ($1, $2)";
+
+const CLAIM: &str =
+    "SELECT id, topic, payload FROM outbox ORDER BY id LIMIT $1 FOR UPDATE SKIP LOCKED";
+
+const DELETE: &str = "DELETE FROM outbox WHERE id = $1";
+
+/// An event stored in the outbox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    /// Identifies the event. Events are drained in the order of their ids.
+    pub id: i64,
+    /// The topic (or channel, queue, etc.) that the event should be delivered to.
+    pub topic: String,
+    /// The contents of the event.
+    pub payload: String,
+}
+
+/// Create the `outbox` table, unless it already exists.
+pub async fn create_table<C>(client: &C) -> Result<()>
+where
+    C: GenericClient + Sync,
+{
+    Query::new_static(CREATE_TABLE, Vec::new())
+        .execute(client)
+        .await?;
+    Ok(())
+}
+
+/// Add an event to the outbox.
+///
+/// This should be called within the transaction that makes the changes the event describes.
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{outbox, query, Result};
+/// # async fn foo() -> Result<(), Box<dyn std::error::Error>> {
+/// # let mut client: Client = unimplemented!();
+/// let tx = client.transaction().await?;
+///
+/// query!("INSERT INTO people VALUES ($name)", name = "John Wick")
+///     .execute(&tx)
+///     .await?;
+/// outbox::enqueue(&tx, "people.created", r#"{"name":"John Wick"}"#).await?;
+///
+/// tx.commit().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn enqueue<C>(client: &C, topic: &str, payload: &str) -> Result<()>
+where
+    C: GenericClient + Sync,
+{
+    Query::new_static(INSERT, vec![&topic, &payload])
+        .execute(client)
+        .await?;
+    Ok(())
+}
+
+/// Hand up to `batch` of the oldest events in the outbox to `handler`, and remove the events that
+/// were handled. Returns the number of events handled.
+///
+/// The events are claimed with `FOR UPDATE SKIP LOCKED` in a (nested) transaction, so multiple
+/// pollers may drain the same outbox concurrently. If the handler fails, the transaction is rolled
+/// back and the error is returned: the failing event, and any event after it in the batch, will be
+/// handed out again by a later call.
+///
+/// The handler is moved into the transaction, so it must own any state it needs (for example,
+/// through an `Arc`).
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{outbox, Error};
+/// # async fn publish(topic: &str, payload: &str) -> Result<(), Error> { Ok(()) }
+/// # async fn foo() -> Result<(), Error> {
+/// # let mut client: Client = unimplemented!();
+/// loop {
+///     let handled = outbox::drain(&mut client, 100, |message| async move {
+///         publish(&message.topic, &message.payload).await
+///     })
+///     .await?;
+///
+///     if handled == 0 {
+///         break;
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn drain<C, F, Fut, E>(client: &mut C, batch: usize, mut handler: F) -> Result<usize, E>
+where
+    C: Nested,
+    F: FnMut(Message) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), E>> + Send,
+    E: From<Error> + Send,
+{
+    let limit = i64::try_from(batch).unwrap_or(i64::MAX);
+
+    client
+        .nested(move |tx| {
+            async move {
+                let messages: Vec<(i64, String, String)> =
+                    Query::new_static(CLAIM, vec![&limit]).fetch(&*tx).await?;
+
+                let count = messages.len();
+                for (id, topic, payload) in messages {
+                    handler(Message { id, topic, payload }).await?;
+
+                    Query::new_static(DELETE, vec![&id]).execute(&*tx).await?;
+                }
+
+                Ok(count)
+            }
+            .boxed()
+        })
+        .await
+}
@@ -1,61 +1,412 @@
-use super::Parameter;
-use crate::error::{Error, ParseError, Result};
+use super::{Bindings, Parameter};
+use crate::error::{ParseError, Result};
+use crate::execute::quote_identifier;
+use postgres_types::Type;
+use std::borrow::Cow;
 use std::fmt::Write;
-use std::iter::Peekable;
 
 pub fn parse<'a>(
     text: &str,
     bindings: &[(&str, Parameter<'a>)],
-) -> Result<(String, Vec<Parameter<'a>>)> {
+) -> Result<(String, Vec<Parameter<'a>>, Bindings)> {
     let mut sql = String::with_capacity(text.len());
     let mut parameters = Vec::with_capacity(bindings.len());
     let mut param_indices = vec![None; bindings.len()];
 
-    let mut chars = text.chars().peekable();
+    let mut tokens = named_tokens(text).peekable();
 
-    while let Some(ch) = chars.next() {
-        if ch != '$' {
-            sql.push(ch);
-        } else if let Some('$') = chars.peek() {
-            sql.push(chars.next().unwrap());
-        } else {
-            let name = next_identifier(&mut chars)?;
+    while let Some((_, token)) = tokens.next() {
+        match token {
+            Token::Binding(binding) => {
+                let name = &binding[1..];
+                let argument = bindings
+                    .iter()
+                    .position(|(binding, _)| *binding == name)
+                    .ok_or_else(|| ParseError::UndefinedBinding {
+                        binding: name.to_owned(),
+                    })?;
 
-            let argument = bindings
-                .iter()
-                .position(|(binding, _)| *binding == name)
-                .ok_or_else(|| ParseError::UndefinedBinding { binding: name })?;
+                let index = param_indices[argument].unwrap_or_else(|| {
+                    let (_, value) = bindings[argument];
+                    parameters.push(value);
+                    let index = parameters.len();
+                    param_indices[argument] = Some(index);
+                    index
+                });
 
-            let index = param_indices[argument].unwrap_or_else(|| {
-                let (_, value) = bindings[argument];
-                parameters.push(value);
-                let index = parameters.len();
-                param_indices[argument] = Some(index);
-                index
-            });
+                write!(sql, "${}", index).unwrap();
+            }
+            Token::Parameter(parameter) => {
+                let index = parameter[1..].to_owned();
+                return Err(ParseError::PositionalParameter { index }.into());
+            }
+            Token::Other("$") => {
+                let found = tokens
+                    .peek()
+                    .and_then(|(_, token)| token.text().chars().next());
+                return Err(ParseError::EmptyIdentifier { found }.into());
+            }
+            _ => sql.push_str(&unescape(token.text())),
+        }
+    }
+
+    let names = bindings
+        .iter()
+        .zip(param_indices)
+        .filter_map(|((name, _), index)| Some(((*name).to_owned(), index?)))
+        .collect();
+
+    Ok((sql, parameters, Bindings::Dynamic(names)))
+}
+
+/// Picks how `query_dyn!` parses its text, using autoref-based dispatch: text which is `AsRef<str>`
+/// is parsed in place, and text which is only `Display` is formatted first.
+pub mod text {
+    use crate::{Parameter, Query, Result};
+    use std::fmt::Display;
+
+    pub struct Text<'t, T: ?Sized>(pub &'t T);
+
+    pub trait ViaAsRef {
+        fn __parse_query<'a>(&self, bindings: &[(&str, Parameter<'a>)]) -> Result<Query<'a>>;
+    }
+
+    impl<T> ViaAsRef for Text<'_, T>
+    where
+        T: AsRef<str> + ?Sized,
+    {
+        fn __parse_query<'a>(&self, bindings: &[(&str, Parameter<'a>)]) -> Result<Query<'a>> {
+            Query::parse(self.0.as_ref(), bindings)
+        }
+    }
+
+    pub trait ViaDisplay {
+        fn __parse_query<'a>(&self, bindings: &[(&str, Parameter<'a>)]) -> Result<Query<'a>>;
+    }
+
+    impl<T> ViaDisplay for &Text<'_, T>
+    where
+        T: Display + ?Sized,
+    {
+        fn __parse_query<'a>(&self, bindings: &[(&str, Parameter<'a>)]) -> Result<Query<'a>> {
+            Query::parse_display(self.0, bindings)
+        }
+    }
+}
+
+/// Deduplicates the text of `query!` across call sites.
+///
+/// Cached statements are keyed by the address of their text, but identical literals in different
+/// expansions of `query!` need not share an address. Each expansion stores its text in an
+/// `Interned`, which looks up a canonical copy by content the first time it is used.
+pub mod intern {
+    use std::collections::BTreeSet;
+    use std::sync::{Mutex, OnceLock};
+
+    static TEXTS: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+    pub struct Interned {
+        text: &'static str,
+        canonical: OnceLock<&'static str>,
+    }
+
+    impl Interned {
+        pub const fn new(text: &'static str) -> Interned {
+            Interned {
+                text,
+                canonical: OnceLock::new(),
+            }
+        }
+
+        pub fn get(&self) -> &'static str {
+            self.canonical.get_or_init(|| {
+                let mut texts = TEXTS.lock().unwrap_or_else(|error| error.into_inner());
+                match texts.get(self.text) {
+                    Some(canonical) => canonical,
+                    None => {
+                        texts.insert(self.text);
+                        self.text
+                    }
+                }
+            })
+        }
+    }
+
+    /// The interned copy of `text`, which is leaked the first time it is seen.
+    pub fn intern(text: &str) -> &'static str {
+        let mut texts = TEXTS.lock().unwrap_or_else(|error| error.into_inner());
+        match texts.get(text) {
+            Some(canonical) => canonical,
+            None => {
+                let text = Box::leak(text.to_owned().into_boxed_str());
+                texts.insert(text);
+                text
+            }
+        }
+    }
+}
+
+/// Append `text` to `sql`, shifting the index of every positional parameter (`$1`, `$2`, ...) by
+/// `offset`. Parameters inside string literals, quoted identifiers and comments are left as is.
+pub fn renumber(sql: &mut String, text: &str, offset: usize) {
+    for (_, token) in tokens(text) {
+        match token {
+            Token::Parameter(parameter) => match parameter[1..].parse::<usize>() {
+                Ok(index) => write!(sql, "${}", index + offset).unwrap(),
+                Err(_) => sql.push_str(parameter),
+            },
+            _ => sql.push_str(token.text()),
+        }
+    }
+}
+
+/// Find the keywords at the top level of `sql`: outside of parentheses, string literals, quoted
+/// identifiers and comments. Returns the byte offset of each keyword, in uppercase. Semicolons are
+/// included as `;`.
+pub fn top_level_keywords(sql: &str) -> Vec<(usize, String)> {
+    let mut keywords = Vec::new();
+    let mut depth = 0usize;
+
+    for (start, token) in tokens(sql) {
+        match token {
+            Token::Other("(") => depth += 1,
+            Token::Other(")") => depth = depth.saturating_sub(1),
+            Token::Other(";") if depth == 0 => keywords.push((start, ";".to_owned())),
+            Token::Word(word) if depth == 0 => keywords.push((start, word.to_uppercase())),
+            _ => {}
+        }
+    }
+
+    keywords
+}
+
+/// Count the rows listed in the `VALUES` clause at the top level of `sql`, such as the two rows of
+/// `INSERT INTO t VALUES (1), (2)`. Returns `None` if there is no such clause.
+pub fn values_rows(sql: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut rows = None;
+
+    for (_, token) in tokens(sql) {
+        match (rows, token) {
+            (_, Token::Other("(")) => depth += 1,
+            (_, Token::Other(")")) => depth = depth.saturating_sub(1),
+            (_, _) if depth > 0 => {}
+            (None, Token::Word(word)) if word.eq_ignore_ascii_case("VALUES") => rows = Some(1),
+            (Some(count), Token::Other(",")) => rows = Some(count + 1),
+            (Some(_), Token::Word(_)) | (Some(_), Token::Other(";")) => break,
+            _ => {}
+        }
+    }
+
+    rows
+}
+
+/// Remove the semicolon which may terminate `sql`, along with any whitespace and comments after
+/// it. Returns `None` if `sql` consists of more than one statement.
+pub fn single_statement(sql: &str) -> Option<&str> {
+    let mut end = None;
+
+    for (start, token) in tokens(sql) {
+        match (end, token) {
+            (None, Token::Other(";")) => end = Some(start),
+            (None, _) | (Some(_), Token::Comment(_)) => {}
+            (Some(_), token) if token.text().trim().is_empty() => {}
+            (Some(_), _) => return None,
+        }
+    }
+
+    Some(&sql[..end.unwrap_or(sql.len())])
+}
+
+/// Cast each positional parameter of `sql` to the type at its index in `types`, which may be
+/// shorter than the number of parameters. Parameters inside string literals, quoted identifiers
+/// and comments are left as is.
+pub fn cast_parameters(sql: &str, types: &[Type]) -> String {
+    let mut cast = String::with_capacity(sql.len());
 
-            write!(sql, "${}", index).unwrap();
+    for (_, token) in tokens(sql) {
+        cast.push_str(token.text());
+
+        if let Token::Parameter(parameter) = token {
+            let ty = parameter[1..]
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| types.get(index.checked_sub(1)?));
+            if let Some(ty) = ty {
+                write!(
+                    cast,
+                    "::{}.{}",
+                    quote_identifier(ty.schema()),
+                    quote_identifier(ty.name())
+                )
+                .unwrap();
+            }
         }
     }
 
-    Ok((sql, parameters))
+    cast
 }
 
-fn next_identifier(chars: &mut Peekable<impl Iterator<Item = char>>) -> Result<String> {
-    let mut name = String::new();
+/// A piece of SQL, as split by [`tokens`] or [`named_tokens`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// A keyword or an unquoted identifier.
+    Word(&'a str),
+    /// A positional parameter, such as `$1`.
+    Parameter(&'a str),
+    /// A named binding, such as `$name`. Only found by [`named_tokens`].
+    Binding(&'a str),
+    /// An escaped dollar sign (`$$`). Only found by [`named_tokens`].
+    Escaped(&'a str),
+    /// A string literal, quoted identifier or dollar-quoted string, including its quotes.
+    Quoted(&'a str),
+    /// A comment, including its delimiters.
+    Comment(&'a str),
+    /// A single character of anything else, such as whitespace or punctuation.
+    Other(&'a str),
+}
 
-    while let Some(&ch) = chars.peek() {
-        if ch.is_ascii_alphanumeric() || ch == '_' {
-            name.push(chars.next().unwrap());
-        } else {
-            break;
+impl<'a> Token<'a> {
+    /// The text of the token, exactly as it appears in the SQL.
+    pub fn text(self) -> &'a str {
+        match self {
+            Token::Word(text)
+            | Token::Parameter(text)
+            | Token::Binding(text)
+            | Token::Escaped(text)
+            | Token::Quoted(text)
+            | Token::Comment(text)
+            | Token::Other(text) => text,
         }
     }
+}
 
-    if name.is_empty() {
-        let found = chars.peek().copied();
-        return Err(Error::from(ParseError::EmptyIdentifier { found }));
+/// Split `sql` into tokens, each with its byte offset. Joining the tokens gives back `sql`.
+///
+/// Unterminated literals and comments extend to the end of `sql`.
+pub fn tokens<'a>(sql: &'a str) -> impl Iterator<Item = (usize, Token<'a>)> {
+    split(sql, false)
+}
+
+/// Split text with named bindings, as given to [`parse`], into tokens. Unlike [`tokens`], `$$` is
+/// an escaped dollar sign and `$name` is a binding, so there are no dollar-quoted strings. Literals
+/// and comments may still contain escaped dollar signs, see [`unescape`].
+///
+/// The macros mirror this in `postgres_query_macro/src/tokens.rs`, and have to be kept in sync.
+pub fn named_tokens<'a>(text: &'a str) -> impl Iterator<Item = (usize, Token<'a>)> {
+    split(text, true)
+}
+
+/// Replace the escaped dollar signs (`$$`) in a token of text with named bindings.
+pub fn unescape(text: &str) -> Cow<'_, str> {
+    if text.contains("$$") {
+        Cow::Owned(text.replace("$$", "$"))
+    } else {
+        Cow::Borrowed(text)
     }
+}
+
+fn split<'a>(sql: &'a str, named: bool) -> impl Iterator<Item = (usize, Token<'a>)> {
+    let mut position = 0;
+
+    std::iter::from_fn(move || {
+        let start = position;
+        let rest = &sql[start..];
+        let ch = rest.chars().next()?;
+
+        let (len, token): (usize, fn(&'a str) -> Token<'a>) = match ch {
+            '\'' | '"' => (quoted_len(rest, ch), Token::Quoted),
+            '-' if rest.starts_with("--") => {
+                (rest.find('\n').unwrap_or(rest.len()), Token::Comment)
+            }
+            '/' if rest.starts_with("/*") => {
+                let len = rest[2..].find("*/").map_or(rest.len(), |end| end + 4);
+                (len, Token::Comment)
+            }
+            '$' if named => binding_len(rest),
+            '$' => dollar_len(rest),
+            _ if ch.is_alphabetic() || ch == '_' => {
+                // Only plain SQL allows `$` in identifiers: with named bindings, it starts one.
+                let word = |ch: char| ch.is_alphanumeric() || ch == '_' || (ch == '$' && !named);
+                let len = rest.find(|ch: char| !word(ch)).unwrap_or(rest.len());
+                (len, Token::Word)
+            }
+            _ => (ch.len_utf8(), Token::Other),
+        };
+
+        position += len;
+        Some((start, token(&rest[..len])))
+    })
+}
+
+/// The length of the literal or quoted identifier at the start of `rest`, delimited by `quote`.
+fn quoted_len(rest: &str, quote: char) -> usize {
+    let mut chars = rest.char_indices().skip(1).peekable();
+
+    while let Some((index, ch)) = chars.next() {
+        if ch == quote {
+            // A doubled quote is an escaped quote.
+            if chars.peek().map(|&(_, ch)| ch) == Some(quote) {
+                chars.next();
+            } else {
+                return index + 1;
+            }
+        }
+    }
+
+    rest.len()
+}
+
+/// The length and kind of the token at the start of `rest`, which starts with `$`, in text with
+/// named bindings: an escaped dollar sign (`$$`), a binding (`$name`) or a parameter (`$1`).
+fn binding_len<'a>(rest: &'a str) -> (usize, fn(&'a str) -> Token<'a>) {
+    let after = &rest[1..];
+
+    if after.starts_with('$') {
+        return (2, Token::Escaped);
+    }
+
+    let len = after
+        .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_'))
+        .unwrap_or(after.len());
+    match after[..len].chars().next() {
+        None => (1, Token::Other),
+        Some(first) if first.is_ascii_digit() => (1 + len, Token::Parameter),
+        Some(_) => (1 + len, Token::Binding),
+    }
+}
+
+/// The length and kind of the token at the start of `rest`, which starts with `$`: either a
+/// parameter (`$1`) or a dollar-quoted string (`$tag$ ... $tag$`).
+fn dollar_len<'a>(rest: &'a str) -> (usize, fn(&'a str) -> Token<'a>) {
+    let after = &rest[1..];
+
+    if after.starts_with(|ch: char| ch.is_ascii_digit()) {
+        let digits = after
+            .find(|ch: char| !ch.is_ascii_digit())
+            .unwrap_or(after.len());
+        return (1 + digits, Token::Parameter);
+    }
+
+    let tag_len = after
+        .find(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+        .unwrap_or(after.len());
+    if !after[tag_len..].starts_with('$') {
+        return (1, Token::Other);
+    }
+
+    let delimiter = &rest[..tag_len + 2];
+    let body = &rest[delimiter.len()..];
+    let len = body
+        .find(delimiter)
+        .map_or(rest.len(), |end| delimiter.len() + end + delimiter.len());
+    (len, Token::Quoted)
+}
 
-    Ok(name)
+/// `true` if `text` contains a binding of `name` (`$name`), as understood by [`parse`].
+pub fn refers_to(text: &str, name: &str) -> bool {
+    named_tokens(text).any(|(_, token)| match token {
+        Token::Binding(binding) => binding[1..] == *name,
+        _ => false,
+    })
 }
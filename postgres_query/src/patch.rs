@@ -0,0 +1,117 @@
+//! Partial updates, such as those made in response to a `PATCH` request.
+//!
+//! See [`Patch`] and [`update!`].
+//!
+//! [`Patch`]: trait.Patch.html
+//! [`update!`]: ../macro.update.html
+
+use crate::{Parameter, Query};
+
+/// A set of changes to some of the columns of a row.
+///
+/// This trait is usually derived. Each field of the struct corresponds to the column with the same
+/// name, and fields of type `Option<T>` are left out of the update when they are `None`:
+///
+/// ```
+/// # use postgres_query::Patch;
+/// #[derive(Patch)]
+/// struct PersonPatch {
+///     // Never updated
+///     #[patch(skip)]
+///     id: i32,
+///     // Only updated if `Some`
+///     name: Option<String>,
+///     // Updates the column `shoe_size`
+///     #[patch(rename = "shoe_size")]
+///     shoe: Option<i32>,
+///     // Only updated if `Some`, in which case the column may be set to `NULL`
+///     nickname: Option<Option<String>>,
+///     // Always updated
+///     age: i32,
+/// }
+/// ```
+pub trait Patch {
+    /// The columns to update, and their new values.
+    fn assignments(&self) -> Vec<(&'static str, Parameter<'_>)>;
+}
+
+/// Construct an `UPDATE` of the rows in `table` matching `filter`, which assigns the values in
+/// `patch`. See [`update!`] for a more convenient way to call this function.
+///
+/// The table and column names are inserted verbatim into the query. Returns `None` if the patch
+/// does not assign any columns, since there is nothing to update.
+///
+/// [`update!`]: ../macro.update.html
+pub fn update<'a, P>(table: &str, patch: &'a P, filter: Query<'a>) -> Option<Query<'a>>
+where
+    P: Patch + ?Sized,
+{
+    let assignments = patch.assignments();
+    if assignments.is_empty() {
+        return None;
+    }
+
+    let mut sql = format!("UPDATE {} SET ", table);
+    let mut parameters = Vec::with_capacity(assignments.len() + filter.parameters().len());
+    for (i, (column, value)) in assignments.into_iter().enumerate() {
+        if i != 0 {
+            sql.push_str(", ");
+        }
+        sql.push_str(&format!("{} = ${}", column, i + 1));
+        parameters.push(value);
+    }
+    sql.push_str(" WHERE ");
+
+    let mut query = Query::new(sql, parameters);
+    query.append(filter);
+    Some(query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct PersonPatch {
+        name: Option<&'static str>,
+        age: i32,
+    }
+
+    impl Patch for PersonPatch {
+        fn assignments(&self) -> Vec<(&'static str, Parameter<'_>)> {
+            let mut assignments = Vec::<(&'static str, Parameter<'_>)>::new();
+            if let Some(name) = &self.name {
+                assignments.push(("name", name));
+            }
+            assignments.push(("age", &self.age));
+            assignments
+        }
+    }
+
+    #[test]
+    fn omits_missing_columns() {
+        let filter = Query::new_static("id = $1", vec![&7]);
+        let patch = PersonPatch {
+            name: None,
+            age: 42,
+        };
+
+        let query = update("people", &patch, filter).unwrap();
+        assert_eq!(query.sql(), "UPDATE people SET age = $1 WHERE id = $2");
+        assert_eq!(query.parameters().len(), 2);
+    }
+
+    #[test]
+    fn renumbers_filter() {
+        let filter = Query::new_static("id = $1", vec![&7]);
+        let patch = PersonPatch {
+            name: Some("John Wick"),
+            age: 42,
+        };
+
+        let query = update("people", &patch, filter).unwrap();
+        assert_eq!(
+            query.sql(),
+            "UPDATE people SET name = $1, age = $2 WHERE id = $3"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! Measure how long it takes to extract values from rows, field by field.
+//!
+//! Requires the `profile` feature.
+//!
+//! Types deriving `FromSqlRow` time the extraction of each of their fields. Hooks registered with
+//! [`register`] are called with a [`DecodeEvent`] once the rows of a query have been extracted,
+//! which tells whether decoding (of `JSON` or `NUMERIC` columns, for example) is a significant
+//! part of the time spent reading. Hooks stay registered until the returned [`Registration`] is
+//! dropped:
+//!
+//! ```
+//! # use postgres_query::profile;
+//! let registration = profile::register(|event| {
+//!     println!("{} rows of {} in {:?}", event.rows, event.type_name, event.duration);
+//!     for field in event.fields {
+//!         println!("  {}::{}: {:?}", field.container, field.field, field.duration);
+//!     }
+//! });
+//! ```
+//!
+//! Rows fetched together (such as with [`Query::fetch`]) are reported as a single event, with the
+//! time of each field summed over all rows. Rows extracted one at a time (such as with
+//! [`Query::fetch_one`] or from a stream) are reported individually. The fields of flattened values
+//! are reported along with those of the value they are part of.
+//!
+//! Timing every field adds some overhead, so this feature is best enabled while investigating.
+//!
+//! [`register`]: fn.register.html
+//! [`Registration`]: struct.Registration.html
+//! [`DecodeEvent`]: struct.DecodeEvent.html
+//! [`Query::fetch`]: ../struct.Query.html#method.fetch
+//! [`Query::fetch_one`]: ../struct.Query.html#method.fetch_one
+
+use crate::hooks::Hooks;
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+pub use crate::hooks::Registration;
+
+static HOOKS: Hooks<dyn Fn(&DecodeEvent) + Send + Sync> = Hooks::new();
+
+thread_local! {
+    /// The fields timed so far by the outermost extraction on this thread.
+    static ACTIVE: RefCell<Option<Vec<FieldTiming>>> = const { RefCell::new(None) };
+}
+
+/// Reported to the hooks once one or more rows have been extracted.
+#[derive(Debug, Clone)]
+pub struct DecodeEvent<'a> {
+    /// The type the rows were extracted as.
+    pub type_name: &'static str,
+    /// The number of rows extracted.
+    pub rows: usize,
+    /// The time it took to extract all rows.
+    pub duration: Duration,
+    /// The time spent on each field, summed over all rows.
+    pub fields: &'a [FieldTiming],
+}
+
+/// The time spent extracting a field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldTiming {
+    /// The type the field belongs to.
+    pub container: &'static str,
+    /// The name of the field, or its position if it is unnamed.
+    pub field: &'static str,
+    pub duration: Duration,
+}
+
+/// Register a hook, which is called whenever rows have been extracted, until the returned
+/// [`Registration`] is dropped.
+///
+/// [`Registration`]: struct.Registration.html
+pub fn register<F>(hook: F) -> Registration
+where
+    F: Fn(&DecodeEvent) + Send + Sync + 'static,
+{
+    HOOKS.register(Box::new(hook))
+}
+
+/// Time the extraction of `rows` rows as `T`, unless it is part of another extraction.
+#[doc(hidden)]
+pub fn __scope<T: ?Sized, R>(rows: usize, extract: impl FnOnce() -> R) -> R {
+    let outermost = ACTIVE.with(|active| {
+        let mut active = active.borrow_mut();
+        if active.is_some() {
+            return false;
+        }
+        *active = Some(Vec::new());
+        true
+    });
+    if !outermost {
+        return extract();
+    }
+
+    // Stop collecting even if the extraction panics.
+    struct Reset;
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            ACTIVE.with(|active| active.borrow_mut().take());
+        }
+    }
+    let _reset = Reset;
+
+    let start = Instant::now();
+    let result = extract();
+    let duration = start.elapsed();
+
+    let fields = ACTIVE
+        .with(|active| active.borrow_mut().take())
+        .unwrap_or_default();
+
+    let event = DecodeEvent {
+        type_name: std::any::type_name::<T>(),
+        rows,
+        duration,
+        fields: &fields,
+    };
+    HOOKS.for_each(|hook| hook(&event));
+
+    result
+}
+
+/// Time the extraction of a field of `T`.
+#[doc(hidden)]
+pub fn __field<T: ?Sized, R>(field: &'static str, extract: impl FnOnce() -> R) -> R {
+    let start = Instant::now();
+    let result = extract();
+    let duration = start.elapsed();
+
+    let container = std::any::type_name::<T>();
+    ACTIVE.with(|active| {
+        if let Some(fields) = active.borrow_mut().as_mut() {
+            match fields
+                .iter_mut()
+                .find(|timing| timing.container == container && timing.field == field)
+            {
+                Some(timing) => timing.duration += duration,
+                None => fields.push(FieldTiming {
+                    container,
+                    field,
+                    duration,
+                }),
+            }
+        }
+    });
+
+    result
+}
@@ -0,0 +1,213 @@
+//! Requests which know their own SQL, for building repository layers.
+//!
+//! See [`Queryable`].
+//!
+//! [`Queryable`]: trait.Queryable.html
+
+use crate::client::GenericClient;
+use crate::error::Result;
+use crate::execute::Error;
+use crate::extract::FromSqlRow;
+use crate::Query;
+use async_trait::async_trait;
+
+/// A request for data, which knows how to build its query and what comes out of it.
+///
+/// Putting the SQL and its bindings in one place lets the rest of the application deal with
+/// requests instead of queries, such as a repository whose methods each run one request:
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{query, queryable::Run, FromSqlRow, Query, Queryable, Result};
+/// #[derive(FromSqlRow)]
+/// struct User {
+///     id: i32,
+///     name: String,
+/// }
+///
+/// struct GetUserById {
+///     id: i32,
+/// }
+///
+/// impl Queryable for GetUserById {
+///     type Output = Option<User>;
+///
+///     fn query(&self) -> Query<'_> {
+///         query!("SELECT id, name FROM users WHERE id = $id", id = self.id)
+///     }
+/// }
+///
+/// struct RenameUser<'a> {
+///     id: i32,
+///     name: &'a str,
+/// }
+///
+/// impl Queryable for RenameUser<'_> {
+///     type Output = u64;
+///
+///     fn query(&self) -> Query<'_> {
+///         query!(
+///             "UPDATE users SET name = $name WHERE id = $id",
+///             id = self.id,
+///             name = self.name,
+///         )
+///     }
+/// }
+///
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// let user: Option<User> = client.run(GetUserById { id: 7 }).await?;
+/// let renamed: u64 = client.run(RenameUser { id: 7, name: "John Wick" }).await?;
+///
+/// // The query may also be run in any of the usual ways.
+/// let users: Vec<User> = GetUserById { id: 7 }.fetch(&client).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[async_trait]
+pub trait Queryable {
+    /// The result of [`run`]: the number of affected rows (`u64`), nothing (`()`), all rows
+    /// (`Vec<T>`) or at most one row (`Option<T>`).
+    ///
+    /// [`run`]: #method.run
+    type Output: Outcome;
+
+    /// The query to execute.
+    fn query(&self) -> Query<'_>;
+
+    /// Execute the query, and return its [`Output`].
+    ///
+    /// [`Output`]: #associatedtype.Output
+    async fn run<C>(&self, client: &C) -> Result<Self::Output>
+    where
+        C: GenericClient + Sync,
+        Self: Sync,
+    {
+        Self::Output::from_query(&self.query(), client).await
+    }
+
+    /// Execute the query and return the number of affected rows. See [`Query::execute`].
+    ///
+    /// [`Query::execute`]: ../struct.Query.html#method.execute
+    async fn execute<C>(&self, client: &C) -> Result<u64>
+    where
+        C: GenericClient + Sync,
+        Self: Sync,
+    {
+        self.query().execute(client).await
+    }
+
+    /// Execute the query and return the resulting values. See [`Query::fetch`].
+    ///
+    /// [`Query::fetch`]: ../struct.Query.html#method.fetch
+    async fn fetch<T, C>(&self, client: &C) -> Result<Vec<T>>
+    where
+        T: FromSqlRow,
+        C: GenericClient + Sync,
+        Self: Sync,
+    {
+        self.query().fetch(client).await
+    }
+
+    /// Execute the query and return the resulting value, which must be the only one. See
+    /// [`Query::fetch_one`].
+    ///
+    /// [`Query::fetch_one`]: ../struct.Query.html#method.fetch_one
+    async fn fetch_one<T, C>(&self, client: &C) -> Result<T>
+    where
+        T: FromSqlRow,
+        C: GenericClient + Sync,
+        Self: Sync,
+    {
+        self.query().fetch_one(client).await
+    }
+}
+
+/// What can come out of executing a query. See [`Queryable::Output`].
+///
+/// [`Queryable::Output`]: trait.Queryable.html#associatedtype.Output
+#[async_trait]
+pub trait Outcome: Sized {
+    /// Execute the query, and turn its result into `Self`.
+    async fn from_query<C>(query: &Query<'_>, client: &C) -> Result<Self>
+    where
+        C: GenericClient + Sync;
+}
+
+/// The number of affected rows.
+#[async_trait]
+impl Outcome for u64 {
+    async fn from_query<C>(query: &Query<'_>, client: &C) -> Result<Self>
+    where
+        C: GenericClient + Sync,
+    {
+        query.execute(client).await
+    }
+}
+
+#[async_trait]
+impl Outcome for () {
+    async fn from_query<C>(query: &Query<'_>, client: &C) -> Result<Self>
+    where
+        C: GenericClient + Sync,
+    {
+        query.execute(client).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T> Outcome for Vec<T>
+where
+    T: FromSqlRow + Send,
+{
+    async fn from_query<C>(query: &Query<'_>, client: &C) -> Result<Self>
+    where
+        C: GenericClient + Sync,
+    {
+        query.fetch(client).await
+    }
+}
+
+/// `None` if there are no rows, and an error if there are more than one.
+#[async_trait]
+impl<T> Outcome for Option<T>
+where
+    T: FromSqlRow + Send,
+{
+    async fn from_query<C>(query: &Query<'_>, client: &C) -> Result<Self>
+    where
+        C: GenericClient + Sync,
+    {
+        let rows = query.query(client).await?;
+        match rows.as_slice() {
+            [] => Ok(None),
+            [row] => Ok(Some(T::from_row(row).map_err(Error::from)?)),
+            _ => Err(Error::TooManyRows.into()),
+        }
+    }
+}
+
+/// Run requests directly on a client, as in `client.run(GetUserById { id })`.
+#[async_trait]
+pub trait Run {
+    /// Execute the request's query, and return its [`Output`].
+    ///
+    /// [`Output`]: trait.Queryable.html#associatedtype.Output
+    async fn run<Q>(&self, request: Q) -> Result<Q::Output>
+    where
+        Q: Queryable + Send + Sync;
+}
+
+#[async_trait]
+impl<C> Run for C
+where
+    C: GenericClient + Sync,
+{
+    async fn run<Q>(&self, request: Q) -> Result<Q::Output>
+    where
+        Q: Queryable + Send + Sync,
+    {
+        request.run(self).await
+    }
+}
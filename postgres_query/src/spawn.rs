@@ -0,0 +1,129 @@
+//! Run background tasks on any async runtime.
+//!
+//! A connection from `tokio-postgres` is a plain future, but it has to be driven by a task of its
+//! own for its client to make progress. Instead of spawning that task onto a Tokio runtime, the
+//! helpers which open connections for you (such as [`connect_with_retry_on`] and
+//! [`TestDatabase::create_on`]) can hand it to a [`Spawner`], so that it runs on async-std, smol or
+//! any other executor:
+//!
+//! ```
+//! # use postgres_query::spawn::Spawner;
+//! # use futures::future::BoxFuture;
+//! # mod async_std { pub mod task { pub fn spawn<F>(_: F) {} } }
+//! struct AsyncStd;
+//!
+//! impl Spawner for AsyncStd {
+//!     fn spawn(&self, task: BoxFuture<'static, ()>) {
+//!         async_std::task::spawn(task);
+//!     }
+//! }
+//! ```
+//!
+//! Any `Fn(BoxFuture<'static, ()>)` is also a `Spawner`, and with the `tokio` feature (enabled by
+//! `connect`, `sync` and `test-db`) so is [`TokioSpawner`], which the helpers use by default.
+//!
+//! A [`Subscription`] does not need a spawner at all, since it drives its connection itself
+//! whenever it is polled.
+//!
+//! Dropping a [`TestDatabase`] without closing it is the exception: it has to block until the
+//! database is gone, which it does with a Tokio runtime of its own on a separate thread, whatever
+//! the spawner. [`TestDatabase::close`] uses the spawner instead.
+//!
+//! [`Spawner`]: trait.Spawner.html
+//! [`TokioSpawner`]: struct.TokioSpawner.html
+//! [`connect_with_retry_on`]: ../connect/fn.connect_with_retry_on.html
+//! [`TestDatabase::create_on`]: ../test_db/struct.TestDatabase.html#method.create_on
+//! [`Subscription`]: ../notify/struct.Subscription.html
+//! [`TestDatabase`]: ../test_db/struct.TestDatabase.html
+//! [`TestDatabase::close`]: ../test_db/struct.TestDatabase.html#method.close
+
+use futures::channel::oneshot::{self, Canceled};
+use futures::future::{BoxFuture, FutureExt};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio_postgres::error::Error as SqlError;
+
+/// Runs futures in the background, to completion.
+pub trait Spawner {
+    /// Run `task` in the background, without waiting for it to complete.
+    fn spawn(&self, task: BoxFuture<'static, ()>);
+}
+
+impl<F> Spawner for F
+where
+    F: Fn(BoxFuture<'static, ()>),
+{
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        self(task)
+    }
+}
+
+/// Spawns tasks onto the current Tokio runtime, panicking if there is none.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TokioSpawner;
+
+#[cfg(feature = "tokio")]
+impl Spawner for TokioSpawner {
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        tokio::spawn(task);
+    }
+}
+
+/// Completes once the connection it belongs to is closed, with the error that closed it (if any).
+///
+/// Fails with `Canceled` if the spawner dropped the connection before it completed.
+#[derive(Debug)]
+pub struct ConnectionHandle {
+    result: oneshot::Receiver<Result<(), SqlError>>,
+}
+
+impl Future for ConnectionHandle {
+    type Output = Result<Result<(), SqlError>, Canceled>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.result.poll_unpin(cx)
+    }
+}
+
+/// Drive `connection` on a task spawned with `spawner`.
+pub fn spawn_connection<S, F>(spawner: &S, connection: F) -> ConnectionHandle
+where
+    S: Spawner + ?Sized,
+    F: Future<Output = Result<(), SqlError>> + Send + 'static,
+{
+    let (sender, result) = oneshot::channel();
+    spawner.spawn(
+        async move {
+            // Nobody may be waiting for the connection to close.
+            let _ = sender.send(connection.await);
+        }
+        .boxed(),
+    );
+    ConnectionHandle { result }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use std::sync::Mutex;
+
+    #[test]
+    fn closures_spawn() {
+        let tasks = Mutex::new(Vec::new());
+        let spawner = |task: BoxFuture<'static, ()>| tasks.lock().unwrap().push(task);
+
+        let handle = spawn_connection(&spawner, async { Ok(()) });
+        let dropped = spawn_connection(&spawner, async { Ok(()) });
+
+        let mut tasks = tasks.into_inner().unwrap();
+        assert_eq!(tasks.len(), 2);
+        drop(tasks.pop());
+        block_on(tasks.pop().unwrap());
+
+        assert!(matches!(block_on(handle), Ok(Ok(()))));
+        assert!(block_on(dropped).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+//! Map errors to HTTP status codes.
+//!
+//! Requires the `with-http-1` feature. With the `with-axum-0_7` feature, [`Error`] also implements
+//! axum's `IntoResponse`, using the default mapping.
+//!
+//! Errors are first classified into an [`ErrorKind`], which is then mapped to a status code by a
+//! [`StatusMapping`]. By default:
+//!
+//! | Kind                  | Status                      | Caused by                                   |
+//! |-----------------------|-----------------------------|---------------------------------------------|
+//! | `NoRows`              | `404 Not Found`             | `fetch_one` and `query_one` without rows    |
+//! | `UniqueViolation`     | `409 Conflict`              | `unique_violation`, `exclusion_violation`   |
+//! | `ForeignKeyViolation` | `409 Conflict`              | `foreign_key_violation`                     |
+//! | `CheckViolation`      | `422 Unprocessable Entity`  | `check_violation`, `not_null_violation`     |
+//! | `Timeout`             | `504 Gateway Timeout`       | [timeouts], [deadlines], `query_canceled`   |
+//! | `Unavailable`         | `503 Service Unavailable`   | serialization failures, deadlocks, lost connections |
+//! | `Other`               | `500 Internal Server Error` | anything else                               |
+//!
+//! ```
+//! # use postgres_query::status::{ErrorKind, StatusMapping};
+//! # use http::StatusCode;
+//! # use postgres_query::{execute, Error};
+//! let mapping = StatusMapping::default().set(ErrorKind::NoRows, StatusCode::GONE);
+//!
+//! let error = Error::from(execute::Error::NoRows);
+//! assert_eq!(mapping.status(&error), StatusCode::GONE);
+//! ```
+//!
+//! [`Error`]: ../enum.Error.html
+//! [`ErrorKind`]: enum.ErrorKind.html
+//! [`StatusMapping`]: struct.StatusMapping.html
+//! [timeouts]: ../execute/struct.ExecuteOptions.html#method.timeout
+//! [deadlines]: ../execute/struct.ExecuteOptions.html#method.deadline
+
+use crate::error::Error;
+use crate::{copy, execute};
+use http::StatusCode;
+use tokio_postgres::error::{Error as SqlError, SqlState};
+
+/// The cause of an error, as far as a client of a web service is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// A single row was expected, but none were found.
+    NoRows,
+    /// A row conflicts with an existing one.
+    UniqueViolation,
+    /// A row refers to one which does not exist, or is referred to by others.
+    ForeignKeyViolation,
+    /// A value was rejected by a constraint of its column.
+    CheckViolation,
+    /// The query took too long.
+    Timeout,
+    /// The query may succeed if it is retried later.
+    Unavailable,
+    /// Any other error.
+    Other,
+}
+
+impl ErrorKind {
+    /// Classify an error.
+    pub fn of(error: &Error) -> ErrorKind {
+        if let Error::Execute(execute::Error::NoRows) = error {
+            return ErrorKind::NoRows;
+        }
+        if let Error::Execute(execute::Error::Timeout(_) | execute::Error::DeadlineExceeded) = error
+        {
+            return ErrorKind::Timeout;
+        }
+        #[cfg(feature = "deadpool")]
+        {
+            if let Error::Pool(_) | Error::PoolTimeout { .. } = error {
+                return ErrorKind::Unavailable;
+            }
+        }
+
+        let sql = match sql_error(error) {
+            Some(sql) => sql,
+            None => return ErrorKind::Other,
+        };
+
+        if sql.is_closed() {
+            return ErrorKind::Unavailable;
+        }
+
+        match sql.code() {
+            Some(code) if *code == SqlState::UNIQUE_VIOLATION => ErrorKind::UniqueViolation,
+            Some(code) if *code == SqlState::EXCLUSION_VIOLATION => ErrorKind::UniqueViolation,
+            Some(code) if *code == SqlState::FOREIGN_KEY_VIOLATION => {
+                ErrorKind::ForeignKeyViolation
+            }
+            Some(code) if *code == SqlState::CHECK_VIOLATION => ErrorKind::CheckViolation,
+            Some(code) if *code == SqlState::NOT_NULL_VIOLATION => ErrorKind::CheckViolation,
+            Some(code) if *code == SqlState::QUERY_CANCELED => ErrorKind::Timeout,
+            Some(code) if *code == SqlState::T_R_SERIALIZATION_FAILURE => ErrorKind::Unavailable,
+            Some(code) if *code == SqlState::T_R_DEADLOCK_DETECTED => ErrorKind::Unavailable,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// The error reported by the database, if any.
+fn sql_error(error: &Error) -> Option<&SqlError> {
+    match error {
+        Error::Execute(execute::Error::Sql(sql)) => Some(sql),
+        Error::Copy(copy::Error::Sql(sql)) => Some(sql),
+        Error::BeginTransaction(sql)
+        | Error::CommitTransaction(sql)
+        | Error::RollbackTransaction(sql) => Some(sql),
+        _ => None,
+    }
+}
+
+/// Maps each [`ErrorKind`] to a status code. See the [module documentation] for the defaults.
+///
+/// [`ErrorKind`]: enum.ErrorKind.html
+/// [module documentation]: index.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusMapping {
+    statuses: Vec<(ErrorKind, StatusCode)>,
+}
+
+impl Default for StatusMapping {
+    fn default() -> Self {
+        StatusMapping {
+            statuses: vec![
+                (ErrorKind::NoRows, StatusCode::NOT_FOUND),
+                (ErrorKind::UniqueViolation, StatusCode::CONFLICT),
+                (ErrorKind::ForeignKeyViolation, StatusCode::CONFLICT),
+                (ErrorKind::CheckViolation, StatusCode::UNPROCESSABLE_ENTITY),
+                (ErrorKind::Timeout, StatusCode::GATEWAY_TIMEOUT),
+                (ErrorKind::Unavailable, StatusCode::SERVICE_UNAVAILABLE),
+                (ErrorKind::Other, StatusCode::INTERNAL_SERVER_ERROR),
+            ],
+        }
+    }
+}
+
+impl StatusMapping {
+    /// Map errors of the given kind to `status`.
+    pub fn set(mut self, kind: ErrorKind, status: StatusCode) -> StatusMapping {
+        match self.statuses.iter_mut().find(|(other, _)| *other == kind) {
+            Some((_, previous)) => *previous = status,
+            None => self.statuses.push((kind, status)),
+        }
+        self
+    }
+
+    /// The status code of an error.
+    pub fn status(&self, error: &Error) -> StatusCode {
+        let kind = ErrorKind::of(error);
+        self.statuses
+            .iter()
+            .find(|(other, _)| *other == kind)
+            .map_or(StatusCode::INTERNAL_SERVER_ERROR, |(_, status)| *status)
+    }
+}
+
+/// Responds with the status code of the [default mapping] and an empty body, so that details
+/// about the database are not leaked to clients.
+///
+/// [default mapping]: status/struct.StatusMapping.html
+#[cfg(feature = "with-axum-0_7")]
+impl axum::response::IntoResponse for Error {
+    fn into_response(self) -> axum::response::Response {
+        let status = StatusMapping::default().status(&self);
+        axum::response::IntoResponse::into_response(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_statuses() {
+        let mapping = StatusMapping::default();
+
+        let no_rows = Error::from(execute::Error::NoRows);
+        assert_eq!(mapping.status(&no_rows), StatusCode::NOT_FOUND);
+
+        let timeout = Error::from(execute::Error::Timeout(Default::default()));
+        assert_eq!(mapping.status(&timeout), StatusCode::GATEWAY_TIMEOUT);
+
+        let other = Error::from(execute::Error::TooManyRows);
+        assert_eq!(mapping.status(&other), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,404 @@
+//! Adapters over streams of rows and extracted values.
+//!
+//! See [`Query::query_streaming`], [`Query::fetch_streaming`], [`Query::fetch_adaptive`] and
+//! [`prefetch_pages`].
+//!
+//! [`Query::query_streaming`]: ../struct.Query.html#method.query_streaming
+//! [`Query::fetch_streaming`]: ../struct.Query.html#method.fetch_streaming
+//! [`Query::fetch_adaptive`]: ../struct.Query.html#method.fetch_adaptive
+//! [`prefetch_pages`]: fn.prefetch_pages.html
+
+use crate::error::Result;
+use crate::execute;
+use futures::future::{Either, Future};
+use futures::stream::{self, Stream, StreamExt};
+use futures::task::{Context, Poll};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use tokio_postgres::{Column, Row, Statement};
+
+/// A stream of the rows returned by a query, which also gives access to the prepared statement.
+///
+/// Since the columns are known before the first row arrives, this makes it possible to write
+/// headers or choose an encoding up front, for example when streaming rows as CSV:
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{query, Result};
+/// # use futures::TryStreamExt;
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// let mut rows = query!("SELECT name, age FROM people")
+///     .query_streaming(&client)
+///     .await?;
+///
+/// let header = rows.columns().iter().map(|column| column.name()).collect::<Vec<_>>();
+/// println!("{}", header.join(","));
+///
+/// while let Some(row) = rows.try_next().await? {
+///     let (name, age): (&str, i32) = (row.get(0), row.get(1));
+///     println!("{},{}", name, age);
+/// }
+///
+/// println!("{} rows", rows.rows_read());
+/// # Ok(())
+/// # }
+/// ```
+pub struct StatementRows {
+    statement: Statement,
+    rows: Pin<Box<tokio_postgres::RowStream>>,
+    rows_read: u64,
+}
+
+impl StatementRows {
+    pub(crate) fn new(statement: Statement, rows: tokio_postgres::RowStream) -> StatementRows {
+        StatementRows {
+            statement,
+            rows: Box::pin(rows),
+            rows_read: 0,
+        }
+    }
+
+    /// The statement which produces the rows.
+    pub fn statement(&self) -> &Statement {
+        &self.statement
+    }
+
+    /// The name and type of each column in the rows.
+    pub fn columns(&self) -> &[Column] {
+        self.statement.columns()
+    }
+
+    /// The number of rows read from the stream so far.
+    pub fn rows_read(&self) -> u64 {
+        self.rows_read
+    }
+}
+
+impl Stream for StatementRows {
+    type Item = Result<Row>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let row = match self.rows.as_mut().poll_next(cx) {
+            Poll::Ready(Some(Ok(row))) => row,
+            Poll::Ready(Some(Err(error))) => {
+                return Poll::Ready(Some(Err(execute::Error::from(error).into())))
+            }
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        };
+
+        self.rows_read += 1;
+        Poll::Ready(Some(Ok(row)))
+    }
+}
+
+/// The values returned by [`Query::fetch_adaptive`]: either all of them, if there were few enough,
+/// or a stream of them.
+///
+/// [`Query::fetch_adaptive`]: ../struct.Query.html#method.fetch_adaptive
+pub enum Adaptive<T, S> {
+    /// All values, since there were no more than the threshold.
+    Buffered(Vec<T>),
+    /// All values, including those which were read before the threshold was exceeded.
+    Streaming(S),
+}
+
+impl<T, S> Adaptive<T, S>
+where
+    S: Stream<Item = Result<T>>,
+{
+    /// `true` if all values were buffered.
+    pub fn is_buffered(&self) -> bool {
+        match self {
+            Adaptive::Buffered(_) => true,
+            Adaptive::Streaming(_) => false,
+        }
+    }
+
+    /// A stream of the values, regardless of whether they were buffered.
+    pub fn into_stream(self) -> impl Stream<Item = Result<T>> {
+        match self {
+            Adaptive::Buffered(values) => Either::Left(stream::iter(values.into_iter().map(Ok))),
+            Adaptive::Streaming(values) => Either::Right(values),
+        }
+    }
+}
+
+/// Group adjacent `(parent, child)` pairs which share the same parent key into a single parent
+/// with all of its children.
+///
+/// This is the same as the `#[row(group)]` attribute, but works on a stream of rows and for any two
+/// types, which is useful when the parent and child are defined in separate crates and can't share
+/// a single derive. As with `#[row(group)]`, only **adjacent** rows are merged, so the query should
+/// order the rows by the parent key.
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{query, stream::group_adjacent_by_key, FromSqlRow, Result};
+/// # use futures::TryStreamExt;
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// # #[derive(FromSqlRow)] struct Author { id: i32, name: String }
+/// # #[derive(FromSqlRow)] struct Book { title: String }
+/// // `Author` and `Book` are defined elsewhere.
+/// #[derive(FromSqlRow)]
+/// #[row(exact)]
+/// struct AuthorBook {
+///     #[row(flatten)]
+///     author: Author,
+///     #[row(flatten)]
+///     book: Book,
+/// }
+///
+/// let rows = query!(
+///         "SELECT authors.id, authors.name, books.title
+///          FROM authors INNER JOIN books ON books.author = authors.id
+///          ORDER BY authors.id"
+///     )
+///     .fetch_streaming::<AuthorBook, _>(&client)
+///     .await?
+///     .map_ok(|row| (row.author, row.book));
+///
+/// let authors: Vec<(Author, Vec<Book>)> = group_adjacent_by_key(rows, |author| author.id)
+///     .try_collect()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn group_adjacent_by_key<S, P, C, E, K, F>(
+    rows: S,
+    key: F,
+) -> impl Stream<Item = Result<(P, Vec<C>), E>>
+where
+    S: Stream<Item = Result<(P, C), E>>,
+    F: FnMut(&P) -> K,
+    K: PartialEq,
+{
+    struct State<S, P, C, K, F> {
+        rows: Pin<Box<S>>,
+        current: Option<(K, P, Vec<C>)>,
+        key: F,
+    }
+
+    let state = State {
+        rows: Box::pin(rows),
+        current: None,
+        key,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        loop {
+            match state.rows.next().await {
+                Some(Ok((parent, child))) => {
+                    let key = (state.key)(&parent);
+                    match state.current.take() {
+                        Some((current, group, mut children)) if current == key => {
+                            children.push(child);
+                            state.current = Some((current, group, children));
+                        }
+                        previous => {
+                            state.current = Some((key, parent, vec![child]));
+                            if let Some((_, parent, children)) = previous {
+                                return Some((Ok((parent, children)), state));
+                            }
+                        }
+                    }
+                }
+                Some(Err(error)) => return Some((Err(error), state)),
+                None => {
+                    let (_, parent, children) = state.current.take()?;
+                    return Some((Ok((parent, children)), state));
+                }
+            }
+        }
+    })
+}
+
+/// Stream the items of a paginated query, fetching the next page while the current one is being
+/// consumed.
+///
+/// `fetch` is called with the token of a page (an offset, the key of the last item, etc.), starting
+/// with `first`, and returns the items of that page along with the token of the next page, or
+/// `None` if it was the last one. As soon as a page arrives, the next one is requested, so the
+/// database is working on the next page while the consumer processes the current one. At most one
+/// page is fetched ahead.
+///
+/// If fetching a page fails, the error is returned after the items that were already fetched, and
+/// the stream ends.
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{query, stream::prefetch_pages, Error, Result};
+/// # use futures::TryStreamExt;
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// let people = prefetch_pages(0, |after: i32| {
+///     let client = &client;
+///     async move {
+///         let page: Vec<(i32, String)> = query!(
+///                 "SELECT id, name FROM people WHERE id > $after ORDER BY id LIMIT 1000",
+///                 after
+///             )
+///             .fetch(client)
+///             .await?;
+///
+///         let next = page.last().map(|(id, _)| *id);
+///         Ok::<_, Error>((page, next))
+///     }
+/// });
+///
+/// futures::pin_mut!(people);
+/// while let Some((id, name)) = people.try_next().await? {
+///     println!("{}: {}", id, name);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn prefetch_pages<P, T, E, F, Fut>(first: P, fetch: F) -> impl Stream<Item = Result<T, E>>
+where
+    F: FnMut(P) -> Fut,
+    Fut: Future<Output = Result<(Vec<T>, Option<P>), E>>,
+{
+    PrefetchPages {
+        fetch,
+        next: Some(first),
+        pending: None,
+        ready: None,
+        current: VecDeque::new(),
+        error: None,
+    }
+}
+
+struct PrefetchPages<P, T, E, F, Fut> {
+    fetch: F,
+    /// The token of the next page to fetch.
+    next: Option<P>,
+    /// The page currently being fetched.
+    pending: Option<Pin<Box<Fut>>>,
+    /// A page which has been fetched, but not yet consumed.
+    ready: Option<VecDeque<T>>,
+    current: VecDeque<T>,
+    error: Option<E>,
+}
+
+// `fetch` is never pinned, and the pending future is boxed.
+impl<P, T, E, F, Fut> Unpin for PrefetchPages<P, T, E, F, Fut> {}
+
+impl<P, T, E, F, Fut> Stream for PrefetchPages<P, T, E, F, Fut>
+where
+    F: FnMut(P) -> Fut,
+    Fut: Future<Output = Result<(Vec<T>, Option<P>), E>>,
+{
+    type Item = Result<T, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(pending) = &mut this.pending {
+                if let Poll::Ready(result) = pending.as_mut().poll(cx) {
+                    this.pending = None;
+                    match result {
+                        Ok((items, next)) => {
+                            this.ready = Some(items.into());
+                            this.next = next;
+                        }
+                        Err(error) => this.error = Some(error),
+                    }
+                }
+            }
+
+            if this.current.is_empty() {
+                if let Some(ready) = this.ready.take() {
+                    this.current = ready;
+                }
+            }
+
+            if this.pending.is_none() && this.ready.is_none() {
+                if let Some(next) = this.next.take() {
+                    this.pending = Some(Box::pin((this.fetch)(next)));
+                    continue;
+                }
+            }
+
+            break;
+        }
+
+        if let Some(item) = this.current.pop_front() {
+            Poll::Ready(Some(Ok(item)))
+        } else if let Some(error) = this.error.take() {
+            Poll::Ready(Some(Err(error)))
+        } else if this.pending.is_some() {
+            Poll::Pending
+        } else {
+            Poll::Ready(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn group_adjacent_pairs() {
+        let rows = stream::iter(vec![
+            Ok::<_, ()>((1, 'a')),
+            Ok((1, 'b')),
+            Ok((2, 'c')),
+            Ok((1, 'd')),
+        ]);
+
+        let groups = block_on(group_adjacent_by_key(rows, |parent| *parent).collect::<Vec<_>>());
+
+        assert_eq!(
+            groups,
+            vec![Ok((1, vec!['a', 'b'])), Ok((2, vec!['c'])), Ok((1, vec!['d']))]
+        );
+    }
+
+    #[test]
+    fn group_adjacent_error() {
+        let rows = stream::iter(vec![Ok((1, 'a')), Err("failed"), Ok((1, 'b'))]);
+
+        let groups = block_on(group_adjacent_by_key(rows, |parent| *parent).collect::<Vec<_>>());
+
+        assert_eq!(groups, vec![Err("failed"), Ok((1, vec!['a', 'b']))]);
+    }
+
+    #[test]
+    fn prefetch_next_page() {
+        let requested = std::cell::RefCell::new(Vec::new());
+        let pages = prefetch_pages(0, |page| {
+            requested.borrow_mut().push(page);
+            let items = vec![page * 10, page * 10 + 1];
+            let next = if page < 2 { Some(page + 1) } else { None };
+            futures::future::ready(Ok::<_, ()>((items, next)))
+        });
+        futures::pin_mut!(pages);
+
+        assert_eq!(block_on(pages.next()), Some(Ok(0)));
+        assert_eq!(*requested.borrow(), [0, 1]);
+
+        let rest = block_on(pages.collect::<Vec<_>>());
+        assert_eq!(rest, vec![Ok(1), Ok(10), Ok(11), Ok(20), Ok(21)]);
+        assert_eq!(*requested.borrow(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn prefetch_error() {
+        let pages = prefetch_pages(0, |page| {
+            let result = if page == 0 {
+                Ok((vec!['a', 'b'], Some(1)))
+            } else {
+                Err("failed")
+            };
+            futures::future::ready(result)
+        });
+
+        let items = block_on(pages.collect::<Vec<_>>());
+        assert_eq!(items, vec![Ok('a'), Ok('b'), Err("failed")]);
+    }
+}
@@ -0,0 +1,132 @@
+//! A blocking client, for programs without an asynchronous runtime.
+//!
+//! Requires the `sync` feature.
+//!
+//! ```no_run
+//! # use postgres_query::{query, sync::Client, FromSqlRow, Result};
+//! # fn foo() -> Result<()> {
+//! #[derive(FromSqlRow)]
+//! struct Person {
+//!     name: String,
+//!     age: i32,
+//! }
+//!
+//! let client = Client::connect("host=localhost user=postgres", tokio_postgres::NoTls)?;
+//!
+//! let people: Vec<Person> = client.fetch(&query!("SELECT name, age FROM people"))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{Error, Result};
+use crate::extract::FromSqlRow;
+use crate::Query;
+use postgres_types::FromSql;
+use std::future::Future;
+use tokio::runtime::{self, Runtime};
+use tokio_postgres::tls::{MakeTlsConnect, TlsConnect};
+use tokio_postgres::{Row, Socket};
+
+/// A client which blocks the current thread until each query is complete.
+///
+/// The client drives a `tokio_postgres::Client` on a private, single-threaded runtime. It must not
+/// be used from within an asynchronous context.
+pub struct Client {
+    // Declared before the runtime so that it is dropped while the runtime is still alive.
+    client: tokio_postgres::Client,
+    runtime: Runtime,
+}
+
+impl Client {
+    /// Connect to the database. See [`tokio_postgres::connect`] for the format of `config`.
+    ///
+    /// [`tokio_postgres::connect`]: https://docs.rs/tokio-postgres/0.7.0/tokio_postgres/fn.connect.html
+    pub fn connect<T>(config: &str, tls: T) -> Result<Client>
+    where
+        T: MakeTlsConnect<Socket> + 'static + Send,
+        T::TlsConnect: Send,
+        T::Stream: Send,
+        <T::TlsConnect as TlsConnect<Socket>>::Future: Send,
+    {
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(Error::Runtime)?;
+
+        let (client, connection) = runtime
+            .block_on(tokio_postgres::connect(config, tls))
+            .map_err(Error::Connect)?;
+
+        // Errors are reported to the client through the queries that fail.
+        runtime.spawn(connection);
+
+        Ok(Client { client, runtime })
+    }
+
+    /// Get the underlying asynchronous client.
+    pub fn get_ref(&self) -> &tokio_postgres::Client {
+        &self.client
+    }
+
+    /// Run any future which uses the underlying client to completion. See [`Client::get_ref`].
+    ///
+    /// [`Client::get_ref`]: #method.get_ref
+    pub fn block_on<F>(&self, future: F) -> F::Output
+    where
+        F: Future,
+    {
+        self.runtime.block_on(future)
+    }
+
+    /// Execute a query and return the number of affected rows. See [`Query::execute`].
+    ///
+    /// [`Query::execute`]: ../struct.Query.html#method.execute
+    pub fn execute(&self, query: &Query<'_>) -> Result<u64> {
+        self.block_on(query.execute(&self.client))
+    }
+
+    /// Execute a query and return the resulting values. See [`Query::fetch`].
+    ///
+    /// [`Query::fetch`]: ../struct.Query.html#method.fetch
+    pub fn fetch<T>(&self, query: &Query<'_>) -> Result<Vec<T>>
+    where
+        T: FromSqlRow,
+    {
+        self.block_on(query.fetch(&self.client))
+    }
+
+    /// Execute a query and return the single resulting value. See [`Query::fetch_one`].
+    ///
+    /// [`Query::fetch_one`]: ../struct.Query.html#method.fetch_one
+    pub fn fetch_one<T>(&self, query: &Query<'_>) -> Result<T>
+    where
+        T: FromSqlRow,
+    {
+        self.block_on(query.fetch_one(&self.client))
+    }
+
+    /// Execute a query and return the elements of the single resulting array. See
+    /// [`Query::fetch_array`].
+    ///
+    /// [`Query::fetch_array`]: ../struct.Query.html#method.fetch_array
+    pub fn fetch_array<T>(&self, query: &Query<'_>) -> Result<Vec<T>>
+    where
+        T: for<'r> FromSql<'r>,
+    {
+        self.block_on(query.fetch_array(&self.client))
+    }
+
+    /// Execute a query and return the resulting rows. See [`Query::query`].
+    ///
+    /// [`Query::query`]: ../struct.Query.html#method.query
+    pub fn query(&self, query: &Query<'_>) -> Result<Vec<Row>> {
+        self.block_on(query.query(&self.client))
+    }
+
+    /// Execute a query and return the single resulting row. See [`Query::query_one`].
+    ///
+    /// [`Query::query_one`]: ../struct.Query.html#method.query_one
+    pub fn query_one(&self, query: &Query<'_>) -> Result<Row> {
+        self.block_on(query.query_one(&self.client))
+    }
+}
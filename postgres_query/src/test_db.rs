@@ -0,0 +1,319 @@
+//! Throwaway databases for integration tests.
+//!
+//! Tests sharing a database tend to interfere with each other when run in parallel. A
+//! [`TestDatabase`] instead gives each test its own uniquely named database (or schema), set up
+//! from a script, which is dropped along with it.
+//!
+//! Requires the `test-db` feature.
+//!
+//! ```no_run
+//! # use postgres_query::{query, test_db::TestDatabase};
+//! # async fn foo() -> Result<(), Box<dyn std::error::Error>> {
+//! let admin = "host=localhost user=postgres".parse()?;
+//! let db = TestDatabase::create(admin, "CREATE TABLE people (name TEXT, age INT)").await?;
+//!
+//! query!("INSERT INTO people VALUES ('John Wick', 42)")
+//!     .execute(db.client())
+//!     .await?;
+//!
+//! // The database is dropped here.
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`TestDatabase`]: struct.TestDatabase.html
+
+use crate::client::Caching;
+use crate::execute::quote_identifier;
+use crate::spawn::{self, Spawner, TokioSpawner};
+use std::ops::Deref;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::runtime;
+use tokio_postgres::{error::Error as SqlError, Client, Config, NoTls};
+
+/// An error while provisioning a test database.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to connect to the database")]
+    Connect(#[source] SqlError),
+
+    #[error("failed to create the {0}")]
+    Create(&'static str, #[source] SqlError),
+
+    #[error("failed to run the setup script")]
+    Setup(#[source] SqlError),
+
+    #[error("failed to drop the {0}")]
+    Drop(&'static str, #[source] SqlError),
+}
+
+/// A database (or schema) which only exists for as long as this guard, along with a client
+/// connected to it.
+///
+/// Connections are made without TLS. [`close`] drops the database through the spawner the guard
+/// was created with. Dropping the guard without closing it does the same, but blocks the current
+/// thread until it is done, so it has to connect from a new thread running a Tokio runtime of its
+/// own, whatever runtime the test itself uses. Should that fail, the database is left behind.
+///
+/// [`close`]: #method.close
+pub struct TestDatabase {
+    client: Option<Caching<Client>>,
+    spawner: Box<dyn Spawner + Send + Sync>,
+    admin: Config,
+    target: Target,
+    // The server process of the client, which has to be terminated before dropping the database.
+    pid: i32,
+}
+
+#[derive(Clone)]
+enum Target {
+    Database(String),
+    Schema(String),
+}
+
+impl TestDatabase {
+    /// Create a new database, using the connection parameters of a user allowed to create
+    /// databases, and run `setup` in it.
+    ///
+    /// The connections are spawned onto the current Tokio runtime.
+    pub async fn create(admin: Config, setup: &str) -> Result<TestDatabase, Error> {
+        TestDatabase::create_on(admin, setup, TokioSpawner).await
+    }
+
+    /// Like [`create`], but drive the connections on tasks spawned with `spawner`, including the
+    /// one which drops the database in [`close`].
+    ///
+    /// Dropping the guard without closing it does not use `spawner`: it connects through a Tokio
+    /// runtime of its own, on a thread of its own, since it has to block until the database is
+    /// gone.
+    ///
+    /// [`create`]: #method.create
+    /// [`close`]: #method.close
+    pub async fn create_on<S>(admin: Config, setup: &str, spawner: S) -> Result<TestDatabase, Error>
+    where
+        S: Spawner + Send + Sync + 'static,
+    {
+        let name = unique_name();
+
+        let (client, _) = connect(&admin, &spawner).await?;
+        client
+            .batch_execute(&format!("CREATE DATABASE {}", quote_identifier(&name)))
+            .await
+            .map_err(|error| Error::Create("database", error))?;
+
+        let mut config = admin.clone();
+        config.dbname(&name);
+
+        TestDatabase::setup(config, admin, Target::Database(name), setup, spawner).await
+    }
+
+    /// Create a new schema in the database of the connection parameters, and run `setup` with the
+    /// schema first in the `search_path`.
+    ///
+    /// Cheaper than a database, but objects outside the schema are shared with other tests.
+    pub async fn create_schema(admin: Config, setup: &str) -> Result<TestDatabase, Error> {
+        TestDatabase::create_schema_on(admin, setup, TokioSpawner).await
+    }
+
+    /// Like [`create_schema`], but drive the connections on tasks spawned with `spawner`.
+    ///
+    /// As with [`create_on`], the schema is dropped through `spawner` when closed, and through a
+    /// Tokio runtime of its own otherwise.
+    ///
+    /// [`create_schema`]: #method.create_schema
+    /// [`create_on`]: #method.create_on
+    pub async fn create_schema_on<S>(
+        admin: Config,
+        setup: &str,
+        spawner: S,
+    ) -> Result<TestDatabase, Error>
+    where
+        S: Spawner + Send + Sync + 'static,
+    {
+        let name = unique_name();
+
+        let (client, _) = connect(&admin, &spawner).await?;
+        client
+            .batch_execute(&format!("CREATE SCHEMA {}", quote_identifier(&name)))
+            .await
+            .map_err(|error| Error::Create("schema", error))?;
+
+        let search_path = format!("-c search_path={}", name);
+        let options = match admin.get_options() {
+            Some(options) => format!("{} {}", options, search_path),
+            None => search_path,
+        };
+        let mut config = admin.clone();
+        config.options(&options);
+
+        TestDatabase::setup(config, admin, Target::Schema(name), setup, spawner).await
+    }
+
+    async fn setup<S>(
+        config: Config,
+        admin: Config,
+        target: Target,
+        setup: &str,
+        spawner: S,
+    ) -> Result<TestDatabase, Error>
+    where
+        S: Spawner + Send + Sync + 'static,
+    {
+        let (client, pid) = match connect(&config, &spawner).await {
+            Ok(connected) => connected,
+            Err(error) => {
+                // The original error is more useful than any error while cleaning up.
+                let _ = drop_target(&admin, &target, None, &spawner).await;
+                return Err(error);
+            }
+        };
+
+        let db = TestDatabase {
+            client: Some(Caching::new(client)),
+            spawner: Box::new(spawner),
+            admin,
+            target,
+            pid,
+        };
+
+        if let Err(error) = db.client().batch_execute(setup).await {
+            let _ = db.close().await;
+            return Err(Error::Setup(error));
+        }
+
+        Ok(db)
+    }
+
+    /// Close the client and drop the database, through the spawner the guard was created with.
+    ///
+    /// Unlike dropping the guard, this neither blocks the current thread nor needs a runtime of its
+    /// own. Should it fail, the database is left behind.
+    pub async fn close(mut self) -> Result<(), Error> {
+        // Taking the client also tells `drop` that there is nothing left to clean up.
+        self.client = None;
+        drop_target(&self.admin, &self.target, Some(self.pid), &*self.spawner).await
+    }
+
+    /// The client connected to the database.
+    pub fn client(&self) -> &Caching<Client> {
+        self.client
+            .as_ref()
+            .expect("the client is only taken when dropped")
+    }
+
+    /// The name of the database (or schema).
+    pub fn name(&self) -> &str {
+        match &self.target {
+            Target::Database(name) | Target::Schema(name) => name,
+        }
+    }
+}
+
+impl Deref for TestDatabase {
+    type Target = Caching<Client>;
+
+    fn deref(&self) -> &Self::Target {
+        self.client()
+    }
+}
+
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        if self.client.take().is_some() {
+            cleanup(&self.admin, &self.target, self.pid);
+        }
+    }
+}
+
+/// Drop the database, blocking until it is done.
+///
+/// Runs on a thread of its own, with a runtime of its own, since the current runtime may not be
+/// able to make progress while blocked (or may not exist at all).
+fn cleanup(admin: &Config, target: &Target, pid: i32) {
+    let admin = admin.clone();
+    let target = target.clone();
+
+    let cleanup = thread::spawn(move || {
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .ok()?;
+
+        runtime
+            .block_on(drop_target(&admin, &target, Some(pid), &TokioSpawner))
+            .ok()
+    });
+
+    // There is nothing to be done about errors, other than leaving the database behind.
+    let _ = cleanup.join();
+}
+
+/// Drop the database, terminating the connection of the client first (if any).
+async fn drop_target<S>(
+    admin: &Config,
+    target: &Target,
+    pid: Option<i32>,
+    spawner: &S,
+) -> Result<(), Error>
+where
+    S: Spawner + ?Sized,
+{
+    let (kind, statement) = match target {
+        Target::Database(name) => (
+            "database",
+            format!("DROP DATABASE IF EXISTS {}", quote_identifier(name)),
+        ),
+        Target::Schema(name) => (
+            "schema",
+            format!("DROP SCHEMA IF EXISTS {} CASCADE", quote_identifier(name)),
+        ),
+    };
+
+    let (client, _) = connect(admin, spawner).await?;
+    if let Some(pid) = pid {
+        client
+            .execute("SELECT pg_terminate_backend($1)", &[&pid])
+            .await
+            .map_err(|error| Error::Drop(kind, error))?;
+    }
+    client
+        .batch_execute(&statement)
+        .await
+        .map_err(|error| Error::Drop(kind, error))
+}
+
+/// Connect to the database, returning the client and its server process ID.
+async fn connect<S>(config: &Config, spawner: &S) -> Result<(Client, i32), Error>
+where
+    S: Spawner + ?Sized,
+{
+    let (client, connection) = config.connect(NoTls).await.map_err(Error::Connect)?;
+
+    // Errors are reported to the client through the queries that fail.
+    spawn::spawn_connection(spawner, connection);
+
+    let pid = client
+        .query_one("SELECT pg_backend_pid()", &[])
+        .await
+        .map_err(Error::Connect)?
+        .get(0);
+
+    Ok((client, pid))
+}
+
+/// A name which is unique across processes and runs.
+fn unique_name() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos())
+        .unwrap_or_default();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    format!("test_{}_{}_{}", process::id(), nanos, count)
+}
@@ -0,0 +1,434 @@
+//! Helpers for testing the queries an application builds.
+//!
+//! Comparing the SQL of a dynamically built query against a string literal breaks whenever the
+//! whitespace or the case of a keyword changes. [`assert_query_eq!`] compares both after
+//! [`normalize_sql`], along with the number of parameters:
+//!
+//! ```
+//! # use postgres_query::{assert_query_eq, query};
+//! let query = query!(
+//!     "select name
+//!      from people
+//!      where age > $age",
+//!     age = 18,
+//! );
+//!
+//! assert_query_eq!(query, "SELECT name FROM people WHERE age > $1", 1);
+//! ```
+//!
+//! Similarly, [`assert_rows_eq`] compares the rows extracted by a query with the expected ones,
+//! and lists the rows that are missing, the ones that should not be there, and the fields that
+//! differ in the rest.
+//!
+//! [`assert_query_eq!`]: ../macro.assert_query_eq.html
+//! [`normalize_sql`]: fn.normalize_sql.html
+//! [`assert_rows_eq`]: fn.assert_rows_eq.html
+
+use crate::Query;
+use std::fmt::{Debug, Write};
+
+/// Assert that the SQL of a query is the same as the expected SQL once both are normalized with
+/// [`normalize_sql`], and optionally that the query has the expected number of parameters.
+///
+/// On failure, the normalized SQL of both is shown.
+///
+/// [`normalize_sql`]: testing/fn.normalize_sql.html
+#[macro_export]
+macro_rules! assert_query_eq {
+    ($query:expr, $sql:expr $(,)?) => {
+        $crate::testing::__assert_query_eq(&$query, $sql, None)
+    };
+    ($query:expr, $sql:expr, $parameters:expr $(,)?) => {
+        $crate::testing::__assert_query_eq(&$query, $sql, Some($parameters))
+    };
+}
+
+/// Write SQL in a canonical form, so that two queries which differ only in formatting compare
+/// equal.
+///
+/// - Runs of whitespace are replaced by a single space, and whitespace around parentheses,
+///   commas and semicolons, as well as at the start and end, is removed.
+/// - Words outside of quotes (keywords and identifiers alike) are converted to lowercase, as
+///   Postgres does with unquoted identifiers.
+///
+/// String literals and quoted identifiers are left as they are. Comments are not removed.
+///
+/// ```
+/// # use postgres_query::testing::normalize_sql;
+/// assert_eq!(
+///     normalize_sql("SELECT COUNT( * )\n  FROM \"People\"  WHERE name = 'John Wick'"),
+///     "select count(*) from \"People\" where name = 'John Wick'",
+/// );
+/// ```
+pub fn normalize_sql(sql: &str) -> String {
+    let mut normalized = String::with_capacity(sql.len());
+    let mut space = false;
+    let mut chars = sql.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch.is_whitespace() {
+            space = true;
+            continue;
+        }
+
+        let after_tight = normalized.is_empty() || normalized.ends_with(['(', ',', ';']);
+        let before_tight = matches!(ch, '(' | ')' | ',' | ';');
+        if space && !after_tight && !before_tight {
+            normalized.push(' ');
+        }
+        space = false;
+
+        match ch {
+            '\'' | '"' => {
+                // Quotes within the literal are escaped by doubling them, which is the same as
+                // ending the literal and immediately starting a new one.
+                normalized.push(ch);
+                for inner in chars.by_ref() {
+                    normalized.push(inner);
+                    if inner == ch {
+                        break;
+                    }
+                }
+            }
+            _ => normalized.extend(ch.to_lowercase()),
+        }
+    }
+
+    normalized
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn __assert_query_eq(query: &Query, expected: &str, parameters: Option<usize>) {
+    let actual = normalize_sql(query.sql());
+    let expected = normalize_sql(expected);
+    if actual != expected {
+        panic!(
+            "assertion failed: query matches the expected SQL\n   \
+             query: {}\nexpected: {}\n\nthe query was: {}",
+            actual,
+            expected,
+            query.sql(),
+        );
+    }
+
+    if let Some(parameters) = parameters {
+        let actual = query.parameters().len();
+        if actual != parameters {
+            panic!(
+                "assertion failed: query has {} parameters, but {} were expected\n   \
+                 query: {}",
+                actual,
+                parameters,
+                query.sql(),
+            );
+        }
+    }
+}
+
+/// Assert that two lists of rows are equal, in the same order. On failure, the differences are
+/// listed row by row:
+///
+/// ```text
+/// assertion failed: rows are equal (found 3 rows, expected 3)
+/// unexpected row 0: Person { id: 4, name: "Charon" }
+/// row 2 differs:
+///     name: expected "John Wick", found "John"
+/// missing row 2: Person { id: 3, name: "Winston" }
+/// ```
+///
+/// Rows are told apart by comparing their `Debug` representations field by field, so this works
+/// best for structs and tuples which derive `Debug`:
+///
+/// ```
+/// # use postgres_query::{testing::assert_rows_eq, FromSqlRow};
+/// #[derive(Debug, PartialEq, FromSqlRow)]
+/// struct Person {
+///     id: i32,
+///     name: String,
+/// }
+///
+/// # let people = vec![Person { id: 1, name: "John Wick".to_owned() }];
+/// // let people: Vec<Person> = query!("SELECT id, name FROM people").fetch(&client).await?;
+/// assert_rows_eq(
+///     &people,
+///     &[Person {
+///         id: 1,
+///         name: "John Wick".to_owned(),
+///     }],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_rows_eq<T>(actual: &[T], expected: &[T])
+where
+    T: Debug + PartialEq,
+{
+    if actual != expected {
+        panic!(
+            "assertion failed: rows are equal (found {} rows, expected {})\n{}",
+            actual.len(),
+            expected.len(),
+            diff_rows(actual, expected),
+        );
+    }
+}
+
+/// A row in the alignment of the actual rows with the expected ones.
+enum Step {
+    Same,
+    Missing(usize),
+    Extra(usize),
+}
+
+/// Describe how the rows differ, after aligning the rows which are equal in both lists.
+fn diff_rows<T>(actual: &[T], expected: &[T]) -> String
+where
+    T: Debug + PartialEq,
+{
+    // The lengths of the longest common subsequences of the remaining rows.
+    let mut common = vec![vec![0usize; expected.len() + 1]; actual.len() + 1];
+    for i in (0..actual.len()).rev() {
+        for j in (0..expected.len()).rev() {
+            common[i][j] = if actual[i] == expected[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut steps = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < actual.len() || j < expected.len() {
+        if i < actual.len() && j < expected.len() && actual[i] == expected[j] {
+            steps.push(Step::Same);
+            i += 1;
+            j += 1;
+        } else if j < expected.len() && (i == actual.len() || common[i][j + 1] >= common[i + 1][j])
+        {
+            steps.push(Step::Missing(j));
+            j += 1;
+        } else {
+            steps.push(Step::Extra(i));
+            i += 1;
+        }
+    }
+
+    // Within each run of differing rows, pair up the missing rows with the unexpected ones, and
+    // show the fields that changed between them.
+    let mut diff = String::new();
+    for run in steps.split(|step| matches!(step, Step::Same)) {
+        let missing = run.iter().filter_map(|step| match step {
+            Step::Missing(j) => Some(*j),
+            _ => None,
+        });
+        let extra = run.iter().filter_map(|step| match step {
+            Step::Extra(i) => Some(*i),
+            _ => None,
+        });
+
+        let mut missing = missing.peekable();
+        let mut extra = extra.peekable();
+        while let (Some(&j), Some(&i)) = (missing.peek(), extra.peek()) {
+            missing.next();
+            extra.next();
+            match diff_fields(&actual[i], &expected[j]) {
+                Some(fields) if !fields.is_empty() => {
+                    let _ = writeln!(diff, "row {} differs:", i);
+                    diff.push_str(&fields);
+                }
+                _ => {
+                    let _ = writeln!(
+                        diff,
+                        "row {}: expected {:?}, found {:?}",
+                        i, expected[j], actual[i]
+                    );
+                }
+            }
+        }
+        for j in missing {
+            let _ = writeln!(diff, "missing row {}: {:?}", j, expected[j]);
+        }
+        for i in extra {
+            let _ = writeln!(diff, "unexpected row {}: {:?}", i, actual[i]);
+        }
+    }
+
+    diff
+}
+
+/// The fields which differ between two values of the same struct or tuple, one per line. `None`
+/// if the values do not have the same fields.
+fn diff_fields<T>(actual: &T, expected: &T) -> Option<String>
+where
+    T: Debug,
+{
+    let actual = fields(&format!("{:#?}", actual))?;
+    let expected = fields(&format!("{:#?}", expected))?;
+    if actual.len() != expected.len() {
+        return None;
+    }
+
+    let mut diff = String::new();
+    for ((name, actual), (expected_name, expected)) in actual.iter().zip(&expected) {
+        if name != expected_name {
+            return None;
+        }
+        if actual != expected {
+            // Keep the lines of nested values indented below the field.
+            let expected = expected.replace('\n', "\n    ");
+            let actual = actual.replace('\n', "\n    ");
+            let _ = writeln!(
+                diff,
+                "    {}: expected {}, found {}",
+                name, expected, actual
+            );
+        }
+    }
+
+    Some(diff)
+}
+
+/// Split the pretty `Debug` representation of a struct or tuple into the names (or indices) and
+/// values of its fields.
+fn fields(debug: &str) -> Option<Vec<(String, String)>> {
+    let mut lines = debug.lines();
+    let named = match lines.next()? {
+        first if first.ends_with(" {") => true,
+        first if first.ends_with('(') => false,
+        _ => return None,
+    };
+
+    let mut fields: Vec<String> = Vec::new();
+    for line in lines {
+        let line = match line.strip_prefix("    ") {
+            Some(line) => line,
+            // The closing brace.
+            None => break,
+        };
+        match fields.last_mut() {
+            Some(field) if line.starts_with(' ') || line.starts_with(['}', ')', ']']) => {
+                field.push('\n');
+                field.push_str(line);
+            }
+            _ => fields.push(line.to_owned()),
+        }
+    }
+
+    let fields = fields.into_iter().enumerate().map(|(index, field)| {
+        let field = field.strip_suffix(',').unwrap_or(&field);
+        match field.split_once(": ") {
+            Some((name, value)) if named => (name.to_owned(), value.to_owned()),
+            _ => (index.to_string(), field.to_owned()),
+        }
+    });
+
+    Some(fields.collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_whitespace_and_case() {
+        assert_eq!(
+            normalize_sql("  Select a ,b\n\tFROM t WHERE ( a=$1 )  ;  "),
+            "select a,b from t where(a=$1);"
+        );
+        assert_eq!(
+            normalize_sql("INSERT INTO t (a, b) VALUES ($1, $2)"),
+            "insert into t(a,b) values($1,$2)"
+        );
+    }
+
+    #[test]
+    fn normalize_keeps_quotes() {
+        assert_eq!(
+            normalize_sql("SELECT 'It''s  MINE' AS \"Owner  Name\""),
+            "select 'It''s  MINE' as \"Owner  Name\""
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "expected: select 2")]
+    fn mismatched_sql() {
+        __assert_query_eq(&Query::new_static("SELECT 1", vec![]), "SELECT 2", None);
+    }
+
+    #[test]
+    #[should_panic(expected = "query has 0 parameters, but 1 were expected")]
+    fn mismatched_parameters() {
+        assert_query_eq!(Query::new_static("SELECT 1", vec![]), "select 1", 1);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Person {
+        id: i32,
+        name: &'static str,
+        tags: Vec<&'static str>,
+    }
+
+    fn person(id: i32, name: &'static str) -> Person {
+        Person {
+            id,
+            name,
+            tags: vec!["a"],
+        }
+    }
+
+    #[test]
+    fn diff_struct_rows() {
+        let mut changed = person(2, "Jon");
+        changed.tags.push("b");
+        let actual = [person(1, "John"), changed, person(4, "Charon")];
+        let expected = [person(1, "John"), person(2, "John"), person(3, "Winston")];
+
+        assert_eq!(
+            diff_rows(&actual, &expected),
+            "row 1 differs:
+    name: expected \"John\", found \"Jon\"
+    tags: expected [
+        \"a\",
+    ], found [
+        \"a\",
+        \"b\",
+    ]
+row 2 differs:
+    id: expected 3, found 4
+    name: expected \"Winston\", found \"Charon\"
+"
+        );
+
+        assert_eq!(
+            diff_rows(&actual[..1], &expected[..2]),
+            "missing row 1: Person { id: 2, name: \"John\", tags: [\"a\"] }\n"
+        );
+        assert_eq!(
+            diff_rows(&expected[..2], &expected[1..2]),
+            "unexpected row 0: Person { id: 1, name: \"John\", tags: [\"a\"] }\n"
+        );
+    }
+
+    #[test]
+    fn diff_tuple_rows() {
+        let actual = [(1, "a"), (2, "b")];
+        let expected = [(2, "b"), (3, "c")];
+        assert_eq!(
+            diff_rows(&actual, &expected),
+            "unexpected row 0: (1, \"a\")\nmissing row 1: (3, \"c\")\n"
+        );
+
+        assert_eq!(
+            diff_rows(&[(1, "a")], &[(1, "b")]),
+            "row 0 differs:\n    1: expected \"b\", found \"a\"\n"
+        );
+        assert_eq!(diff_rows(&[1], &[2]), "row 0: expected 2, found 1\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "rows are equal (found 1 rows, expected 2)\nmissing row 1: 2")]
+    fn mismatched_rows() {
+        assert_rows_eq(&[1], &[1, 2]);
+    }
+}
@@ -0,0 +1,189 @@
+//! Optional integrations with third-party types.
+//!
+//! Each integration is enabled through a feature flag, which also enables the matching feature in
+//! `postgres-types`. This means downstream crates don't have to depend on `postgres-types` directly
+//! just to pass the right flags along. The third-party crate is re-exported, along with aliases for
+//! the most common mappings.
+//!
+//! | Feature               | Rust type                       | Postgres type  |
+//! |-----------------------|---------------------------------|----------------|
+//! | `with-chrono-0_4`     | `chrono::NaiveDateTime`         | `TIMESTAMP`    |
+//! |                       | `chrono::DateTime<Utc>`         | `TIMESTAMPTZ`  |
+//! |                       | `chrono::NaiveDate`             | `DATE`         |
+//! |                       | `chrono::NaiveTime`             | `TIME`         |
+//! | `with-time-0_3`       | `time::PrimitiveDateTime`       | `TIMESTAMP`    |
+//! |                       | `time::OffsetDateTime`          | `TIMESTAMPTZ`  |
+//! |                       | `time::Date`                    | `DATE`         |
+//! |                       | `time::Time`                    | `TIME`         |
+//! | `with-uuid-1`         | `uuid::Uuid`                    | `UUID`         |
+//! | `with-rust_decimal-1` | `rust_decimal::Decimal`         | `NUMERIC`      |
+//!
+//! Independent of any feature, [`Money`] maps to `MONEY`, [`Interval`] maps to `INTERVAL`, and
+//! [`Unsigned`] maps unsigned integers to `SMALLINT`, `INT`, `BIGINT` or `NUMERIC`, checking that
+//! they fit. The `geo` feature adds
+//! [geometric types] for PostGIS, and the `range` feature adds [range types] (such as
+//! `tstzrange`), neither of which depend on any other crate.
+//!
+//! All of these may be used as fields when deriving `FromSqlRow`, as elements of tuples when
+//! fetching rows, and as query parameters:
+//!
+//! ```
+//! # #[cfg(feature = "with-uuid-1")]
+//! # async fn foo(client: &tokio_postgres::Client) -> postgres_query::Result<()> {
+//! use postgres_query::{query, types::uuid::Uuid};
+//!
+//! let (id, name): (Uuid, String) = query!("SELECT id, name FROM people LIMIT 1")
+//!     .fetch_one(client)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Money`]: struct.Money.html
+//! [`Interval`]: struct.Interval.html
+//! [`Unsigned`]: struct.Unsigned.html
+//! [geometric types]: geo/index.html
+//! [range types]: range/index.html
+
+use bytes::{BufMut, BytesMut};
+use postgres_types::{accepts, to_sql_checked, FromSql, IsNull, ToSql, Type};
+use std::convert::TryInto;
+use std::error::Error as StdError;
+
+/// Date and time types from [`chrono`](https://docs.rs/chrono/0.4).
+#[cfg(feature = "with-chrono-0_4")]
+pub mod chrono {
+    pub use ::chrono::*;
+
+    /// Maps to `TIMESTAMP WITH TIME ZONE`.
+    pub type Timestamptz = DateTime<Utc>;
+
+    /// Maps to `TIMESTAMP`.
+    pub type Timestamp = NaiveDateTime;
+}
+
+/// Date and time types from [`time`](https://docs.rs/time/0.3).
+#[cfg(feature = "with-time-0_3")]
+pub mod time {
+    pub use ::time::*;
+
+    /// Maps to `TIMESTAMP WITH TIME ZONE`.
+    pub type Timestamptz = OffsetDateTime;
+
+    /// Maps to `TIMESTAMP`.
+    pub type Timestamp = PrimitiveDateTime;
+}
+
+/// Universally unique identifiers from [`uuid`](https://docs.rs/uuid/1).
+#[cfg(feature = "with-uuid-1")]
+pub mod uuid {
+    pub use ::uuid::*;
+}
+
+/// Arbitrary precision decimals from [`rust_decimal`](https://docs.rs/rust_decimal/1).
+#[cfg(feature = "with-rust_decimal-1")]
+pub mod rust_decimal {
+    pub use ::rust_decimal::*;
+
+    /// Maps to `NUMERIC`.
+    pub type Numeric = Decimal;
+}
+
+/// Points, lines and polygons for PostGIS.
+#[cfg(feature = "with-geo")]
+pub mod geo;
+
+/// Ranges of values, with inclusive, exclusive or no bounds.
+#[cfg(feature = "with-range")]
+pub mod range;
+
+mod interval;
+mod unsigned;
+
+pub use interval::{Interval, IntervalError};
+pub use unsigned::{Fractional, Integer, OutOfRange, Unsigned};
+
+/// An amount of money, stored as a number of cents.
+///
+/// `MONEY` is stored as a fixed-point number, with the number of fractional digits determined by the
+/// `lc_monetary` setting of the database. This type assumes two fractional digits, which is the
+/// case for most locales:
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{query, types::Money, Result};
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// let (item, price): (String, Money) = query!("SELECT item, price FROM orders LIMIT 1")
+///     .fetch_one(&client)
+///     .await?;
+///
+/// println!("{} costs {}.{:02}", item, price.cents() / 100, price.cents() % 100);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// With the `with-rust_decimal-1` feature, `Money` may be converted to and from a `Decimal`, see
+/// [`Money::from_decimal`].
+///
+/// [`Money::from_decimal`]: #method.from_decimal
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money(pub i64);
+
+impl Money {
+    /// An amount of money, in cents.
+    pub fn from_cents(cents: i64) -> Money {
+        Money(cents)
+    }
+
+    /// The amount of money, in cents.
+    pub fn cents(self) -> i64 {
+        self.0
+    }
+}
+
+impl<'a> FromSql<'a> for Money {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn StdError + Sync + Send>> {
+        let bytes = raw.try_into().map_err(|_| "invalid buffer size")?;
+        Ok(Money(i64::from_be_bytes(bytes)))
+    }
+
+    accepts!(MONEY);
+}
+
+impl ToSql for Money {
+    fn to_sql(
+        &self,
+        _: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn StdError + Sync + Send>> {
+        out.put_i64(self.0);
+        Ok(IsNull::No)
+    }
+
+    accepts!(MONEY);
+    to_sql_checked!();
+}
+
+#[cfg(feature = "with-rust_decimal-1")]
+impl From<Money> for ::rust_decimal::Decimal {
+    fn from(money: Money) -> Self {
+        ::rust_decimal::Decimal::new(money.0, 2)
+    }
+}
+
+#[cfg(feature = "with-rust_decimal-1")]
+impl Money {
+    /// Convert a decimal to an amount of money. Returns `None` if the decimal has more than two
+    /// fractional digits, or is too large.
+    pub fn from_decimal(decimal: ::rust_decimal::Decimal) -> Option<Money> {
+        use ::rust_decimal::prelude::ToPrimitive;
+
+        let cents = decimal.checked_mul(::rust_decimal::Decimal::ONE_HUNDRED)?;
+        if cents.fract().is_zero() {
+            cents.to_i64().map(Money)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,522 @@
+//! Geometric types for PostGIS' `geometry` and `geography` columns.
+//!
+//! Values are sent and received in PostGIS' binary format (extended well-known binary, or EWKB),
+//! so no additional crates are required. Only two-dimensional coordinates are supported: receiving
+//! a geometry with Z or M coordinates is an error.
+//!
+//! By default, geometries are sent without a spatial reference system. Columns which are
+//! constrained to a specific one, such as `geometry(Point, 4326)`, require the SRID to be given
+//! with [`Srid`]:
+//!
+//! ```
+//! # use tokio_postgres::Client;
+//! # use postgres_query::{query, types::geo::{Point, Srid}, Result};
+//! # async fn foo() -> Result<()> {
+//! # let client: Client = unimplemented!();
+//! let location = Srid::new(4326, Point::new(18.07, 59.33));
+//! query!("INSERT INTO cities VALUES ($name, $location)", name = "Stockholm", location)
+//!     .execute(&client)
+//!     .await?;
+//!
+//! let (location,): (Point,) = query!("SELECT location FROM cities LIMIT 1")
+//!     .fetch_one(&client)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Srid`]: struct.Srid.html
+
+use bytes::{BufMut, BytesMut};
+use postgres_types::{to_sql_checked, FromSql, IsNull, ToSql, Type};
+use std::convert::TryInto;
+use std::error::Error as StdError;
+
+type BoxError = Box<dyn StdError + Sync + Send>;
+
+const POINT: u32 = 1;
+const LINE_STRING: u32 = 2;
+const POLYGON: u32 = 3;
+const MULTI_POINT: u32 = 4;
+const MULTI_LINE_STRING: u32 = 5;
+const MULTI_POLYGON: u32 = 6;
+
+const FLAG_Z: u32 = 0x8000_0000;
+const FLAG_M: u32 = 0x4000_0000;
+const FLAG_SRID: u32 = 0x2000_0000;
+
+/// A single position.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub fn new(x: f64, y: f64) -> Point {
+        Point { x, y }
+    }
+}
+
+/// A sequence of points, connected by straight lines.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LineString(pub Vec<Point>);
+
+/// An area, given by its exterior ring followed by the rings of any holes. Each ring is closed:
+/// its first and last points are the same.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Polygon(pub Vec<LineString>);
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MultiPoint(pub Vec<Point>);
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MultiLineString(pub Vec<LineString>);
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MultiPolygon(pub Vec<Polygon>);
+
+/// Any of the supported geometries, for columns which may contain more than one kind.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Geometry {
+    Point(Point),
+    LineString(LineString),
+    Polygon(Polygon),
+    MultiPoint(MultiPoint),
+    MultiLineString(MultiLineString),
+    MultiPolygon(MultiPolygon),
+}
+
+/// A geometry in a specific spatial reference system, such as `4326` for WGS 84.
+///
+/// Geometries received without a spatial reference system are given the SRID `0`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Srid<T> {
+    pub srid: i32,
+    pub geometry: T,
+}
+
+impl<T> Srid<T> {
+    pub fn new(srid: i32, geometry: T) -> Srid<T> {
+        Srid { srid, geometry }
+    }
+}
+
+/// Reads values from an EWKB buffer. The byte order may change between nested geometries.
+struct Reader<'a> {
+    raw: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn new(raw: &'a [u8]) -> Reader<'a> {
+        Reader {
+            raw,
+            little_endian: true,
+        }
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], BoxError> {
+        if self.raw.len() < N {
+            return Err("unexpected end of geometry".into());
+        }
+        let (bytes, rest) = self.raw.split_at(N);
+        self.raw = rest;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> Result<u32, BoxError> {
+        let bytes = self.take()?;
+        Ok(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn f64(&mut self) -> Result<f64, BoxError> {
+        let bytes = self.take()?;
+        Ok(if self.little_endian {
+            f64::from_le_bytes(bytes)
+        } else {
+            f64::from_be_bytes(bytes)
+        })
+    }
+
+    fn len(&mut self) -> Result<usize, BoxError> {
+        let len = self.u32()? as usize;
+        // Guard against allocating huge buffers for corrupt lengths: every element takes up at
+        // least one byte.
+        if len > self.raw.len() {
+            return Err("invalid number of elements in geometry".into());
+        }
+        Ok(len)
+    }
+
+    /// Read the header of a geometry, returning its kind and SRID (if any).
+    fn header(&mut self) -> Result<(u32, Option<i32>), BoxError> {
+        let [order] = self.take()?;
+        self.little_endian = match order {
+            0 => false,
+            1 => true,
+            _ => return Err(format!("invalid byte order in geometry: {}", order).into()),
+        };
+
+        let kind = self.u32()?;
+        if kind & (FLAG_Z | FLAG_M) != 0 || (kind & !FLAG_SRID) >= 1000 {
+            return Err("geometries with Z or M coordinates are not supported".into());
+        }
+
+        let srid = if kind & FLAG_SRID != 0 {
+            Some(self.u32()? as i32)
+        } else {
+            None
+        };
+
+        Ok((kind & !FLAG_SRID, srid))
+    }
+}
+
+trait Shape: Sized {
+    fn kind(&self) -> u32;
+
+    fn write_body(&self, out: &mut BytesMut);
+
+    /// Read the rest of a geometry, with the given kind.
+    fn read_body(kind: u32, reader: &mut Reader) -> Result<Self, BoxError>;
+}
+
+fn write<T: Shape>(shape: &T, srid: Option<i32>, out: &mut BytesMut) {
+    out.put_u8(1);
+    match srid {
+        None => out.put_u32_le(shape.kind()),
+        Some(srid) => {
+            out.put_u32_le(shape.kind() | FLAG_SRID);
+            out.put_i32_le(srid);
+        }
+    }
+    shape.write_body(out);
+}
+
+fn read<T: Shape>(reader: &mut Reader) -> Result<(Option<i32>, T), BoxError> {
+    let (kind, srid) = reader.header()?;
+    let shape = T::read_body(kind, reader)?;
+    Ok((srid, shape))
+}
+
+fn read_all<T: Shape>(raw: &[u8]) -> Result<(Option<i32>, T), BoxError> {
+    let mut reader = Reader::new(raw);
+    let result = read(&mut reader)?;
+    if !reader.raw.is_empty() {
+        return Err("unexpected trailing bytes after geometry".into());
+    }
+    Ok(result)
+}
+
+fn expect_kind(kind: u32, expected: u32, name: &str) -> Result<(), BoxError> {
+    if kind == expected {
+        Ok(())
+    } else {
+        Err(format!("expected a {}, found a geometry of type {}", name, kind).into())
+    }
+}
+
+fn write_points(points: &[Point], out: &mut BytesMut) {
+    out.put_u32_le(points.len() as u32);
+    for point in points {
+        point.write_body(out);
+    }
+}
+
+fn read_points(reader: &mut Reader) -> Result<Vec<Point>, BoxError> {
+    let len = reader.len()?;
+    (0..len).map(|_| Point::read_body(POINT, reader)).collect()
+}
+
+fn write_many<T: Shape>(shapes: &[T], out: &mut BytesMut) {
+    out.put_u32_le(shapes.len() as u32);
+    for shape in shapes {
+        write(shape, None, out);
+    }
+}
+
+fn read_many<T: Shape>(reader: &mut Reader) -> Result<Vec<T>, BoxError> {
+    let len = reader.len()?;
+    (0..len)
+        .map(|_| read(reader).map(|(_, shape)| shape))
+        .collect()
+}
+
+impl Shape for Point {
+    fn kind(&self) -> u32 {
+        POINT
+    }
+
+    fn write_body(&self, out: &mut BytesMut) {
+        out.put_f64_le(self.x);
+        out.put_f64_le(self.y);
+    }
+
+    fn read_body(kind: u32, reader: &mut Reader) -> Result<Self, BoxError> {
+        expect_kind(kind, POINT, "point")?;
+        Ok(Point {
+            x: reader.f64()?,
+            y: reader.f64()?,
+        })
+    }
+}
+
+impl Shape for LineString {
+    fn kind(&self) -> u32 {
+        LINE_STRING
+    }
+
+    fn write_body(&self, out: &mut BytesMut) {
+        write_points(&self.0, out);
+    }
+
+    fn read_body(kind: u32, reader: &mut Reader) -> Result<Self, BoxError> {
+        expect_kind(kind, LINE_STRING, "line string")?;
+        read_points(reader).map(LineString)
+    }
+}
+
+impl Shape for Polygon {
+    fn kind(&self) -> u32 {
+        POLYGON
+    }
+
+    // The rings of a polygon are stored without headers.
+    fn write_body(&self, out: &mut BytesMut) {
+        out.put_u32_le(self.0.len() as u32);
+        for ring in &self.0 {
+            ring.write_body(out);
+        }
+    }
+
+    fn read_body(kind: u32, reader: &mut Reader) -> Result<Self, BoxError> {
+        expect_kind(kind, POLYGON, "polygon")?;
+        let len = reader.len()?;
+        (0..len)
+            .map(|_| LineString::read_body(LINE_STRING, reader))
+            .collect::<Result<_, _>>()
+            .map(Polygon)
+    }
+}
+
+impl Shape for MultiPoint {
+    fn kind(&self) -> u32 {
+        MULTI_POINT
+    }
+
+    fn write_body(&self, out: &mut BytesMut) {
+        write_many(&self.0, out);
+    }
+
+    fn read_body(kind: u32, reader: &mut Reader) -> Result<Self, BoxError> {
+        expect_kind(kind, MULTI_POINT, "multi point")?;
+        read_many(reader).map(MultiPoint)
+    }
+}
+
+impl Shape for MultiLineString {
+    fn kind(&self) -> u32 {
+        MULTI_LINE_STRING
+    }
+
+    fn write_body(&self, out: &mut BytesMut) {
+        write_many(&self.0, out);
+    }
+
+    fn read_body(kind: u32, reader: &mut Reader) -> Result<Self, BoxError> {
+        expect_kind(kind, MULTI_LINE_STRING, "multi line string")?;
+        read_many(reader).map(MultiLineString)
+    }
+}
+
+impl Shape for MultiPolygon {
+    fn kind(&self) -> u32 {
+        MULTI_POLYGON
+    }
+
+    fn write_body(&self, out: &mut BytesMut) {
+        write_many(&self.0, out);
+    }
+
+    fn read_body(kind: u32, reader: &mut Reader) -> Result<Self, BoxError> {
+        expect_kind(kind, MULTI_POLYGON, "multi polygon")?;
+        read_many(reader).map(MultiPolygon)
+    }
+}
+
+impl Shape for Geometry {
+    fn kind(&self) -> u32 {
+        match self {
+            Geometry::Point(shape) => shape.kind(),
+            Geometry::LineString(shape) => shape.kind(),
+            Geometry::Polygon(shape) => shape.kind(),
+            Geometry::MultiPoint(shape) => shape.kind(),
+            Geometry::MultiLineString(shape) => shape.kind(),
+            Geometry::MultiPolygon(shape) => shape.kind(),
+        }
+    }
+
+    fn write_body(&self, out: &mut BytesMut) {
+        match self {
+            Geometry::Point(shape) => shape.write_body(out),
+            Geometry::LineString(shape) => shape.write_body(out),
+            Geometry::Polygon(shape) => shape.write_body(out),
+            Geometry::MultiPoint(shape) => shape.write_body(out),
+            Geometry::MultiLineString(shape) => shape.write_body(out),
+            Geometry::MultiPolygon(shape) => shape.write_body(out),
+        }
+    }
+
+    fn read_body(kind: u32, reader: &mut Reader) -> Result<Self, BoxError> {
+        match kind {
+            POINT => Shape::read_body(kind, reader).map(Geometry::Point),
+            LINE_STRING => Shape::read_body(kind, reader).map(Geometry::LineString),
+            POLYGON => Shape::read_body(kind, reader).map(Geometry::Polygon),
+            MULTI_POINT => Shape::read_body(kind, reader).map(Geometry::MultiPoint),
+            MULTI_LINE_STRING => Shape::read_body(kind, reader).map(Geometry::MultiLineString),
+            MULTI_POLYGON => Shape::read_body(kind, reader).map(Geometry::MultiPolygon),
+            _ => Err(format!("unsupported geometry type: {}", kind).into()),
+        }
+    }
+}
+
+fn accepts_geometry(ty: &Type) -> bool {
+    ty.name() == "geometry" || ty.name() == "geography"
+}
+
+macro_rules! impl_sql {
+    ($($shape:ident),*) => {
+        $(
+            impl<'a> FromSql<'a> for $shape {
+                fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Self, BoxError> {
+                    read_all(raw).map(|(_, shape)| shape)
+                }
+
+                fn accepts(ty: &Type) -> bool {
+                    accepts_geometry(ty)
+                }
+            }
+
+            impl ToSql for $shape {
+                fn to_sql(&self, _: &Type, out: &mut BytesMut) -> Result<IsNull, BoxError> {
+                    write(self, None, out);
+                    Ok(IsNull::No)
+                }
+
+                fn accepts(ty: &Type) -> bool {
+                    accepts_geometry(ty)
+                }
+
+                to_sql_checked!();
+            }
+
+            impl<'a> FromSql<'a> for Srid<$shape> {
+                fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Self, BoxError> {
+                    let (srid, geometry) = read_all(raw)?;
+                    Ok(Srid::new(srid.unwrap_or(0), geometry))
+                }
+
+                fn accepts(ty: &Type) -> bool {
+                    accepts_geometry(ty)
+                }
+            }
+
+            impl ToSql for Srid<$shape> {
+                fn to_sql(&self, _: &Type, out: &mut BytesMut) -> Result<IsNull, BoxError> {
+                    write(&self.geometry, Some(self.srid), out);
+                    Ok(IsNull::No)
+                }
+
+                fn accepts(ty: &Type) -> bool {
+                    accepts_geometry(ty)
+                }
+
+                to_sql_checked!();
+            }
+        )*
+    };
+}
+
+impl_sql!(
+    Point,
+    LineString,
+    Polygon,
+    MultiPoint,
+    MultiLineString,
+    MultiPolygon,
+    Geometry
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode<T: Shape>(shape: &T, srid: Option<i32>) -> BytesMut {
+        let mut out = BytesMut::new();
+        write(shape, srid, &mut out);
+        out
+    }
+
+    #[test]
+    fn point_wkb() {
+        // `SELECT 'SRID=4326;POINT(1 2)'::geometry::bytea`
+        let expected = [
+            0x01, 0x01, 0x00, 0x00, 0x20, 0xe6, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0xf0, 0x3f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40,
+        ];
+        assert_eq!(
+            &encode(&Point::new(1.0, 2.0), Some(4326))[..],
+            &expected[..]
+        );
+
+        let (srid, point) = read_all::<Point>(&expected).unwrap();
+        assert_eq!(srid, Some(4326));
+        assert_eq!(point, Point::new(1.0, 2.0));
+    }
+
+    #[test]
+    fn big_endian() {
+        // `SELECT ST_AsBinary('POINT(1 2)'::geometry, 'XDR')`
+        let raw = [
+            0x00, 0x00, 0x00, 0x00, 0x01, 0x3f, 0xf0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let (srid, point) = read_all::<Point>(&raw).unwrap();
+        assert_eq!(srid, None);
+        assert_eq!(point, Point::new(1.0, 2.0));
+    }
+
+    #[test]
+    fn round_trip() {
+        let square = LineString(vec![
+            Point::new(0.0, 0.0),
+            Point::new(1.0, 0.0),
+            Point::new(1.0, 1.0),
+            Point::new(0.0, 0.0),
+        ]);
+        let geometry = Geometry::MultiPolygon(MultiPolygon(vec![
+            Polygon(vec![square.clone()]),
+            Polygon(vec![square.clone(), square]),
+        ]));
+
+        let raw = encode(&geometry, None);
+        let (_, decoded) = read_all::<Geometry>(&raw).unwrap();
+        assert_eq!(decoded, geometry);
+
+        assert!(read_all::<Point>(&raw).is_err());
+        assert!(read_all::<Geometry>(&raw[..raw.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn reject_z_coordinates() {
+        // `SELECT 'POINT Z(1 2 3)'::geometry::bytea`
+        let mut raw = vec![0x01, 0x01, 0x00, 0x00, 0x80];
+        raw.extend_from_slice(&[0; 24]);
+        assert!(read_all::<Point>(&raw).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! Intervals of time, as Postgres stores them.
+
+use bytes::{Buf, BufMut, BytesMut};
+use postgres_types::{accepts, to_sql_checked, FromSql, IsNull, ToSql, Type};
+use std::convert::TryFrom;
+use std::error::Error as StdError;
+use std::time::Duration;
+use thiserror::Error;
+
+/// An `INTERVAL`, which Postgres stores as separate numbers of months, days and microseconds.
+///
+/// The three parts are kept apart since they do not convert into each other: a month has a
+/// different number of days depending on when it starts, and a day has 23 or 25 hours when daylight
+/// saving time begins or ends. For the same reason, an interval only converts to and from a
+/// [`Duration`] if it consists of microseconds alone:
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{query, types::Interval, Result};
+/// # use std::convert::TryFrom;
+/// # use std::time::Duration;
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// let timeout = Interval::try_from(Duration::from_secs(90)).unwrap();
+/// query!("UPDATE jobs SET timeout = $timeout", timeout)
+///     .execute(&client)
+///     .await?;
+///
+/// let (retention,): (Interval,) = query!("SELECT '1 year 2 days'::interval")
+///     .fetch_one(&client)
+///     .await?;
+/// assert_eq!(retention, Interval::new(12, 2, 0));
+/// assert!(Duration::try_from(retention).is_err());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Duration`]: https://doc.rust-lang.org/std/time/struct.Duration.html
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Interval {
+    pub months: i32,
+    pub days: i32,
+    pub microseconds: i64,
+}
+
+/// An interval could not be converted to or from a `Duration`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum IntervalError {
+    #[error("the interval has months or days, which do not have a fixed length")]
+    Calendar,
+
+    #[error("the interval is negative")]
+    Negative,
+
+    #[error("the duration is too long for an interval")]
+    TooLong,
+
+    #[error("the duration is not a whole number of microseconds")]
+    Precision,
+}
+
+impl Interval {
+    /// An interval of `months`, plus `days`, plus `microseconds`, each of which may be negative.
+    pub fn new(months: i32, days: i32, microseconds: i64) -> Interval {
+        Interval {
+            months,
+            days,
+            microseconds,
+        }
+    }
+
+    /// An interval of whole months.
+    pub fn from_months(months: i32) -> Interval {
+        Interval::new(months, 0, 0)
+    }
+
+    /// An interval of whole days, as opposed to multiples of 24 hours.
+    pub fn from_days(days: i32) -> Interval {
+        Interval::new(0, days, 0)
+    }
+
+    /// An interval of a fixed length.
+    pub fn from_micros(microseconds: i64) -> Interval {
+        Interval::new(0, 0, microseconds)
+    }
+}
+
+impl TryFrom<Duration> for Interval {
+    type Error = IntervalError;
+
+    /// Fails if the duration is not a whole number of microseconds, which is the precision of an
+    /// interval, or is longer than about 292 000 years.
+    fn try_from(duration: Duration) -> Result<Self, Self::Error> {
+        if duration.subsec_micros() * 1000 != duration.subsec_nanos() {
+            return Err(IntervalError::Precision);
+        }
+
+        i64::try_from(duration.as_micros())
+            .map(Interval::from_micros)
+            .map_err(|_| IntervalError::TooLong)
+    }
+}
+
+impl TryFrom<Interval> for Duration {
+    type Error = IntervalError;
+
+    /// Fails if the interval has any months or days, or is negative.
+    fn try_from(interval: Interval) -> Result<Self, Self::Error> {
+        if interval.months != 0 || interval.days != 0 {
+            return Err(IntervalError::Calendar);
+        }
+
+        let micros = u64::try_from(interval.microseconds).map_err(|_| IntervalError::Negative)?;
+        Ok(Duration::from_micros(micros))
+    }
+}
+
+impl<'a> FromSql<'a> for Interval {
+    fn from_sql(_: &Type, mut raw: &'a [u8]) -> Result<Self, Box<dyn StdError + Sync + Send>> {
+        if raw.len() != 16 {
+            return Err("invalid buffer size".into());
+        }
+
+        let microseconds = raw.get_i64();
+        let days = raw.get_i32();
+        let months = raw.get_i32();
+        Ok(Interval::new(months, days, microseconds))
+    }
+
+    accepts!(INTERVAL);
+}
+
+impl ToSql for Interval {
+    fn to_sql(
+        &self,
+        _: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn StdError + Sync + Send>> {
+        out.put_i64(self.microseconds);
+        out.put_i32(self.days);
+        out.put_i32(self.months);
+        Ok(IsNull::No)
+    }
+
+    accepts!(INTERVAL);
+    to_sql_checked!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MICROS_PER_SECOND: i64 = 1_000_000;
+
+    #[test]
+    fn duration_conversions() {
+        let interval = Interval::try_from(Duration::from_millis(1500)).unwrap();
+        assert_eq!(interval, Interval::from_micros(3 * MICROS_PER_SECOND / 2));
+        assert_eq!(
+            Duration::try_from(interval),
+            Ok(Duration::from_millis(1500))
+        );
+
+        assert_eq!(
+            Interval::try_from(Duration::from_nanos(1001)),
+            Err(IntervalError::Precision)
+        );
+        assert_eq!(
+            Interval::try_from(Duration::from_secs(u64::MAX)),
+            Err(IntervalError::TooLong)
+        );
+
+        assert_eq!(
+            Duration::try_from(Interval::from_days(1)),
+            Err(IntervalError::Calendar)
+        );
+        assert_eq!(
+            Duration::try_from(Interval::from_micros(-1)),
+            Err(IntervalError::Negative)
+        );
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let interval = Interval::new(-14, 3, 4 * 3600 * MICROS_PER_SECOND + 5);
+        let mut out = BytesMut::new();
+        interval.to_sql(&Type::INTERVAL, &mut out).unwrap();
+        assert_eq!(out.len(), 16);
+        assert_eq!(Interval::from_sql(&Type::INTERVAL, &out).unwrap(), interval);
+    }
+}
@@ -0,0 +1,271 @@
+//! Range types, such as `int4range`, `daterange` and `tstzrange`.
+//!
+//! A [`Range`] may be extracted from (and bound to) a column of any range type whose elements map
+//! to `T`. Ranges of time are a common fit for scheduling and booking:
+//!
+//! ```
+//! # use tokio_postgres::Client;
+//! # use postgres_query::{query, types::range::Range, Result};
+//! # use std::time::SystemTime;
+//! # async fn foo() -> Result<()> {
+//! # let client: Client = unimplemented!();
+//! # let (start, end) = (SystemTime::now(), SystemTime::now());
+//! let during = Range::from(start..end);
+//! query!("INSERT INTO bookings VALUES ($room, $during)", room = 7, during)
+//!     .execute(&client)
+//!     .await?;
+//!
+//! let (during,): (Range<SystemTime>,) = query!("SELECT during FROM bookings LIMIT 1")
+//!     .fetch_one(&client)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Note that the database normalizes ranges of discrete types: the `int4range` `[1,3]` is returned
+//! as `[1,4)`, that is `Range::from(1..4)`.
+//!
+//! [`Range`]: enum.Range.html
+
+use bytes::{BufMut, BytesMut};
+use postgres_types::{to_sql_checked, FromSql, IsNull, Kind, ToSql, Type};
+use std::convert::{TryFrom, TryInto};
+use std::error::Error as StdError;
+use std::ops::{self, Bound};
+
+type BoxError = Box<dyn StdError + Sync + Send>;
+
+const EMPTY: u8 = 0x01;
+const LOWER_INCLUSIVE: u8 = 0x02;
+const UPPER_INCLUSIVE: u8 = 0x04;
+const LOWER_UNBOUNDED: u8 = 0x08;
+const UPPER_UNBOUNDED: u8 = 0x10;
+
+/// A range of values, which is either empty or has a lower and an upper bound (either of which may
+/// be unbounded).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Range<T> {
+    /// A range without any values.
+    Empty,
+    Bounded {
+        lower: Bound<T>,
+        upper: Bound<T>,
+    },
+}
+
+impl<T> Range<T> {
+    /// A range between two bounds.
+    pub fn new(lower: Bound<T>, upper: Bound<T>) -> Range<T> {
+        Range::Bounded { lower, upper }
+    }
+
+    /// `true` if the range is `Empty`.
+    ///
+    /// Bounded ranges which happen to not contain any values (such as `[1,1)`) are never returned
+    /// by the database, since it normalizes them to `Empty`.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Range::Empty => true,
+            Range::Bounded { .. } => false,
+        }
+    }
+
+    /// The lower bound, or `None` if the range is empty.
+    pub fn lower(&self) -> Option<Bound<&T>> {
+        match self {
+            Range::Empty => None,
+            Range::Bounded { lower, .. } => Some(as_ref(lower)),
+        }
+    }
+
+    /// The upper bound, or `None` if the range is empty.
+    pub fn upper(&self) -> Option<Bound<&T>> {
+        match self {
+            Range::Empty => None,
+            Range::Bounded { upper, .. } => Some(as_ref(upper)),
+        }
+    }
+
+    /// `true` if the value is within the bounds of the range.
+    pub fn contains(&self, value: &T) -> bool
+    where
+        T: PartialOrd,
+    {
+        let (lower, upper) = match self {
+            Range::Empty => return false,
+            Range::Bounded { lower, upper } => (lower, upper),
+        };
+
+        let above = match lower {
+            Bound::Included(lower) => lower <= value,
+            Bound::Excluded(lower) => lower < value,
+            Bound::Unbounded => true,
+        };
+        let below = match upper {
+            Bound::Included(upper) => value <= upper,
+            Bound::Excluded(upper) => value < upper,
+            Bound::Unbounded => true,
+        };
+
+        above && below
+    }
+}
+
+fn as_ref<T>(bound: &Bound<T>) -> Bound<&T> {
+    match bound {
+        Bound::Included(value) => Bound::Included(value),
+        Bound::Excluded(value) => Bound::Excluded(value),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+/// `[start,end)`
+impl<T> From<ops::Range<T>> for Range<T> {
+    fn from(range: ops::Range<T>) -> Self {
+        Range::new(Bound::Included(range.start), Bound::Excluded(range.end))
+    }
+}
+
+/// `[start,end]`
+impl<T> From<ops::RangeInclusive<T>> for Range<T> {
+    fn from(range: ops::RangeInclusive<T>) -> Self {
+        let (start, end) = range.into_inner();
+        Range::new(Bound::Included(start), Bound::Included(end))
+    }
+}
+
+/// `[start,)`
+impl<T> From<ops::RangeFrom<T>> for Range<T> {
+    fn from(range: ops::RangeFrom<T>) -> Self {
+        Range::new(Bound::Included(range.start), Bound::Unbounded)
+    }
+}
+
+/// `(,end)`
+impl<T> From<ops::RangeTo<T>> for Range<T> {
+    fn from(range: ops::RangeTo<T>) -> Self {
+        Range::new(Bound::Unbounded, Bound::Excluded(range.end))
+    }
+}
+
+/// `(,)`
+impl<T> From<ops::RangeFull> for Range<T> {
+    fn from(_: ops::RangeFull) -> Self {
+        Range::new(Bound::Unbounded, Bound::Unbounded)
+    }
+}
+
+impl<'a, T> FromSql<'a> for Range<T>
+where
+    T: FromSql<'a>,
+{
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, BoxError> {
+        let element = element_type(ty).ok_or("expected a range type")?;
+
+        let (&flags, mut raw) = raw.split_first().ok_or("invalid buffer size")?;
+        if flags & EMPTY != 0 {
+            return Ok(Range::Empty);
+        }
+
+        let mut read_bound = |unbounded: u8, inclusive: u8| -> Result<Bound<T>, BoxError> {
+            if flags & unbounded != 0 {
+                return Ok(Bound::Unbounded);
+            }
+
+            let (len, rest) = split_at(raw, 4)?;
+            let len = i32::from_be_bytes(len.try_into()?);
+            let len = len.try_into().map_err(|_| "range bounds cannot be NULL")?;
+            let (value, rest) = split_at(rest, len)?;
+            raw = rest;
+
+            let value = T::from_sql(element, value)?;
+            if flags & inclusive != 0 {
+                Ok(Bound::Included(value))
+            } else {
+                Ok(Bound::Excluded(value))
+            }
+        };
+
+        let lower = read_bound(LOWER_UNBOUNDED, LOWER_INCLUSIVE)?;
+        let upper = read_bound(UPPER_UNBOUNDED, UPPER_INCLUSIVE)?;
+        Ok(Range::new(lower, upper))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        element_type(ty).is_some_and(T::accepts)
+    }
+}
+
+impl<T> ToSql for Range<T>
+where
+    T: ToSql,
+{
+    fn to_sql(&self, ty: &Type, out: &mut BytesMut) -> Result<IsNull, BoxError> {
+        let element = element_type(ty).ok_or("expected a range type")?;
+
+        let (lower, upper) = match self {
+            Range::Empty => {
+                out.put_u8(EMPTY);
+                return Ok(IsNull::No);
+            }
+            Range::Bounded { lower, upper } => (lower, upper),
+        };
+
+        let flags = |bound: &Bound<T>, unbounded: u8, inclusive: u8| match bound {
+            Bound::Included(_) => inclusive,
+            Bound::Excluded(_) => 0,
+            Bound::Unbounded => unbounded,
+        };
+        out.put_u8(
+            flags(lower, LOWER_UNBOUNDED, LOWER_INCLUSIVE)
+                | flags(upper, UPPER_UNBOUNDED, UPPER_INCLUSIVE),
+        );
+
+        for bound in &[lower, upper] {
+            if let Bound::Included(value) | Bound::Excluded(value) = bound {
+                write_bound(value, element, out)?;
+            }
+        }
+
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        element_type(ty).is_some_and(T::accepts)
+    }
+
+    to_sql_checked!();
+}
+
+/// Write a bound, prefixed with its length.
+fn write_bound<T>(value: &T, ty: &Type, out: &mut BytesMut) -> Result<(), BoxError>
+where
+    T: ToSql,
+{
+    let start = out.len();
+    out.put_i32(0);
+
+    match value.to_sql(ty, out)? {
+        IsNull::Yes => Err("range bounds cannot be NULL".into()),
+        IsNull::No => {
+            let len = i32::try_from(out.len() - start - 4)?;
+            out[start..start + 4].copy_from_slice(&len.to_be_bytes());
+            Ok(())
+        }
+    }
+}
+
+fn element_type(ty: &Type) -> Option<&Type> {
+    match ty.kind() {
+        Kind::Range(element) => Some(element),
+        _ => None,
+    }
+}
+
+fn split_at(raw: &[u8], len: usize) -> Result<(&[u8], &[u8]), BoxError> {
+    if raw.len() < len {
+        Err("invalid buffer size".into())
+    } else {
+        Ok(raw.split_at(len))
+    }
+}
@@ -0,0 +1,266 @@
+//! Unsigned integers, which Postgres has no types for.
+
+use bytes::{BufMut, BytesMut};
+use postgres_types::{to_sql_checked, FromSql, IsNull, ToSql, Type};
+use std::convert::{TryFrom, TryInto};
+use std::error::Error as StdError;
+use std::fmt::{Debug, Display, Write};
+use thiserror::Error;
+
+type BoxError = Box<dyn StdError + Sync + Send>;
+
+const NUMERIC_POS: u16 = 0x0000;
+const NUMERIC_NEG: u16 = 0x4000;
+const NUMERIC_BASE: u64 = 10000;
+
+/// An unsigned integer, stored in a signed integer column (`SMALLINT`, `INT`, `BIGINT`) or in a
+/// `NUMERIC`.
+///
+/// Postgres has no unsigned types, so plain `u32` and `u64` cannot be bound or extracted. This
+/// wrapper checks the range of the value instead, in both directions: binding a value which does
+/// not fit the column, or extracting a negative (or too large) value fails with an [`OutOfRange`]
+/// error rather than wrapping around. `NUMERIC` holds all values of a `u64`:
+///
+/// ```
+/// # use tokio_postgres::Client;
+/// # use postgres_query::{query, types::Unsigned, Result};
+/// # async fn foo() -> Result<()> {
+/// # let client: Client = unimplemented!();
+/// let downloads = Unsigned(u64::MAX);
+/// query!("UPDATE stats SET downloads = $downloads::numeric", downloads)
+///     .execute(&client)
+///     .await?;
+///
+/// let (downloads,): (Unsigned<u64>,) = query!("SELECT downloads FROM stats")
+///     .fetch_one(&client)
+///     .await?;
+/// assert_eq!(downloads.get(), u64::MAX);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`OutOfRange`]: struct.OutOfRange.html
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Unsigned<T>(pub T);
+
+impl<T> Unsigned<T> {
+    /// The wrapped integer.
+    pub fn get(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Unsigned<T>
+where
+    T: Integer,
+{
+    fn from(value: T) -> Self {
+        Unsigned(value)
+    }
+}
+
+/// The integers which may be wrapped in [`Unsigned`]: `u8`, `u16`, `u32` and `u64`.
+///
+/// [`Unsigned`]: struct.Unsigned.html
+pub trait Integer: Copy + Debug + Into<u64> + TryFrom<u64> + private::Sealed {}
+
+impl Integer for u8 {}
+impl Integer for u16 {}
+impl Integer for u32 {}
+impl Integer for u64 {}
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+    impl Sealed for u64 {}
+}
+
+/// A value did not fit in the type it was converted to.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("the value {value} is out of range for `{target}`")]
+pub struct OutOfRange {
+    /// The value, as written in SQL.
+    pub value: String,
+    /// The type the value was converted to: an SQL type when binding, a Rust type when extracting.
+    pub target: &'static str,
+}
+
+/// The value of a `NUMERIC` had digits after the decimal point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("the `NUMERIC` has a fractional part, and cannot be extracted as an integer")]
+pub struct Fractional;
+
+impl<T> Unsigned<T>
+where
+    T: Integer,
+{
+    fn from_u64(value: u64) -> Result<Self, OutOfRange> {
+        T::try_from(value)
+            .map(Unsigned)
+            .map_err(|_| out_of_range::<T>(value))
+    }
+
+    fn from_i64(value: i64) -> Result<Self, OutOfRange> {
+        let unsigned = u64::try_from(value).map_err(|_| out_of_range::<T>(value))?;
+        Self::from_u64(unsigned)
+    }
+}
+
+fn out_of_range<T>(value: impl Display) -> OutOfRange {
+    OutOfRange {
+        value: value.to_string(),
+        target: std::any::type_name::<T>(),
+    }
+}
+
+impl<'a, T> FromSql<'a> for Unsigned<T>
+where
+    T: Integer,
+{
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, BoxError> {
+        let unsigned = match *ty {
+            Type::INT2 => Self::from_i64(i16::from_sql(ty, raw)?.into())?,
+            Type::INT4 => Self::from_i64(i32::from_sql(ty, raw)?.into())?,
+            Type::INT8 => Self::from_i64(i64::from_sql(ty, raw)?)?,
+            _ => {
+                let text = read_numeric(raw)?;
+                let value = text.parse().map_err(|_| out_of_range::<T>(&text))?;
+                Self::from_u64(value)?
+            }
+        };
+        Ok(unsigned)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        accepts(ty)
+    }
+}
+
+impl<T> ToSql for Unsigned<T>
+where
+    T: Integer,
+{
+    fn to_sql(&self, ty: &Type, out: &mut BytesMut) -> Result<IsNull, BoxError> {
+        let value: u64 = self.0.into();
+        let overflow = |target| OutOfRange {
+            value: value.to_string(),
+            target,
+        };
+
+        match *ty {
+            Type::INT2 => out.put_i16(value.try_into().map_err(|_| overflow("SMALLINT"))?),
+            Type::INT4 => out.put_i32(value.try_into().map_err(|_| overflow("INT"))?),
+            Type::INT8 => out.put_i64(value.try_into().map_err(|_| overflow("BIGINT"))?),
+            _ => write_numeric(value, out),
+        }
+
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        accepts(ty)
+    }
+
+    to_sql_checked!();
+}
+
+fn accepts(ty: &Type) -> bool {
+    matches!(*ty, Type::INT2 | Type::INT4 | Type::INT8 | Type::NUMERIC)
+}
+
+/// Read the decimal digits of a `NUMERIC`, which must be an integer.
+///
+/// A `NUMERIC` is sent as a header followed by its digits in base 10000, the first of which is
+/// multiplied by `10000^weight`.
+fn read_numeric(raw: &[u8]) -> Result<String, BoxError> {
+    let mut words = raw
+        .chunks(2)
+        .map(|word| word.try_into().map(u16::from_be_bytes));
+    let mut next = || -> Result<u16, BoxError> { Ok(words.next().ok_or("invalid buffer size")??) };
+
+    let count = next()?;
+    let weight = next()? as i16;
+    let sign = next()?;
+    let _scale = next()?;
+    let digits = (0..count).map(|_| next()).collect::<Result<Vec<_>, _>>()?;
+
+    let mut text = String::new();
+    if sign == NUMERIC_NEG {
+        text.push('-');
+    } else if sign != NUMERIC_POS {
+        return Err("the `NUMERIC` is not a number".into());
+    }
+
+    if weight < 0 {
+        text.push('0');
+    }
+    for i in 0..(weight + 1).max(0) as usize {
+        let digit = digits.get(i).copied().unwrap_or(0);
+        if i == 0 {
+            write!(text, "{}", digit)?;
+        } else {
+            write!(text, "{:04}", digit)?;
+        }
+    }
+
+    let integer = (weight + 1).max(0) as usize;
+    if digits.iter().skip(integer).any(|&digit| digit != 0) {
+        return Err(Fractional.into());
+    }
+
+    Ok(text)
+}
+
+/// Write an integer as a `NUMERIC`. See `read_numeric` for the format.
+fn write_numeric(mut value: u64, out: &mut BytesMut) {
+    let mut digits = Vec::new();
+    while value > 0 {
+        digits.push((value % NUMERIC_BASE) as i16);
+        value /= NUMERIC_BASE;
+    }
+    digits.reverse();
+    let weight = digits.len() as i16 - 1;
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+
+    out.put_i16(digits.len() as i16);
+    out.put_i16(weight.max(0));
+    out.put_u16(NUMERIC_POS);
+    out.put_u16(0);
+    for digit in digits {
+        out.put_i16(digit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numeric(value: u64) -> String {
+        let mut out = BytesMut::new();
+        write_numeric(value, &mut out);
+        read_numeric(&out).unwrap()
+    }
+
+    #[test]
+    fn numeric_round_trip() {
+        for &value in &[0, 1, 9999, 10000, 12345678, 100000000, u64::MAX] {
+            assert_eq!(numeric(value), value.to_string());
+        }
+    }
+
+    #[test]
+    fn numeric_fractions() {
+        // `SELECT '1.5'::numeric`, with the digits `1` and `5000`.
+        let raw = [0, 2, 0, 0, 0, 0, 0, 1, 0, 1, 0x13, 0x88];
+        assert!(read_numeric(&raw).unwrap_err().is::<Fractional>());
+
+        // `SELECT '-2.0'::numeric`
+        let raw = [0, 1, 0, 0, 0x40, 0, 0, 1, 0, 2];
+        assert_eq!(read_numeric(&raw).unwrap(), "-2");
+    }
+}
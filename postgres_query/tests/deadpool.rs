@@ -1,7 +1,8 @@
 #![cfg(feature = "deadpool")]
 
-use postgres_query::*;
-use deadpool_postgres::{Pool, Client, Config};
+use deadpool_postgres::{Client, Config, Pool};
+use postgres_query::{client::HealthCheck, *};
+use std::time::Duration;
 
 fn connect() -> Pool {
     let mut cfg = Config::new();
@@ -17,3 +18,9 @@ async fn simple_query() {
     let query: Query = query_dyn!("SELECT 14").unwrap();
     let res = query.fetch_one::<(i32,), _>(&client).await;
 }
+
+#[tokio::test]
+async fn pool_health() {
+    let pool = connect();
+    assert!(pool.is_healthy(Duration::from_secs(5)).await);
+}
@@ -10,8 +10,29 @@
 //! `tokio_postgres::connect`).
 
 use anyhow::{anyhow, Error};
-use postgres_query::{client::Caching, query, FromSqlRow};
+use futures::future::{self, Either};
+use futures::{FutureExt, StreamExt, TryStreamExt};
+use postgres_query::{
+    changes::execute_returning_changes,
+    client::{
+        Caching, Exclusive, GenericClient, HealthCheck, Nested, PrepareEvent, Recording,
+        ResultCache, Scoped,
+    },
+    conflict::{self, Conflict, ConflictKind, InsertOutcome, Outcome},
+    copy::{copy_in_rows, CopyIn},
+    cte::{Cte, Unnest},
+    execute::{self, ExecuteOptions, PrepareMode},
+    execute_batch,
+    extract::{self, GroupAccumulator, Nullable, Row as _},
+    fetch_row, interop, metrics, notify,
+    order::OrderBy,
+    outbox, query, query_dyn, update, FromSqlRow, Patch, Query, ToSqlDelegate, ToSqlRow,
+};
+use postgres_types::{accepts, FromSql, Type};
 use std::env;
+use std::error::Error as StdError;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio_postgres::Client;
 
 type Result<T = (), E = Error> = std::result::Result<T, E>;
@@ -77,6 +98,264 @@ async fn cached_fetch() -> Result {
     Ok(())
 }
 
+#[tokio::test]
+async fn caching_hooks() -> Result {
+    let prepared = Arc::new(Mutex::new(Vec::new()));
+    let evicted = Arc::new(Mutex::new(Vec::new()));
+
+    let client = Caching::new(establish().await?)
+        .on_prepare({
+            let prepared = prepared.clone();
+            move |event| {
+                let mut prepared = prepared.lock().unwrap();
+                prepared.push((event.sql.to_owned(), event.types.to_vec(), event.cached));
+            }
+        })
+        .on_evict({
+            let evicted = evicted.clone();
+            move |sql| evicted.lock().unwrap().push(sql.to_owned())
+        });
+
+    for _ in 0..3usize {
+        let (value,): (i32,) = query!("SELECT 1").fetch_one(&client).await?;
+        assert_eq!(value, 1);
+    }
+    client
+        .prepare_typed_static("SELECT $1", &[Type::INT8])
+        .await?;
+
+    assert_eq!(
+        *prepared.lock().unwrap(),
+        vec![
+            ("SELECT 1".to_owned(), vec![], false),
+            ("SELECT 1".to_owned(), vec![], true),
+            ("SELECT 1".to_owned(), vec![], true),
+            ("SELECT $1".to_owned(), vec![Type::INT8], false),
+        ]
+    );
+
+    client.clear().await;
+    assert_eq!(*evicted.lock().unwrap(), ["SELECT 1", "SELECT $1"]);
+
+    // The statement is prepared again once it has been evicted.
+    query!("SELECT 1").fetch_one::<(i32,), _>(&client).await?;
+    assert!(!prepared.lock().unwrap().last().unwrap().2);
+
+    // Moving to a new connection evicts the statements of the old one.
+    evicted.lock().unwrap().clear();
+    let (old, detached) = client.into_parts();
+    drop(old);
+    let _client = Caching::from_parts(establish().await?, detached);
+    assert_eq!(*evicted.lock().unwrap(), ["SELECT 1"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn caching_skips_ddl() -> Result {
+    let cached = Arc::new(Mutex::new(Vec::new()));
+    let record = |cached: &Arc<Mutex<Vec<bool>>>| {
+        let cached = cached.clone();
+        move |event: &PrepareEvent| cached.lock().unwrap().push(event.cached)
+    };
+
+    let mut client = Caching::new(establish().await?).on_prepare(record(&cached));
+    let tx = client.transaction().await?;
+
+    for _ in 0..2usize {
+        query!("CREATE TEMP TABLE IF NOT EXISTS people (name TEXT)")
+            .execute(&tx)
+            .await?;
+        query!("SELECT 1").no_cache().execute(&tx).await?;
+    }
+    for _ in 0..2usize {
+        query!("SELECT 1").execute(&tx).await?;
+    }
+    assert_eq!(
+        *cached.lock().unwrap(),
+        [false, false, false, false, false, true]
+    );
+
+    cached.lock().unwrap().clear();
+    let client = Caching::new(establish().await?)
+        .skip_cache_for(&["select"])
+        .on_prepare(record(&cached));
+    for _ in 0..2usize {
+        query!("SELECT 1").execute(&client).await?;
+    }
+    assert_eq!(*cached.lock().unwrap(), [false, false]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn caching_prepared_handle() -> Result {
+    let prepared = Arc::new(Mutex::new(0));
+    let mut client = establish().await?;
+    let tx = Recording::new(client.transaction().await?);
+    let client = Caching::new(tx).on_prepare({
+        let prepared = prepared.clone();
+        move |_| *prepared.lock().unwrap() += 1
+    });
+
+    client
+        .batch_execute("CREATE TABLE people (name TEXT, age INT)")
+        .await?;
+
+    let insert = client
+        .prepared(&Query::new_static(
+            "INSERT INTO people VALUES ($1, $2)",
+            vec![],
+        ))
+        .await?;
+    for (name, age) in &[("John Wick", 42), ("Winston", 65)] {
+        assert_eq!(insert.execute(&[name, age]).await?, 1);
+    }
+
+    let older = client
+        .prepared(&query!("SELECT name FROM people WHERE age > $age", age = 0))
+        .await?;
+    let names: Vec<(String,)> = older.fetch(&[&40]).await?;
+    assert_eq!(names.len(), 2);
+    let (name,): (String,) = older.fetch_one(&[&50]).await?;
+    assert_eq!(name, "Winston");
+    assert!(older.fetch_one::<(String,)>(&[&70]).await.is_err());
+
+    // Each statement is only requested from the cache once.
+    assert_eq!(*prepared.lock().unwrap(), 2);
+
+    let mutations = client.mutations();
+    assert_eq!(mutations.len(), 2);
+    assert_eq!(mutations[1].sql, "INSERT INTO people VALUES ($1, $2)");
+    assert_eq!(mutations[1].parameters.len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn caching_transaction_rollback() -> Result {
+    let prepared = Arc::new(Mutex::new(Vec::new()));
+    let evicted = Arc::new(Mutex::new(Vec::new()));
+
+    let mut client = Caching::new(establish().await?)
+        .on_prepare({
+            let prepared = prepared.clone();
+            move |event| prepared.lock().unwrap().push(event.cached)
+        })
+        .on_evict({
+            let evicted = evicted.clone();
+            move |sql| evicted.lock().unwrap().push(sql.to_owned())
+        });
+
+    async fn is_happy<C: GenericClient + Sync>(client: &C) -> Result<bool> {
+        let (happy,) = query!("SELECT 'happy'::mood = 'happy'")
+            .fetch_one(client)
+            .await?;
+        Ok(happy)
+    }
+
+    let mut tx = client.transaction().await?;
+
+    // The statement refers to a type which no longer exists after the rollback.
+    let savepoint = tx.transaction().await?;
+    savepoint
+        .batch_execute("CREATE TYPE mood AS ENUM ('happy', 'sad')")
+        .await?;
+    assert!(is_happy(&savepoint).await?);
+    savepoint.rollback().await?;
+    assert_eq!(*evicted.lock().unwrap(), ["SELECT 'happy'::mood = 'happy'"]);
+
+    let savepoint = tx.transaction().await?;
+    savepoint
+        .batch_execute("CREATE TYPE mood AS ENUM ('happy', 'sad')")
+        .await?;
+    assert!(is_happy(&savepoint).await?);
+    savepoint.commit().await?;
+    assert_eq!(evicted.lock().unwrap().len(), 1);
+
+    // Committed statements remain cached until the outer transaction is rolled back.
+    assert!(is_happy(&tx).await?);
+    assert_eq!(*prepared.lock().unwrap(), [false, false, true]);
+    tx.rollback().await?;
+    assert_eq!(evicted.lock().unwrap().len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn shared_clients() -> Result {
+    let cached = Arc::new(Mutex::new(Vec::new()));
+    let client = Arc::new(Caching::new(establish().await?).on_prepare({
+        let cached = cached.clone();
+        move |event| cached.lock().unwrap().push(event.cached)
+    }));
+
+    let mut values = Vec::new();
+    for i in 0..2i32 {
+        let client = client.clone();
+        let task = tokio::spawn(async move {
+            query!("SELECT $i::INT4 + 1", i)
+                .fetch_one::<(i32,), _>(&client)
+                .await
+        });
+        let (value,) = task.await??;
+        values.push(value);
+    }
+    assert_eq!(values, [1, 2]);
+
+    // Statements are still cached by the client behind the `Arc`.
+    assert_eq!(*cached.lock().unwrap(), [false, true]);
+
+    let boxed = Box::new(establish().await?);
+    let (value,): (i32,) = query!("SELECT 1").fetch_one(&boxed).await?;
+    assert_eq!(value, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cached_prepare_typed() -> Result {
+    let cached = Arc::new(Mutex::new(Vec::new()));
+    let client = establish().await?;
+    let client = Caching::new(client).on_prepare({
+        let cached = cached.clone();
+        move |event| {
+            let mut cached = cached.lock().unwrap();
+            cached.push((event.types.to_vec(), event.cached));
+        }
+    });
+
+    const SQL: &str = "SELECT $1";
+
+    for _ in 0..3usize {
+        let statement = client.prepare_typed_static(SQL, &[Type::INT8]).await?;
+        assert_eq!(statement.params(), [Type::INT8]);
+
+        let statement = client.prepare_typed_static(SQL, &[Type::TEXT]).await?;
+        assert_eq!(statement.params(), [Type::TEXT]);
+    }
+
+    let statement = client.prepare_typed_static(SQL, &[Type::INT8]).await?;
+    let value: i64 = client.query_one(&statement, &[&7i64]).await?.get(0);
+    assert_eq!(value, 7);
+
+    let cached = cached.lock().unwrap();
+    let int8 = cached
+        .iter()
+        .filter(|(types, _)| *types == [Type::INT8])
+        .map(|&(_, cached)| cached)
+        .collect::<Vec<_>>();
+    let text = cached
+        .iter()
+        .filter(|(types, _)| *types == [Type::TEXT])
+        .map(|&(_, cached)| cached)
+        .collect::<Vec<_>>();
+    assert_eq!(int8, [false, true, true, true]);
+    assert_eq!(text, [false, true, true]);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn fetch_named_struct() -> Result {
     let client = establish().await?;
@@ -309,6 +588,48 @@ async fn multi_mapping_custom_stride() -> Result {
     Ok(())
 }
 
+#[tokio::test]
+async fn multi_mapping_repeat() -> Result {
+    let mut client = establish().await?;
+    let tx = client.transaction().await?;
+
+    #[derive(Debug, FromSqlRow)]
+    struct Person {
+        id: i32,
+        name: String,
+    }
+
+    #[derive(Debug, FromSqlRow)]
+    #[row(exact)]
+    struct Race {
+        id: i32,
+        #[row(repeat = 2)]
+        podium: [Person; 2],
+        #[row(repeat = 2)]
+        others: Vec<Person>,
+    }
+
+    let race = query!(
+        "SELECT
+            7 as id,
+            1 as id, 'Bob' as name,
+            2 as id, 'Ike' as name,
+            3 as id, 'Amy' as name,
+            4 as id, 'Tom' as name"
+    )
+    .fetch_one::<Race, _>(&tx)
+    .await?;
+
+    assert_eq!(race.id, 7);
+    assert_eq!(race.podium[0].id, 1);
+    assert_eq!(race.podium[1].name, "Ike");
+    assert_eq!(race.others.len(), 2);
+    assert_eq!(race.others[0].name, "Amy");
+    assert_eq!(race.others[1].id, 4);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn multi_mapping_exact_mixed_fields() -> Result {
     let mut client = establish().await?;
@@ -784,6 +1105,7 @@ async fn optional_flatten_invalid_type() -> Result {
     let mut client = establish().await?;
     let tx = client.transaction().await?;
 
+    #[allow(dead_code)]
     #[derive(FromSqlRow, Clone)]
     #[row(split)]
     struct Family {
@@ -793,6 +1115,7 @@ async fn optional_flatten_invalid_type() -> Result {
         father: Option<Person>,
     }
 
+    #[allow(dead_code)]
     #[derive(FromSqlRow, Clone)]
     struct Person {
         id: i32,
@@ -856,3 +1179,3054 @@ async fn optional_flatten_nested_option() -> Result {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn optional_flatten_none_when() -> Result {
+    let mut client = establish().await?;
+    let tx = client.transaction().await?;
+
+    #[derive(FromSqlRow)]
+    #[row(split)]
+    struct Family {
+        #[row(flatten, split = "id")]
+        child: Person,
+        #[row(flatten, split = "id", none_when = "key_null")]
+        father: Option<Person>,
+        #[row(flatten, split = "id", none_when = "all_null")]
+        mother: Option<Person>,
+    }
+
+    #[derive(FromSqlRow)]
+    struct Person {
+        id: i32,
+        name: String,
+    }
+
+    let family: Family = query!(
+        "SELECT 1 as id, 'Luke Skywalker' as name,
+                NULL::int as id, 'Darth Vader' as name,
+                NULL::int as id, NULL::text as name"
+    )
+    .fetch_one(&tx)
+    .await?;
+
+    assert_eq!(family.child.id, 1);
+    assert_eq!(family.child.name, "Luke Skywalker");
+    assert!(family.father.is_none());
+    assert!(family.mother.is_none());
+
+    let family = query!(
+        "SELECT 1 as id, 'Luke Skywalker' as name,
+                2 as id, NULL::text as name,
+                NULL::int as id, NULL::text as name"
+    )
+    .fetch_one::<Family, _>(&tx)
+    .await;
+
+    // The father is present, but without a name
+    assert!(family.is_err());
+
+    let family = query!(
+        "SELECT 1 as id, 'Luke Skywalker' as name,
+                NULL::int as id, NULL::text as name,
+                NULL::int as id, 'Padmé Amidala' as name"
+    )
+    .fetch_one::<Family, _>(&tx)
+    .await;
+
+    // The mother is present, but without an id
+    assert!(family.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn optional_flatten_positional_columns() -> Result {
+    let client = establish().await?;
+
+    #[derive(FromSqlRow)]
+    #[row(split)]
+    struct Entry {
+        #[row(flatten, split = "id")]
+        key: Key,
+        #[row(flatten, split = "first", none_when = "all_null")]
+        detail: Option<Detail>,
+    }
+
+    #[derive(FromSqlRow)]
+    struct Key {
+        id: i32,
+    }
+
+    #[derive(FromSqlRow)]
+    #[row(positional)]
+    struct Detail {
+        #[row(flatten)]
+        name: Name,
+        score: Option<i32>,
+    }
+
+    #[derive(FromSqlRow)]
+    struct Name {
+        first: Option<String>,
+        last: Option<String>,
+    }
+
+    // The score follows both columns of the name, not just one.
+    let entry: Entry = query!("SELECT 1 AS id, NULL::text AS first, NULL::text AS last, 7")
+        .fetch_one(&client)
+        .await?;
+
+    assert_eq!(entry.key.id, 1);
+    let detail = entry.detail.expect("the score is not NULL");
+    assert_eq!(detail.name.first, None);
+    assert_eq!(detail.name.last, None);
+    assert_eq!(detail.score, Some(7));
+
+    let entry: Entry =
+        query!("SELECT 1 AS id, NULL::text AS first, NULL::text AS last, NULL::int4")
+            .fetch_one(&client)
+            .await?;
+
+    assert!(entry.detail.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn optional_flatten_partial() -> Result {
+    let client = establish().await?;
+
+    #[derive(FromSqlRow)]
+    #[row(split)]
+    struct Document {
+        #[row(flatten, split = "title")]
+        title: Title,
+        #[row(flatten, split = "created_by")]
+        audit: Option<Audit>,
+        #[row(flatten, split = "created_by", none_when = "all_null")]
+        strict: Option<Audit>,
+    }
+
+    #[derive(FromSqlRow)]
+    struct Title {
+        title: String,
+    }
+
+    #[derive(Debug, PartialEq, FromSqlRow)]
+    struct Audit {
+        created_by: String,
+        updated_by: Option<String>,
+    }
+
+    async fn fetch(client: &Client, audits: &'static str) -> Result<Document> {
+        let sql = format!("SELECT 'Report' AS title, {}", audits);
+        let document = Query::new(sql, Vec::new()).fetch_one(client).await?;
+        Ok(document)
+    }
+
+    // All columns are `NULL`, even though the optional one could be extracted.
+    let document = fetch(
+        &client,
+        "NULL::text AS created_by, NULL::text AS updated_by,
+         NULL::text AS created_by, NULL::text AS updated_by",
+    )
+    .await?;
+    assert_eq!(document.title.title, "Report");
+    assert_eq!(document.audit, None);
+    assert_eq!(document.strict, None);
+
+    // Partial data is kept.
+    let document = fetch(
+        &client,
+        "'alice' AS created_by, NULL::text AS updated_by,
+         'alice' AS created_by, NULL::text AS updated_by",
+    )
+    .await?;
+    let audit = Audit {
+        created_by: "alice".to_owned(),
+        updated_by: None,
+    };
+    assert_eq!(document.audit.as_ref(), Some(&audit));
+    assert_eq!(document.strict.as_ref(), Some(&audit));
+
+    // A required column is `NULL`, which is only an error in strict mode.
+    let document = fetch(
+        &client,
+        "NULL::text AS created_by, 'bob' AS updated_by,
+         'alice' AS created_by, 'bob' AS updated_by",
+    )
+    .await?;
+    assert_eq!(document.audit, None);
+    assert_eq!(document.strict.unwrap().created_by, "alice");
+
+    let document = fetch(
+        &client,
+        "'alice' AS created_by, 'bob' AS updated_by,
+         NULL::text AS created_by, 'bob' AS updated_by",
+    )
+    .await;
+    assert!(document.is_err());
+
+    // Flattened values whose fields are all optional are also `None` once all their columns are
+    // `NULL`, but fetching such a value directly still extracts it.
+    type Edit = Option<(Option<String>, Option<i32>)>;
+
+    #[derive(FromSqlRow)]
+    struct Flattened {
+        #[row(flatten)]
+        edit: Edit,
+    }
+
+    let flattened: Flattened = query!("SELECT NULL::text, NULL::int")
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(flattened.edit, None);
+    let flattened: Flattened = query!("SELECT 'bob', NULL::int").fetch_one(&client).await?;
+    assert_eq!(flattened.edit, Some((Some("bob".to_owned()), None)));
+
+    let edit: Edit = query!("SELECT NULL::text, NULL::int")
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(edit, Some((None, None)));
+
+    // Errors while checking for `NULL`s are returned.
+    #[derive(FromSqlRow)]
+    struct Missing {
+        #[row(flatten)]
+        audit: Option<Audit>,
+    }
+
+    let missing = query!("SELECT 'alice' AS created_by")
+        .fetch_one::<Missing, _>(&client)
+        .await
+        .map(|missing| missing.audit);
+    assert!(missing.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ping() -> Result {
+    let client = establish().await?;
+
+    client.ping().await?;
+    assert!(client.is_healthy(Duration::from_secs(5)).await);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn nested_savepoint() -> Result {
+    let mut client = establish().await?;
+    let mut tx = client.transaction().await?;
+
+    query!("CREATE TABLE people (name TEXT PRIMARY KEY)")
+        .execute(&tx)
+        .await?;
+
+    let mut results = Vec::new();
+    for _ in 0..2usize {
+        let inserted = tx
+            .nested(|sp| {
+                async move {
+                    query!("INSERT INTO people VALUES ($name)", name = "John Wick")
+                        .execute(sp)
+                        .await
+                }
+                .boxed()
+            })
+            .await;
+        results.push(inserted);
+    }
+
+    let duplicate = results.pop().unwrap();
+    let first = results.pop().unwrap();
+
+    assert_eq!(first?, 1);
+    assert!(duplicate.is_err());
+
+    let (count,): (i64,) = query!("SELECT COUNT(*) FROM people").fetch_one(&tx).await?;
+    assert_eq!(count, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn batch_execute() -> Result {
+    let mut client = establish().await?;
+    let mut tx = client.transaction().await?;
+
+    query!("CREATE TABLE people (name TEXT PRIMARY KEY)")
+        .execute(&tx)
+        .await?;
+
+    let insert_john = query!("INSERT INTO people VALUES ($name)", name = "John Wick");
+
+    let (john, others) = execute_batch!(
+        tx;
+        insert_john,
+        query!("INSERT INTO people VALUES ('Bob'), ('Ike')"),
+    )
+    .await?;
+
+    assert_eq!(john, 1);
+    assert_eq!(others, 2);
+
+    let failed = execute_batch!(
+        tx;
+        query!("INSERT INTO people VALUES ('Anna')"),
+        query!("INSERT INTO people VALUES ('Bob')"),
+    )
+    .await;
+
+    assert!(failed.is_err());
+
+    let (count,): (i64,) = query!("SELECT COUNT(*) FROM people").fetch_one(&tx).await?;
+    assert_eq!(count, 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn query_columns() -> Result {
+    let client = establish().await?;
+
+    let query = query!("SELECT 14 as id, 'Myke' as name");
+    let columns = query.columns(&client).await?;
+
+    let names = columns.names().collect::<Vec<_>>();
+    assert_eq!(names, ["id", "name"]);
+
+    match query.fetch_one::<(i32,), _>(&client).await {
+        Err(postgres_query::Error::Execute(execute::Error::Extract(
+            error @ extract::Error::ColumnCount { .. },
+        ))) => {
+            let message = error.to_string();
+            assert!(
+                message.contains("columns were: `id` (int4), `name` (text)"),
+                "{}",
+                message
+            );
+        }
+        Ok((id,)) => panic!("expected an extraction error, found id {}", id),
+        Err(error) => return Err(error.into()),
+    }
+
+    #[derive(FromSqlRow)]
+    #[row(split)]
+    struct Aged {
+        #[row(split = "age")]
+        age: i32,
+    }
+
+    match query.fetch_one::<Aged, _>(&client).await {
+        Err(postgres_query::Error::Execute(execute::Error::Extract(error))) => {
+            let message = error.to_string();
+            assert!(
+                message.contains("`id` (int4), `name` (text)"),
+                "{}",
+                message
+            );
+        }
+        Ok(aged) => panic!("expected an extraction error, found age {}", aged.age),
+        Err(error) => return Err(error.into()),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_wide_nullable_tuple() -> Result {
+    let client = establish().await?;
+
+    type Wide = (
+        i32,
+        i32,
+        i32,
+        i32,
+        i32,
+        i32,
+        i32,
+        i32,
+        i32,
+        i32,
+        i32,
+        i32,
+        i32,
+        Option<i32>,
+        Nullable<i32>,
+        Nullable<String>,
+    );
+
+    let row: Wide = query!(
+        "SELECT 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, NULL::int, NULL::int, NULL::text"
+    )
+    .fetch_one(&client)
+    .await?;
+
+    assert_eq!(row.0, 1);
+    assert_eq!(row.12, 13);
+    assert_eq!(row.13, None);
+    assert_eq!(row.14, Nullable(0));
+    assert_eq!(*row.15, "");
+
+    let missing = query!("SELECT NULL::int")
+        .fetch_one::<(i32,), _>(&client)
+        .await;
+    assert!(missing.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_money() -> Result {
+    use postgres_query::types::Money;
+
+    let client = establish().await?;
+
+    let price = Money::from_cents(-1250);
+    let (fetched, total): (Money, Money) = query!("SELECT $price::money, '12.34'::money", price)
+        .fetch_one(&client)
+        .await?;
+
+    assert_eq!(fetched, price);
+    assert_eq!(total.cents(), 1234);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn interval_round_trip() -> Result {
+    use postgres_query::types::{Interval, IntervalError};
+    use std::convert::TryFrom;
+    use std::time::Duration;
+
+    let client = establish().await?;
+
+    let timeout = Interval::try_from(Duration::from_millis(2500))?;
+    let mixed = Interval::new(-1, 2, 3);
+    let (timeout_text, fetched, parsed): (String, Interval, Interval) = query!(
+        "SELECT $timeout::interval::text, $mixed::interval, '1 year 2 mons 3 days 04:05:06.5'::interval",
+        timeout,
+        mixed,
+    )
+    .fetch_one(&client)
+    .await?;
+
+    assert_eq!(timeout_text, "00:00:02.5");
+    assert_eq!(fetched, mixed);
+    assert_eq!(parsed, Interval::new(14, 3, 14_706_500_000));
+    assert_eq!(Duration::try_from(parsed), Err(IntervalError::Calendar));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn unsigned_integers() -> Result {
+    use postgres_query::types::{OutOfRange, Unsigned};
+
+    let client = establish().await?;
+
+    let max = Unsigned(u64::MAX);
+    let small = Unsigned(3_000_000_000u32);
+    let (numeric, bigint): (Unsigned<u64>, Unsigned<u32>) =
+        query!("SELECT $max::numeric, $small::bigint", max, small)
+            .fetch_one(&client)
+            .await?;
+    assert_eq!(numeric, max);
+    assert_eq!(bigint, small);
+
+    let out_of_range = |error: Error| {
+        let mut source: Option<&(dyn StdError + 'static)> = Some(error.as_ref());
+        while let Some(error) = source {
+            if let Some(out_of_range) = error.downcast_ref::<OutOfRange>() {
+                return Some(out_of_range.clone());
+            }
+            source = error.source();
+        }
+        None
+    };
+
+    let bind = query!("SELECT $max::bigint", max)
+        .fetch_one::<(i64,), _>(&client)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        out_of_range(bind.into()),
+        Some(OutOfRange {
+            value: u64::MAX.to_string(),
+            target: "BIGINT"
+        })
+    );
+
+    let negative = query!("SELECT -1::int")
+        .fetch_one::<(Unsigned<u64>,), _>(&client)
+        .await
+        .unwrap_err();
+    assert_eq!(out_of_range(negative.into()).unwrap().target, "u64");
+
+    let large = query!("SELECT 5000000000::numeric")
+        .fetch_one::<(Unsigned<u32>,), _>(&client)
+        .await
+        .unwrap_err();
+    assert_eq!(out_of_range(large.into()).unwrap().value, "5000000000");
+
+    let fractional = query!("SELECT 1.5::numeric")
+        .fetch_one::<(Unsigned<u64>,), _>(&client)
+        .await;
+    assert!(fractional.is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "with-uuid-1")]
+#[tokio::test]
+async fn fetch_uuid_tuple() -> Result {
+    use postgres_query::types::uuid::Uuid;
+
+    let client = establish().await?;
+
+    let id = Uuid::from_u128(0x936d_a01f_9abd_4d9d_80c7_02af_85c8_22a8);
+    let (fetched, name): (Uuid, String) = query!("SELECT $id::uuid, 'Myke'", id)
+        .fetch_one(&client)
+        .await?;
+
+    assert_eq!(fetched, id);
+    assert_eq!(name, "Myke");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn result_cache() -> Result {
+    let mut client = establish().await?;
+    let tx = client.transaction().await?;
+
+    query!("CREATE TABLE people (name TEXT, age INTEGER)")
+        .execute(&tx)
+        .await?;
+    query!("INSERT INTO people VALUES ('Myke', 31)")
+        .execute(&tx)
+        .await?;
+
+    let cache = ResultCache::new(&tx, Duration::from_secs(60), 16);
+
+    let adults = query!("SELECT name FROM people WHERE age >= $age", age = 18);
+    let first: Vec<(String,)> = cache.fetch(&adults).await?;
+    assert_eq!(first, [("Myke".to_owned(),)]);
+
+    query!("INSERT INTO people VALUES ('Anna', 42)")
+        .execute(&tx)
+        .await?;
+
+    let cached: Vec<(String,)> = cache.fetch(&adults).await?;
+    assert_eq!(cached, first);
+
+    let seniors = query!("SELECT name FROM people WHERE age >= $age", age = 50);
+    let other: Vec<(String,)> = cache.fetch(&seniors).await?;
+    assert!(other.is_empty());
+
+    cache.invalidate(&adults).await;
+    let fresh: Vec<(String,)> = cache.fetch(&adults).await?;
+    assert_eq!(fresh.len(), 2);
+
+    /// Has the same `Debug` output for every age.
+    #[derive(ToSqlDelegate)]
+    struct Age(i32);
+
+    impl std::fmt::Debug for Age {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("Age")
+        }
+    }
+
+    let adults = query!("SELECT name FROM people WHERE age >= $age", age = Age(18));
+    let seniors = query!("SELECT name FROM people WHERE age >= $age", age = Age(40));
+    let first: Vec<(String,)> = cache.fetch(&adults).await?;
+    let other: Vec<(String,)> = cache.fetch(&seniors).await?;
+    assert_eq!(first.len(), 2);
+    assert_eq!(other, [("Anna".to_owned(),)]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn copy_in() -> Result {
+    let mut client = establish().await?;
+    let tx = client.transaction().await?;
+
+    query!("CREATE TABLE people (name TEXT, age INTEGER, nickname TEXT)")
+        .execute(&tx)
+        .await?;
+
+    let people = (0..1000).map(|i| (format!("Person #{}", i), i, None::<String>));
+
+    let mut progress = Vec::new();
+    let count = CopyIn::new("people", &["name", "age", "nickname"])
+        .chunk_size(4096)
+        .on_progress(|rows| progress.push(rows))
+        .rows(&tx, people)
+        .await?;
+
+    assert_eq!(count, 1000);
+    assert!(progress.len() > 1);
+    assert_eq!(progress.last(), Some(&1000));
+
+    let (name, age, nickname): (String, i32, Option<String>) =
+        query!("SELECT name, age, nickname FROM people WHERE age = 42")
+            .fetch_one(&tx)
+            .await?;
+    assert_eq!(name, "Person #42");
+    assert_eq!(age, 42);
+    assert_eq!(nickname, None);
+
+    let count = copy_in_rows(&tx, "people", &["name", "age"], &[("John Wick", 52)]).await?;
+    assert_eq!(count, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn upsert_unnested_rows() -> Result {
+    let mut client = establish().await?;
+    let tx = client.transaction().await?;
+
+    query!("CREATE TABLE people (id INTEGER PRIMARY KEY, name TEXT, nickname TEXT)")
+        .execute(&tx)
+        .await?;
+    query!("INSERT INTO people VALUES (1, 'John', NULL)")
+        .execute(&tx)
+        .await?;
+
+    #[derive(ToSqlRow)]
+    struct Person {
+        #[sql_row(sql_type = "int4")]
+        id: i32,
+        #[sql_row(rename = "full_name", sql_type = "text")]
+        name: String,
+        #[sql_row(sql_type = "text")]
+        nickname: Option<String>,
+    }
+
+    let people = (1..=1000)
+        .map(|id| Person {
+            id,
+            name: format!("Person #{}", id),
+            nickname: if id == 1 {
+                Some("Baba Yaga".to_owned())
+            } else {
+                None
+            },
+        })
+        .collect::<Vec<_>>();
+
+    fn upsert<'a>(values: &'a Unnest) -> Query<'a> {
+        Cte::new().with("new_people", values.query()).select(query!(
+            "INSERT INTO people (id, name, nickname)
+             SELECT id, full_name, nickname FROM new_people
+             ON CONFLICT (id) DO UPDATE SET name = excluded.name, nickname = excluded.nickname"
+        ))
+    }
+
+    let values = Unnest::new(&people);
+    assert_eq!(values.query().parameters().len(), 3);
+    assert_eq!(upsert(&values).execute(&tx).await?, 1000);
+
+    let (name, nickname): (String, Option<String>) =
+        query!("SELECT name, nickname FROM people WHERE id = 1")
+            .fetch_one(&tx)
+            .await?;
+    assert_eq!(name, "Person #1");
+    assert_eq!(nickname.as_deref(), Some("Baba Yaga"));
+
+    let (count, nicknames): (i64, i64) = query!("SELECT count(*), count(nickname) FROM people")
+        .fetch_one(&tx)
+        .await?;
+    assert_eq!((count, nicknames), (1000, 1));
+
+    let empty = Vec::<Person>::new();
+    assert_eq!(upsert(&Unnest::new(&empty)).execute(&tx).await?, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn partial_update() -> Result {
+    let mut client = establish().await?;
+    let tx = client.transaction().await?;
+
+    query!("CREATE TABLE people (id INTEGER, name TEXT, age INTEGER, nickname TEXT)")
+        .execute(&tx)
+        .await?;
+    query!("INSERT INTO people VALUES (1, 'John Wick', 42, 'Baba Yaga'), (2, 'Winston', 65, NULL)")
+        .execute(&tx)
+        .await?;
+
+    #[derive(Patch)]
+    struct PersonPatch {
+        #[patch(skip)]
+        id: i32,
+        name: Option<String>,
+        #[patch(rename = "age")]
+        years: Option<i32>,
+        nickname: Option<Option<String>>,
+    }
+
+    let patch = PersonPatch {
+        id: 1,
+        name: None,
+        years: Some(43),
+        nickname: Some(None),
+    };
+
+    let count = update!("people", &patch, where "id = $id", id = patch.id)
+        .unwrap()
+        .execute(&tx)
+        .await?;
+    assert_eq!(count, 1);
+
+    let people: Vec<(String, i32, Option<String>)> =
+        query!("SELECT name, age, nickname FROM people ORDER BY id")
+            .fetch(&tx)
+            .await?;
+    assert_eq!(
+        people,
+        vec![
+            ("John Wick".to_owned(), 43, None),
+            ("Winston".to_owned(), 65, None)
+        ]
+    );
+
+    let empty = PersonPatch {
+        id: 2,
+        name: None,
+        years: None,
+        nickname: None,
+    };
+    assert!(update!("people", &empty, where "id = $id", id = empty.id).is_none());
+
+    #[derive(Patch)]
+    struct AgePatch<T>
+    where
+        T: postgres_types::ToSql + Sync,
+    {
+        age: Option<T>,
+    }
+
+    let patch = AgePatch { age: Some(66) };
+    let count = update!("people", &patch, where "id = $id", id = 2)
+        .unwrap()
+        .execute(&tx)
+        .await?;
+    assert_eq!(count, 1);
+
+    let (age,): (i32,) = query!("SELECT age FROM people WHERE id = 2")
+        .fetch_one(&tx)
+        .await?;
+    assert_eq!(age, 66);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn returning_changes() -> Result {
+    let mut client = establish().await?;
+    let tx = client.transaction().await?;
+
+    query!("CREATE TABLE people (id SERIAL PRIMARY KEY, name TEXT, age INTEGER)")
+        .execute(&tx)
+        .await?;
+    query!("INSERT INTO people (name, age) VALUES ('Myke', 31), ('Anna', 42), ('Bob', 18)")
+        .execute(&tx)
+        .await?;
+
+    #[derive(Debug, PartialEq, FromSqlRow)]
+    struct Person {
+        name: String,
+        age: i32,
+    }
+
+    let mut changes: Vec<(Person, Person)> = execute_returning_changes(
+        &tx,
+        "people",
+        "id",
+        query!("age = age + $years", years = 10),
+        query!("age > $age", age = 20),
+    )
+    .await?;
+    changes.sort_by_key(|(before, _)| before.age);
+
+    let person = |name: &str, age| Person {
+        name: name.to_owned(),
+        age,
+    };
+
+    assert_eq!(
+        changes,
+        vec![
+            (person("Myke", 31), person("Myke", 41)),
+            (person("Anna", 42), person("Anna", 52)),
+        ]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn multi_mapping_hash_key_by() -> Result {
+    let mut client = establish().await?;
+    let tx = client.transaction().await?;
+
+    #[derive(Debug, PartialEq)]
+    struct AuthorId(i32);
+
+    impl<'a> FromSql<'a> for AuthorId {
+        fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn StdError + Sync + Send>> {
+            i32::from_sql(ty, raw).map(AuthorId)
+        }
+
+        accepts!(INT4);
+    }
+
+    #[derive(Debug, FromSqlRow)]
+    #[row(hash)]
+    struct Author {
+        #[row(key, by = "id.0")]
+        id: AuthorId,
+
+        #[row(merge)]
+        books: Vec<Book>,
+    }
+
+    #[derive(Debug, FromSqlRow)]
+    struct Book {
+        title: String,
+    }
+
+    let authors = query!(
+        "SELECT 1 as id, 'The Fellowship of the Ring' as title
+        UNION ALL SELECT 2, 'The Last Wish'
+        UNION ALL SELECT 1, 'The Two Towers'"
+    )
+    .fetch::<Author, _>(&tx)
+    .await?;
+
+    assert_eq!(authors.len(), 2);
+    assert_eq!(authors[0].id, AuthorId(1));
+    assert_eq!(authors[0].books.len(), 2);
+    assert_eq!(authors[0].books[1].title, "The Two Towers");
+    assert_eq!(authors[1].id, AuthorId(2));
+    assert_eq!(authors[1].books.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn parse_text_columns() -> Result {
+    let client = establish().await?;
+
+    #[derive(FromSqlRow)]
+    struct Measurement {
+        #[row(parse)]
+        value: f64,
+        #[row(parse, rename = "count")]
+        samples: u32,
+    }
+
+    let measurement: Measurement = query!("SELECT '3.5' as value, '12' as count")
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(measurement.value, 3.5);
+    assert_eq!(measurement.samples, 12);
+
+    let invalid = query!("SELECT 'abc' as value, '12' as count")
+        .fetch_one::<Measurement, _>(&client)
+        .await;
+
+    match invalid {
+        Err(postgres_query::Error::Execute(execute::Error::Extract(error))) => {
+            assert_eq!(
+                error.to_string(),
+                "failed to parse column `value` from `abc`: invalid float literal"
+            );
+        }
+        _ => panic!("expected a parse error"),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn outbox_drain() -> Result {
+    let mut client = establish().await?;
+    let mut tx = client.transaction().await?;
+
+    outbox::create_table(&tx).await?;
+    for name in &["John Wick", "Winston", "Charon"] {
+        outbox::enqueue(&tx, "people.created", name).await?;
+    }
+
+    let received = Arc::new(Mutex::new(Vec::new()));
+
+    let handled = outbox::drain(&mut tx, 2, {
+        let received = received.clone();
+        move |message| {
+            received.lock().unwrap().push(message.payload);
+            async { Ok::<_, Error>(()) }
+        }
+    })
+    .await?;
+    assert_eq!(handled, 2);
+    assert_eq!(*received.lock().unwrap(), ["John Wick", "Winston"]);
+
+    let failed = outbox::drain(&mut tx, 10, |_| async { Err(anyhow!("unavailable")) }).await;
+    assert!(failed.is_err());
+
+    let handled = outbox::drain(&mut tx, 10, {
+        let received = received.clone();
+        move |message| {
+            assert_eq!(message.topic, "people.created");
+            received.lock().unwrap().push(message.payload);
+            async { Ok::<_, Error>(()) }
+        }
+    })
+    .await?;
+    assert_eq!(handled, 1);
+    assert_eq!(
+        *received.lock().unwrap(),
+        ["John Wick", "Winston", "Charon"]
+    );
+
+    let (count,): (i64,) = query!("SELECT COUNT(*) FROM outbox").fetch_one(&tx).await?;
+    assert_eq!(count, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn execute_options() -> Result {
+    let client = establish().await?;
+    let client = Caching::new(client).with_options(
+        ExecuteOptions::new()
+            .timeout(Duration::from_millis(50))
+            .tag("tests"),
+    );
+
+    let timeout = query!("SELECT pg_sleep(0.2)").execute(&client).await;
+    match timeout {
+        Err(postgres_query::Error::Execute(execute::Error::Timeout(duration))) => {
+            assert_eq!(duration, Duration::from_millis(50))
+        }
+        result => panic!("expected a timeout, found {:?}", result),
+    }
+
+    query!("SELECT pg_sleep(0.2)")
+        .with_options(ExecuteOptions::new().timeout(Duration::from_secs(5)))
+        .execute(&client)
+        .await?;
+
+    let (sql,): (String,) = query!("SELECT current_query()")
+        .with_options(ExecuteOptions::new().prepare(PrepareMode::Uncached))
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(sql, "/* tests */ SELECT current_query()");
+
+    // Roles are scoped to the transaction.
+    let (user,): (String,) = query!("SELECT current_user::text")
+        .fetch_one(&client)
+        .await?;
+    let role = query!("SELECT current_setting('role')")
+        .with_options(ExecuteOptions::new().role(user.clone()));
+    assert!(role.fetch_one::<(String,), _>(&client).await.is_err());
+
+    let mut client = client.into_inner();
+    let tx = client.transaction().await?;
+    let (role,): (String,) = role.fetch_one(&tx).await?;
+    assert_eq!(role, user);
+
+    let (role,): (String,) = query!("SELECT current_setting('role')")
+        .fetch_one(&tx)
+        .await?;
+    assert_eq!(role, "none");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn caching_tagged_queries() -> Result {
+    let cached = Arc::new(Mutex::new(Vec::new()));
+    let client = Caching::new(establish().await?).on_prepare({
+        let cached = cached.clone();
+        move |event| cached.lock().unwrap().push(event.cached)
+    });
+
+    for tag in &["a", "b", "a"] {
+        query!("SELECT 1")
+            .with_options(ExecuteOptions::new().tag(*tag))
+            .execute(&client)
+            .await?;
+    }
+    assert_eq!(*cached.lock().unwrap(), [false, false, true]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn execute_deadlines() -> Result {
+    let mut client = establish().await?;
+    let deadline = ExecuteOptions::new().deadline(Instant::now() + Duration::from_secs(30));
+
+    // Within a transaction, the server enforces the deadline as well.
+    let tx = client.transaction().await?;
+    let (timeout,): (String,) = query!("SELECT current_setting('statement_timeout')")
+        .with_options(deadline.clone())
+        .fetch_one(&tx)
+        .await?;
+    assert_ne!(timeout, "0");
+
+    // The parameter only applies to the query.
+    let (timeout,): (String,) = query!("SELECT current_setting('statement_timeout')")
+        .fetch_one(&tx)
+        .await?;
+    assert_eq!(timeout, "0");
+    tx.rollback().await?;
+
+    // Otherwise it is only enforced by the client.
+    let client = Caching::new(client).with_options(deadline);
+    let (timeout,): (String,) = query!("SELECT current_setting('statement_timeout')")
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(timeout, "0");
+
+    // The earlier deadline of the query applies.
+    let slow = query!("SELECT pg_sleep(1)")
+        .with_options(ExecuteOptions::new().deadline(Instant::now() + Duration::from_millis(50)))
+        .execute(&client)
+        .await;
+    assert!(
+        matches!(
+            slow,
+            Err(postgres_query::Error::Execute(
+                execute::Error::DeadlineExceeded
+            ))
+        ),
+        "expected the deadline to pass, found {:?}",
+        slow
+    );
+
+    // A shorter timeout still applies.
+    let slow = query!("SELECT pg_sleep(1)")
+        .with_options(ExecuteOptions::new().timeout(Duration::from_millis(50)))
+        .execute(&client)
+        .await;
+    assert!(matches!(
+        slow,
+        Err(postgres_query::Error::Execute(execute::Error::Timeout(_)))
+    ));
+
+    // Queries are not sent once the deadline has passed.
+    let passed = query!("SELECT 1")
+        .with_options(ExecuteOptions::new().deadline(Instant::now()))
+        .execute(&client)
+        .await;
+    assert!(matches!(
+        passed,
+        Err(postgres_query::Error::Execute(
+            execute::Error::DeadlineExceeded
+        ))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn named_transaction() -> Result {
+    let mut client = establish().await?;
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let registration = metrics::register({
+        let events = events.clone();
+        move |event| {
+            if event.name.starts_with("named_transaction") {
+                events
+                    .lock()
+                    .unwrap()
+                    .push((event.name.to_owned(), event.outcome));
+            }
+        }
+    });
+
+    let sql = metrics::transaction_named(&mut client, "named_transaction_ok", |tx| {
+        async move {
+            let (sql,): (String,) = query!("SELECT current_query()")
+                .with_options(ExecuteOptions::new().prepare(PrepareMode::Uncached))
+                .fetch_one(tx)
+                .await?;
+            Ok::<_, Error>(sql)
+        }
+        .boxed()
+    })
+    .await?;
+    assert_eq!(sql, "/* named_transaction_ok */ SELECT current_query()");
+
+    let failed = metrics::transaction_named(&mut client, "named_transaction_err", |tx| {
+        async move {
+            query!("SELECT 1 / 0").execute(tx).await?;
+            Ok::<_, Error>(())
+        }
+        .boxed()
+    })
+    .await;
+    assert!(failed.is_err());
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            (
+                "named_transaction_ok".to_owned(),
+                metrics::Outcome::Committed
+            ),
+            (
+                "named_transaction_err".to_owned(),
+                metrics::Outcome::RolledBack
+            ),
+        ]
+    );
+
+    // Dropping the registration removes the hook.
+    drop(registration);
+    metrics::transaction_named(&mut client, "named_transaction_removed", |_| {
+        async move { Ok::<_, Error>(()) }.boxed()
+    })
+    .await?;
+    assert_eq!(events.lock().unwrap().len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_json_stream() -> Result {
+    let client = establish().await?;
+
+    let mut json = Vec::new();
+    let count = query!(
+        "SELECT * FROM (VALUES (1, 'John Wick', NULL), (2, 'Winston', 65)) AS people (id, name, age)
+         WHERE id >= $min",
+        min = 1
+    )
+    .fetch_json_stream(&client, &mut json)
+    .await?;
+
+    assert_eq!(count, 2);
+    assert_eq!(
+        String::from_utf8(json)?,
+        r#"[{"id":1,"name":"John Wick","age":null},{"id":2,"name":"Winston","age":65}]"#
+    );
+
+    let mut json = Vec::new();
+    let count = query!("SELECT 1 AS id WHERE false")
+        .fetch_json_stream(&client, &mut json)
+        .await?;
+    assert_eq!(count, 0);
+    assert_eq!(json, b"[]");
+
+    let mut json = Vec::new();
+    query!("SELECT n FROM generate_series(1, 3) AS n ORDER BY n DESC; -- newest first")
+        .fetch_json_stream(&client, &mut json)
+        .await?;
+    assert_eq!(json, br#"[{"n":3},{"n":2},{"n":1}]"#);
+
+    let result = query!("SELECT 1; SELECT 2")
+        .fetch_json_stream(&client, &mut Vec::new())
+        .await;
+    assert!(matches!(
+        result,
+        Err(postgres_query::Error::Execute(
+            execute::Error::MultipleStatements
+        ))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn query_streaming_columns() -> Result {
+    let client = establish().await?;
+
+    let mut rows = query!("SELECT n, n::text AS label FROM generate_series(1, 3) AS n")
+        .query_streaming(&client)
+        .await?;
+
+    let columns = rows
+        .columns()
+        .iter()
+        .map(|column| (column.name(), column.type_().clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(columns, [("n", Type::INT4), ("label", Type::TEXT)]);
+    assert_eq!(rows.rows_read(), 0);
+
+    let mut labels = Vec::new();
+    while let Some(row) = rows.try_next().await? {
+        labels.push(row.get::<_, String>("label"));
+    }
+    assert_eq!(labels, ["1", "2", "3"]);
+    assert_eq!(rows.rows_read(), 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn positional_named_fields() -> Result {
+    let client = establish().await?;
+
+    #[derive(FromSqlRow)]
+    #[row(positional)]
+    struct Person {
+        name: String,
+        age: i32,
+    }
+
+    let person: Person = query!("SELECT 'John Wick', 42 AS years")
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(person.name, "John Wick");
+    assert_eq!(person.age, 42);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn scoped_bindings() -> Result {
+    let mut client = establish().await?;
+    let tx = client.transaction().await?;
+
+    query!("CREATE TABLE orders (tenant_id INTEGER, item TEXT, price INTEGER)")
+        .execute(&tx)
+        .await?;
+    query!("INSERT INTO orders VALUES (1, 'apple', 10), (1, 'melon', 40), (2, 'pear', 20)")
+        .execute(&tx)
+        .await?;
+
+    let scoped = Scoped::new(&tx).bind("tenant_id", 1).strict();
+
+    let items: Vec<(String,)> = scoped
+        .parse(
+            "SELECT item FROM orders WHERE tenant_id = $tenant_id AND price > $price ORDER BY item",
+            &[("price", &5), ("tenant_id", &2)],
+        )?
+        .fetch(&scoped)
+        .await?;
+    assert_eq!(items, [("apple".to_owned(),), ("melon".to_owned(),)]);
+
+    match scoped.parse(
+        "SELECT item FROM orders WHERE price > $price",
+        &[("price", &5)],
+    ) {
+        Err(postgres_query::Error::Unscoped { binding }) => assert_eq!(binding, "tenant_id"),
+        result => panic!("expected an unscoped query, found {:?}", result),
+    }
+
+    // Queries which were not parsed by the client are rejected when executed through it, even if
+    // they filter by tenant themselves.
+    let unscoped = query!(
+        "SELECT item FROM orders WHERE tenant_id = $tenant_id",
+        tenant_id = 2
+    )
+    .fetch::<(String,), _>(&scoped)
+    .await;
+    match unscoped {
+        Err(postgres_query::Error::Unscoped { binding }) => assert_eq!(binding, "tenant_id"),
+        result => panic!("expected an unscoped query, found {:?}", result),
+    }
+
+    let lenient = Scoped::new(&tx).bind("tenant_id", 1);
+    let items: Vec<(String,)> = query!("SELECT item FROM orders WHERE tenant_id = 2")
+        .fetch(&lenient)
+        .await?;
+    assert_eq!(items, [("pear".to_owned(),)]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ignore_conflicts() -> Result {
+    let client = establish().await?;
+
+    query!("CREATE TEMP TABLE people (name TEXT CONSTRAINT people_name UNIQUE)")
+        .execute(&client)
+        .await?;
+
+    let insert = query!(
+        "INSERT INTO people VALUES ($name) RETURNING name",
+        name = "John Wick"
+    );
+
+    let outcome = conflict::try_execute_or_ignore_conflict(&client, &insert).await?;
+    assert_eq!(outcome, Outcome::Done(1));
+
+    let outcome: Outcome<(String,)> =
+        conflict::try_fetch_one_or_ignore_conflict(&client, &insert).await?;
+    assert_eq!(
+        outcome,
+        Outcome::Conflict(Conflict {
+            kind: ConflictKind::Unique,
+            constraint: Some("people_name".to_owned()),
+        })
+    );
+
+    // Other errors are still reported.
+    let invalid = query!("INSERT INTO people VALUES (1, 2)");
+    assert!(conflict::try_execute_or_ignore_conflict(&client, &invalid)
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn insert_outcomes() -> Result {
+    let mut client = establish().await?;
+    let tx = client.transaction().await?;
+
+    query!("CREATE TABLE people (name TEXT UNIQUE)")
+        .execute(&tx)
+        .await?;
+
+    // Within the same transaction, since the conflict does not abort it.
+    let expected = [
+        ("John Wick", InsertOutcome::Inserted),
+        ("John Wick", InsertOutcome::Skipped),
+        ("Winston", InsertOutcome::Inserted),
+    ];
+    for (name, expected) in &expected {
+        let outcome = query!(
+            "INSERT INTO people VALUES ($name) ON CONFLICT (name) DO NOTHING",
+            name
+        )
+        .execute_ignoring_conflicts(&tx)
+        .await?;
+        assert_eq!(outcome, *expected);
+    }
+
+    let many = query!("INSERT INTO people VALUES ('Sofia'), ('Charon') ON CONFLICT DO NOTHING")
+        .execute_ignoring_conflicts(&tx)
+        .await;
+    assert!(matches!(
+        many,
+        Err(postgres_query::Error::Execute(
+            execute::Error::UnexpectedRowCount {
+                expected: 1,
+                actual: 2
+            }
+        ))
+    ));
+
+    // The rows are counted before the query is executed, so nothing was inserted.
+    let count: i64 = query!("SELECT COUNT(*) FROM people")
+        .fetch_one::<(i64,), _>(&tx)
+        .await?
+        .0;
+    assert_eq!(count, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn delegate_newtypes() -> Result {
+    let client = establish().await?;
+
+    #[derive(Debug, PartialEq, ToSqlDelegate)]
+    struct UserId(i64);
+
+    #[derive(Debug, PartialEq, ToSqlDelegate)]
+    struct Email {
+        address: String,
+    }
+
+    #[derive(Debug, PartialEq, ToSqlDelegate)]
+    struct Name<'a>(&'a str);
+
+    let id = UserId(7);
+    let (id, email): (UserId, Email) = query!("SELECT $id::BIGINT, 'john@wick.com'", id)
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(id, UserId(7));
+    assert_eq!(
+        email,
+        Email {
+            address: "john@wick.com".to_owned()
+        }
+    );
+
+    // Borrowed values can only be extracted from the row directly.
+    let name = Name("John Wick");
+    let row = query!("SELECT $name::TEXT", name)
+        .query_one(&client)
+        .await?;
+    let name: Name = row.get(0);
+    assert_eq!(name, Name("John Wick"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn accumulate_groups_across_chunks() -> Result {
+    let client = establish().await?;
+
+    #[derive(Debug, FromSqlRow)]
+    #[row(group)]
+    struct Author {
+        #[row(key)]
+        id: i32,
+        #[row(merge)]
+        books: Vec<Book>,
+    }
+
+    #[derive(Debug, FromSqlRow)]
+    #[row(hash)]
+    struct HashedAuthor {
+        #[row(key)]
+        id: i32,
+        #[row(merge)]
+        books: Vec<Book>,
+    }
+
+    #[derive(Debug, FromSqlRow)]
+    struct Book {
+        title: String,
+    }
+
+    let rows = query!(
+        "SELECT 1 as id, 'The Fellowship of the Ring' as title
+         UNION ALL SELECT 1, 'The Two Towers'
+         UNION ALL SELECT 2, 'The Last Wish'
+         UNION ALL SELECT 1, 'Return of the King'"
+    )
+    .query(&client)
+    .await?;
+
+    // The first chunk ends in the middle of the first group.
+    let mut authors = GroupAccumulator::<Author>::new();
+    let complete = authors.feed(&rows[..1])?;
+    assert!(complete.is_empty());
+    let complete = authors.feed(&rows[1..])?;
+    assert_eq!(complete.len(), 2);
+    assert_eq!(complete[0].books.len(), 2);
+    assert_eq!(complete[1].id, 2);
+    let rest = authors.finish();
+    assert_eq!(rest.len(), 1);
+    assert_eq!(rest[0].books[0].title, "Return of the King");
+
+    let mut authors = GroupAccumulator::<HashedAuthor>::new();
+    for chunk in rows.chunks(1) {
+        assert!(authors.feed(chunk)?.is_empty());
+    }
+    let authors = authors.finish();
+    assert_eq!(authors.len(), 2);
+    assert_eq!(authors[0].id, 1);
+    assert_eq!(authors[0].books.len(), 3);
+
+    let mut books = GroupAccumulator::<Book>::new();
+    assert_eq!(books.feed(&rows[..3])?.len(), 3);
+    assert!(books.finish().is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn execute_with_locals() -> Result {
+    let mut client = establish().await?;
+
+    let options = ExecuteOptions::new()
+        .local("enable_seqscan", "off")
+        .local("application_name", "it's local");
+
+    let setting =
+        query!("SELECT current_setting('enable_seqscan'), current_setting('application_name')");
+
+    // Outside of a transaction
+    let outside = setting
+        .clone()
+        .with_options(options.clone())
+        .fetch_one::<(String, String), _>(&client)
+        .await;
+    assert!(matches!(
+        outside,
+        Err(postgres_query::Error::Execute(
+            execute::Error::NoTransaction
+        ))
+    ));
+
+    // Within a transaction
+    let tx = client.transaction().await?;
+    query!("SET LOCAL enable_seqscan = off")
+        .execute(&tx)
+        .await?;
+
+    let (seqscan, name): (String, String) = setting
+        .clone()
+        .with_options(
+            ExecuteOptions::new()
+                .local("enable_seqscan", "on")
+                .local("application_name", "inner"),
+        )
+        .fetch_one(&tx)
+        .await?;
+    assert_eq!(seqscan, "on");
+    assert_eq!(name, "inner");
+
+    let (seqscan, name): (String, String) = setting.fetch_one(&tx).await?;
+    assert_eq!(seqscan, "off");
+    assert_ne!(name, "inner");
+
+    // Queries which fail without aborting the transaction restore the parameters as well
+    let empty = query!("SELECT 1 WHERE false")
+        .with_options(options)
+        .fetch_one::<(i32,), _>(&tx)
+        .await;
+    assert!(matches!(
+        empty,
+        Err(postgres_query::Error::Execute(execute::Error::NoRows))
+    ));
+
+    let (seqscan, name): (String, String) = setting.fetch_one(&tx).await?;
+    assert_eq!(seqscan, "off");
+    assert_ne!(name, "it's local");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn query_in_schema() -> Result {
+    let mut client = establish().await?;
+    let tx = client.transaction().await?;
+
+    tx.batch_execute(
+        r#"CREATE SCHEMA tenant_1;
+           CREATE SCHEMA "Tenant 2";
+           CREATE TABLE tenant_1.orders (item TEXT);
+           CREATE TABLE "Tenant 2".orders (item TEXT);
+           INSERT INTO tenant_1.orders VALUES ('apple');
+           INSERT INTO "Tenant 2".orders VALUES ('pear');"#,
+    )
+    .await?;
+
+    let client = Caching::new(tx);
+    let items = |schema: &str| query!("SELECT item FROM orders").in_schema(schema);
+
+    let (item,): (String,) = items("tenant_1").fetch_one(&client).await?;
+    assert_eq!(item, "apple");
+    let (item,): (String,) = items("Tenant 2").fetch_one(&client).await?;
+    assert_eq!(item, "pear");
+
+    // The search path is restored after the query.
+    let (path,): (String,) = query!("SHOW search_path").fetch_one(&client).await?;
+    assert!(!path.contains("tenant_1"));
+
+    // Cached results are kept apart for each schema.
+    let cache = ResultCache::new(&client, Duration::from_secs(60), 16);
+    let first: Vec<(String,)> = cache.fetch(&items("tenant_1")).await?;
+    assert_eq!(first, [("apple".to_owned(),)]);
+    let second: Vec<(String,)> = cache.fetch(&items("Tenant 2")).await?;
+    assert_eq!(second, [("pear".to_owned(),)]);
+
+    // The name is quoted rather than spliced into the setting.
+    let injected = items("tenant_1, public")
+        .fetch_one::<(String,), _>(&client)
+        .await;
+    assert!(injected.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn query_in_schema_outside_transaction() -> Result {
+    let mut client = establish().await?;
+
+    let path = query!("SELECT current_setting('search_path')").in_schema("tenant_1");
+
+    // A shared client may be used by other tasks in the meantime.
+    let shared = path.fetch_one::<(String,), _>(&client).await;
+    assert!(matches!(
+        shared,
+        Err(postgres_query::Error::Execute(
+            execute::Error::NoTransaction
+        ))
+    ));
+
+    let (inner,): (String,) = path.fetch_one(&Exclusive::new(&mut client)).await?;
+    assert!(inner.contains("tenant_1"));
+
+    // The implicit transaction has ended, along with the setting.
+    let (path,): (String,) = query!("SHOW search_path").fetch_one(&client).await?;
+    assert!(!path.contains("tenant_1"));
+    assert!(!client.is_closed());
+
+    // A failed query rolls the implicit transaction back, so the client can carry on.
+    let failed = query!("SELECT 1 / 0")
+        .in_schema("tenant_1")
+        .execute(&Exclusive::new(&mut client))
+        .await;
+    assert!(failed.is_err());
+    let (one,): (i32,) = query!("SELECT 1").fetch_one(&client).await?;
+    assert_eq!(one, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn order_by_user_input() -> Result {
+    let client = establish().await?;
+
+    let order = OrderBy::try_new("-age, name", &["name", "age"])?;
+    let people: Vec<(String, i32)> = query!(
+        "SELECT * FROM (VALUES ('Bob', 30), ('Anna', 30), ('Carl', 25), ('Dave', 40)) \
+         AS people (name, age) WHERE age > $min",
+        min = 20,
+    )
+    .order_by(&order)
+    .limit(&3)
+    .fetch(&client)
+    .await?;
+
+    assert_eq!(
+        people,
+        [
+            ("Dave".to_owned(), 40),
+            ("Anna".to_owned(), 30),
+            ("Bob".to_owned(), 30),
+        ]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_keyed_values() -> Result {
+    let client = establish().await?;
+
+    #[derive(Debug, PartialEq, FromSqlRow)]
+    struct Order {
+        id: i32,
+        customer: String,
+    }
+
+    let orders = query!(
+        "SELECT * FROM (VALUES (1, 'Alice'), (2, 'Bob'), (3, 'Alice')) AS orders (id, customer)"
+    );
+
+    let by_id = orders
+        .fetch_map_by_key(&client, |order: &Order| order.id)
+        .await?;
+    assert_eq!(by_id.len(), 3);
+    assert_eq!(by_id[&2].customer, "Bob");
+
+    let by_customer = orders
+        .fetch_grouped_by_key(&client, |order: &Order| order.customer.clone())
+        .await?;
+    let ids = |customer: &str| {
+        by_customer[customer]
+            .iter()
+            .map(|order| order.id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(by_customer.len(), 2);
+    assert_eq!(ids("Alice"), [1, 3]);
+    assert_eq!(ids("Bob"), [2]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_with_conversion() -> Result {
+    let client = establish().await?;
+
+    #[derive(Debug, PartialEq)]
+    struct Email(String);
+
+    impl Email {
+        fn parse(text: String) -> std::result::Result<Email, String> {
+            if text.contains('@') {
+                Ok(Email(text))
+            } else {
+                Err(format!("`{}` is not an email address", text))
+            }
+        }
+    }
+
+    let valid =
+        query!("SELECT * FROM (VALUES ('john@wick.com'), ('winston@continental.com')) AS _");
+    let invalid = query!("SELECT * FROM (VALUES ('john@wick.com'), ('charon')) AS _");
+
+    let lengths = valid
+        .fetch_with(&client, |(email,): (String,)| email.len())
+        .await?;
+    assert_eq!(lengths, [13, 23]);
+
+    let emails = valid
+        .try_fetch_with(&client, |(email,): (String,)| Email::parse(email))
+        .await?;
+    assert_eq!(
+        emails,
+        [
+            Email("john@wick.com".into()),
+            Email("winston@continental.com".into())
+        ]
+    );
+
+    match invalid
+        .try_fetch_with(&client, |(email,): (String,)| Email::parse(email))
+        .await
+    {
+        Err(postgres_query::Error::Execute(execute::Error::Extract(extract::Error::Convert(
+            error,
+        )))) => {
+            assert_eq!(error.to_string(), "`charon` is not an email address")
+        }
+        result => panic!("expected the conversion to fail, found {:?}", result),
+    }
+
+    let lengths = valid
+        .fetch_streaming_with(&client, |(email,): (String,)| email.len())
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert_eq!(lengths, [13, 23]);
+
+    let emails = invalid
+        .try_fetch_streaming_with(&client, |(email,): (String,)| Email::parse(email))
+        .await?
+        .collect::<Vec<_>>()
+        .await;
+    assert!(matches!(emails[0], Ok(Email(_))));
+    assert!(matches!(
+        emails[1],
+        Err(postgres_query::Error::Execute(execute::Error::Extract(
+            extract::Error::Convert(_)
+        )))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn run_queryable_requests() -> Result {
+    use postgres_query::queryable::Run;
+    use postgres_query::Queryable;
+
+    let client = establish().await?;
+
+    #[derive(Debug, PartialEq, FromSqlRow)]
+    struct Order {
+        id: i32,
+        customer: String,
+    }
+
+    struct OrdersBy<'a> {
+        customer: &'a str,
+    }
+
+    impl Queryable for OrdersBy<'_> {
+        type Output = Vec<Order>;
+
+        fn query(&self) -> Query<'_> {
+            query!(
+                "SELECT * FROM (VALUES (1, 'Alice'), (2, 'Bob'), (3, 'Alice')) AS orders (id, customer)
+                 WHERE customer = $customer ORDER BY id",
+                customer = self.customer,
+            )
+        }
+    }
+
+    struct OrderById(i32);
+
+    impl Queryable for OrderById {
+        type Output = Option<Order>;
+
+        fn query(&self) -> Query<'_> {
+            query!(
+                "SELECT $id::int AS id, 'Alice' AS customer WHERE $id < 10",
+                id = self.0
+            )
+        }
+    }
+
+    let orders = client.run(OrdersBy { customer: "Alice" }).await?;
+    assert_eq!(
+        orders.iter().map(|order| order.id).collect::<Vec<_>>(),
+        [1, 3]
+    );
+
+    assert_eq!(
+        client.run(OrderById(4)).await?.map(|order| order.id),
+        Some(4)
+    );
+    assert_eq!(client.run(OrderById(12)).await?, None);
+
+    let bob: Order = OrdersBy { customer: "Bob" }.fetch_one(&client).await?;
+    assert_eq!(bob.id, 2);
+    assert_eq!(OrdersBy { customer: "Carol" }.execute(&client).await?, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_row_into_locals() -> Result {
+    let client = establish().await?;
+
+    let min = 18;
+    let (n, oldest, names) = fetch_row!(
+        &client,
+        "SELECT count(*) AS n, max(age) AS oldest, array_agg(name ORDER BY name) AS names
+         FROM (VALUES ('John', 42), ('Jane', 17), ('Ada', 36)) AS people (name, age)
+         WHERE age > $min",
+        { n: i64, oldest: Option<i32>, names: Vec<String> },
+        min,
+    )
+    .await?;
+    assert_eq!(n, 2);
+    assert_eq!(oldest, Some(42));
+    assert_eq!(names, ["Ada", "John"]);
+
+    let (nothing,) =
+        fetch_row!(&client, "SELECT NULL::int AS nothing", { nothing: Option<i32> }).await?;
+    assert_eq!(nothing, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn check_column_types() -> Result {
+    let client = establish().await?;
+
+    #[derive(Debug, FromSqlRow)]
+    #[row(check_types)]
+    struct Person {
+        id: i32,
+        name: String,
+        #[row(flatten)]
+        age: Age,
+    }
+
+    #[derive(Debug, FromSqlRow)]
+    struct Age {
+        age: i32,
+    }
+
+    let row = query!("SELECT 'one' as id, 'Myke' as name, 3.5 as age")
+        .query_one(&client)
+        .await?;
+    assert_eq!(row.column_type("id"), Some(&Type::TEXT));
+    assert_eq!(row.column_type(2), Some(&Type::NUMERIC));
+    assert_eq!(row.column_type("missing"), None);
+
+    match Person::from_row(&row) {
+        Err(extract::Error::TypeMismatch { mismatches }) => {
+            let fields = mismatches
+                .iter()
+                .map(|mismatch| (mismatch.field.as_str(), mismatch.column.as_str()))
+                .collect::<Vec<_>>();
+            assert_eq!(fields, [("id", "id"), ("age", "age")]);
+            assert_eq!(mismatches[1].found, Type::NUMERIC);
+        }
+        other => panic!("expected a type mismatch, found {:?}", other),
+    }
+
+    let person: Person = query!("SELECT 1 as id, 'Myke' as name, 32 as age")
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(
+        (person.id, person.name, person.age.age),
+        (1, "Myke".to_owned(), 32)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribe_to_notifications() -> Result {
+    let client = establish().await?;
+
+    let mut config: tokio_postgres::Config = env::var("POSTGRES_DB_CONFIG")
+        .unwrap_or_else(|_| "user=postgres_query_test host=localhost".to_owned())
+        .parse()?;
+    config.application_name("postgres_query_notify");
+
+    let mut events = notify::subscribe(config, &["notify \"test\""])
+        .retry_delay(Duration::from_millis(10), Duration::from_millis(100))
+        .dedup_by(16, |notification| notification.payload().to_owned());
+
+    /// Keep notifying until the next event is received, since the subscription only starts
+    /// listening once polled.
+    async fn notify_until(
+        client: &Client,
+        events: &mut notify::Subscription,
+        payload: &str,
+    ) -> Result<notify::Event> {
+        loop {
+            let send = async {
+                query!("SELECT pg_notify('notify \"test\"', $payload)", payload)
+                    .execute(client)
+                    .await?;
+                futures_timer::Delay::new(Duration::from_millis(20)).await;
+                Ok::<_, Error>(())
+            };
+            futures::pin_mut!(send);
+
+            match future::select(events.next(), send).await {
+                Either::Left((event, _)) => return Ok(event.unwrap()),
+                Either::Right((result, _)) => result?,
+            }
+        }
+    }
+
+    match notify_until(&client, &mut events, "first").await? {
+        notify::Event::Notification(notification) => assert_eq!(notification.payload(), "first"),
+        other => panic!("expected a notification, found {:?}", other),
+    }
+
+    query!(
+        "SELECT pg_terminate_backend(pid) FROM pg_stat_activity
+         WHERE application_name = 'postgres_query_notify'"
+    )
+    .execute(&client)
+    .await?;
+
+    match notify_until(&client, &mut events, "first").await? {
+        notify::Event::Gap(gap) => assert!(gap.attempts >= 1),
+        other => panic!("expected a gap, found {:?}", other),
+    }
+
+    // Repeated notifications of "first" are skipped.
+    match notify_until(&client, &mut events, "second").await? {
+        notify::Event::Notification(notification) => assert_eq!(notification.payload(), "second"),
+        other => panic!("expected a notification, found {:?}", other),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscription_gives_up() -> Result {
+    let config = "host=localhost port=1 user=postgres".parse()?;
+    let events = notify::subscribe(config, &["orders"])
+        .retry_delay(Duration::from_millis(1), Duration::from_millis(1))
+        .max_attempts(3)
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(events.len(), 1);
+    assert!(matches!(events[0], notify::Event::Failed(_)));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_arrays() -> Result {
+    let client = establish().await?;
+
+    let numbers: Vec<i32> = query!("SELECT array_agg(x ORDER BY x) FROM generate_series(1, 4) x")
+        .fetch_array(&client)
+        .await?;
+    assert_eq!(numbers, [1, 2, 3, 4]);
+
+    let empty: Vec<i32> = query!("SELECT array_agg(x) FROM generate_series(1, 0) x")
+        .fetch_array(&client)
+        .await?;
+    assert!(empty.is_empty());
+
+    let groups: Vec<Vec<String>> = query!(
+        "SELECT array_agg(name ORDER BY name)
+         FROM (VALUES (1, 'b'), (1, 'a'), (2, 'c')) people (team, name)
+         GROUP BY team ORDER BY team"
+    )
+    .fetch(&client)
+    .await?;
+    assert_eq!(groups, [vec!["a", "b"], vec!["c"]]);
+
+    let extra = query!("SELECT ARRAY[1], 2")
+        .fetch_array::<i32, _>(&client)
+        .await;
+    assert!(extra.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn execute_built_queries() -> Result {
+    let client = establish().await?;
+
+    let built = interop::Built::new("SELECT $1::INT + $2::INT", vec![1, 2]);
+    let (sum,): (i32,) = built.query().fetch_one(&client).await?;
+    assert_eq!(sum, 3);
+
+    let built = interop::Built::from_boxed(
+        "SELECT $1::TEXT || $2::INT",
+        vec![Box::new("number ") as interop::Value],
+    )
+    .bind(7);
+    let (text,): (String,) = built.query().fetch_one(&client).await?;
+    assert_eq!(text, "number 7");
+
+    Ok(())
+}
+
+#[cfg(feature = "with-sea-query-0_32")]
+#[tokio::test]
+async fn execute_sea_query() -> Result {
+    use sea_query::{Alias, Expr, PostgresQueryBuilder, Query as SeaQuery};
+    use std::convert::TryFrom;
+
+    let client = establish().await?;
+
+    let select = SeaQuery::select()
+        .expr(
+            Expr::expr(Expr::val(7u32).cast_as(Alias::new("BIGINT")))
+                .add(Expr::val(1i8).cast_as(Alias::new("SMALLINT"))),
+        )
+        .expr(Expr::val('a'))
+        .expr(Expr::expr(Expr::val(None::<String>).cast_as(Alias::new("TEXT"))).is_null())
+        .to_owned();
+    let built = interop::Built::try_from(select.build(PostgresQueryBuilder))?;
+    let (sum, text, null): (i64, String, bool) = built.query().fetch_one(&client).await?;
+    assert_eq!((sum, text.as_str(), null), (8, "a", true));
+
+    let values = sea_query::Values(vec![sea_query::Value::BigUnsigned(Some(u64::MAX))]);
+    let overflow = interop::Built::try_from(("SELECT $1".to_owned(), values));
+    assert!(matches!(
+        overflow,
+        Err(interop::Error::UnsupportedValue { .. })
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "with-sql_builder-3")]
+#[tokio::test]
+async fn execute_sql_builder() -> Result {
+    use sql_builder::SqlBuilder;
+    use std::convert::TryFrom;
+
+    let client = establish().await?;
+
+    let mut builder = SqlBuilder::select_from("generate_series(1, 10) x");
+    builder.field("count(*)").and_where("x > $1::INT");
+    let built = interop::Built::try_from(&builder)?.bind(4);
+    let (count,): (i64,) = built.query().fetch_one(&client).await?;
+    assert_eq!(count, 6);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cached_concurrent_prepare() -> Result {
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio_postgres::{RowStream, Statement};
+
+    /// Counts the number of statements prepared.
+    struct Counting {
+        client: Client,
+        prepared: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl GenericClient for Counting {
+        async fn prepare(&self, sql: &str) -> Result<Statement, tokio_postgres::Error> {
+            self.prepared.fetch_add(1, Ordering::SeqCst);
+            GenericClient::prepare(&self.client, sql).await
+        }
+
+        async fn execute_raw<'a>(
+            &'a self,
+            statement: &Statement,
+            parameters: &[&'a (dyn postgres_types::ToSql + Sync)],
+        ) -> Result<u64, tokio_postgres::Error> {
+            GenericClient::execute_raw(&self.client, statement, parameters).await
+        }
+
+        async fn query_raw<'a>(
+            &'a self,
+            statement: &Statement,
+            parameters: &[&'a (dyn postgres_types::ToSql + Sync)],
+        ) -> Result<RowStream, tokio_postgres::Error> {
+            GenericClient::query_raw(&self.client, statement, parameters).await
+        }
+    }
+
+    let client = Caching::new(Counting {
+        client: establish().await?,
+        prepared: AtomicUsize::new(0),
+    });
+
+    let ids = future::try_join_all((0..8).map(|id| {
+        let client = &client;
+        async move {
+            query!("SELECT $id::INT", id)
+                .fetch_one::<(i32,), _>(client)
+                .await
+        }
+    }))
+    .await?;
+
+    assert_eq!(ids, (0..8).map(|id| (id,)).collect::<Vec<_>>());
+    assert_eq!(client.prepared.load(Ordering::SeqCst), 1);
+
+    // Without `prepare_typed`, the types are given as casts to `prepare`.
+    let statement = client
+        .prepare_typed_static("SELECT $1, '$2'", &[Type::INT8, Type::TEXT])
+        .await?;
+    assert_eq!(statement.params(), [Type::INT8]);
+    assert_eq!(client.prepared.load(Ordering::SeqCst), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn record_mutations() -> Result {
+    let mut client = establish().await?;
+
+    let tx = Recording::new(client.transaction().await?).redact(|i, value| {
+        if i == 1 {
+            "?".to_owned()
+        } else {
+            value.to_owned()
+        }
+    });
+
+    query!("CREATE TEMP TABLE people (name TEXT, age INT)")
+        .execute(&tx)
+        .await?;
+    query!(
+        "INSERT INTO people VALUES ($name, $age), ('Anna', 31)",
+        name = "Bob",
+        age = 42
+    )
+    .with_options(ExecuteOptions::new().tag("insert people"))
+    .execute(&tx)
+    .await?;
+    query!("SELECT * FROM people").query(&tx).await?;
+
+    let (tx, mutations) = tx.into_parts();
+    tx.commit().await?;
+
+    assert_eq!(mutations.len(), 2);
+    assert_eq!(mutations[0].rows, 0);
+    assert_eq!(
+        mutations[1].sql,
+        "INSERT INTO people VALUES ($1, $2), ('Anna', 31)"
+    );
+    assert_eq!(mutations[1].tag.as_deref(), Some("insert people"));
+    assert_eq!(mutations[1].parameters, ["?", "42"]);
+    assert_eq!(mutations[1].rows, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn indeterminate_parameter_type() -> Result {
+    let client = establish().await?;
+
+    let result = query!(
+        "SELECT $id::INT, $value IS NULL",
+        id = 1,
+        value = None::<i32>
+    )
+    .fetch_one::<(i32, bool), _>(&client)
+    .await;
+
+    match result {
+        Err(postgres_query::Error::Execute(execute::Error::IndeterminateType {
+            index,
+            null,
+            ..
+        })) => {
+            assert_eq!(index, 2);
+            assert!(null);
+        }
+        other => panic!("expected an indeterminate type, found {:?}", other),
+    }
+
+    // Detected by encoding the value, rather than by how it is formatted.
+    #[derive(Debug, ToSqlDelegate)]
+    struct Score(Option<i32>);
+
+    let result = query!("SELECT $value IS NULL", value = Score(None))
+        .fetch_one::<(bool,), _>(&client)
+        .await;
+    match result {
+        Err(postgres_query::Error::Execute(execute::Error::IndeterminateType {
+            index: 1,
+            null: true,
+            ..
+        })) => {}
+        other => panic!("expected an indeterminate type, found {:?}", other),
+    }
+
+    let (_, missing): (i32, bool) = query!(
+        "SELECT $id::INT, $value::INT IS NULL",
+        id = 1,
+        value = None::<i32>
+    )
+    .fetch_one(&client)
+    .await?;
+    assert!(missing);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flatten_fallback() -> Result {
+    let client = establish().await?;
+
+    #[derive(FromSqlRow)]
+    struct Order {
+        total: i64,
+        #[row(flatten, fallback)]
+        customer: Option<Customer>,
+    }
+
+    #[derive(Debug, Default, PartialEq, FromSqlRow)]
+    struct Customer {
+        id: i32,
+        name: String,
+    }
+
+    let order: Order = query!("SELECT 10::BIGINT AS total, 1 AS id, 'John' AS name")
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(order.total, 10);
+    assert_eq!(
+        order.customer,
+        Some(Customer {
+            id: 1,
+            name: "John".to_owned()
+        })
+    );
+
+    let order: Order = query!("SELECT 10::BIGINT AS total")
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(order.customer, None);
+
+    #[derive(FromSqlRow)]
+    #[row(split)]
+    struct Split {
+        #[row(flatten, split = "id")]
+        order: Customer,
+        #[row(flatten, split = "id", fallback)]
+        customer: Option<Customer>,
+    }
+
+    let split: Split = query!("SELECT 1 AS id, 'Order' AS name, 2 AS id, 'John' AS name")
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(split.order.id, 1);
+    assert_eq!(split.customer.map(|customer| customer.id), Some(2));
+
+    let split: Split = query!("SELECT 1 AS id, 'Order' AS name")
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(split.order.name, "Order");
+    assert_eq!(split.customer, None);
+
+    #[derive(FromSqlRow)]
+    #[row(exact)]
+    struct Exact {
+        #[row(flatten)]
+        order: Customer,
+        #[row(flatten, fallback)]
+        customer: Option<Customer>,
+    }
+
+    let exact: Exact = query!("SELECT 1 AS id, 'Order' AS name")
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(exact.order.id, 1);
+    assert_eq!(exact.customer, None);
+
+    #[derive(Debug, FromSqlRow)]
+    struct Strict {
+        total: i64,
+        #[row(flatten, fallback)]
+        customer: Customer,
+    }
+
+    let strict: Strict = query!("SELECT 10::BIGINT AS total")
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(strict.total, 10);
+    assert_eq!(strict.customer, Customer::default());
+
+    // The columns are present, so errors are not swallowed.
+    let result = query!("SELECT 10::BIGINT AS total, 1 AS id, NULL::TEXT AS name")
+        .fetch_one::<Strict, _>(&client)
+        .await;
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flatten_rename_prefix() -> Result {
+    let client = establish().await?;
+
+    #[derive(Debug, PartialEq, FromSqlRow)]
+    struct Person {
+        id: i32,
+        name: String,
+    }
+
+    #[derive(FromSqlRow)]
+    struct Family {
+        #[row(flatten, rename_prefix = "parent_")]
+        parent: Person,
+        #[row(flatten, rename_prefix = "child_")]
+        child: Option<Person>,
+    }
+
+    let family: Family =
+        query!("SELECT 1 AS child_id, 'Bob' AS child_name, 2 AS parent_id, 'Alice' AS parent_name")
+            .fetch_one(&client)
+            .await?;
+    assert_eq!(family.parent.id, 2);
+    assert_eq!(family.parent.name, "Alice");
+    assert_eq!(
+        family.child,
+        Some(Person {
+            id: 1,
+            name: "Bob".to_owned()
+        })
+    );
+
+    let family: Family = query!(
+        "SELECT 2 AS parent_id, 'Alice' AS parent_name, NULL::INT AS child_id, NULL::TEXT AS child_name"
+    )
+    .fetch_one(&client)
+    .await?;
+    assert_eq!(family.child, None);
+
+    // Prefixes of nested flattened values are combined.
+    #[derive(FromSqlRow)]
+    struct Tree {
+        #[row(flatten, rename_prefix = "left_")]
+        left: Family,
+    }
+
+    let tree: Tree = query!(
+        "SELECT 1 AS left_parent_id, 'Alice' AS left_parent_name, 2 AS left_child_id, 'Bob' AS left_child_name"
+    )
+    .fetch_one(&client)
+    .await?;
+    assert_eq!(tree.left.parent.name, "Alice");
+    assert_eq!(tree.left.child.map(|child| child.id), Some(2));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn select_renamed_columns() -> Result {
+    let client = establish().await?;
+
+    client
+        .batch_execute(
+            "CREATE TEMP TABLE people (id INT, name TEXT, age INT);
+             INSERT INTO people VALUES (1, 'John Wick', 42);",
+        )
+        .await?;
+
+    #[derive(FromSqlRow)]
+    struct Person {
+        id: i32,
+        #[row(rename = "firstName")]
+        name: String,
+        #[row(flatten)]
+        details: Details,
+    }
+
+    #[derive(FromSqlRow)]
+    struct Details {
+        #[row(rename = "Age")]
+        age: i32,
+    }
+
+    assert_eq!(extract::columns::<Person>(), ["id", "firstName", "Age"]);
+
+    let sql = format!(
+        "SELECT {} FROM people p",
+        extract::select_list::<Person>("p")
+    );
+    assert_eq!(
+        sql,
+        r#"SELECT p.id, p.name AS "firstName", p.age AS "Age" FROM people p"#
+    );
+
+    let person: Person = Query::new(sql, Vec::new()).fetch_one(&client).await?;
+    assert_eq!(person.id, 1);
+    assert_eq!(person.name, "John Wick");
+    assert_eq!(person.details.age, 42);
+
+    Ok(())
+}
+
+#[cfg(feature = "connect")]
+#[tokio::test]
+async fn connect_with_retry() -> Result {
+    use postgres_query::{connect_with_retry, RetryPolicy};
+
+    let config = env::var("POSTGRES_DB_CONFIG")
+        .unwrap_or_else(|_| "user=postgres_query_test host=localhost".to_owned());
+    let config: tokio_postgres::Config = config.parse()?;
+
+    let (client, connection) = connect_with_retry(&config, RetryPolicy::default()).await?;
+    let (answer,): (i32,) = query!("SELECT 42").fetch_one(&client).await?;
+    assert_eq!(answer, 42);
+
+    drop(client);
+    connection.await??;
+
+    // Nothing listens on the port, so every attempt fails.
+    let mut unreachable = tokio_postgres::Config::new();
+    unreachable.host("localhost").port(1).user("postgres");
+    let policy = RetryPolicy::default()
+        .attempts(3)
+        .delay(Duration::from_millis(1), Duration::from_millis(2));
+    match connect_with_retry(&unreachable, policy).await {
+        Err(postgres_query::Error::Retries { attempts, source }) => {
+            assert_eq!(attempts, 3);
+            assert!(matches!(*source, postgres_query::Error::Connect(_)));
+        }
+        other => panic!("expected the retries to fail: {:?}", other.map(|_| ())),
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "connect")]
+#[tokio::test]
+async fn connect_on_spawner() -> Result {
+    use futures::future::BoxFuture;
+    use postgres_query::{connect::connect_with_retry_on, RetryPolicy};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let config = env::var("POSTGRES_DB_CONFIG")
+        .unwrap_or_else(|_| "user=postgres_query_test host=localhost".to_owned());
+    let config: tokio_postgres::Config = config.parse()?;
+
+    let spawned = AtomicUsize::new(0);
+    let spawner = |task: BoxFuture<'static, ()>| {
+        spawned.fetch_add(1, Ordering::SeqCst);
+        std::thread::spawn(|| futures::executor::block_on(task));
+    };
+
+    let (client, connection) = connect_with_retry_on(
+        &config,
+        tokio_postgres::NoTls,
+        RetryPolicy::default(),
+        &spawner,
+    )
+    .await?;
+    assert_eq!(spawned.load(Ordering::SeqCst), 1);
+
+    let (answer,): (i32,) = query!("SELECT 42").fetch_one(&client).await?;
+    assert_eq!(answer, 42);
+
+    drop(client);
+    connection.await??;
+
+    Ok(())
+}
+
+#[cfg(feature = "profile")]
+#[tokio::test]
+async fn profile_extraction() -> Result {
+    use postgres_query::profile;
+
+    let client = establish().await?;
+
+    #[derive(FromSqlRow)]
+    struct Profiled {
+        id: i32,
+        #[row(flatten)]
+        details: ProfiledDetails,
+    }
+
+    #[derive(FromSqlRow)]
+    struct ProfiledDetails {
+        price: String,
+    }
+
+    // Hooks are global, so only keep the events of the types above.
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+    let _registration = profile::register(move |event| {
+        if event.type_name.ends_with("Profiled") {
+            let fields = event
+                .fields
+                .iter()
+                .map(|timing| (timing.container.rsplit("::").next().unwrap(), timing.field))
+                .collect::<Vec<_>>();
+            recorded.lock().unwrap().push((event.rows, fields));
+        }
+    });
+
+    let rows: Vec<Profiled> =
+        query!("SELECT * FROM (VALUES (1, '1.5'), (2, '2.5')) AS t (id, price)")
+            .fetch(&client)
+            .await?;
+    let row: Profiled = query!("SELECT 3 AS id, '3.5' AS price")
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(rows[1].details.price, "2.5");
+    assert_eq!(row.id, 3);
+
+    let fields = vec![
+        ("Profiled", "id"),
+        ("ProfiledDetails", "price"),
+        ("Profiled", "details"),
+    ];
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![(2, fields.clone()), (1, fields)]
+    );
+
+    Ok(())
+}
+
+#[cfg(all(feature = "with-smallvec-1", feature = "with-arrayvec-0_7"))]
+#[tokio::test]
+async fn merge_into_inline_collections() -> Result {
+    let client = establish().await?;
+
+    #[derive(Debug, FromSqlRow)]
+    #[row(group)]
+    struct Author {
+        #[row(key)]
+        id: i32,
+        #[row(merge)]
+        titles: smallvec::SmallVec<[Title; 2]>,
+        #[row(merge)]
+        years: arrayvec::ArrayVec<Year, 4>,
+    }
+
+    #[derive(Debug, FromSqlRow)]
+    struct Title {
+        title: String,
+    }
+
+    #[derive(Debug, FromSqlRow)]
+    struct Year {
+        year: i32,
+    }
+
+    let authors = query!(
+        "
+        SELECT 1 as id, 'The Fellowship of the Ring' as title, 1954 as year
+        UNION ALL
+        SELECT 1 as id, 'The Two Towers' as title, 1954 as year
+        UNION ALL
+        SELECT 1 as id, 'Return of the King' as title, 1955 as year
+        UNION ALL
+        SELECT 2 as id, 'The Last Wish' as title, 1993 as year
+        "
+    )
+    .fetch::<Author, _>(&client)
+    .await?;
+
+    assert_eq!(authors.len(), 2);
+    assert_eq!(authors[0].id, 1);
+    assert_eq!(authors[0].titles.len(), 3);
+    assert!(authors[0].titles.spilled());
+    assert_eq!(authors[0].titles[2].title, "Return of the King");
+    assert_eq!(authors[0].years.len(), 3);
+    assert_eq!(authors[0].years[2].year, 1955);
+
+    assert_eq!(authors[1].id, 2);
+    assert!(!authors[1].titles.spilled());
+    assert_eq!(authors[1].titles[0].title, "The Last Wish");
+    assert_eq!(authors[1].years[0].year, 1993);
+
+    let overflow = query!("SELECT 1 as id, 'Title' as title, generate_series(1, 5) as year")
+        .fetch::<Author, _>(&client)
+        .await;
+    assert!(matches!(
+        overflow,
+        Err(postgres_query::Error::Execute(execute::Error::Extract(
+            extract::Error::Custom { .. }
+        )))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn raw_identifier_fields() -> Result {
+    let client = establish().await?;
+
+    #[derive(FromSqlRow)]
+    struct Item {
+        r#type: String,
+    }
+
+    let item: Item = query!("SELECT 'book' AS type").fetch_one(&client).await?;
+    assert_eq!(item.r#type, "book");
+
+    Ok(())
+}
+
+#[cfg(feature = "codegen")]
+#[tokio::test]
+async fn generate_row_struct() -> Result {
+    use postgres_query::codegen;
+
+    let mut client = establish().await?;
+    let tx = client.transaction().await?;
+
+    query!(
+        r#"CREATE TABLE people (
+            id SERIAL PRIMARY KEY,
+            "fullName" TEXT NOT NULL,
+            type TEXT,
+            tags TEXT[] NOT NULL,
+            search TSVECTOR
+        )"#
+    )
+    .execute(&tx)
+    .await?;
+
+    let source = codegen::row_struct(&tx, "Person", "SELECT * FROM people").await?;
+    assert_eq!(
+        source,
+        r#"#[derive(Debug, FromSqlRow)]
+pub struct Person {
+    pub id: i32,
+    #[row(rename = "fullName")]
+    pub full_name: String,
+    pub r#type: Option<String>,
+    pub tags: Vec<String>,
+    // TODO: no Rust type for `tsvector`
+    pub search: Option<String>,
+}
+"#
+    );
+
+    let source = codegen::row_struct(
+        &tx,
+        "Pair",
+        "SELECT a.id, b.id, a.id + 1 FROM people AS a LEFT JOIN people AS b ON false",
+    )
+    .await?;
+    assert_eq!(
+        source,
+        r#"#[derive(Debug, FromSqlRow)]
+#[row(positional)]
+pub struct Pair {
+    pub id: Option<i32>,
+    pub column_1: Option<i32>,
+    pub column: Option<i32>,
+}
+"#
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "with-range")]
+#[tokio::test]
+async fn range_types() -> Result {
+    use postgres_query::types::range::Range;
+    use std::ops::Bound;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let client = establish().await?;
+
+    let (numbers, empty, unbounded): (Range<i32>, Range<i32>, Range<i64>) =
+        query!("SELECT '[1,3]'::int4range, 'empty'::int4range, '(,10)'::int8range")
+            .fetch_one(&client)
+            .await?;
+    assert_eq!(numbers, Range::from(1..4));
+    assert!(numbers.contains(&3));
+    assert!(!numbers.contains(&4));
+    assert!(empty.is_empty());
+    assert_eq!(unbounded, Range::from(..10));
+
+    let start = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    let end = start + Duration::from_secs(3600);
+    let during = Range::new(Bound::Excluded(start), Bound::Included(end));
+    let time = start + Duration::from_secs(60);
+    let (fetched, inside): (Range<SystemTime>, bool) = query!(
+        "SELECT $during::tstzrange, $during::tstzrange @> $time::timestamptz",
+        during,
+        time,
+    )
+    .fetch_one(&client)
+    .await?;
+    assert_eq!(fetched, during);
+    assert!(inside);
+
+    let (all,): (Range<i32>,) = query!("SELECT $all::int4range", all = Range::<i32>::from(..))
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(all.lower(), Some(Bound::Unbounded));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn bind_smart_pointers() -> Result {
+    let client = establish().await?;
+
+    struct Config {
+        name: Arc<String>,
+        limit: Box<i32>,
+    }
+
+    let config = Arc::new(Config {
+        name: Arc::new("John Wick".to_owned()),
+        limit: Box::new(42),
+    });
+
+    let (name, limit): (String, i32) = query!(
+        "SELECT $name::TEXT, $limit::INT",
+        name = config.name,
+        limit = config.limit,
+    )
+    .fetch_one(&client)
+    .await?;
+    assert_eq!(name, "John Wick");
+    assert_eq!(limit, 42);
+
+    let name = Arc::new("Myke".to_owned());
+    let (name,): (String,) = query_dyn!("SELECT $name::TEXT", name)?
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(name, "Myke");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn merge_into_newtypes() -> Result {
+    let client = establish().await?;
+
+    #[derive(Debug, Default, postgres_query::Merge)]
+    struct Books(Vec<Book>);
+
+    #[derive(Debug, Default, postgres_query::Merge)]
+    struct Sorted<T: Ord> {
+        items: std::collections::BTreeSet<T>,
+    }
+
+    #[derive(Debug, FromSqlRow)]
+    #[row(hash)]
+    struct Author {
+        #[row(key)]
+        id: i32,
+        #[row(merge)]
+        books: Books,
+        #[row(merge)]
+        genres: Sorted<Genre>,
+    }
+
+    #[derive(Debug, FromSqlRow)]
+    struct Book {
+        title: String,
+    }
+
+    #[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, FromSqlRow)]
+    struct Genre {
+        genre: String,
+    }
+
+    let authors = query!(
+        "SELECT * FROM (VALUES
+            (1, 'The Hobbit', 'fantasy'),
+            (2, 'The Last Wish', 'fantasy'),
+            (1, 'The Silmarillion', 'myth')
+        ) AS books (id, title, genre)"
+    )
+    .fetch::<Author, _>(&client)
+    .await?;
+
+    assert_eq!(authors.len(), 2);
+    assert_eq!(authors[0].id, 1);
+    let titles = authors[0].books.0.iter().map(|book| &book.title);
+    assert_eq!(
+        titles.collect::<Vec<_>>(),
+        ["The Hobbit", "The Silmarillion"]
+    );
+    assert_eq!(
+        authors[0]
+            .genres
+            .items
+            .iter()
+            .map(|genre| &genre.genre)
+            .collect::<Vec<_>>(),
+        ["fantasy", "myth"]
+    );
+    assert_eq!(authors[1].books.0.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_adaptive() -> Result {
+    use postgres_query::stream::Adaptive;
+
+    let client = establish().await?;
+    let numbers = query!("SELECT generate_series(1, $count)", count = 5);
+
+    match numbers.fetch_adaptive::<(i32,), _>(&client, 5).await? {
+        Adaptive::Buffered(values) => assert_eq!(values.len(), 5),
+        Adaptive::Streaming(_) => panic!("expected the values to be buffered"),
+    }
+
+    let values = numbers.fetch_adaptive::<(i32,), _>(&client, 2).await?;
+    assert!(!values.is_buffered());
+    let values: Vec<(i32,)> = values.into_stream().try_collect().await?;
+    assert_eq!(values, [(1,), (2,), (3,), (4,), (5,)]);
+
+    let values = numbers.fetch_adaptive::<(i32,), _>(&client, 10).await?;
+    let values: Vec<(i32,)> = values.into_stream().try_collect().await?;
+    assert_eq!(values.len(), 5);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn execute_verbose() -> Result {
+    use execute::{CommandTag, ExecStatus};
+
+    let client = establish().await?;
+
+    let status = query!("CREATE TEMP TABLE people (name TEXT, age INT)")
+        .execute_verbose(&client)
+        .await?;
+    assert_eq!(status.command, CommandTag::Other("CREATE".to_owned()));
+
+    let status = query!("INSERT INTO people VALUES ('John Wick', 42), ('Jane Doe', 25)")
+        .execute_verbose(&client)
+        .await?;
+    assert_eq!(
+        status,
+        ExecStatus {
+            rows_affected: 2,
+            command: CommandTag::Insert,
+        }
+    );
+
+    let status = query!(
+        "WITH old AS (SELECT name FROM people WHERE age > 30) \
+         UPDATE people SET age = age + 1 WHERE name IN (SELECT name FROM old)"
+    )
+    .execute_verbose(&client)
+    .await?;
+    assert_eq!(status.command, CommandTag::Update);
+    assert_eq!(status.rows_affected, 1);
+
+    let status = query!("/* cleanup */ DELETE FROM people")
+        .execute_verbose(&client)
+        .await?;
+    assert_eq!(status.command, CommandTag::Delete);
+    assert_eq!(status.rows_affected, 2);
+
+    let status = query!("SELECT 1").execute_verbose(&client).await?;
+    assert_eq!(status.command, CommandTag::Select);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn execute_expecting_row_count() -> Result {
+    let client = establish().await?;
+
+    query!("CREATE TEMP TABLE people (name TEXT, age INT)")
+        .execute(&client)
+        .await?;
+    query!("INSERT INTO people VALUES ('John Wick', 42), ('Jane Doe', 42)")
+        .execute_expecting(&client, 2)
+        .await?;
+
+    let error = query!("UPDATE people SET age = 43 WHERE age = 42")
+        .execute_expecting(&client, 1)
+        .await
+        .unwrap_err();
+
+    match error {
+        postgres_query::Error::Execute(execute::Error::UnexpectedRowCount { expected, actual }) => {
+            assert_eq!((expected, actual), (1, 2))
+        }
+        error => panic!("unexpected error: {:?}", error),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn defer_constraints_and_disable_triggers() -> Result {
+    use postgres_query::client::Integrity;
+
+    let mut client = establish().await?;
+
+    client
+        .batch_execute(
+            "CREATE TEMP TABLE users (id INT PRIMARY KEY);
+             CREATE TEMP TABLE orders (
+                 id INT,
+                 user_id INT CONSTRAINT orders_user_fk REFERENCES users DEFERRABLE
+             );
+             CREATE FUNCTION pg_temp.reject() RETURNS trigger AS $$
+                 BEGIN RAISE EXCEPTION 'rejected'; END
+             $$ LANGUAGE plpgsql;
+             CREATE TRIGGER reject BEFORE INSERT ON orders
+                 FOR EACH ROW EXECUTE FUNCTION pg_temp.reject();",
+        )
+        .await?;
+
+    let insert_order = query!("INSERT INTO orders VALUES (1, 7)");
+    let insert_user = query!("INSERT INTO users VALUES (7)");
+
+    let tx = client.transaction().await?;
+    assert!(insert_order.execute(&tx).await.is_err());
+    tx.rollback().await?;
+
+    let tx = client.transaction().await?;
+    tx.defer_constraints(&["pg_temp.orders_user_fk"]).await?;
+    tx.disable_triggers("pg_temp.orders").await?;
+    insert_order.execute(&tx).await?;
+    insert_user.execute(&tx).await?;
+    tx.check_constraints(&["orders_user_fk"]).await?;
+    tx.enable_triggers("orders").await?;
+    tx.commit().await?;
+
+    let (count,): (i64,) = query!("SELECT COUNT(*) FROM orders")
+        .fetch_one(&client)
+        .await?;
+    assert_eq!(count, 1);
+
+    // The trigger is enabled again.
+    assert!(query!("INSERT INTO orders VALUES (2, 7)")
+        .execute(&client)
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn safe_like_patterns() -> Result {
+    use postgres_query::filters::SafePattern;
+
+    let client = establish().await?;
+
+    async fn search(client: &Client, pattern: SafePattern) -> Result<Vec<String>> {
+        let titles: Vec<(String,)> = query!(
+            "SELECT title FROM (VALUES ('100% sure'), ('1000 sure'), ('a_b'), ('axb')) AS posts (title)
+             WHERE title ILIKE $pattern
+             ORDER BY title",
+            pattern,
+        )
+        .fetch(client)
+        .await?;
+        Ok(titles.into_iter().map(|(title,)| title).collect())
+    }
+
+    assert_eq!(
+        search(&client, SafePattern::contains("0%")).await?,
+        ["100% sure"]
+    );
+    assert_eq!(
+        search(&client, SafePattern::starts_with("100")).await?,
+        ["100% sure", "1000 sure"]
+    );
+    assert_eq!(search(&client, SafePattern::exact("A_B")).await?, ["a_b"]);
+    assert_eq!(
+        search(&client, SafePattern::ends_with("_b")).await?,
+        ["a_b"]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn caching_moves_to_new_connection() -> Result {
+    let client = Caching::new(establish().await?)
+        .with_options(ExecuteOptions::new().timeout(Duration::from_secs(5)));
+
+    let select = || query!("SELECT 1 + $n", n = 1);
+    let (value,): (i32,) = select().fetch_one(&client).await?;
+    assert_eq!(value, 2);
+
+    let (old, detached) = client.into_parts();
+    drop(old);
+
+    // Statements prepared on the old connection do not exist on the new one.
+    let client = Caching::from_parts(establish().await?, detached);
+    let (value,): (i32,) = select().fetch_one(&client).await?;
+    assert_eq!(value, 2);
+    assert_eq!(
+        client.execute_options(),
+        ExecuteOptions::new().timeout(Duration::from_secs(5))
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn route_by_tag() -> Result {
+    use postgres_query::client::Router;
+
+    let router = Router::new(establish().await?).route("analytics.*", establish().await?);
+
+    let backend = |tag: Option<&str>| {
+        let query = query!("SELECT pg_backend_pid()");
+        match tag {
+            Some(tag) => query.with_options(ExecuteOptions::new().tag(tag)),
+            None => query,
+        }
+    };
+
+    let mut pids = Vec::new();
+    for tag in &[None, Some("billing"), Some("analytics.visits")] {
+        let query = backend(*tag);
+        let (pid,): (i32,) = query.fetch_one(router.client_for(&query)).await?;
+        pids.push(pid);
+    }
+
+    assert_eq!(pids[0], pids[1]);
+    assert_ne!(pids[0], pids[2]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn balance_reads() -> Result {
+    use postgres_query::client::Balancer;
+
+    let balancer = Balancer::new(establish().await?).replica(establish().await?);
+    let pid = |client| async move {
+        let (pid,): (i32,) = query!("SELECT pg_backend_pid()").fetch_one(client).await?;
+        Result::<i32>::Ok(pid)
+    };
+
+    let primary = pid(balancer.primary()).await?;
+    let replica = pid(balancer.reader()).await?;
+    assert_ne!(primary, replica);
+    assert!(balancer.replicas()[0].latency().is_some());
+
+    // Errors reported by the server do not count against the backend.
+    let error = query!("SELECT 1/0").execute(balancer.reader()).await;
+    assert!(error.is_err());
+    assert_eq!(balancer.replicas()[0].error_rate(), 0.0);
+
+    let session = balancer.session();
+    assert_eq!(pid(session.reader()).await?, replica);
+    assert_eq!(pid(session.writer()).await?, primary);
+    assert_eq!(pid(session.reader()).await?, primary);
+
+    balancer.check(std::time::Duration::from_secs(5)).await;
+    assert_eq!(balancer.primary().error_rate(), 0.0);
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+#![cfg(feature = "explain")]
+
+use postgres_query::{assert_plan, explain, query};
+use std::env;
+
+#[tokio::test]
+async fn plan_regressions() {
+    let config = env::var("POSTGRES_DB_CONFIG")
+        .unwrap_or_else(|_| "user=postgres_query_test host=localhost".to_owned());
+    let (client, conn) = tokio_postgres::connect(&config, tokio_postgres::NoTls)
+        .await
+        .unwrap();
+    tokio::spawn(conn);
+
+    client
+        .batch_execute(
+            "CREATE TEMP TABLE people (id INT PRIMARY KEY, name TEXT);
+             INSERT INTO people SELECT i, 'Person ' || i FROM generate_series(1, 10000) AS i;
+             ANALYZE people;",
+        )
+        .await
+        .unwrap();
+
+    let by_id = query!("SELECT name FROM people WHERE id = $id", id = 7);
+    assert_plan!(client, by_id, contains: "Index Scan", excludes: "Seq Scan", max_cost: 100.0);
+
+    let by_name = query!(
+        "SELECT id FROM people WHERE name = $name",
+        name = "Person 7"
+    );
+    let plan = explain::explain(&client, &by_name).await.unwrap();
+    assert!(plan.contains("Seq Scan"));
+    assert_eq!(plan.root.relation.as_deref(), Some("people"));
+    assert!(plan.to_string().starts_with("Seq Scan on people"));
+}
+
+#[tokio::test]
+#[should_panic(expected = "expected the plan not to contain `Seq Scan`")]
+async fn sequential_scan() {
+    let config = env::var("POSTGRES_DB_CONFIG")
+        .unwrap_or_else(|_| "user=postgres_query_test host=localhost".to_owned());
+    let (client, conn) = tokio_postgres::connect(&config, tokio_postgres::NoTls)
+        .await
+        .unwrap();
+    tokio::spawn(conn);
+
+    let query = query!("SELECT * FROM generate_series(1, 10) AS i, pg_class");
+    assert_plan!(client, query, excludes: "Seq Scan");
+}
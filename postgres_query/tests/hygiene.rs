@@ -0,0 +1,90 @@
+//! The derives should compile even when the names they use are shadowed where they are invoked.
+
+#![allow(dead_code, non_camel_case_types)]
+
+type Result<T> = std::result::Result<T, ()>;
+type Option<T> = std::result::Result<T, ()>;
+type Vec<T> = [T; 0];
+type String = ();
+struct Ok;
+struct Err;
+struct Some;
+struct None;
+struct Box;
+struct Default;
+trait Sync {}
+trait Send {}
+
+#[allow(unused_macros)]
+macro_rules! vec {
+    ($($tt:tt)*) => {
+        compile_error!("used the shadowed `vec!`")
+    };
+}
+
+#[allow(unused_macros)]
+macro_rules! assert_eq {
+    ($($tt:tt)*) => {
+        compile_error!("used the shadowed `assert_eq!`")
+    };
+}
+
+#[derive(postgres_query::FromSqlRow)]
+#[row(test_layout)]
+struct Person {
+    id: i32,
+    #[row(rename = "full_name")]
+    name: std::string::String,
+    #[row(flatten, none_when = "all_null")]
+    address: std::option::Option<Address>,
+}
+
+#[derive(postgres_query::FromSqlRow)]
+struct Address {
+    street: std::string::String,
+}
+
+#[derive(postgres_query::FromSqlRow)]
+#[row(split, test_layout)]
+struct Family {
+    #[row(split = "id")]
+    #[row(flatten)]
+    parent: Person,
+    #[row(split = "id")]
+    #[row(flatten)]
+    child: Person,
+}
+
+#[derive(postgres_query::FromSqlRow)]
+#[row(group)]
+struct Author {
+    #[row(key)]
+    id: i32,
+    #[row(merge)]
+    books: std::vec::Vec<Book>,
+}
+
+#[derive(postgres_query::FromSqlRow)]
+struct Book {
+    title: std::string::String,
+}
+
+#[derive(std::default::Default, postgres_query::Merge)]
+struct Books(std::vec::Vec<Book>);
+
+#[derive(postgres_query::ToSqlRow)]
+struct NewPerson {
+    #[sql_row(sql_type = "int4")]
+    id: i32,
+    #[sql_row(sql_type = "text")]
+    name: std::string::String,
+}
+
+#[derive(postgres_query::Patch)]
+struct PersonPatch {
+    id: i32,
+    name: std::option::Option<std::string::String>,
+}
+
+#[derive(Debug, postgres_query::ToSqlDelegate)]
+struct UserId(i32);
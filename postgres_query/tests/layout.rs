@@ -0,0 +1,181 @@
+//! Containers with `#[row(test_layout)]` generate their own unit tests, which run as part of this
+//! file.
+
+#![allow(dead_code)]
+
+use postgres_query::{extract, FromSqlRow};
+
+#[derive(FromSqlRow)]
+#[row(test_layout)]
+struct Person {
+    id: i32,
+    #[row(rename = "full_name")]
+    name: String,
+}
+
+#[derive(FromSqlRow)]
+#[row(test_layout)]
+struct Pair(i32, String);
+
+#[derive(FromSqlRow)]
+#[row(exact, test_layout)]
+struct Family {
+    generation: i32,
+    #[row(flatten, stride = 3)]
+    parent: Person,
+    #[row(flatten)]
+    child: Person,
+}
+
+#[derive(FromSqlRow)]
+#[row(split, test_layout)]
+struct Siblings {
+    generation: i32,
+    #[row(flatten, split = "id")]
+    older: Person,
+    #[row(flatten, split = "id")]
+    younger: Person,
+    #[row(split = "origin")]
+    origin: String,
+}
+
+#[derive(FromSqlRow)]
+#[row(split, test_layout)]
+struct Marked {
+    #[row(split = "person")]
+    #[row(flatten)]
+    person: Person,
+    #[row(split = "pair", flatten)]
+    pair: Pair,
+}
+
+#[test]
+fn synthetic_columns() {
+    assert_eq!(Person::__column_names(), vec!["id", "full_name"]);
+    assert_eq!(Pair::__column_names(), vec!["?", "?"]);
+    assert_eq!(
+        Family::__column_names(),
+        vec!["generation", "id", "full_name", "?", "id", "full_name"]
+    );
+    assert_eq!(
+        Siblings::__column_names(),
+        vec!["generation", "id", "full_name", "id", "full_name", "origin"]
+    );
+    assert_eq!(
+        Marked::__column_names(),
+        vec!["person", "id", "full_name", "pair", "?"]
+    );
+}
+
+#[test]
+fn describe_exact_layout() {
+    let columns = ["generation", "id", "full_name", "age", "id", "full_name"];
+    let report = extract::describe_layout::<Family, _>(&columns);
+
+    assert!(report.errors.is_empty(), "{}", report);
+    assert_eq!(report.partitions, vec![0..1, 1..4, 4..6]);
+
+    let fields = report
+        .fields
+        .iter()
+        .map(|field| (field.field.as_str(), field.partition, field.columns.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        fields,
+        vec![
+            ("generation", 0, Some(0..1)),
+            ("parent.id", 1, Some(1..2)),
+            ("parent.name", 1, Some(2..3)),
+            ("child.id", 2, Some(4..5)),
+            ("child.name", 2, Some(5..6)),
+        ]
+    );
+}
+
+#[test]
+fn describe_split_layout() {
+    let columns = ["generation", "id", "full_name", "id", "full_name", "origin"];
+    let report = extract::describe_layout::<Siblings, _>(&columns);
+
+    assert!(report.errors.is_empty(), "{}", report);
+    assert_eq!(report.partitions, vec![0..1, 1..3, 3..5, 5..6]);
+    assert_eq!(report.fields[4].field, "younger.name");
+    assert_eq!(report.fields[4].partition, 2);
+    assert_eq!(report.fields[5].field, "origin");
+    assert_eq!(report.fields[5].columns, Some(5..6));
+}
+
+#[test]
+fn describe_missing_columns() {
+    let columns = ["generation", "id", "name", "origin"];
+    let report = extract::describe_layout::<Siblings, _>(&columns);
+
+    assert_eq!(
+        report.errors,
+        vec![
+            "row: failed to split on `id`, since no later column has that name",
+            "older.name: no column named `full_name`",
+            "younger.id: no column named `id`",
+            "younger.name: no column named `full_name`",
+            "origin: no column named `origin`",
+        ]
+    );
+    assert!(report.to_string().contains("older.name <- (missing)"));
+}
+
+#[derive(FromSqlRow)]
+#[row(columns)]
+struct Address {
+    street: String,
+    #[row(rename = "zipCode")]
+    zip_code: String,
+}
+
+#[derive(FromSqlRow)]
+#[row(columns)]
+struct Resident {
+    name: String,
+    #[row(flatten)]
+    home: Address,
+    #[row(flatten)]
+    work: Option<Address>,
+}
+
+#[test]
+fn static_columns() {
+    assert_eq!(Address::COLUMNS, ["street", "zipCode"]);
+    assert_eq!(
+        Resident::COLUMNS,
+        ["name", "street", "zipCode", "street", "zipCode"]
+    );
+    assert_eq!(
+        <Resident as extract::StaticColumns>::COLUMNS,
+        extract::columns::<Resident>()
+    );
+}
+
+#[derive(FromSqlRow)]
+#[row(test_layout)]
+struct Household {
+    #[row(flatten, rename_prefix = "parent_")]
+    parent: Person,
+    #[row(flatten, rename_prefix = "child_")]
+    child: Person,
+}
+
+#[test]
+fn prefixed_columns() {
+    let columns = [
+        "parent_id",
+        "parent_full_name",
+        "child_id",
+        "child_full_name",
+    ];
+    assert_eq!(Household::__column_names(), columns);
+    assert_eq!(extract::columns::<Household>(), columns);
+
+    let report = extract::describe_layout::<Household, _>(&columns);
+    assert!(report.errors.is_empty(), "{}", report);
+    assert_eq!(report.fields[3].field, "child.name");
+    assert_eq!(report.fields[3].columns, Some(3..4));
+}
@@ -0,0 +1,41 @@
+#![cfg(all(feature = "lint", debug_assertions))]
+
+use postgres_query::{lint, query, query_dyn, Error};
+use std::env;
+
+// The rules are global, so all checks live in a single test.
+#[tokio::test]
+async fn lint_rules() {
+    let config = env::var("POSTGRES_DB_CONFIG")
+        .unwrap_or_else(|_| "user=postgres_query_test host=localhost".to_owned());
+    let (client, conn) = tokio_postgres::connect(&config, tokio_postgres::NoTls)
+        .await
+        .unwrap();
+    tokio::spawn(conn);
+
+    lint::register("no_select_star", lint::no_select_star);
+    lint::register("delete_has_where", lint::delete_has_where);
+
+    match query!("SELECT * FROM (SELECT 1) AS numbers")
+        .fetch::<(i32,), _>(&client)
+        .await
+    {
+        Err(Error::Lint { rule }) => assert_eq!(rule, "no_select_star"),
+        result => panic!("expected a lint error, found {:?}", result),
+    }
+
+    match query_dyn!("DELETE FROM people") {
+        Err(Error::Lint { rule }) => assert_eq!(rule, "delete_has_where"),
+        result => panic!("expected a lint error, found {:?}", result),
+    }
+
+    let (value,): (i32,) = query!("SELECT 14").fetch_one(&client).await.unwrap();
+    assert_eq!(value, 14);
+
+    lint::clear();
+    let (value,): (i32,) = query!("SELECT * FROM (SELECT 14) AS numbers")
+        .fetch_one(&client)
+        .await
+        .unwrap();
+    assert_eq!(value, 14);
+}
@@ -17,6 +17,32 @@ fn escape_dollar() {
     assert_params_eq(query.parameters(), &[])
 }
 
+#[test]
+fn literals_and_comments_are_not_substituted() {
+    let id = 1;
+    let query = query!("SELECT '$id $$x', \"$id\" /* $id */ WHERE id = $id", id);
+
+    assert_eq!(query.sql(), "SELECT '$id $x', \"$id\" /* $id */ WHERE id = $1");
+    assert_params_eq(query.parameters(), &[(&id, &Type::INT4)])
+}
+
+#[test]
+fn keyword_fields() {
+    struct Item {
+        r#type: &'static str,
+    }
+
+    let item = Item { r#type: "book" };
+    let query = query_with!("SELECT name FROM items WHERE type = $type", &item);
+
+    assert_eq!(query.sql(), "SELECT name FROM items WHERE type = $1");
+    assert_params_eq(query.parameters(), &[(&item.r#type, &Type::TEXT)]);
+
+    let r#type = 2;
+    let query = query!("SELECT $type", r#type);
+    assert_params_eq(query.parameters(), &[(&r#type, &Type::INT4)])
+}
+
 #[test]
 fn parameter_substitution_implicit_name() {
     let age = 42;
@@ -49,6 +75,163 @@ fn parameter_substitution_multiple_parameters() {
     )
 }
 
+#[test]
+fn parameter_substitution_tuple_fields() {
+    let point = (3, 7);
+    let query = query!("SELECT $point.0, $point.1, $point.0", point);
+
+    assert_eq!(query.sql(), "SELECT $1, $2, $1");
+    assert_params_eq(
+        query.parameters(),
+        &[(&point.0, &Type::INT4), (&point.1, &Type::INT4)],
+    )
+}
+
+#[test]
+fn parameter_substitution_struct_fields() {
+    struct Person {
+        name: &'static str,
+        age: i32,
+    }
+
+    let person = Person {
+        name: "John Wick",
+        age: 42,
+    };
+    let query = query!(
+        "INSERT INTO people VALUES ($person.name, $person.age)",
+        person = &person,
+    );
+
+    assert_eq!(query.sql(), "INSERT INTO people VALUES ($1, $2)");
+    assert_params_eq(
+        query.parameters(),
+        &[(&person.name, &Type::TEXT), (&person.age, &Type::INT4)],
+    )
+}
+
+#[test]
+fn parameters_from_struct() {
+    struct Form {
+        name: String,
+        age: i32,
+        address: (&'static str, &'static str),
+        #[allow(dead_code)]
+        unused: bool,
+    }
+
+    let form = Form {
+        name: "John Wick".to_owned(),
+        age: 42,
+        address: ("Main Street", "New York"),
+        unused: true,
+    };
+    let query = query_with!(
+        "INSERT INTO people VALUES ($name, $age, $address.1) "
+        "ON CONFLICT DO UPDATE SET age = $age",
+        &form,
+    );
+
+    assert_eq!(
+        query.sql(),
+        "INSERT INTO people VALUES ($1, $2, $3) ON CONFLICT DO UPDATE SET age = $2"
+    );
+    assert_params_eq(
+        query.parameters(),
+        &[
+            (&form.name, &Type::TEXT),
+            (&form.age, &Type::INT4),
+            (&form.address.1, &Type::TEXT),
+        ],
+    )
+}
+
+#[test]
+fn binding_order() -> Result<()> {
+    let point = (3, 7);
+    let query = query!("SELECT $point.1, $z, $point.0, $point.1", point, z = 5);
+    assert_eq!(
+        query.binding_order(),
+        [("point.1", 1), ("z", 2), ("point.0", 3)]
+    );
+
+    struct Filter {
+        min_age: i32,
+        name: &'static str,
+    }
+
+    let filter = Filter {
+        min_age: 18,
+        name: "John Wick",
+    };
+    let query = query_with!("SELECT $name WHERE $min_age < 30 AND $name <> ''", &filter);
+    assert_eq!(query.binding_order(), [("name", 1), ("min_age", 2)]);
+
+    let bindings = vec![("unused", &0 as Parameter), ("age", &42)];
+    let query = query_dyn!("SELECT $age, $id, $age", id = 7, ..bindings)?;
+    assert_eq!(query.binding_order(), [("age", 1), ("id", 2)]);
+
+    let query = Query::new_static("SELECT $1", vec![&1]);
+    assert_eq!(query.binding_order(), []);
+
+    Ok(())
+}
+
+#[test]
+fn adjacent_literals() {
+    let min_age = 18;
+    let query = query!(
+        "SELECT name "
+        "FROM people "
+        "WHERE age >= $min_age",
+        min_age
+    );
+
+    assert_eq!(query.sql(), "SELECT name FROM people WHERE age >= $1");
+    assert_params_eq(query.parameters(), &[(&min_age, &Type::INT4)]);
+}
+
+#[test]
+fn concat_literals() {
+    macro_rules! select_people {
+        ($($filter:tt)*) => {
+            query!(concat!("SELECT name FROM people WHERE ", concat!("age >= $min_age")) $($filter)*)
+        };
+    }
+
+    let query = select_people!(" AND name = $name", min_age = 18, name = "John Wick");
+
+    assert_eq!(
+        query.sql(),
+        "SELECT name FROM people WHERE age >= $1 AND name = $2"
+    );
+    assert_eq!(query.parameters().len(), 2);
+}
+
+#[test]
+fn identical_literals_share_text() {
+    macro_rules! select_person {
+        () => {
+            query!("SELECT name FROM people WHERE id = $id", id = 1)
+        };
+    }
+
+    let first = select_person!();
+    let second = select_person!();
+    let written_out = query!("SELECT name FROM people WHERE id = $id", id = 2);
+
+    assert_eq!(first.sql().as_ptr(), second.sql().as_ptr());
+    assert_eq!(first.sql().as_ptr(), written_out.sql().as_ptr());
+}
+
+#[test]
+fn dynamic_query_adjacent_literals() {
+    let query =
+        query_dyn!("SELECT * " "FROM people " "WHERE age > $min_age", min_age = 32).unwrap();
+
+    assert_eq!(query.sql(), "SELECT * FROM people WHERE age > $1");
+}
+
 #[test]
 fn dynamic_query() {
     let filters = ["age > $min_age", "name LIKE $name"].join(" AND ");
@@ -95,6 +278,45 @@ fn dynamic_query_dynamic_bindings() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn dynamic_query_from_display() -> Result<()> {
+    use std::borrow::Cow;
+    use std::fmt::{self, Display};
+    use std::sync::Arc;
+
+    struct Select<'a> {
+        table: &'a str,
+    }
+
+    impl Display for Select<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "SELECT * FROM {} WHERE age > $min_age", self.table)
+        }
+    }
+
+    let expected = "SELECT * FROM people WHERE age > $1";
+    let bindings = vec![("min_age", &32 as Parameter)];
+
+    let select = Select { table: "people" };
+    assert_eq!(query_dyn!(select, min_age = 32)?.sql(), expected);
+    assert_eq!(query_dyn!(&select, ..bindings.clone())?.sql(), expected);
+
+    let formatted = query_dyn!(
+        format_args!("SELECT * FROM {} WHERE age > $min_age", "people"),
+        min_age = 32,
+    )?;
+    assert_eq!(formatted.sql(), expected);
+    assert_params_eq(formatted.parameters(), &[(&32, &Type::INT4)]);
+
+    let cow: Cow<str> = Cow::Owned(select.to_string());
+    assert_eq!(query_dyn!(cow, ..bindings.clone())?.sql(), expected);
+
+    let arc: Arc<str> = Arc::from(select.to_string());
+    assert_eq!(query_dyn!(arc, min_age = 32)?.sql(), expected);
+
+    Ok(())
+}
+
 fn assert_params_eq<'a>(a: &[&'a (dyn ToSql + Sync)], b: &[(&'a dyn ToSql, &'a Type)]) {
     assert_eq!(a.len(), b.len());
     for (a, (b, ty)) in a.iter().copied().zip(b.iter().copied()) {
@@ -0,0 +1,50 @@
+#![cfg(feature = "sync")]
+
+use postgres_query::{query, sync::Client, FromSqlRow};
+use std::env;
+
+fn connect() -> Client {
+    let config = env::var("POSTGRES_DB_CONFIG")
+        .unwrap_or_else(|_| "user=postgres_query_test host=localhost".to_owned());
+    Client::connect(&config, tokio_postgres::NoTls).unwrap()
+}
+
+#[derive(FromSqlRow)]
+struct Person {
+    name: String,
+    age: i32,
+}
+
+#[test]
+fn fetch_blocking() {
+    let client = connect();
+
+    let people: Vec<Person> = client
+        .fetch(&query!("SELECT 'John Wick' as name, 42 as age"))
+        .unwrap();
+    assert_eq!(people.len(), 1);
+    assert_eq!(people[0].name, "John Wick");
+    assert_eq!(people[0].age, 42);
+
+    let (value,): (i32,) = client.fetch_one(&query!("SELECT 14")).unwrap();
+    assert_eq!(value, 14);
+}
+
+#[test]
+fn execute_blocking() {
+    let client = connect();
+
+    client
+        .execute(&query!(
+            "CREATE TEMPORARY TABLE people (name TEXT, age INTEGER)"
+        ))
+        .unwrap();
+    let inserted = client
+        .execute(&query!(
+            "INSERT INTO people VALUES ($name, $age)",
+            name = "Anna",
+            age = 42
+        ))
+        .unwrap();
+    assert_eq!(inserted, 1);
+}
@@ -0,0 +1,92 @@
+#![cfg(feature = "test-db")]
+
+use futures::future::BoxFuture;
+use postgres_query::{query, test_db::TestDatabase};
+use std::env;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use tokio_postgres::{Config, NoTls};
+
+type Result<T = (), E = anyhow::Error> = std::result::Result<T, E>;
+
+fn config() -> Config {
+    env::var("POSTGRES_DB_CONFIG")
+        .unwrap_or_else(|_| "user=postgres_query_test host=localhost".to_owned())
+        .parse()
+        .unwrap()
+}
+
+const SETUP: &str = "
+    CREATE TABLE people (name TEXT, age INT);
+    INSERT INTO people VALUES ('John Wick', 42);
+";
+
+async fn schema_exists(name: &str) -> Result<bool> {
+    let (client, connection) = config().connect(NoTls).await?;
+    tokio::spawn(connection);
+    let (exists,) = query!(
+        "SELECT EXISTS (SELECT 1 FROM pg_namespace WHERE nspname = $name)",
+        name
+    )
+    .fetch_one(&client)
+    .await?;
+    Ok(exists)
+}
+
+#[tokio::test]
+async fn isolated_schemas() -> Result {
+    let first = TestDatabase::create_schema(config(), SETUP).await?;
+    let second = TestDatabase::create_schema(config(), SETUP).await?;
+    assert_ne!(first.name(), second.name());
+
+    query!("DELETE FROM people").execute(first.client()).await?;
+
+    let (count,): (i64,) = query!("SELECT COUNT(*) FROM people")
+        .fetch_one(first.client())
+        .await?;
+    assert_eq!(count, 0);
+
+    let (name,): (String,) = query!("SELECT name FROM people")
+        .fetch_one(second.client())
+        .await?;
+    assert_eq!(name, "John Wick");
+
+    let name = first.name().to_owned();
+    assert!(schema_exists(&name).await?);
+    drop(first);
+    assert!(!schema_exists(&name).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn close_through_spawner() -> Result {
+    let spawned = Arc::new(AtomicUsize::new(0));
+    let spawner = {
+        let spawned = spawned.clone();
+        move |task: BoxFuture<'static, ()>| {
+            spawned.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(task);
+        }
+    };
+
+    let db = TestDatabase::create_schema_on(config(), SETUP, spawner).await?;
+    let name = db.name().to_owned();
+    let before = spawned.load(Ordering::SeqCst);
+
+    assert!(schema_exists(&name).await?);
+    db.close().await?;
+    assert!(!schema_exists(&name).await?);
+    assert_eq!(spawned.load(Ordering::SeqCst), before + 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_setup() -> Result {
+    let result = TestDatabase::create_schema(config(), "SELECT * FROM missing").await;
+    assert!(result.is_err());
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use proc_macro2::{Span, TokenStream};
+use quote::*;
+use syn::{
+    parse_quote, Data, DeriveInput, Field, GenericParam, Index, Lifetime, LifetimeDef, Member,
+    Result,
+};
+
+pub fn derive(input: DeriveInput) -> TokenStream {
+    match make_impl(&input) {
+        Ok(output) => output,
+        Err(e) => e.to_compile_error(),
+    }
+}
+
+fn make_impl(input: &DeriveInput) -> Result<TokenStream> {
+    let ident = &input.ident;
+
+    let (field, member) = single_field(input, "ToSqlDelegate")?;
+    let inner = &field.ty;
+    let construct = match member {
+        Member::Named(_) => quote! { |__inner| #ident { #member: __inner } },
+        Member::Unnamed(_) => quote! { #ident },
+    };
+
+    let lib = lib!();
+    let types = quote! { #lib::__postgres_types };
+    let error = quote! {
+        ::std::boxed::Box<dyn ::std::error::Error + ::std::marker::Sync + ::std::marker::Send>
+    };
+
+    let mut to_generics = input.generics.clone();
+    to_generics
+        .make_where_clause()
+        .predicates
+        .push(parse_quote! { #inner: #types::ToSql });
+    let (impl_generics, ty_generics, where_clause) = to_generics.split_for_impl();
+
+    // `FromSql` borrows from the raw value for some lifetime, which must outlive those of the
+    // struct.
+    let raw = Lifetime::new("'__raw", Span::call_site());
+    let mut from_generics = input.generics.clone();
+    let bounds = input
+        .generics
+        .lifetimes()
+        .map(|def| def.lifetime.clone())
+        .collect::<Vec<_>>();
+    let mut raw_def = LifetimeDef::new(raw.clone());
+    raw_def.bounds.extend(bounds);
+    from_generics
+        .params
+        .insert(0, GenericParam::Lifetime(raw_def));
+    let from_where = from_generics.make_where_clause();
+    from_where
+        .predicates
+        .push(parse_quote! { #inner: #types::FromSql<#raw> });
+    let (from_impl_generics, _, from_where_clause) = from_generics.split_for_impl();
+
+    Ok(quote! {
+        #[automatically_derived]
+        #[allow(clippy::all)]
+        impl #impl_generics #types::ToSql for #ident #ty_generics #where_clause {
+            fn to_sql(
+                &self,
+                ty: &#types::Type,
+                out: &mut #lib::__bytes::BytesMut,
+            ) -> ::std::result::Result<#types::IsNull, #error> {
+                #types::ToSql::to_sql(&self.#member, ty, out)
+            }
+
+            fn accepts(ty: &#types::Type) -> bool {
+                <#inner as #types::ToSql>::accepts(ty)
+            }
+
+            fn to_sql_checked(
+                &self,
+                ty: &#types::Type,
+                out: &mut #lib::__bytes::BytesMut,
+            ) -> ::std::result::Result<#types::IsNull, #error> {
+                #types::ToSql::to_sql_checked(&self.#member, ty, out)
+            }
+        }
+
+        #[automatically_derived]
+        #[allow(clippy::all)]
+        impl #from_impl_generics #types::FromSql<#raw> for #ident #ty_generics #from_where_clause {
+            fn from_sql(ty: &#types::Type, raw: &#raw [u8]) -> ::std::result::Result<Self, #error> {
+                <#inner as #types::FromSql<#raw>>::from_sql(ty, raw).map(#construct)
+            }
+
+            fn from_sql_null(ty: &#types::Type) -> ::std::result::Result<Self, #error> {
+                <#inner as #types::FromSql<#raw>>::from_sql_null(ty).map(#construct)
+            }
+
+            fn accepts(ty: &#types::Type) -> bool {
+                <#inner as #types::FromSql<#raw>>::accepts(ty)
+            }
+        }
+    })
+}
+
+/// The only field of a struct which delegates to it, and how to access that field.
+///
+/// Shared by the derives of `ToSqlDelegate` and `Merge`, named by `derive` in errors.
+pub fn single_field<'a>(input: &'a DeriveInput, derive: &str) -> Result<(&'a Field, Member)> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(err!(
+                input.ident,
+                "`{}` may only be derived for structs",
+                derive
+            ))
+        }
+    };
+
+    if fields.len() != 1 {
+        return Err(syn::Error::new_spanned(
+            fields,
+            format!(
+                "`{}` may only be derived for structs with exactly one field",
+                derive
+            ),
+        ));
+    }
+
+    let field = fields.iter().next().unwrap();
+    let member = match &field.ident {
+        Some(ident) => Member::Named(ident.clone()),
+        None => Member::Unnamed(Index::from(0)),
+    };
+
+    Ok((field, member))
+}
@@ -1,15 +1,19 @@
 mod attrs;
+mod layout;
 mod partition;
 mod validate;
 
-use attrs::{ContainerAttributes, FieldAttributes, MergeKind, PartitionKind};
-use partition::partition_initializers;
+use attrs::{Attr, ContainerAttributes, FieldAttributes, MergeKind, NoneWhen, PartitionKind};
+use layout::{column_names, describe, layout_test, static_columns, Layout};
+use partition::{partition, partition_initializers};
 use proc_macro2::{Span, TokenStream};
 use quote::*;
+use syn::ext::IdentExt;
 use syn::{
     spanned::Spanned,
     token::{Enum, Union},
-    Data, DataEnum, DataStruct, DataUnion, DeriveInput, Field, Fields, Ident, Result, Type,
+    Data, DataEnum, DataStruct, DataUnion, DeriveInput, Expr, Field, Fields, GenericArgument,
+    Ident, PathArguments, Result, Type,
 };
 use validate::validate_properties;
 
@@ -21,6 +25,12 @@ pub fn derive(input: DeriveInput) -> TokenStream {
         locals,
         columns,
         merge,
+        column_names,
+        describe,
+        check_types,
+        is_none,
+        test,
+        static_columns,
     } = match extract_columns(&input) {
         Ok(columns) => columns,
         Err(e) => return e.to_compile_error(),
@@ -31,20 +41,40 @@ pub fn derive(input: DeriveInput) -> TokenStream {
     let multi = merge.map(|merge| make_merge(merge, &constructor, &getters));
 
     let lib = lib!();
+    let from_row = profile_scope(
+        quote! { 1 },
+        quote! {
+            #getters
+            ::std::result::Result::Ok(#constructor)
+        },
+    );
     quote! {
+        #[automatically_derived]
+        #[allow(clippy::all)]
         impl #lib::FromSqlRow for #ident {
             const COLUMN_COUNT: usize = #columns;
 
-            fn from_row<R>(__row: &R) -> Result<Self, #lib::extract::Error>
+            fn from_row<R>(__row: &R) -> ::std::result::Result<Self, #lib::extract::Error>
             where
                 R: #lib::extract::Row
             {
-                #getters
-                Ok(#constructor)
+                #from_row
             }
 
             #multi
+
+            #column_names
+
+            #describe
+
+            #check_types
+
+            #is_none
         }
+
+        #test
+
+        #static_columns
     }
 }
 
@@ -60,8 +90,8 @@ fn make_constructor(input: &DeriveInput, locals: impl IntoIterator<Item = Local>
                 ident.clone(),
                 quote! {
                     {
-                        let mut collections = <#base as Default>::default();
-                        #lib::extract::Merge::insert(&mut collections, #ident);
+                        let mut collections = <#base as ::std::default::Default>::default();
+                        #lib::extract::Merge::try_insert(&mut collections, #ident)?;
                         collections
                     }
                 },
@@ -106,84 +136,180 @@ fn make_merge(merge: Merge, constructor: &TokenStream, getters: &TokenStream) ->
         collections,
     } = merge;
 
-    let key_idents = keys.iter().map(|(ident, _)| ident).collect::<Vec<_>>();
+    let key_idents = keys.iter().map(|key| &key.ident).collect::<Vec<_>>();
     let collection_idents = collections
         .iter()
         .map(|(ident, _)| ident)
         .collect::<Vec<_>>();
 
-    let body = match kind {
+    // Each merge below extracts the values of `__rows` into `__values`.
+    let (mode, from_rows, merge_rows) = match kind {
         MergeKind::Group => {
-            quote! {
-                let mut __objects = Vec::<Self>::new();
+            let merge = quote! {
                 for __row in __rows {
                     #getters
 
-                    if let Some(__last) = __objects.last_mut() {
+                    if let ::std::option::Option::Some(__last) = __values.last_mut() {
                         if #(#key_idents == __last.#key_idents) && * {
                             #(
-                                #lib::extract::Merge::insert(
+                                #lib::extract::Merge::try_insert(
                                     &mut __last.#collection_idents,
                                     #collection_idents
-                                );
+                                )?;
                             )*
-                        } else {
-                            __objects.push(#constructor);
+                            continue;
                         }
-                    } else {
-                        __objects.push(#constructor);
                     }
+
+                    __values.push(#constructor);
                 }
-                Ok(__objects)
-            }
+            };
+            let from_rows = quote! {
+                let mut __values = ::std::vec::Vec::<Self>::new();
+                #merge
+                ::std::result::Result::Ok(__values)
+            };
+            let merge_rows = quote! {
+                let __values = &mut __state.values;
+                #merge
+                ::std::result::Result::Ok(())
+            };
+            (quote! { Group }, from_rows, merge_rows)
         }
 
         MergeKind::Hash => {
-            let key_types = keys.iter().map(|(_, ty)| ty);
-
-            quote! {
-                let mut __objects = Vec::<Self>::new();
-                let mut __indices = ::std::collections::HashMap::<(#(#key_types,)*), usize>::new();
+            let mut key_types = Vec::new();
+            let mut key_values = Vec::new();
+            let mut key_patterns = Vec::new();
+            let mut derived = Vec::new();
+            let mut assertions = Vec::new();
+
+            for (i, key) in keys.iter().enumerate() {
+                let ident = &key.ident;
+                let ty = &key.ty;
+
+                match &key.by {
+                    None => {
+                        key_types.push(quote! { #ty });
+                        key_values.push(quote! { #ident });
+                        key_patterns.push(quote! { #ident });
+                        assertions.push(quote_spanned! {ty.span()=>
+                            #lib::extract::__assert_hash_key::<#ty>(&#ident);
+                        });
+                    }
+                    Some(by) => {
+                        let expr = &by.value;
+                        let value = Ident::new(&format!("__key_{}", i), Span::call_site());
+                        key_types.push(quote! { _ });
+                        key_values.push(quote! { #value });
+                        key_patterns.push(quote! { _ });
+                        derived.push(quote! {
+                            let #value = ::std::clone::Clone::clone(&(#expr));
+                        });
+                        assertions.push(quote_spanned! {by.span=>
+                            #lib::extract::__assert_hash_key(&#value);
+                        });
+                    }
+                }
+            }
 
+            // `__indices` maps each key to its index in `__values`.
+            let merge = quote! {
                 for __row in __rows {
                     #getters
+                    #(#derived)*
+                    #(#assertions)*
 
-                    let __key = (#(#key_idents,)*);
+                    let __key = (#(#key_values,)*);
 
-                    if let Some(&__index) = __indices.get(&__key) {
+                    if let ::std::option::Option::Some(&__index) = __indices.get(&__key) {
                         #(
-                            #lib::extract::Merge::insert(
-                                &mut __objects[__index].#collection_idents,
+                            #lib::extract::Merge::try_insert(
+                                &mut __values[__index].#collection_idents,
                                 #collection_idents
-                            );
+                            )?;
                         )*
                     } else {
-                        let __index = __objects.len();
+                        let __index = __values.len();
                         __indices.insert(__key.clone(), __index);
-                        let (#(#key_idents,)*) = __key;
-                        __objects.push(#constructor);
+                        let (#(#key_patterns,)*) = __key;
+                        __values.push(#constructor);
                     }
                 }
-
-                Ok(__objects)
-            }
+            };
+            // The key indices are kept in a typed `HashMap`, which the accumulator has to store
+            // between chunks.
+            let key_type = quote! { (#(#key_types,)*) };
+            let from_rows = quote! {
+                let mut __values = ::std::vec::Vec::<Self>::new();
+                let mut __indices = ::std::collections::HashMap::<#key_type, usize>::new();
+                #merge
+                ::std::result::Result::Ok(__values)
+            };
+            let merge_rows = quote! {
+                let __values = &mut __state.values;
+                let mut __indices =
+                    #lib::extract::__take_indices::<#key_type>(&mut __state.indices);
+                let __result = (|| -> ::std::result::Result<(), #lib::extract::Error> {
+                    #merge
+                    ::std::result::Result::Ok(())
+                })();
+                __state.indices = ::std::option::Option::Some(::std::boxed::Box::new(__indices));
+                __result
+            };
+            (quote! { Hash }, from_rows, merge_rows)
         }
     };
 
+    let from_rows = profile_scope(quote! { __rows.len() }, from_rows);
+
     quote! {
-        fn from_row_multi<R>(__rows: &[R]) -> Result<Vec<Self>, #lib::extract::Error>
+        const __MERGE_MODE: ::std::option::Option<#lib::extract::MergeMode> =
+            ::std::option::Option::Some(#lib::extract::MergeMode::#mode);
+
+        fn __merge_rows<R>(
+            __state: &mut #lib::extract::MergeState<Self>,
+            __rows: &[R],
+        ) -> ::std::result::Result<(), #lib::extract::Error>
         where
             R: #lib::extract::Row
         {
-            #body
+            #merge_rows
+        }
+
+        fn from_row_multi<R>(
+            __rows: &[R],
+        ) -> ::std::result::Result<::std::vec::Vec<Self>, #lib::extract::Error>
+        where
+            R: #lib::extract::Row
+        {
+            #from_rows
         }
     }
 }
 
+/// Time `body`, which extracts `rows` rows, with the `profile` feature.
+fn profile_scope(rows: TokenStream, body: TokenStream) -> TokenStream {
+    if cfg!(feature = "profile") {
+        let lib = lib!();
+        quote! {
+            #lib::extract::__profile::scope::<Self, _>(#rows, || { #body })
+        }
+    } else {
+        body
+    }
+}
+
 enum Index {
     Position,
     Flatten,
     Name(String),
+    /// A collection of `count` flattened values of type `elem`, laid out one after another.
+    Repeat {
+        count: usize,
+        elem: Box<Type>,
+        array: bool,
+    },
 }
 
 struct Extractor {
@@ -191,8 +317,17 @@ struct Extractor {
     locals: Vec<Local>,
     columns: TokenStream,
     merge: Option<Merge>,
+    column_names: TokenStream,
+    describe: TokenStream,
+    check_types: TokenStream,
+    is_none: TokenStream,
+    test: Option<TokenStream>,
+    static_columns: Option<TokenStream>,
 }
 
+/// Generates the code for a group of fields, given the row (or slice) they are extracted from.
+type FieldsFn = fn(&[Property], &Ident) -> (TokenStream, Vec<Local>);
+
 struct Local {
     ident: Ident,
     merge: Option<Type>,
@@ -200,10 +335,16 @@ struct Local {
 
 struct Merge {
     kind: MergeKind,
-    keys: Vec<(Ident, Type)>,
+    keys: Vec<Key>,
     collections: Vec<(Ident, Type)>,
 }
 
+struct Key {
+    ident: Ident,
+    ty: Type,
+    by: Option<Attr<Expr>>,
+}
+
 struct Property {
     ident: Ident,
     ty: Type,
@@ -213,23 +354,91 @@ struct Property {
     field: Field,
 }
 
+impl Property {
+    /// The name of the field, or its position if it is unnamed.
+    fn name(&self) -> String {
+        match &self.field.ident {
+            Some(ident) => ident.unraw().to_string(),
+            None => self
+                .ident
+                .to_string()
+                .trim_start_matches("column_")
+                .to_owned(),
+        }
+    }
+}
+
 fn extract_columns(input: &DeriveInput) -> Result<Extractor> {
     match &input.data {
         Data::Struct(data) => {
             let container = ContainerAttributes::from_attrs(&input.attrs)?;
-            let props = extract_properties(&data)?;
+            let props = extract_properties(&container, data)?;
 
-            validate_properties(&container, &props)?;
+            validate_properties(&container, &data.fields, &props)?;
 
             let columns = count_columns(&props);
 
             let merge = extract_merge(&container, &props);
 
-            let (getters, locals) = if let Some(kind) = container.partition {
-                partition_initializers(props, kind)?
+            let layout = match container.partition {
+                Some(kind) => Layout::Partitioned(partition(props, kind)?),
+                None => Layout::Plain(props),
+            };
+
+            let row = Ident::new("__row", Span::call_site());
+            let generate = |fields: FieldsFn| match &layout {
+                Layout::Partitioned(partitions) => partition_initializers(partitions, fields),
+                Layout::Plain(props) => fields(props, &row),
+            };
+
+            let (mut getters, locals) = generate(field_initializers);
+            let (checks, _) = generate(field_checks);
+            let (null_checks, _) = generate(field_null_checks);
+
+            let lib = lib!();
+            if container.check_types {
+                getters = quote! {
+                    #lib::extract::check_types::<Self, R>(__row)?;
+                    #getters
+                };
+            }
+
+            let check_types = quote! {
+                fn __check_types<R>(
+                    __row: &R,
+                    __mismatches: &mut ::std::vec::Vec<#lib::extract::TypeMismatch>,
+                ) -> ::std::result::Result<(), #lib::extract::Error>
+                where
+                    R: #lib::extract::Row
+                {
+                    #checks
+                    ::std::result::Result::Ok(())
+                }
+            };
+
+            let is_none = quote! {
+                fn __is_none<R>(
+                    __row: &R,
+                    __when: #lib::extract::NoneWhen,
+                ) -> ::std::result::Result<bool, #lib::extract::Error>
+                where
+                    R: #lib::extract::Row
+                {
+                    #null_checks
+                    ::std::result::Result::Ok(true)
+                }
+            };
+
+            let column_names = column_names(&layout);
+            let describe = describe(&layout);
+            let test = if container.test_layout {
+                Some(layout_test(&input.ident, &layout))
             } else {
-                let row = Ident::new("__row", Span::call_site());
-                field_initializers(&props, &row)
+                None
+            };
+            let static_columns = match container.columns {
+                Some(attr) => Some(static_columns(&input.ident, &layout, attr.span)?),
+                None => None,
             };
 
             Ok(Extractor {
@@ -237,6 +446,12 @@ fn extract_columns(input: &DeriveInput) -> Result<Extractor> {
                 locals,
                 columns,
                 merge,
+                column_names,
+                describe,
+                check_types,
+                is_none,
+                test,
+                static_columns,
             })
         }
         Data::Enum(DataEnum {
@@ -258,22 +473,26 @@ fn extract_merge(container: &ContainerAttributes, props: &[Property]) -> Option<
         kind: kind.value,
         keys: props
             .iter()
-            .filter_map(|prop| match prop.attrs.key {
-                Some(_) => Some((prop.ident.clone(), prop.ty.clone())),
-                None => None,
+            .filter_map(|prop| {
+                prop.attrs.key.map(|_| Key {
+                    ident: prop.ident.clone(),
+                    ty: prop.ty.clone(),
+                    by: prop.attrs.by.clone(),
+                })
             })
             .collect(),
         collections: props
             .iter()
-            .filter_map(|prop| match prop.attrs.merge {
-                Some(_) => Some((prop.ident.clone(), prop.ty.clone())),
-                None => None,
+            .filter_map(|prop| {
+                prop.attrs
+                    .merge
+                    .map(|_| (prop.ident.clone(), prop.ty.clone()))
             })
             .collect(),
     })
 }
 
-fn extract_properties(data: &DataStruct) -> Result<Vec<Property>> {
+fn extract_properties(container: &ContainerAttributes, data: &DataStruct) -> Result<Vec<Property>> {
     let mut props = Vec::new();
 
     for (i, field) in data.fields.iter().enumerate() {
@@ -281,13 +500,28 @@ fn extract_properties(data: &DataStruct) -> Result<Vec<Property>> {
 
         let index = match &field.ident {
             _ if attrs.merge.is_some() => Index::Flatten,
+            _ if attrs.repeat.is_some() => {
+                let count = attrs.repeat.unwrap();
+                let (elem, array) = repeated_element(&field.ty).ok_or_else(|| {
+                    err!(
+                        count.span,
+                        "`repeat` is only available on fields of type `Vec<T>` or `[T; N]`"
+                    )
+                })?;
+                Index::Repeat {
+                    count: count.value,
+                    elem: Box::new(elem),
+                    array,
+                }
+            }
             None => Index::Position,
             Some(_) if attrs.flatten => Index::Flatten,
+            Some(_) if container.positional.is_some() => Index::Position,
             Some(name) => {
                 if let Some(name) = attrs.rename.clone() {
                     Index::Name(name)
                 } else {
-                    Index::Name(name.to_string())
+                    Index::Name(name.unraw().to_string())
                 }
             }
         };
@@ -321,29 +555,111 @@ fn extract_properties(data: &DataStruct) -> Result<Vec<Property>> {
     Ok(props)
 }
 
+/// The type of the elements in a `Vec<T>` or `[T; N]`, and whether it is an array.
+fn repeated_element(ty: &Type) -> Option<(Type, bool)> {
+    match ty {
+        Type::Array(array) => Some(((*array.elem).clone(), true)),
+        Type::Path(path) if path.qself.is_none() => {
+            let segment = path.path.segments.last()?;
+            if segment.ident != "Vec" {
+                return None;
+            }
+            match &segment.arguments {
+                PathArguments::AngleBracketed(arguments) if arguments.args.len() == 1 => {
+                    match &arguments.args[0] {
+                        GenericArgument::Type(elem) => Some((elem.clone(), false)),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
 fn field_initializers(props: &[Property], row: &Ident) -> (TokenStream, Vec<Local>) {
     let mut initializers = Vec::new();
     let mut locals = Vec::new();
+    let offsets = column_offsets(props);
 
     for (i, prop) in props.iter().enumerate() {
         let ident = &prop.ident;
         let ty = &prop.ty;
         let lib = lib!();
 
+        let get = if prop.attrs.parse.is_some() {
+            quote! { #lib::extract::try_parse }
+        } else {
+            quote! { #lib::extract::Row::try_get }
+        };
+
+        let nested = flattened_row(prop, row);
+
+        // Spanned by the type of the field, so that errors about missing trait implementations
+        // point at the field instead of the derive.
+        let span = ty.span();
         let getter = match &prop.index {
-            Index::Position => quote! {
-                #lib::extract::Row::try_get(#row, #i)?
+            Index::Position => {
+                let index = &offsets[i];
+                quote_spanned! {span=>
+                    #get(#row, #index)
+                }
+            }
+            Index::Name(name) => quote_spanned! {span=>
+                #get(#row, &*#lib::extract::Row::__column_name(#row, #name))
             },
-            Index::Name(name) => quote! {
-                #lib::extract::Row::try_get(#row, #name)?
+            Index::Flatten if prop.attrs.fallback.is_some() => quote! {
+                #lib::extract::from_row_or_default(#nested)
             },
-            Index::Flatten => quote! {
-                <#ty as #lib::FromSqlRow>::from_row(#row)?
+            Index::Flatten => match prop.attrs.none_when {
+                None => quote_spanned! {span=>
+                    <#ty as #lib::FromSqlRow>::__from_flattened(#nested)
+                },
+                Some(when) => {
+                    let when = match when.value {
+                        NoneWhen::AllNull => quote! { #lib::extract::NoneWhen::AllNull },
+                        NoneWhen::KeyNull => quote! { #lib::extract::NoneWhen::KeyNull },
+                    };
+                    quote! {
+                        #lib::extract::from_row_or_none(#nested, #when)
+                    }
+                }
             },
+            Index::Repeat { count, elem, array } => {
+                let elements = (0..*count).map(|i| {
+                    quote! {
+                        <#elem as #lib::FromSqlRow>::from_row(
+                            &#lib::extract::Row::slice(#row, #i * __len..(#i + 1) * __len)?
+                        )?
+                    }
+                });
+                let collection = if *array {
+                    quote! { [#(#elements),*] }
+                } else {
+                    quote! { ::std::vec![#(#elements),*] }
+                };
+                quote! {{
+                    let __len = <#elem as #lib::FromSqlRow>::COLUMN_COUNT;
+                    ::std::result::Result::Ok(#collection)
+                }}
+            }
         };
 
-        initializers.push(quote! {
-            let #ident: #ty = #getter;
+        let result = quote! { ::std::result::Result<#ty, #lib::extract::Error> };
+        initializers.push(if cfg!(feature = "profile") {
+            let name = prop.name();
+            quote! {
+                let #ident: #ty = #lib::extract::__profile::field::<Self, _>(
+                    #name,
+                    || -> #result { #getter },
+                )?;
+            }
+        } else {
+            quote! {
+                let #ident: #result = #getter;
+                let #ident = #ident?;
+            }
         });
 
         let merge = prop.attrs.merge.map(|_| prop.field.ty.clone());
@@ -360,19 +676,146 @@ fn field_initializers(props: &[Property], row: &Ident) -> (TokenStream, Vec<Loca
     (initializers, locals)
 }
 
-fn count_columns(props: &[Property]) -> TokenStream {
+/// Check the types of the columns of each field, without extracting them. Mirrors
+/// `field_initializers`.
+fn field_checks(props: &[Property], row: &Ident) -> (TokenStream, Vec<Local>) {
+    let lib = lib!();
+
+    let offsets = column_offsets(props);
+    let checks = props.iter().enumerate().map(|(i, prop)| {
+        let ty = &prop.ty;
+        let name = prop.name();
+
+        let check_column = |index: TokenStream| {
+            let ty = if prop.attrs.parse.is_some() {
+                quote! { &str }
+            } else {
+                quote! { #ty }
+            };
+            quote! {
+                #lib::extract::__check_type::<#ty, _, _>(#row, #index, #name, __mismatches);
+            }
+        };
+
+        match &prop.index {
+            Index::Position => check_column(offsets[i].clone()),
+            Index::Name(column) => check_column(quote! {
+                &*#lib::extract::Row::__column_name(#row, #column)
+            }),
+            // The columns of a fallback may be missing altogether.
+            Index::Flatten if prop.attrs.fallback.is_some() => quote! {},
+            Index::Flatten => {
+                let nested = flattened_row(prop, row);
+                quote! {
+                    <#ty as #lib::FromSqlRow>::__check_types(#nested, __mismatches)?;
+                }
+            }
+            Index::Repeat { count, elem, .. } => {
+                let elements = (0..*count).map(|i| {
+                    quote! {
+                        <#elem as #lib::FromSqlRow>::__check_types(
+                            &#lib::extract::Row::slice(#row, #i * __len..(#i + 1) * __len)?,
+                            __mismatches,
+                        )?;
+                    }
+                });
+                quote! {{
+                    let __len = <#elem as #lib::FromSqlRow>::COLUMN_COUNT;
+                    #(#elements)*
+                }}
+            }
+        }
+    });
+
+    (quote! { #(#checks)* }, Vec::new())
+}
+
+/// Check whether the columns of each field are `NULL`, returning early once a column is not (or
+/// once the first column has been checked, for `NoneWhen::KeyNull`). Mirrors `field_initializers`.
+fn field_null_checks(props: &[Property], row: &Ident) -> (TokenStream, Vec<Local>) {
+    let lib = lib!();
+
+    let offsets = column_offsets(props);
+    let checks = props.iter().enumerate().map(|(i, prop)| {
+        let ty = &prop.ty;
+
+        let is_null = match &prop.index {
+            Index::Position => {
+                let index = &offsets[i];
+                quote! {
+                    #lib::extract::__is_null(#row, #index)?
+                }
+            }
+            Index::Name(column) => quote! {
+                #lib::extract::__is_null(#row, &*#lib::extract::Row::__column_name(#row, #column))?
+            },
+            // The columns of a fallback may be missing altogether.
+            Index::Flatten if prop.attrs.fallback.is_some() => return quote! {},
+            Index::Flatten => {
+                let nested = flattened_row(prop, row);
+                quote! {
+                    <#ty as #lib::FromSqlRow>::__is_none(#nested, __when)?
+                }
+            }
+            Index::Repeat { count, elem, .. } => {
+                let elements = (0..*count).map(|i| {
+                    quote! {
+                        <#elem as #lib::FromSqlRow>::__is_none(
+                            &#lib::extract::Row::slice(#row, #i * __len..(#i + 1) * __len)?,
+                            __when,
+                        )?
+                    }
+                });
+                quote! {{
+                    let __len = <#elem as #lib::FromSqlRow>::COLUMN_COUNT;
+                    true #(&& #elements)*
+                }}
+            }
+        };
+
+        quote! {
+            if !#is_null {
+                return ::std::result::Result::Ok(false);
+            }
+            if __when == #lib::extract::NoneWhen::KeyNull {
+                return ::std::result::Result::Ok(true);
+            }
+        }
+    });
+
+    (quote! { #(#checks)* }, Vec::new())
+}
+
+/// The row a flattened field is extracted from, which looks up its columns with the prefix given
+/// by `rename_prefix`, if any.
+fn flattened_row(prop: &Property, row: &Ident) -> TokenStream {
+    match &prop.attrs.rename_prefix {
+        None => quote! { #row },
+        Some(prefix) => {
+            let lib = lib!();
+            let prefix = &prefix.value;
+            quote! { &#lib::extract::Prefixed::new(#row, #prefix) }
+        }
+    }
+}
+
+fn count_columns<'a>(props: impl IntoIterator<Item = &'a Property>) -> TokenStream {
     let mut external = Vec::new();
     let mut fields: usize = 0;
 
     for prop in props {
-        match prop.index {
+        let lib = lib!();
+        match &prop.index {
             Index::Position | Index::Name(_) => fields += 1,
             Index::Flatten => {
                 let ty = &prop.ty;
-                let lib = lib!();
                 let count = quote! { <#ty as #lib::FromSqlRow>::COLUMN_COUNT };
                 external.push(count);
             }
+            Index::Repeat { count, elem, .. } => {
+                let count = quote! { #count * <#elem as #lib::FromSqlRow>::COLUMN_COUNT };
+                external.push(count);
+            }
         }
     }
 
@@ -380,3 +823,12 @@ fn count_columns(props: &[Property]) -> TokenStream {
         #fields #(+ #external)*
     }
 }
+
+/// The index of the first column of each field, relative to the group of fields: positional
+/// columns come after those of every field before them, including flattened ones.
+fn column_offsets<'a>(props: impl IntoIterator<Item = &'a Property>) -> Vec<TokenStream> {
+    let props: Vec<&Property> = props.into_iter().collect();
+    (0..props.len())
+        .map(|i| count_columns(props[..i].iter().copied()))
+        .collect()
+}
@@ -2,20 +2,30 @@ use proc_macro2::Span;
 use std::fmt::Display;
 use std::ops::Deref;
 use std::str::FromStr;
-use syn::{spanned::Spanned, Attribute, Lit, Meta, NestedMeta, Result};
+use syn::{spanned::Spanned, Attribute, Expr, Lit, Meta, NestedMeta, Result};
 
 pub struct ContainerAttributes {
     pub partition: Option<Attr<PartitionKind>>,
     pub merge: Option<Attr<MergeKind>>,
+    pub positional: Option<Attr<()>>,
+    pub test_layout: bool,
+    pub check_types: bool,
+    pub columns: Option<Attr<()>>,
 }
 
 pub struct FieldAttributes {
     pub flatten: bool,
     pub rename: Option<String>,
+    pub parse: Option<Attr<()>>,
     pub splits: Vec<Attr<String>>,
     pub stride: Option<Attr<usize>>,
+    pub repeat: Option<Attr<usize>>,
     pub key: Option<Attr<()>>,
+    pub by: Option<Attr<Expr>>,
     pub merge: Option<Attr<()>>,
+    pub none_when: Option<Attr<NoneWhen>>,
+    pub fallback: Option<Attr<()>>,
+    pub rename_prefix: Option<Attr<String>>,
 }
 
 #[derive(Copy, Clone)]
@@ -36,6 +46,12 @@ pub enum MergeKind {
     Hash,
 }
 
+#[derive(Copy, Clone)]
+pub enum NoneWhen {
+    AllNull,
+    KeyNull,
+}
+
 impl<T> Attr<T> {
     pub fn new(span: impl Spanned, value: T) -> Self {
         Attr {
@@ -146,6 +162,10 @@ impl ContainerAttributes {
 
         let mut partition = None;
         let mut merge = None;
+        let mut positional = None;
+        let mut test_layout = None;
+        let mut check_types = None;
+        let mut columns = None;
 
         for item in &items {
             use Meta::Path;
@@ -175,10 +195,53 @@ impl ContainerAttributes {
                         set_or_err!(merge, kind, err_multiple_partition!(item))?;
                     }
                 },
+                "positional" => {
+                    Path(_) => {
+                        set_or_err!(
+                            positional,
+                            Attr::new(item, ()),
+                            err_duplicate_attribute!(item, "positional")
+                        )?;
+                    }
+                },
+                "test_layout" => {
+                    Path(_) => {
+                        set_or_err!(
+                            test_layout,
+                            true,
+                            err_duplicate_attribute!(item, "test_layout")
+                        )?;
+                    }
+                },
+                "check_types" => {
+                    Path(_) => {
+                        set_or_err!(
+                            check_types,
+                            true,
+                            err_duplicate_attribute!(item, "check_types")
+                        )?;
+                    }
+                },
+                "columns" => {
+                    Path(_) => {
+                        set_or_err!(
+                            columns,
+                            Attr::new(item, ()),
+                            err_duplicate_attribute!(item, "columns")
+                        )?;
+                    }
+                },
             })
         }
 
-        let container = ContainerAttributes { partition, merge };
+        let container = ContainerAttributes {
+            partition,
+            merge,
+            positional,
+            test_layout: test_layout.unwrap_or(false),
+            check_types: check_types.unwrap_or(false),
+            columns,
+        };
 
         Ok(container)
     }
@@ -192,10 +255,16 @@ impl FieldAttributes {
 
         let mut flatten = None;
         let mut rename = None;
+        let mut parse = None;
         let mut splits = Vec::new();
         let mut stride = None;
+        let mut repeat = None;
         let mut key = None;
+        let mut by = None;
         let mut merge = None;
+        let mut none_when = None;
+        let mut fallback = None;
+        let mut rename_prefix = None;
 
         for item in &items {
             use Meta::{NameValue, Path};
@@ -212,6 +281,12 @@ impl FieldAttributes {
                         set_or_err!(rename, text, err_duplicate_attribute!(item, "rename"))?;
                     }
                 },
+                "parse" => {
+                    Path(_) => {
+                        let attr = Attr::new(item, ());
+                        set_or_err!(parse, attr, err_duplicate_attribute!(item, "parse"))?
+                    }
+                },
                 "split" => {
                     NameValue(pair) => {
                         let text = lit_string(&pair.lit)?;
@@ -225,28 +300,79 @@ impl FieldAttributes {
                         set_or_err!(stride, step, err_duplicate_attribute!(item, "stride"))?
                     }
                 },
+                "repeat" => {
+                    NameValue(pair) => {
+                        let count = lit_int(&pair.lit)?;
+                        let count = Attr::new(pair, count);
+                        set_or_err!(repeat, count, err_duplicate_attribute!(item, "repeat"))?
+                    }
+                },
                 "key" => {
                     Path(_) => {
                         let attr = Attr::new(item, ());
                         set_or_err!(key, attr, err_duplicate_attribute!(item, "key"))?
                     }
                 },
+                "by" => {
+                    NameValue(pair) => {
+                        let expr = lit_expr(&pair.lit)?;
+                        let attr = Attr::new(&pair.lit, expr);
+                        set_or_err!(by, attr, err_duplicate_attribute!(item, "by"))?
+                    }
+                },
                 "merge" => {
                     Path(_) => {
                         let attr = Attr::new(item, ());
                         set_or_err!(merge, attr, err_duplicate_attribute!(item, "merge"))?
                     }
                 },
+                "none_when" => {
+                    NameValue(pair) => {
+                        let when = match lit_string(&pair.lit)?.as_str() {
+                            "all_null" => NoneWhen::AllNull,
+                            "key_null" => NoneWhen::KeyNull,
+                            _ => return Err(err!(
+                                pair.lit,
+                                "expected either `\"all_null\"` or `\"key_null\"`"
+                            )),
+                        };
+                        let attr = Attr::new(pair, when);
+                        set_or_err!(none_when, attr, err_duplicate_attribute!(item, "none_when"))?
+                    }
+                },
+                "fallback" => {
+                    Path(_) => {
+                        let attr = Attr::new(item, ());
+                        set_or_err!(fallback, attr, err_duplicate_attribute!(item, "fallback"))?
+                    }
+                },
+                "rename_prefix" => {
+                    NameValue(pair) => {
+                        let text = lit_string(&pair.lit)?;
+                        let attr = Attr::new(pair, text);
+                        set_or_err!(
+                            rename_prefix,
+                            attr,
+                            err_duplicate_attribute!(item, "rename_prefix")
+                        )?
+                    }
+                },
             })
         }
 
         let field = FieldAttributes {
             flatten: flatten.unwrap_or(false),
             rename,
+            parse,
             splits,
             stride,
+            repeat,
             key,
+            by,
             merge,
+            none_when,
+            fallback,
+            rename_prefix,
         };
 
         Ok(field)
@@ -288,6 +414,13 @@ fn lit_string(lit: &Lit) -> Result<String> {
     }
 }
 
+fn lit_expr(lit: &Lit) -> Result<Expr> {
+    match lit {
+        Lit::Str(text) => text.parse(),
+        _ => Err(err!(lit, "expected string literal")),
+    }
+}
+
 fn lit_int<N>(lit: &Lit) -> Result<N>
 where
     N: FromStr,
@@ -0,0 +1,399 @@
+use super::partition::{Partitions, Split};
+use super::{column_offsets, Index, Property};
+use proc_macro2::{Span, TokenStream};
+use quote::*;
+use syn::{Ident, Result};
+
+/// The columns each field of a container is extracted from.
+pub(super) enum Layout {
+    Plain(Vec<Property>),
+    Partitioned(Partitions),
+}
+
+/// Generate `FromSqlRow::__column_names`, which lays out a synthetic row that the container could
+/// be extracted from.
+pub(super) fn column_names(layout: &Layout) -> TokenStream {
+    let partitions = partition_names(layout);
+
+    quote! {
+        fn __column_names() -> ::std::vec::Vec<::std::string::String> {
+            let mut __names = ::std::vec::Vec::new();
+            #( __names.extend(#partitions); )*
+            __names
+        }
+    }
+}
+
+/// Generate `FromSqlRow::layout`, which describes the columns each field is extracted from.
+pub(super) fn describe(layout: &Layout) -> TokenStream {
+    let lib = lib!();
+    let extract = quote! { #lib::extract };
+
+    let description = match layout {
+        Layout::Plain(props) => {
+            let fields = field_layouts(props);
+            quote! { #extract::RowLayout::Plain(#fields) }
+        }
+
+        Layout::Partitioned(Partitions::Exact(partitions)) => {
+            let partitions = partitions.iter().map(|partition| {
+                let len = &partition.len;
+                let fields = field_layouts(&partition.properties);
+                quote! { (#len, #fields) }
+            });
+            quote! { #extract::RowLayout::Exact(::std::vec![#(#partitions),*]) }
+        }
+
+        Layout::Partitioned(Partitions::Split(splits)) => {
+            // Every split starts a new partition, and the first partition may be empty.
+            let mut names = Vec::new();
+            let mut groups = vec![Vec::new()];
+            for split in splits {
+                match split {
+                    Split::Column(name) => {
+                        names.push(name.as_str());
+                        groups.push(Vec::new());
+                    }
+                    Split::Group(props) => groups.last_mut().unwrap().extend(props),
+                }
+            }
+
+            let partitions = groups.iter().map(|props| {
+                let fields = field_layouts(props.iter().copied());
+                quote! { #fields }
+            });
+
+            quote! {
+                #extract::RowLayout::Split {
+                    splits: ::std::vec![#(#names),*],
+                    partitions: ::std::vec![#(#partitions),*],
+                }
+            }
+        }
+    };
+
+    quote! {
+        fn layout() -> ::std::option::Option<#extract::RowLayout> {
+            ::std::option::Option::Some(#description)
+        }
+    }
+}
+
+/// The layout of a group of fields, as an expression of type `Vec<FieldLayout>`. Positions are
+/// relative to the group, just like in `field_initializers`.
+fn field_layouts<'a>(props: impl IntoIterator<Item = &'a Property>) -> TokenStream {
+    let lib = lib!();
+    let extract = quote! { #lib::extract };
+
+    let props: Vec<&Property> = props.into_iter().collect();
+    let offsets = column_offsets(props.iter().copied());
+
+    let fields = props.iter().zip(offsets).map(|(prop, offset)| {
+        let name = prop.name();
+
+        let source = match &prop.index {
+            Index::Position => quote! { #extract::FieldSource::Position(#offset) },
+            Index::Name(name) => quote! { #extract::FieldSource::Name(#name) },
+            Index::Flatten => {
+                let ty = &prop.ty;
+                let prefix = match &prop.attrs.rename_prefix {
+                    Some(prefix) => {
+                        let prefix = &prefix.value;
+                        quote! { ::std::option::Option::Some(#prefix) }
+                    }
+                    None => quote! { ::std::option::Option::None },
+                };
+                quote! {
+                    #extract::FieldSource::Flatten {
+                        columns: <#ty as #lib::FromSqlRow>::COLUMN_COUNT,
+                        layout: <#ty as #lib::FromSqlRow>::layout().map(::std::boxed::Box::new),
+                        prefix: #prefix,
+                    }
+                }
+            }
+            Index::Repeat { count, elem, .. } => quote! {
+                #extract::FieldSource::Repeat {
+                    count: #count,
+                    columns: <#elem as #lib::FromSqlRow>::COLUMN_COUNT,
+                    layout: <#elem as #lib::FromSqlRow>::layout().map(::std::boxed::Box::new),
+                }
+            },
+        };
+
+        quote! {
+            #extract::FieldLayout {
+                field: #name,
+                source: #source,
+            }
+        }
+    });
+
+    quote! { ::std::vec![#(#fields),*] }
+}
+
+/// Generate a unit test which checks that the container's layout is consistent with the
+/// `COLUMN_COUNT` of its fields and that every partition covers the expected columns.
+pub(super) fn layout_test(ident: &Ident, layout: &Layout) -> TokenStream {
+    let lib = lib!();
+    let name = ident.to_string();
+    let test = Ident::new(&format!("__row_test_layout_{}", ident), Span::call_site());
+
+    let body = match layout {
+        Layout::Plain(_) => quote! {
+            let __names = <#ident as #lib::FromSqlRow>::__column_names();
+            ::std::assert_eq!(
+                __names.len(),
+                <#ident as #lib::FromSqlRow>::COLUMN_COUNT,
+                "`COLUMN_COUNT` of `{}` does not match its columns: {:?}",
+                #name,
+                __names,
+            );
+        },
+
+        Layout::Partitioned(Partitions::Exact(partitions)) => {
+            let checks = partitions.iter().enumerate().map(|(i, partition)| {
+                let names = group_names(&partition.properties);
+                let len = &partition.len;
+                let check = if partition.strided {
+                    quote! { __names.len() <= __len }
+                } else {
+                    quote! { __names.len() == __len }
+                };
+
+                quote! {
+                    let __names: ::std::vec::Vec<::std::string::String> = #names;
+                    let __len: usize = #len;
+                    ::std::assert!(
+                        #check,
+                        "partition {} of `{}` ({:?}) expects the columns {:?}",
+                        #i,
+                        #name,
+                        __start..__start + __len,
+                        __names,
+                    );
+                    __start += __len;
+                }
+            });
+
+            quote! {
+                let mut __start = 0;
+                #(#checks)*
+            }
+        }
+
+        Layout::Partitioned(Partitions::Split(splits)) => {
+            let partitions = partition_names(layout);
+            let split_columns = splits.iter().filter_map(|split| match split {
+                Split::Column(name) => Some(name.as_str()),
+                Split::Group(_) => None,
+            });
+
+            quote! {
+                let __partitions: ::std::vec::Vec<::std::vec::Vec<::std::string::String>> =
+                    ::std::vec![#(#partitions),*];
+                let __names = __partitions.concat();
+
+                let mut __start = 0;
+                let __expected = __partitions
+                    .iter()
+                    .map(|partition| {
+                        let range = __start..__start + partition.len();
+                        __start = range.end;
+                        range
+                    })
+                    .collect::<::std::vec::Vec<_>>();
+
+                let __splits: &[&'static str] = &[#(#split_columns),*];
+                match #lib::extract::__split_names(&__names, __splits) {
+                    ::std::result::Result::Ok(__ranges) => ::std::assert_eq!(
+                        __ranges,
+                        __expected,
+                        "split points of `{}` do not match its partitions: {:?}",
+                        #name,
+                        __partitions,
+                    ),
+                    ::std::result::Result::Err(__error) => {
+                        ::std::panic!("failed to split `{}`: {}", #name, __error)
+                    }
+                }
+            }
+        }
+    };
+
+    quote! {
+        #[cfg(test)]
+        #[test]
+        #[allow(non_snake_case)]
+        fn #test() {
+            #body
+        }
+    }
+}
+
+/// Generate `StaticColumns` and the `COLUMNS` constant for `#[row(columns)]`, by concatenating
+/// the names of the columns with those of flattened fields at compile-time.
+pub(super) fn static_columns(ident: &Ident, layout: &Layout, span: Span) -> Result<TokenStream> {
+    let lib = lib!();
+    let extract = quote! { #lib::extract };
+
+    let props = match layout {
+        Layout::Plain(props) => props,
+        Layout::Partitioned(_) => {
+            return Err(err!(
+                span,
+                "`columns` cannot be combined with `exact` or `split`"
+            ))
+        }
+    };
+
+    let mut parts = Vec::new();
+    for prop in props {
+        match &prop.index {
+            Index::Position if prop.field.ident.is_none() => {
+                return Err(syn::Error::new_spanned(
+                    &prop.field,
+                    "`columns` requires named fields, since the names of the columns are unknown",
+                ))
+            }
+            Index::Position | Index::Name(_) => {
+                let name = match &prop.index {
+                    Index::Name(name) => name.clone(),
+                    _ => prop.name(),
+                };
+                parts.push(quote! { &[#name] });
+            }
+            Index::Flatten => {
+                let ty = &prop.ty;
+                parts.push(quote! { <#ty as #extract::StaticColumns>::COLUMNS });
+            }
+            Index::Repeat { count, elem, .. } => {
+                let columns = quote! { <#elem as #extract::StaticColumns>::COLUMNS };
+                // `iter::repeat_n` requires Rust 1.82.
+                #[allow(clippy::manual_repeat_n)]
+                parts.extend(std::iter::repeat(columns).take(*count));
+            }
+        }
+    }
+
+    Ok(quote! {
+        #[automatically_derived]
+        #[allow(clippy::all)]
+        impl #extract::StaticColumns for #ident {
+            const COLUMNS: &'static [&'static str] = {
+                const __PARTS: &[&[&str]] = &[#(#parts),*];
+                const __COLUMNS: [&str; #extract::__count_columns(__PARTS)] =
+                    #extract::__concat_columns(__PARTS);
+                &__COLUMNS
+            };
+        }
+
+        #[automatically_derived]
+        #[allow(clippy::all)]
+        impl #ident {
+            /// The names of the columns this type is extracted from, in order.
+            pub const COLUMNS: &'static [&'static str] =
+                <Self as #extract::StaticColumns>::COLUMNS;
+        }
+    })
+}
+
+/// The synthetic columns of each partition, as expressions of type `Vec<String>`.
+fn partition_names(layout: &Layout) -> Vec<TokenStream> {
+    let lib = lib!();
+
+    match layout {
+        Layout::Plain(props) => vec![group_names(props)],
+
+        Layout::Partitioned(Partitions::Exact(partitions)) => partitions
+            .iter()
+            .map(|partition| {
+                let names = group_names(&partition.properties);
+                let len = &partition.len;
+                quote! {{
+                    let mut __names: ::std::vec::Vec<::std::string::String> = #names;
+                    while __names.len() < #len {
+                        __names.push(::std::string::String::from("?"));
+                    }
+                    __names
+                }}
+            })
+            .collect(),
+
+        Layout::Partitioned(Partitions::Split(splits)) => {
+            let start_partition = |names: TokenStream, split: Option<&String>| match split {
+                None => names,
+                Some(split) => quote! {{
+                    let mut __names: ::std::vec::Vec<::std::string::String> = #names;
+                    #lib::extract::__start_partition(&mut __names, #split);
+                    __names
+                }},
+            };
+
+            // The columns before the first split form a partition of their own, even if empty.
+            let mut partitions = Vec::new();
+            let mut current = None;
+            let mut group = Vec::new();
+
+            for split in splits {
+                match split {
+                    Split::Column(name) => {
+                        partitions.push(start_partition(concat(&group), current));
+                        current = Some(name);
+                        group.clear();
+                    }
+                    Split::Group(props) => group.push(group_names(props)),
+                }
+            }
+
+            partitions.push(start_partition(concat(&group), current));
+
+            partitions
+        }
+    }
+}
+
+/// The columns read by a group of fields, as an expression of type `Vec<String>`.
+fn group_names(props: &[Property]) -> TokenStream {
+    let lib = lib!();
+
+    let names = props.iter().map(|prop| match &prop.index {
+        Index::Position => quote! { ::std::vec![::std::string::String::from("?")] },
+        Index::Name(name) => quote! { ::std::vec![::std::string::String::from(#name)] },
+        Index::Flatten => {
+            let ty = &prop.ty;
+            match &prop.attrs.rename_prefix {
+                None => quote! { <#ty as #lib::FromSqlRow>::__column_names() },
+                Some(prefix) => {
+                    let prefix = &prefix.value;
+                    quote! {
+                        <#ty as #lib::FromSqlRow>::__column_names()
+                            .into_iter()
+                            .map(|name| match name.as_str() {
+                                "?" => name,
+                                _ => format!("{}{}", #prefix, name),
+                            })
+                            .collect::<::std::vec::Vec<_>>()
+                    }
+                }
+            }
+        }
+        Index::Repeat { count, elem, .. } => {
+            quote! {{
+                let names = <#elem as #lib::FromSqlRow>::__column_names();
+                (0..#count).flat_map(|_| names.clone()).collect::<::std::vec::Vec<_>>()
+            }}
+        }
+    });
+
+    concat(names)
+}
+
+/// Concatenate expressions of type `Vec<String>`.
+fn concat(names: impl IntoIterator<Item = impl ToTokens>) -> TokenStream {
+    let names = names.into_iter();
+    quote! {{
+        let __names: ::std::vec::Vec<::std::vec::Vec<::std::string::String>> =
+            ::std::vec![#(#names),*];
+        __names.concat()
+    }}
+}
@@ -1,38 +1,38 @@
 use super::attrs::Attr;
-use super::{field_initializers, Index, Local, PartitionKind, Property};
+use super::{FieldsFn, Index, Local, PartitionKind, Property};
 use proc_macro2::{Span, TokenStream};
 use quote::*;
 use std::mem;
 use syn::{Ident, Result};
 
-struct ExactPartition {
-    len: TokenStream,
-    properties: Vec<Property>,
+pub(super) enum Partitions {
+    Exact(Vec<ExactPartition>),
+    Split(Vec<Split>),
 }
 
-enum Split {
+pub(super) struct ExactPartition {
+    pub len: TokenStream,
+    pub strided: bool,
+    pub properties: Vec<Property>,
+}
+
+pub(super) enum Split {
     Column(String),
     Group(Vec<Property>),
 }
 
-pub(super) fn partition_initializers(
-    props: Vec<Property>,
-    kind: Attr<PartitionKind>,
-) -> Result<(TokenStream, Vec<Local>)> {
+pub(super) fn partition(props: Vec<Property>, kind: Attr<PartitionKind>) -> Result<Partitions> {
     match kind.value {
         PartitionKind::Exact => {
             let partitions = exact::partition(props)?;
-            Ok(exact::initializers(partitions))
+            Ok(Partitions::Exact(partitions))
         }
         PartitionKind::Split => {
             let splits = split::partition(props);
 
             let split_count = splits
                 .iter()
-                .filter(|split| match split {
-                    Split::Column(_) => true,
-                    _ => false,
-                })
+                .filter(|split| is_match!(split, Split::Column(_)))
                 .count();
 
             if split_count == 0 {
@@ -42,11 +42,23 @@ pub(super) fn partition_initializers(
                 ));
             }
 
-            Ok(split::initializers(splits))
+            Ok(Partitions::Split(splits))
         }
     }
 }
 
+/// Slice the row into its partitions, and generate the code for the fields of each partition with
+/// `fields`.
+pub(super) fn partition_initializers(
+    partitions: &Partitions,
+    fields: FieldsFn,
+) -> (TokenStream, Vec<Local>) {
+    match partitions {
+        Partitions::Exact(partitions) => exact::initializers(partitions, fields),
+        Partitions::Split(splits) => split::initializers(splits, fields),
+    }
+}
+
 mod exact {
     use super::*;
 
@@ -65,6 +77,7 @@ mod exact {
                     let stride = prop.attrs.stride.unwrap().value;
                     partitions.push(ExactPartition {
                         len: quote! { #stride },
+                        strided: true,
                         properties: vec![prop],
                     });
                 }
@@ -83,10 +96,27 @@ mod exact {
                     let len = properties.len();
                     partitions.push(ExactPartition {
                         len: quote! { #len },
+                        strided: false,
                         properties,
                     });
                 }
 
+                prop if is_match!(prop.index, Index::Repeat { .. }) => {
+                    let (count, elem) = match &prop.index {
+                        Index::Repeat { count, elem, .. } => (count, elem),
+                        _ => unreachable!(),
+                    };
+                    let lib = lib!();
+                    let len = quote! {
+                        #count * <#elem as #lib::FromSqlRow>::COLUMN_COUNT
+                    };
+                    partitions.push(ExactPartition {
+                        len,
+                        strided: false,
+                        properties: vec![prop],
+                    });
+                }
+
                 prop if is_match!(prop.index, Index::Flatten) => {
                     let ty = &prop.ty;
                     let lib = lib!();
@@ -95,18 +125,27 @@ mod exact {
                     };
                     partitions.push(ExactPartition {
                         len,
+                        strided: false,
                         properties: vec![prop],
                     });
                 }
 
-                _ => return Err(err!(prop.span, "failed to compute `stride` for field")),
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        &prop.field,
+                        "failed to compute `stride` for field",
+                    ))
+                }
             }
         }
 
         Ok(partitions)
     }
 
-    pub(super) fn initializers(partitions: Vec<ExactPartition>) -> (TokenStream, Vec<Local>) {
+    pub(super) fn initializers(
+        partitions: &[ExactPartition],
+        fields: FieldsFn,
+    ) -> (TokenStream, Vec<Local>) {
         let mut getters = Vec::new();
         let mut locals = Vec::new();
 
@@ -114,21 +153,35 @@ mod exact {
 
         getters.push(quote! { let #previous_end = 0; });
 
-        for (i, partition) in partitions.into_iter().enumerate() {
+        for (i, partition) in partitions.iter().enumerate() {
             let end = Ident::new(&format!("__end_{}", i), Span::call_site());
             let current = Ident::new(&format!("__slice_{}", i), Span::call_site());
-            let len = partition.len;
+            let len = &partition.len;
 
             let lib = lib!();
+            let slice = if has_fallback(&partition.properties) {
+                // The columns of a fallback may be missing, leaving its partition empty.
+                quote! {
+                    match #lib::extract::Row::slice(__row, #previous_end..#end) {
+                        ::std::result::Result::Ok(slice) => slice,
+                        ::std::result::Result::Err(_) => {
+                            let __len = #lib::extract::Row::len(__row);
+                            #lib::extract::Row::slice(__row, __len..__len)?
+                        }
+                    }
+                }
+            } else {
+                quote! { #lib::extract::Row::slice(__row, #previous_end..#end)? }
+            };
             let advance = quote! {
                 let #end = #previous_end + #len;
-                let #current = #lib::extract::Row::slice(__row, #previous_end..#end)?;
+                let #current = #slice;
                 let #current = &#current;
             };
 
             previous_end = end;
 
-            let (initializers, idents) = field_initializers(&partition.properties, &current);
+            let (initializers, idents) = fields(&partition.properties, &current);
 
             locals.extend(idents);
 
@@ -177,7 +230,7 @@ mod split {
         splits
     }
 
-    pub(super) fn initializers(layout: Vec<Split>) -> (TokenStream, Vec<Local>) {
+    pub(super) fn initializers(layout: &[Split], fields: FieldsFn) -> (TokenStream, Vec<Local>) {
         let mut fragments = Vec::new();
         let mut locals = Vec::new();
 
@@ -192,10 +245,27 @@ mod split {
         let lib = lib!();
         let row_trait = quote! { #lib::extract::Row };
 
+        // The splits leading up to a fallback may be missing, along with its columns.
+        let optional = match layout.last() {
+            Some(Split::Group(props)) if has_fallback(props) => layout
+                .iter()
+                .rev()
+                .skip(1)
+                .take_while(|split| is_match!(split, Split::Column(_)))
+                .count(),
+            _ => 0,
+        };
+
+        let split_columns = if optional == 0 {
+            quote! { #lib::extract::split_columns_many(columns, &splits) }
+        } else {
+            quote! { #lib::extract::__split_columns_fallback(columns, &splits, #optional) }
+        };
+
         fragments.push(quote! {
             let columns = #row_trait::columns(__row);
             let splits: &[&'static str] = &[#(#splits),*];
-            let mut splits = #lib::extract::split_columns_many(columns, &splits);
+            let mut splits = #split_columns;
         });
 
         let next_partition = quote! {
@@ -222,7 +292,7 @@ mod split {
                     fragments.push(advance(&partition));
                 }
                 Split::Group(props) => {
-                    let (initializers, idents) = field_initializers(&props, &partition);
+                    let (initializers, idents) = fields(props, &partition);
                     fragments.push(initializers);
                     locals.extend(idents);
                 }
@@ -236,3 +306,7 @@ mod split {
         (getters, locals)
     }
 }
+
+fn has_fallback(props: &[Property]) -> bool {
+    props.iter().any(|prop| prop.attrs.fallback.is_some())
+}
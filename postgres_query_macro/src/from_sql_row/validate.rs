@@ -1,8 +1,9 @@
-use super::{ContainerAttributes, PartitionKind, Property};
-use syn::Result;
+use super::{ContainerAttributes, Index, MergeKind, PartitionKind, Property};
+use syn::{Fields, Result};
 
 pub(super) fn validate_properties(
     container: &ContainerAttributes,
+    fields: &Fields,
     props: &[Property],
 ) -> Result<()> {
     check_split_in_non_split_container(container, props)?;
@@ -10,6 +11,13 @@ pub(super) fn validate_properties(
 
     check_merging_container_attributes(container, props)?;
     check_not_key_and_merge(props)?;
+    check_none_when_flatten(props)?;
+    check_key_by(container, props)?;
+    check_parse_not_flatten(props)?;
+    check_positional(container, fields, props)?;
+    check_repeat(container, props)?;
+    check_fallback(props)?;
+    check_rename_prefix(container, props)?;
 
     Ok(())
 }
@@ -110,12 +118,156 @@ fn check_merging_container_attributes(
 fn check_not_key_and_merge(props: &[Property]) -> Result<()> {
     props
         .iter()
-        .map(|prop| match (prop.attrs.key, prop.attrs.merge) {
+        .try_for_each(|prop| match (prop.attrs.key, prop.attrs.merge) {
             (Some(key), Some(merge)) => Err(err!(
                 key.span.join(merge.span).unwrap_or(key.span),
                 "You cannot specify both `#[row(key)]` and `#[row(merge)]` on the same field"
             )),
             _ => Ok(()),
         })
-        .collect()
+}
+
+fn check_none_when_flatten(props: &[Property]) -> Result<()> {
+    props
+        .iter()
+        .try_for_each(|prop| match prop.attrs.none_when {
+            Some(none_when)
+                if !is_match!(prop.index, Index::Flatten) || prop.attrs.merge.is_some() =>
+            {
+                Err(err!(
+                    none_when.span,
+                    "`none_when` is only available on fields with the `#[row(flatten)]` attribute"
+                ))
+            }
+            _ => Ok(()),
+        })
+}
+
+fn check_key_by(container: &ContainerAttributes, props: &[Property]) -> Result<()> {
+    let is_hash = is_match!(
+        container.merge.as_ref().map(|attr| &attr.value),
+        Some(MergeKind::Hash)
+    );
+
+    props.iter().try_for_each(|prop| match &prop.attrs.by {
+        Some(by) if prop.attrs.key.is_none() => Err(err!(
+            by.span,
+            "`by` is only available on fields with the `#[row(key)]` attribute"
+        )),
+        Some(by) if !is_hash => Err(err!(
+            by.span,
+            "`by` is only available in containers with the `#[row(hash)]` attribute"
+        )),
+        _ => Ok(()),
+    })
+}
+
+fn check_parse_not_flatten(props: &[Property]) -> Result<()> {
+    props.iter().try_for_each(|prop| match prop.attrs.parse {
+        Some(parse) if is_match!(prop.index, Index::Flatten) => Err(err!(
+            parse.span,
+            "`parse` is not available on fields with the `#[row(flatten)]` or `#[row(merge)]` attributes"
+        )),
+        _ => Ok(()),
+    })
+}
+
+fn check_positional(
+    container: &ContainerAttributes,
+    fields: &Fields,
+    props: &[Property],
+) -> Result<()> {
+    let positional = match container.positional {
+        Some(positional) => positional,
+        None => return Ok(()),
+    };
+
+    if !is_match!(fields, Fields::Named(_)) {
+        return Err(err!(
+            positional.span,
+            "`positional` is only available on structs with named fields"
+        ));
+    }
+
+    props.iter().try_for_each(|prop| match &prop.attrs.rename {
+        Some(_) => Err(syn::Error::new_spanned(
+            &prop.field,
+            "`rename` has no effect in containers with the `#[row(positional)]` attribute",
+        )),
+        None => Ok(()),
+    })
+}
+
+fn check_repeat(container: &ContainerAttributes, props: &[Property]) -> Result<()> {
+    let is_exact = is_match!(
+        container.partition.as_ref().map(|attr| &attr.value),
+        Some(PartitionKind::Exact)
+    );
+
+    props.iter().try_for_each(|prop| match prop.attrs.repeat {
+        Some(repeat) if !is_exact => Err(err!(
+            repeat.span,
+            "`repeat` is only available in containers with the `#[row(exact)]` attribute"
+        )),
+        Some(repeat) if prop.attrs.flatten || prop.attrs.merge.is_some() => Err(err!(
+            repeat.span,
+            "`repeat` is not available on fields with the `#[row(flatten)]` or `#[row(merge)]` attributes"
+        )),
+        Some(repeat) if prop.attrs.stride.is_some() => Err(err!(
+            repeat.span,
+            "`repeat` and `stride` cannot be used on the same field"
+        )),
+        Some(repeat) if prop.attrs.parse.is_some() => Err(err!(
+            repeat.span,
+            "`repeat` and `parse` cannot be used on the same field"
+        )),
+        _ => Ok(()),
+    })
+}
+
+fn check_fallback(props: &[Property]) -> Result<()> {
+    let last = props.len().saturating_sub(1);
+
+    props
+        .iter()
+        .enumerate()
+        .try_for_each(|(i, prop)| match prop.attrs.fallback {
+            Some(fallback)
+                if !is_match!(prop.index, Index::Flatten) || prop.attrs.merge.is_some() =>
+            {
+                Err(err!(
+                    fallback.span,
+                    "`fallback` is only available on fields with the `#[row(flatten)]` attribute"
+                ))
+            }
+            Some(fallback) if prop.attrs.none_when.is_some() => Err(err!(
+                fallback.span,
+                "`fallback` and `none_when` cannot be used on the same field"
+            )),
+            Some(fallback) if i != last => Err(err!(
+                fallback.span,
+                "`fallback` is only available on the last field"
+            )),
+            _ => Ok(()),
+        })
+}
+
+fn check_rename_prefix(container: &ContainerAttributes, props: &[Property]) -> Result<()> {
+    props
+        .iter()
+        .try_for_each(|prop| match &prop.attrs.rename_prefix {
+            Some(prefix)
+                if !is_match!(prop.index, Index::Flatten) || prop.attrs.merge.is_some() =>
+            {
+                Err(err!(
+                prefix.span,
+                "`rename_prefix` is only available on fields with the `#[row(flatten)]` attribute"
+            ))
+            }
+            Some(prefix) if container.columns.is_some() => Err(err!(
+                prefix.span,
+                "`rename_prefix` cannot be combined with `#[row(columns)]`"
+            )),
+            _ => Ok(()),
+        })
 }
@@ -3,8 +3,13 @@ extern crate proc_macro;
 #[macro_use]
 mod macros;
 
+mod delegate;
 mod from_sql_row;
+mod merge;
+mod patch;
 mod query;
+mod to_sql_row;
+mod tokens;
 
 use proc_macro::TokenStream;
 use proc_macro_hack::proc_macro_hack;
@@ -16,7 +21,12 @@ pub fn query_static(input: TokenStream) -> TokenStream {
 
     let output = match query.convert_to_struct_static() {
         Ok(output) => output,
-        Err(e) => e.to_compile_error(),
+        // An error may consist of several `compile_error!`s, which must be wrapped in a block to
+        // form a single expression.
+        Err(e) => {
+            let errors = e.to_compile_error();
+            quote::quote! {{ #errors }}
+        }
     };
 
     TokenStream::from(output)
@@ -34,9 +44,52 @@ pub fn query_dynamic(input: TokenStream) -> TokenStream {
     TokenStream::from(output)
 }
 
+#[proc_macro_hack]
+pub fn query_with(input: TokenStream) -> TokenStream {
+    let query = parse_macro_input!(input as query::QueryWithInput);
+
+    let output = match query.convert_to_struct() {
+        Ok(output) => output,
+        Err(e) => {
+            let errors = e.to_compile_error();
+            quote::quote! {{ #errors }}
+        }
+    };
+
+    TokenStream::from(output)
+}
+
 #[proc_macro_derive(FromSqlRow, attributes(row))]
 pub fn from_sql_row(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let output = from_sql_row::derive(input);
     TokenStream::from(output)
 }
+
+#[proc_macro_derive(Merge)]
+pub fn merge(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let output = merge::derive(input);
+    TokenStream::from(output)
+}
+
+#[proc_macro_derive(Patch, attributes(patch))]
+pub fn patch(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let output = patch::derive(input);
+    TokenStream::from(output)
+}
+
+#[proc_macro_derive(ToSqlRow, attributes(sql_row))]
+pub fn to_sql_row(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let output = to_sql_row::derive(input);
+    TokenStream::from(output)
+}
+
+#[proc_macro_derive(ToSqlDelegate)]
+pub fn to_sql_delegate(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let output = delegate::derive(input);
+    TokenStream::from(output)
+}
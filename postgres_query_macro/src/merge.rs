@@ -0,0 +1,47 @@
+use crate::delegate::single_field;
+use proc_macro2::TokenStream;
+use quote::*;
+use syn::{parse_quote, DeriveInput, Result};
+
+pub fn derive(input: DeriveInput) -> TokenStream {
+    match make_impl(&input) {
+        Ok(output) => output,
+        Err(e) => e.to_compile_error(),
+    }
+}
+
+fn make_impl(input: &DeriveInput) -> Result<TokenStream> {
+    let ident = &input.ident;
+
+    let (field, member) = single_field(input, "Merge")?;
+    let inner = &field.ty;
+
+    let lib = lib!();
+    let merge = quote! { #lib::extract::Merge };
+
+    let mut generics = input.generics.clone();
+    generics
+        .make_where_clause()
+        .predicates
+        .push(parse_quote! { #inner: #merge });
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        #[automatically_derived]
+        #[allow(clippy::all)]
+        impl #impl_generics #merge for #ident #ty_generics #where_clause {
+            type Item = <#inner as #merge>::Item;
+
+            fn insert(&mut self, item: Self::Item) {
+                <#inner as #merge>::insert(&mut self.#member, item)
+            }
+
+            fn try_insert(
+                &mut self,
+                item: Self::Item,
+            ) -> ::std::result::Result<(), #lib::extract::Error> {
+                <#inner as #merge>::try_insert(&mut self.#member, item)
+            }
+        }
+    })
+}
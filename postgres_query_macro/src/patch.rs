@@ -0,0 +1,115 @@
+use proc_macro2::TokenStream;
+use quote::*;
+use syn::{
+    Attribute, Data, DeriveInput, Field, Fields, Lit, Meta, NestedMeta, PathArguments, Result, Type,
+};
+
+pub fn derive(input: DeriveInput) -> TokenStream {
+    match make_impl(&input) {
+        Ok(output) => output,
+        Err(e) => e.to_compile_error(),
+    }
+}
+
+fn make_impl(input: &DeriveInput) -> Result<TokenStream> {
+    let ident = &input.ident;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            fields => {
+                return Err(syn::Error::new_spanned(
+                    fields,
+                    "`Patch` may only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => return Err(err!(ident, "`Patch` may only be derived for structs")),
+    };
+
+    let mut assignments = Vec::new();
+    for field in fields {
+        let attrs = FieldAttributes::from_attrs(&field.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+
+        let field_ident = field.ident.as_ref().unwrap();
+        let column = attrs.rename.unwrap_or_else(|| field_ident.to_string());
+
+        let assignment = if is_option(field) {
+            quote! {
+                if let ::std::option::Option::Some(__value) = &self.#field_ident {
+                    __assignments.push((#column, __value as _));
+                }
+            }
+        } else {
+            quote! {
+                __assignments.push((#column, &self.#field_ident as _));
+            }
+        };
+
+        assignments.push(assignment);
+    }
+
+    let lib = lib!();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        #[automatically_derived]
+        #[allow(clippy::all)]
+        impl #impl_generics #lib::patch::Patch for #ident #ty_generics #where_clause {
+            fn assignments(&self) -> ::std::vec::Vec<(&'static str, #lib::Parameter<'_>)> {
+                let mut __assignments =
+                    ::std::vec::Vec::<(&'static str, #lib::Parameter<'_>)>::new();
+                #(#assignments)*
+                __assignments
+            }
+        }
+    })
+}
+
+/// Fields of type `Option<T>` are left unchanged when they are `None`.
+fn is_option(field: &Field) -> bool {
+    match &field.ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last().is_some_and(|last| {
+            last.ident == "Option" && is_match!(last.arguments, PathArguments::AngleBracketed(_))
+        }),
+        _ => false,
+    }
+}
+
+#[derive(Default)]
+struct FieldAttributes {
+    rename: Option<String>,
+    skip: bool,
+}
+
+impl FieldAttributes {
+    fn from_attrs(attrs: &[Attribute]) -> Result<FieldAttributes> {
+        let mut field = FieldAttributes::default();
+
+        for attr in attrs.iter().filter(|attr| attr.path.is_ident("patch")) {
+            let list = match attr.parse_meta()? {
+                Meta::List(list) => list,
+                _ => return Err(err!(attr, "expected list: #[patch(...)]")),
+            };
+
+            for inner in list.nested {
+                match inner {
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                        field.skip = true;
+                    }
+                    NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("rename") => {
+                        match pair.lit {
+                            Lit::Str(text) => field.rename = Some(text.value()),
+                            lit => return Err(err!(lit, "expected string literal")),
+                        }
+                    }
+                    inner => return Err(err!(inner, "unknown attribute")),
+                }
+            }
+        }
+
+        Ok(field)
+    }
+}
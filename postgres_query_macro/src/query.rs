@@ -1,18 +1,32 @@
-use proc_macro2::TokenStream;
+use crate::tokens::{self, Token};
+use proc_macro2::{Span, TokenStream};
 use quote::*;
 use std::fmt::Write;
+use std::iter;
+use std::ops::Range;
+use syn::ext::IdentExt;
 use syn::parse::{Parse, ParseStream};
 use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
 use syn::{
-    Expr, ExprAssign, ExprLit, ExprPath, ExprRange, ExprReference, Ident, Lit, LitStr, Path,
-    PathArguments, RangeLimits, Result, Token,
+    Expr, ExprAssign, ExprGroup, ExprLit, ExprMacro, ExprPath, ExprRange, ExprReference, Ident,
+    Lit, LitStr, Member, Path, PathArguments, RangeLimits, Result, Token,
 };
 
 pub struct QueryInput {
     text: Expr,
+    /// String literals directly following the text, which are concatenated with it.
+    adjacent: Vec<LitStr>,
     arguments: Vec<Argument>,
 }
 
+/// The input of `query_with!`: a query whose parameters are all fields of a single struct.
+pub struct QueryWithInput {
+    text: Expr,
+    adjacent: Vec<LitStr>,
+    params: Expr,
+}
+
 enum Argument {
     Single { ident: Ident, value: Expr },
     Dynamic { value: Expr },
@@ -20,52 +34,131 @@ enum Argument {
 
 impl Parse for QueryInput {
     fn parse(input: ParseStream) -> Result<Self> {
-        let mut parameters = Punctuated::<Expr, Token![,]>::parse_terminated(input)?.into_iter();
+        if input.is_empty() {
+            return Err(input.error("argument missing: expected SQL query"));
+        }
+
+        let text = input.parse::<Expr>()?;
+
+        let mut adjacent = Vec::new();
+        while input.peek(LitStr) {
+            adjacent.push(input.parse::<LitStr>()?);
+        }
+
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+
+        let arguments = Punctuated::<Expr, Token![,]>::parse_terminated(input)?
+            .into_iter()
+            .map(expr_to_argument)
+            .collect::<Result<_>>()?;
+
+        Ok(QueryInput {
+            text,
+            adjacent,
+            arguments,
+        })
+    }
+}
+
+impl Parse for QueryWithInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.is_empty() {
+            return Err(input.error("argument missing: expected SQL query"));
+        }
 
-        let text = parameters
-            .next()
-            .ok_or_else(|| input.error("argument missing: expected SQL query"))?;
+        let text = input.parse::<Expr>()?;
 
-        let arguments: Vec<_> = parameters.map(expr_to_argument).collect::<Result<_>>()?;
+        let mut adjacent = Vec::new();
+        while input.peek(LitStr) {
+            adjacent.push(input.parse::<LitStr>()?);
+        }
+
+        if input.is_empty() {
+            return Err(input.error("argument missing: expected a reference to the parameters"));
+        }
+        input.parse::<Token![,]>()?;
+        let params = input.parse::<Expr>()?;
+
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+        if !input.is_empty() {
+            return Err(input.error(
+                "unexpected argument: all parameters are taken from the fields of the struct",
+            ));
+        }
+
+        Ok(QueryWithInput {
+            text,
+            adjacent,
+            params,
+        })
+    }
+}
+
+impl QueryWithInput {
+    pub fn convert_to_struct(self) -> Result<TokenStream> {
+        let text = static_text(&self.text, &self.adjacent)?;
+
+        // Every placeholder is bound to the field of the same name, which fails to compile if
+        // there is no such field.
+        let mut arguments = Vec::<(Ident, Expr)>::new();
+        for name in placeholder_names(&text.value()) {
+            if arguments.iter().all(|(ident, _)| ident.unraw() != name) {
+                let ident = field_ident(&name, &text)?;
+                let value = syn::parse_quote! { __params.#ident };
+                arguments.push((ident, value));
+            }
+        }
 
-        Ok(QueryInput { text, arguments })
+        let params = self.params;
+        let (sql, parameters, names) = parameter_substitution(text, arguments)?;
+        let indices = 1..=names.len();
+
+        let lib = lib!();
+        Ok(quote! {
+            {
+                use #lib::AsParameter as _;
+                let __params = #params;
+                static __SQL: #lib::__Interned = #lib::__Interned::new(#sql);
+                #lib::Query::new_static(__SQL.get(), vec![#((#parameters).as_parameter()),*])
+                    .__with_bindings(&[#((#names, #indices)),*])
+            }
+        })
     }
 }
 
 impl QueryInput {
     pub fn convert_to_struct_static(self) -> Result<TokenStream> {
-        match self.text {
-            Expr::Lit(ExprLit {
-                lit: Lit::Str(text),
-                ..
-            }) => {
-                let arguments = self
-                    .arguments
-                    .into_iter()
-                    .map(|argument| match argument {
-                        Argument::Single { ident, value } => Ok((ident, value)),
-                        Argument::Dynamic { value } => Err(err!(
-                            value,
-                            "found dynamic binding (`..<expr>`) in static context, \
-                             use `query_dyn!` if working with dynamic parameters"
-                        )),
-                    })
-                    .collect::<Result<Vec<_>>>()?;
+        let text = static_text(&self.text, &self.adjacent)?;
+
+        let arguments = self
+            .arguments
+            .into_iter()
+            .map(|argument| match argument {
+                Argument::Single { ident, value } => Ok((ident, value)),
+                Argument::Dynamic { value } => Err(err!(
+                    value,
+                    "found dynamic binding (`..<expr>`) in static context, \
+                     use `query_dyn!` if working with dynamic parameters"
+                )),
+            })
+            .collect::<Result<Vec<_>>>()?;
 
-                let (sql, parameters) = parameter_substitution(text, arguments)?;
+        let (sql, parameters, names) = parameter_substitution(text, arguments)?;
+        let indices = 1..=names.len();
 
-                let lib = lib!();
-                Ok(quote! {
-                    #lib::Query::new_static(#sql, vec![#(&#parameters),*])
-                })
+        let lib = lib!();
+        Ok(quote! {
+            {
+                use #lib::AsParameter as _;
+                static __SQL: #lib::__Interned = #lib::__Interned::new(#sql);
+                #lib::Query::new_static(__SQL.get(), vec![#((#parameters).as_parameter()),*])
+                    .__with_bindings(&[#((#names, #indices)),*])
             }
-
-            _ => Err(err!(
-                self.text,
-                "expected a string literal, \
-                 use `query_dyn!` if working with dynamically generated strings"
-            )),
-        }
+        })
     }
 
     pub fn convert_to_struct_dynamic(self) -> Result<TokenStream> {
@@ -77,7 +170,7 @@ impl QueryInput {
                 Argument::Single { ident, value } => {
                     let name = ident.to_string();
                     simple.push(quote! {
-                        (#name, &#value)
+                        (#name, (#value).as_parameter())
                     });
                 }
                 Argument::Dynamic { value } => {
@@ -86,16 +179,28 @@ impl QueryInput {
             }
         }
 
-        let text = self.text;
+        let text = if self.adjacent.is_empty() {
+            self.text.into_token_stream()
+        } else {
+            let first = self.text;
+            let rest = self.adjacent;
+            quote! { concat!(#first, #(#rest),*) }
+        };
 
         let lib = lib!();
         let result = if dynamic.is_empty() {
             quote! {
-                #lib::Query::parse(#text, &[#(#simple),*])
+                {
+                    use #lib::AsParameter as _;
+                    use #lib::__text::{ViaAsRef as _, ViaDisplay as _};
+                    (&#lib::__text::Text(&#text)).__parse_query(&[#(#simple),*])
+                }
             }
         } else {
             quote! {
                 {
+                    use #lib::AsParameter as _;
+                    use #lib::__text::{ViaAsRef as _, ViaDisplay as _};
                     let mut parameters = Vec::<(&str, #lib::Parameter)>::with_capacity(16);
                     parameters.extend_from_slice(&[#(#simple),*]);
 
@@ -103,7 +208,7 @@ impl QueryInput {
                         parameters.extend(#dynamic);
                     )*
 
-                    #lib::Query::parse(#text, &parameters)
+                    (&#lib::__text::Text(&#text)).__parse_query(&parameters)
                 }
             }
         };
@@ -112,84 +217,328 @@ impl QueryInput {
     }
 }
 
+/// The text of a query known at compile-time, joined with any adjacent literals.
+fn static_text(text: &Expr, adjacent: &[LitStr]) -> Result<LitStr> {
+    let mut joined = String::new();
+    concat_literals(text, &mut joined)?;
+    for literal in adjacent {
+        joined.push_str(&literal.value());
+    }
+
+    // Keep the original literal if possible, so that errors may point inside it.
+    match text {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(literal),
+            ..
+        }) if adjacent.is_empty() => Ok(literal.clone()),
+        _ => Ok(LitStr::new(&joined, text.span())),
+    }
+}
+
+/// The names of the placeholders in a query, in order of appearance (possibly repeated).
+///
+/// Malformed placeholders are skipped, and left for `parameter_substitution` to report.
+fn placeholder_names(text: &str) -> Vec<String> {
+    tokens::named_tokens(text)
+        .filter_map(|(_, token)| match token {
+            Token::Binding(binding) => Some(binding[1..].to_owned()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The identifier of the field a placeholder of `query_with!` refers to. Keywords are written as
+/// raw identifiers (`$type` is the field `r#type`), except those which cannot be.
+fn field_ident(name: &str, text: &LitStr) -> Result<Ident> {
+    if syn::parse_str::<Ident>(name).is_ok() {
+        return Ok(Ident::new(name, text.span()));
+    }
+
+    match name {
+        "_" | "self" | "Self" | "super" | "crate" => Err(err!(
+            text,
+            "`${}` cannot refer to a field, since `{}` is not a valid field name",
+            name,
+            name,
+        )),
+        _ => Ok(Ident::new_raw(name, text.span())),
+    }
+}
+
+/// Append the value of a string literal, or of `concat!` applied to string literals, to `text`.
+fn concat_literals(expr: &Expr, text: &mut String) -> Result<()> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(literal),
+            ..
+        }) => {
+            text.push_str(&literal.value());
+            Ok(())
+        }
+
+        // A literal passed through `macro_rules!` as a `$sql:literal` or `$sql:expr`.
+        Expr::Group(ExprGroup { expr, .. }) => concat_literals(expr, text),
+
+        Expr::Macro(ExprMacro { mac, .. }) if mac.path.is_ident("concat") => {
+            let parts = mac.parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated)?;
+            for part in &parts {
+                concat_literals(part, text)?;
+            }
+            Ok(())
+        }
+
+        _ => Err(err!(
+            expr,
+            "expected a string literal or `concat!` of string literals, \
+             use `query_dyn!` if working with dynamically generated strings"
+        )),
+    }
+}
+
 fn parameter_substitution(
     literal: LitStr,
     bindings: Vec<(Ident, Expr)>,
-) -> Result<(String, Vec<Expr>)> {
+) -> Result<(String, Vec<TokenStream>, Vec<String>)> {
     let text = literal.value();
 
     let mut sql = String::with_capacity(text.len());
     let mut parameters = Vec::with_capacity(bindings.len());
-    let mut param_indices = vec![None; bindings.len()];
+    let mut names = Vec::with_capacity(bindings.len());
+    let mut param_indices = Vec::<(usize, Vec<String>, usize)>::with_capacity(bindings.len());
+    let mut used = vec![false; bindings.len()];
 
-    let mut chars = text.chars().enumerate().peekable();
+    // Literals and comments use character indices, while tokens use byte offsets.
+    let char_index = |byte: usize| text[..byte].chars().count();
 
-    let context = |i: usize| {
-        let start = i.saturating_sub(16);
+    let context = |byte: usize| {
+        let start = char_index(byte).saturating_sub(16);
         text.chars().skip(start).take(32).collect::<String>()
     };
 
-    while let Some((index, ch)) = chars.next() {
-        if ch != '$' {
-            sql.push(ch);
-        } else if let Some((_, '$')) = chars.peek() {
-            let (_, dollar) = chars.next().unwrap();
-            sql.push(dollar);
-        } else {
-            let mut name = String::new();
+    let is_ident_char = |ch: char| ch.is_ascii_alphanumeric() || ch == '_';
 
-            while let Some(&(_, ch)) = chars.peek() {
-                if ch.is_ascii_alphanumeric() || ch == '_' {
-                    let (_, ch) = chars.next().unwrap();
-                    name.push(ch);
-                } else {
-                    break;
-                }
-            }
+    // Tokens are split off one at a time, since a placeholder may extend past its token with field
+    // accesses.
+    let mut position = 0;
+    while let Some((_, token)) = tokens::named_tokens(&text[position..]).next() {
+        let start = position;
+        position += token.text().len();
 
-            if name.is_empty() {
+        let name = match token {
+            Token::Binding(binding) => &binding[1..],
+            Token::Escaped(_) => {
+                sql.push('$');
+                continue;
+            }
+            Token::Quoted(quoted) | Token::Comment(quoted) => {
+                sql.push_str(&tokens::unescape(quoted));
+                continue;
+            }
+            Token::Other("$") => {
                 return Err(err!(
                     literal,
                     "expected an identifier, found {:?}. Dollar signs may be escaped: `$$`. \
                      Here: `{}`",
-                    chars.peek().map(|(_, ch)| *ch).unwrap_or('\0'),
-                    context(index),
+                    text[position..].chars().next().unwrap_or('\0'),
+                    context(start),
+                ));
+            }
+            // Positional placeholders would clash with the indices assigned to the bindings.
+            Token::Parameter(parameter) => {
+                let span =
+                    literal_subspan(&literal, &text, char_index(start), char_index(position));
+                return Err(syn::Error::new(
+                    span,
+                    format!(
+                        "positional placeholders (`{}`) are not supported, since the bindings \
+                         are numbered automatically: bind the value to a name and use `$name` \
+                         instead. Here: `{}`",
+                        parameter,
+                        context(start),
+                    ),
                 ));
             }
+            token => {
+                sql.push_str(token.text());
+                continue;
+            }
+        };
+
+        // Field accesses: `$point.0`, `$user.name`, etc.
+        let mut fields = Vec::new();
+        while let Some(field) = text[position..].strip_prefix('.') {
+            let len = field
+                .find(|ch: char| !is_ident_char(ch))
+                .unwrap_or(field.len());
+            if len == 0 {
+                break;
+            }
+            fields.push(field[..len].to_owned());
+            position += 1 + len;
+        }
+
+        let argument = match bindings
+            .iter()
+            .position(|(binding, _)| binding.unraw() == name)
+        {
+            Some(argument) => argument,
+            None => {
+                let span =
+                    literal_subspan(&literal, &text, char_index(start), char_index(position));
+                return Err(missing_binding(span, name, &bindings, &context(start)));
+            }
+        };
+
+        used[argument] = true;
 
-            let argument = bindings
-                .iter()
-                .position(|(binding, _)| *binding == name)
-                .ok_or_else(|| {
-                    err!(
-                        literal,
-                        "could not find a binding with the name `{}`. Here: `{}`",
-                        name,
-                        context(index),
-                    )
-                })?;
-
-            let index = param_indices[argument].unwrap_or_else(|| {
+        let existing = param_indices
+            .iter()
+            .find(|(binding, path, _)| *binding == argument && *path == fields)
+            .map(|(_, _, index)| *index);
+
+        let index = match existing {
+            Some(index) => index,
+            None => {
                 let (_, value) = &bindings[argument];
-                parameters.push(value.clone());
+                let members = fields
+                    .iter()
+                    .map(|field| {
+                        syn::parse_str::<Member>(field).map_err(|_| {
+                            err!(
+                                literal,
+                                "expected a field name or tuple index, found `{}`. Here: `{}`",
+                                field,
+                                context(start),
+                            )
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                parameters.push(if members.is_empty() {
+                    quote! { #value }
+                } else {
+                    quote! { (#value)#(.#members)* }
+                });
+                names.push(
+                    iter::once(name)
+                        .chain(fields.iter().map(String::as_str))
+                        .collect::<Vec<_>>()
+                        .join("."),
+                );
+
                 let index = parameters.len();
-                param_indices[argument] = Some(index);
+                param_indices.push((argument, fields, index));
                 index
-            });
+            }
+        };
 
-            write!(sql, "${}", index).unwrap();
-        }
+        write!(sql, "${}", index).unwrap();
     }
 
-    if let Some(index) = param_indices
-        .into_iter()
-        .position(|index: Option<usize>| index.is_none())
-    {
+    if let Some(index) = used.into_iter().position(|used| !used) {
         let (ident, _) = &bindings[index];
         Err(err!(ident, "unused argument"))
     } else {
-        Ok((sql, parameters))
+        Ok((sql, parameters, names))
+    }
+}
+
+/// The error reported when a placeholder does not refer to any of the bindings. Suggests the
+/// binding with the most similar name, if any, and points at it.
+fn missing_binding(
+    span: Span,
+    name: &str,
+    bindings: &[(Ident, Expr)],
+    context: &str,
+) -> syn::Error {
+    let closest = bindings
+        .iter()
+        .map(|(binding, _)| (binding, edit_distance(name, &binding.to_string())))
+        .filter(|(_, distance)| *distance <= (name.len() / 3).max(1))
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(binding, _)| binding);
+
+    match closest {
+        None => syn::Error::new(
+            span,
+            format!(
+                "could not find a binding with the name `{}`. Here: `{}`",
+                name, context
+            ),
+        ),
+        Some(binding) => {
+            let mut error = syn::Error::new(
+                span,
+                format!(
+                    "could not find a binding with the name `{}`, did you mean `{}`? Here: `{}`",
+                    name, binding, context
+                ),
+            );
+            error.combine(err!(
+                binding,
+                "a binding with a similar name is defined here: rename it to `{}` or use `${}` \
+                 in the query",
+                name,
+                binding,
+            ));
+            error
+        }
+    }
+}
+
+/// The number of insertions, deletions, substitutions and transpositions of adjacent characters
+/// needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+
+    // `rows[k][j]`: the distance between `b[..j]` and the prefix of `a` ending `k` characters
+    // before the current one.
+    let mut rows = [
+        (0..=b.len()).collect::<Vec<_>>(),
+        vec![0; b.len() + 1],
+        vec![0; b.len() + 1],
+    ];
+
+    for i in 1..=a.len() {
+        rows.rotate_right(1);
+        rows[0][0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance = (rows[1][j] + 1)
+                .min(rows[0][j - 1] + 1)
+                .min(rows[1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[2][j - 2] + 1);
+            }
+            rows[0][j] = distance;
+        }
     }
+
+    rows[0][b.len()]
+}
+
+/// The span of the characters `start..end` of the literal's value. Only available on compilers
+/// which support subspans, and for literals without escapes. Otherwise, the span of the whole
+/// literal.
+fn literal_subspan(literal: &LitStr, text: &str, start: usize, end: usize) -> Span {
+    let token = literal.token();
+    let source = token.to_string();
+
+    value_range(&source, text, start, end)
+        .and_then(|range| token.subspan(range))
+        .unwrap_or_else(|| literal.span())
+}
+
+/// The byte range in `source` of the characters `start..end` of `text`, the value of the string
+/// literal `source`. Only known if the literal is neither raw nor contains escapes.
+fn value_range(source: &str, text: &str, start: usize, end: usize) -> Option<Range<usize>> {
+    if source.len() != text.len() + 2 || !source.starts_with('"') {
+        return None;
+    }
+
+    let offset = |i: usize| text.char_indices().nth(i).map_or(text.len(), |(b, _)| b);
+    Some(1 + offset(start)..1 + offset(end))
 }
 
 fn expr_to_argument(expr: Expr) -> Result<Argument> {
@@ -219,7 +568,7 @@ fn expr_to_argument(expr: Expr) -> Result<Argument> {
         Expr::Reference(ExprReference {
             expr: ref inner, ..
         }) => {
-            if let Some(ident) = expr_as_ident(&inner) {
+            if let Some(ident) = expr_as_ident(inner) {
                 Ok(Argument::Single {
                     ident: ident.clone(),
                     value: expr,
@@ -246,17 +595,61 @@ fn expr_to_argument(expr: Expr) -> Result<Argument> {
 fn path_is_ident(path: &Path) -> bool {
     path.leading_colon.is_none()
         && path.segments.len() == 1
-        && match path.segments[0].arguments {
-            PathArguments::None => true,
-            _ => false,
-        }
+        && is_match!(path.segments[0].arguments, PathArguments::None)
 }
 
 fn expr_as_ident(expr: &Expr) -> Option<&Ident> {
     match expr {
         Expr::Path(ExprPath {
             qself: None, path, ..
-        }) if path_is_ident(&path) => Some(&path.segments[0].ident),
+        }) if path_is_ident(path) => Some(&path.segments[0].ident),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("name", ""), 4);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("user_id", "userid"), 1);
+        assert_eq!(edit_distance("name", "nmae"), 1);
+        assert_eq!(edit_distance("naïve", "naive"), 1);
+        assert_eq!(edit_distance("größe", "grösse"), 2);
+    }
+
+    #[test]
+    fn value_ranges() {
+        let source = "\"SELECT $name\"";
+        let range = value_range(source, "SELECT $name", 7, 12).unwrap();
+        assert_eq!(&source[range], "$name");
+
+        // Characters are counted, not bytes.
+        let source = "\"SELECT 'héllo', $näme\"";
+        let range = value_range(source, "SELECT 'héllo', $näme", 16, 21).unwrap();
+        assert_eq!(&source[range], "$näme");
+    }
+
+    #[test]
+    fn value_ranges_of_escaped_literals() {
+        let source = "\"SELECT \\\"x\\\", $name\"";
+        assert_eq!(value_range(source, "SELECT \"x\", $name", 13, 18), None);
+
+        let source = "r\"SELECT $name\"";
+        assert_eq!(value_range(source, "SELECT $name", 7, 12), None);
+
+        let source = "r#\"SELECT $name\"#";
+        assert_eq!(value_range(source, "SELECT $name", 7, 12), None);
+    }
+
+    #[test]
+    fn subspans_fall_back_to_the_literal() {
+        let literal = LitStr::new("SELECT \"x\", $name", Span::call_site());
+        let span = literal_subspan(&literal, &literal.value(), 13, 18);
+        assert_eq!(format!("{:?}", span), format!("{:?}", literal.span()));
+    }
+}
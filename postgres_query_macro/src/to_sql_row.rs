@@ -0,0 +1,135 @@
+use proc_macro2::TokenStream;
+use quote::*;
+use syn::{Attribute, Data, DeriveInput, Fields, Lit, LitStr, Meta, NestedMeta, Result};
+
+pub fn derive(input: DeriveInput) -> TokenStream {
+    match make_impl(&input) {
+        Ok(output) => output,
+        Err(e) => e.to_compile_error(),
+    }
+}
+
+fn make_impl(input: &DeriveInput) -> Result<TokenStream> {
+    let ident = &input.ident;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(err!(
+                    ident,
+                    "`ToSqlRow` may only be derived for structs with named fields"
+                ))
+            }
+        },
+        _ => return Err(err!(ident, "`ToSqlRow` may only be derived for structs")),
+    };
+
+    let mut values = Vec::new();
+    let mut names = Vec::new();
+    let mut types = Vec::new();
+    let mut untyped = None;
+    for field in fields {
+        let attrs = FieldAttributes::from_attrs(&field.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+
+        let field_ident = field.ident.as_ref().unwrap();
+        values.push(quote! { &self.#field_ident });
+        names.push(attrs.rename.unwrap_or_else(|| field_ident.to_string()));
+
+        match attrs.sql_type {
+            Some(sql_type) => types.push(sql_type),
+            None => untyped = Some(field_ident),
+        }
+    }
+
+    // The types of the columns are either given for all fields, or for none of them.
+    let typed = match (types.is_empty(), untyped) {
+        (true, _) => false,
+        (false, None) => true,
+        (false, Some(field)) => {
+            return Err(err!(
+                field,
+                "missing `#[sql_row(sql_type = \"...\")]`, which is required for all fields \
+                 once given for one of them"
+            ))
+        }
+    };
+
+    let lib = lib!();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let count = values.len();
+
+    let column_types = if typed {
+        quote! {
+            #[automatically_derived]
+            #[allow(clippy::all)]
+            impl #impl_generics #lib::copy::ColumnTypes for #ident #ty_generics #where_clause {
+                const COLUMNS: &'static [(&'static str, &'static str)] = &[
+                    #((#names, #types)),*
+                ];
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    Ok(quote! {
+        #[automatically_derived]
+        #[allow(clippy::all)]
+        impl #impl_generics #lib::copy::ToSqlRow for #ident #ty_generics #where_clause {
+            const COLUMN_COUNT: usize = #count;
+
+            fn to_sql_row(&self) -> ::std::vec::Vec<#lib::Parameter<'_>> {
+                ::std::vec![#(#values),*]
+            }
+        }
+
+        #column_types
+    })
+}
+
+#[derive(Default)]
+struct FieldAttributes {
+    rename: Option<String>,
+    sql_type: Option<LitStr>,
+    skip: bool,
+}
+
+impl FieldAttributes {
+    fn from_attrs(attrs: &[Attribute]) -> Result<FieldAttributes> {
+        let mut field = FieldAttributes::default();
+
+        for attr in attrs.iter().filter(|attr| attr.path.is_ident("sql_row")) {
+            let list = match attr.parse_meta()? {
+                Meta::List(list) => list,
+                _ => return Err(err!(attr, "expected list: #[sql_row(...)]")),
+            };
+
+            for inner in list.nested {
+                match inner {
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                        field.skip = true;
+                    }
+                    NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("rename") => {
+                        match pair.lit {
+                            Lit::Str(text) => field.rename = Some(text.value()),
+                            lit => return Err(err!(lit, "expected string literal")),
+                        }
+                    }
+                    NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("sql_type") => {
+                        match pair.lit {
+                            Lit::Str(text) => field.sql_type = Some(text),
+                            lit => return Err(err!(lit, "expected string literal")),
+                        }
+                    }
+                    inner => return Err(err!(inner, "unknown attribute")),
+                }
+            }
+        }
+
+        Ok(field)
+    }
+}
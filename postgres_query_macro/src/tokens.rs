@@ -0,0 +1,115 @@
+//! Splits query text with named bindings into tokens.
+//!
+//! This mirrors `postgres_query::parse::named_tokens`, which this crate cannot depend on, and has
+//! to be kept in sync with it: the macros and `query_dyn!` must agree on what is a binding.
+
+/// A piece of query text, as split by [`named_tokens`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// A keyword or an unquoted identifier.
+    Word(&'a str),
+    /// A positional parameter, such as `$1`.
+    Parameter(&'a str),
+    /// A named binding, such as `$name`.
+    Binding(&'a str),
+    /// An escaped dollar sign (`$$`).
+    Escaped(&'a str),
+    /// A string literal or quoted identifier, including its quotes.
+    Quoted(&'a str),
+    /// A comment, including its delimiters.
+    Comment(&'a str),
+    /// A single character of anything else, such as whitespace or punctuation.
+    Other(&'a str),
+}
+
+impl<'a> Token<'a> {
+    /// The text of the token, exactly as it appears in the query.
+    pub fn text(self) -> &'a str {
+        match self {
+            Token::Word(text)
+            | Token::Parameter(text)
+            | Token::Binding(text)
+            | Token::Escaped(text)
+            | Token::Quoted(text)
+            | Token::Comment(text)
+            | Token::Other(text) => text,
+        }
+    }
+}
+
+/// Split `text` into tokens, each with its byte offset. Joining the tokens gives back `text`.
+///
+/// Unterminated literals and comments extend to the end of `text`.
+pub fn named_tokens<'a>(text: &'a str) -> impl Iterator<Item = (usize, Token<'a>)> {
+    let mut position = 0;
+
+    std::iter::from_fn(move || {
+        let start = position;
+        let rest = &text[start..];
+        let ch = rest.chars().next()?;
+
+        let (len, token): (usize, fn(&'a str) -> Token<'a>) = match ch {
+            '\'' | '"' => (quoted_len(rest, ch), Token::Quoted),
+            '-' if rest.starts_with("--") => {
+                (rest.find('\n').unwrap_or(rest.len()), Token::Comment)
+            }
+            '/' if rest.starts_with("/*") => {
+                let len = rest[2..].find("*/").map_or(rest.len(), |end| end + 4);
+                (len, Token::Comment)
+            }
+            '$' => binding_len(rest),
+            _ if ch.is_alphabetic() || ch == '_' => {
+                let len = rest
+                    .find(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+                    .unwrap_or(rest.len());
+                (len, Token::Word)
+            }
+            _ => (ch.len_utf8(), Token::Other),
+        };
+
+        position += len;
+        Some((start, token(&rest[..len])))
+    })
+}
+
+/// Replace the escaped dollar signs (`$$`) in the text of a token.
+pub fn unescape(text: &str) -> String {
+    text.replace("$$", "$")
+}
+
+/// The length of the literal or quoted identifier at the start of `rest`, delimited by `quote`.
+fn quoted_len(rest: &str, quote: char) -> usize {
+    let mut chars = rest.char_indices().skip(1).peekable();
+
+    while let Some((index, ch)) = chars.next() {
+        if ch == quote {
+            // A doubled quote is an escaped quote.
+            if chars.peek().map(|&(_, ch)| ch) == Some(quote) {
+                chars.next();
+            } else {
+                return index + 1;
+            }
+        }
+    }
+
+    rest.len()
+}
+
+/// The length and kind of the token at the start of `rest`, which starts with `$`: an escaped
+/// dollar sign (`$$`), a binding (`$name`) or a parameter (`$1`).
+fn binding_len<'a>(rest: &'a str) -> (usize, fn(&'a str) -> Token<'a>) {
+    let after = &rest[1..];
+
+    if after.starts_with('$') {
+        return (2, Token::Escaped);
+    }
+
+    let len = after
+        .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_'))
+        .unwrap_or(after.len());
+    match after[..len].chars().next() {
+        None => (1, Token::Other),
+        Some(first) if first.is_ascii_digit() => (1 + len, Token::Parameter),
+        Some(_) => (1 + len, Token::Binding),
+    }
+}